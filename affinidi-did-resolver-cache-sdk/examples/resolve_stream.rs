@@ -0,0 +1,78 @@
+//! Pre-warms the DID cache from a list of DIDs read from stdin (one DID per line)
+//! DIDs are resolved as they are read, so arbitrarily large inputs use bounded memory
+//!
+//! Example: `cat dids.txt | cargo run --example resolve_stream -- -c 50`
+use affinidi_did_resolver_cache_sdk::{
+    config::ClientConfigBuilder, errors::DIDCacheError, DIDCacheClient,
+};
+use clap::Parser;
+use futures_util::{stream, StreamExt};
+use tokio::io::{stdin, AsyncBufReadExt, BufReader};
+use tracing_subscriber::filter;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// network address if running in network mode (ws://127.0.0.1:8080/did/v1/ws)
+    #[arg(short, long)]
+    network_address: Option<String>,
+    /// Maximum number of DIDs resolved at once
+    #[arg(short, long, default_value_t = 10)]
+    concurrency: usize,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), DIDCacheError> {
+    // **************************************************************
+    // *** Initial setup
+    // **************************************************************
+    let args = Args::parse();
+
+    // construct a subscriber that prints formatted traces to stdout
+    let subscriber = tracing_subscriber::fmt()
+        // Use a more compact, abbreviated log format
+        .with_env_filter(filter::EnvFilter::from_default_env())
+        .finish();
+    // use that subscriber to process traces emitted after this point
+    tracing::subscriber::set_global_default(subscriber).expect("Logging failed, exiting...");
+
+    #[allow(unused_mut)]
+    let mut cache_config = ClientConfigBuilder::default().with_batch_concurrency(args.concurrency);
+    if let Some(_address) = &args.network_address {
+        #[cfg(feature = "network")]
+        {
+            eprintln!("Running in network mode with address: {}", _address);
+            cache_config = cache_config.with_network_mode(_address);
+        }
+        #[cfg(not(feature = "network"))]
+        panic!("Network mode is not enabled in this build. Enable feature `network` to use network mode.");
+    }
+
+    let cache = DIDCacheClient::new(cache_config.build()).await?;
+
+    // Stream the DIDs from stdin, skipping blank lines
+    let lines = BufReader::new(stdin()).lines();
+    let dids = stream::unfold(lines, |mut lines| async move {
+        match lines.next_line().await {
+            Ok(Some(line)) => Some((line.trim().to_string(), lines)),
+            _ => None,
+        }
+    })
+    .filter(|did| std::future::ready(!did.is_empty()));
+
+    let (mut resolved, mut failed) = (0, 0);
+    let mut results = std::pin::pin!(cache.resolve_stream(dids));
+    while let Some((did, result)) = results.next().await {
+        match result {
+            Ok(_) => resolved += 1,
+            Err(e) => {
+                failed += 1;
+                eprintln!("{}: {}", did, e);
+            }
+        }
+    }
+
+    println!("Resolved: {}, Failed: {}", resolved, failed);
+
+    Ok(())
+}
@@ -0,0 +1,253 @@
+//! Resolving many DIDs at once
+//!
+//! [DIDCacheClient::resolve_batch] answers cache hits straight away, then resolves the cache
+//! misses concurrently (up to the configured batch_concurrency).
+//! In network mode the misses share the single websocket connection, the responses are matched
+//! back to each request by the network task's request list.
+//!
+//! For very large inputs (e.g. pre-warming the cache from a file of DIDs),
+//! [DIDCacheClient::resolve_stream] yields each result as it completes instead of collecting
+//! them all.
+//!
+//! [DIDCacheClient::preload] warms the cache with a known set of DIDs (e.g. on startup), and
+//! reports what was loaded instead of returning the DID Documents.
+
+use crate::{
+    cache_key::cache_key, did_hash, errors::DIDCacheError, DIDCacheClient, ResolveOptions,
+    ResolveResponse,
+};
+use futures_util::stream::{self, FuturesUnordered, Stream, StreamExt};
+use std::collections::HashSet;
+
+/// Outcome of [DIDCacheClient::preload]
+/// loaded: DIDs resolved and cached
+/// already_present: DIDs that were already cached, and weren't resolved again
+/// failed: DIDs that failed to resolve
+/// errors: (DID, error) for each failed DID
+#[derive(Debug, Default)]
+pub struct PreloadReport {
+    pub loaded: usize,
+    pub already_present: usize,
+    pub failed: usize,
+    pub errors: Vec<(String, DIDCacheError)>,
+}
+
+impl DIDCacheClient {
+    /// Resolves a list of DIDs, returning a result for each DID in the same order as `dids`
+    /// Cached DIDs are returned first, the rest are resolved concurrently with at most
+    /// batch_concurrency resolves in flight (see
+    /// [with_batch_concurrency](crate::config::ClientConfigBuilder::with_batch_concurrency))
+    /// A DID that fails to resolve doesn't fail the batch, its error is returned in its place
+    pub async fn resolve_batch(
+        &self,
+        dids: &[&str],
+    ) -> Vec<Result<ResolveResponse, DIDCacheError>> {
+        let mut results: Vec<Option<Result<ResolveResponse, DIDCacheError>>> =
+            (0..dids.len()).map(|_| None).collect();
+
+        let mut misses = Vec::new();
+        for (index, did) in dids.iter().enumerate() {
+            if self.cache.contains_key(&cache_key(did)) {
+                results[index] = Some(self.resolve(did).await);
+            } else {
+                misses.push(index);
+            }
+        }
+
+        let mut misses = misses.into_iter();
+        let mut in_flight = FuturesUnordered::new();
+        let resolve = |index: usize| async move { (index, self.resolve(dids[index]).await) };
+        for index in misses.by_ref().take(self.config.batch_concurrency) {
+            in_flight.push(resolve(index));
+        }
+        while let Some((index, result)) = in_flight.next().await {
+            results[index] = Some(result);
+            if let Some(index) = misses.next() {
+                in_flight.push(resolve(index));
+            }
+        }
+
+        results
+            .into_iter()
+            .map(|result| result.expect("every DID in the batch has a result"))
+            .collect()
+    }
+
+    /// Warms the cache with a list of DIDs, e.g. frequently used DIDs on startup
+    /// DIDs already in the cache are skipped unless force is set, in which case every DID is
+    /// resolved again (bypassing the cache) and the fresh DID Document is cached
+    /// Misses are resolved concurrently with at most batch_concurrency resolves in flight
+    /// Duplicate DIDs are only loaded once, so it is safe to call repeatedly with the same list
+    pub async fn preload(&self, dids: &[&str], force: bool) -> PreloadReport {
+        let mut report = PreloadReport::default();
+
+        let mut seen = HashSet::new();
+        let mut misses = Vec::new();
+        for did in dids {
+            if !seen.insert(did_hash(did)) {
+                continue;
+            }
+            if !force && self.contains(did).await {
+                report.already_present += 1;
+            } else {
+                misses.push(*did);
+            }
+        }
+
+        let options = ResolveOptions {
+            force_refresh: force,
+            ..Default::default()
+        };
+        let mut results = stream::iter(misses)
+            .map(|did| async move { (did, self.resolve_with_options(did, options).await) })
+            .buffer_unordered(self.config.batch_concurrency);
+        while let Some((did, result)) = results.next().await {
+            match result {
+                Ok(_) => report.loaded += 1,
+                Err(e) => {
+                    report.failed += 1;
+                    report.errors.push((did.to_string(), e));
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Resolves a stream of DIDs, yielding (DID, result) for each DID as soon as it completes
+    /// At most batch_concurrency resolves are in flight, and the input is only read as results
+    /// are consumed, so memory use stays bounded however many DIDs are streamed
+    /// Results are yielded in completion order, not input order
+    /// Each DID goes through [resolve](Self::resolve), so the usual checks (max_did_size_in_kb,
+    /// max_did_parts) apply per DID, and a DID that fails doesn't end the stream
+    pub fn resolve_stream<'a, S>(
+        &'a self,
+        dids: S,
+    ) -> impl Stream<Item = (String, Result<ResolveResponse, DIDCacheError>)> + 'a
+    where
+        S: Stream<Item = String> + 'a,
+    {
+        dids.map(move |did| async move {
+            let result = self.resolve(&did).await;
+            (did, result)
+        })
+        .buffer_unordered(self.config.batch_concurrency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::{client_with, DID_KEY, DID_KEY_2};
+    use crate::{config, errors::DIDCacheError};
+    use futures_util::StreamExt;
+
+    #[tokio::test]
+    async fn results_in_input_order() {
+        let client =
+            client_with(config::ClientConfigBuilder::default().with_batch_concurrency(10)).await;
+        // Warm the cache with one DID, so the batch mixes cache hits and misses
+        client.resolve(DID_KEY_2).await.unwrap();
+
+        let results = client
+            .resolve_batch(&[DID_KEY, "did:key:z6MkNotAKey", DID_KEY_2, "not a did"])
+            .await;
+
+        assert_eq!(results.len(), 4);
+        let first = results[0].as_ref().unwrap();
+        assert_eq!(first.did, DID_KEY);
+        assert!(!first.cache_hit);
+        assert!(matches!(results[1], Err(DIDCacheError::InvalidDid(_))));
+        let third = results[2].as_ref().unwrap();
+        assert_eq!(third.did, DID_KEY_2);
+        assert!(third.cache_hit);
+        assert!(results[3].is_err());
+    }
+
+    #[tokio::test]
+    async fn concurrency_limit_of_one() {
+        let client =
+            client_with(config::ClientConfigBuilder::default().with_batch_concurrency(0)).await;
+        assert_eq!(client.config.batch_concurrency, 1);
+
+        let results = client.resolve_batch(&[DID_KEY, DID_KEY_2, DID_KEY]).await;
+        let dids: Vec<&str> = results
+            .iter()
+            .map(|result| result.as_ref().unwrap().did.as_str())
+            .collect();
+        assert_eq!(dids, [DID_KEY, DID_KEY_2, DID_KEY]);
+        // Resolved one at a time, so the repeated DID is found in the cache
+        assert!(results[2].as_ref().unwrap().cache_hit);
+
+        client.get_cache().run_pending_tasks().await;
+        assert_eq!(client.get_cache().entry_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn stream_yields_every_did() {
+        let client =
+            client_with(config::ClientConfigBuilder::default().with_batch_concurrency(2)).await;
+        let too_long = format!("did:key:{}", "z".repeat(1000));
+        let dids = vec![
+            DID_KEY.to_string(),
+            too_long.clone(),
+            DID_KEY_2.to_string(),
+            DID_KEY.to_string(),
+        ];
+
+        let mut results: Vec<_> = client
+            .resolve_stream(futures_util::stream::iter(dids))
+            .collect()
+            .await;
+        assert_eq!(results.len(), 4);
+
+        // Sorted: DID_KEY_2, DID_KEY, DID_KEY, too_long
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(results[0].1.as_ref().unwrap().did, DID_KEY_2);
+        assert_eq!(results[1].1.as_ref().unwrap().did, DID_KEY);
+        assert_eq!(results[2].1.as_ref().unwrap().did, DID_KEY);
+        assert_eq!(results[3].0, too_long);
+        assert!(matches!(
+            &results[3].1,
+            Err(DIDCacheError::DIDTooLarge { .. })
+        ));
+
+        client.get_cache().run_pending_tasks().await;
+        assert_eq!(client.get_cache().entry_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn preload() {
+        let client =
+            client_with(config::ClientConfigBuilder::default().with_batch_concurrency(2)).await;
+        client.resolve(DID_KEY_2).await.unwrap();
+
+        let report = client
+            .preload(&[DID_KEY, DID_KEY_2, "did:key:z6MkNotAKey", DID_KEY], false)
+            .await;
+        assert_eq!(report.loaded, 1);
+        assert_eq!(report.already_present, 1);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.errors[0].0, "did:key:z6MkNotAKey");
+        assert!(client.contains(DID_KEY).await);
+
+        // Calling again doesn't resolve anything that is cached
+        let report = client.preload(&[DID_KEY, DID_KEY_2], false).await;
+        assert_eq!((report.loaded, report.already_present), (0, 2));
+
+        // Unless forced
+        let report = client.preload(&[DID_KEY, DID_KEY_2], true).await;
+        assert_eq!((report.loaded, report.already_present), (2, 0));
+        assert!(report.errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn empty_batch() {
+        assert!(
+            client_with(config::ClientConfigBuilder::default().with_batch_concurrency(10))
+                .await
+                .resolve_batch(&[])
+                .await
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! Blocking resolve for callers that aren't async (e.g. behind a synchronous FFI boundary)
+//!
+//! Enabled with the `blocking` feature, and not available on wasm32.
+//! Each calling thread lazily creates its own current-thread tokio runtime, which is reused by
+//! later calls on that thread.
+//!
+//! NOTE: In network mode the client must be created on a runtime that keeps running (the network
+//! task lives on it), the blocking runtime only drives the resolve itself.
+
+use crate::{errors::DIDCacheError, DIDCacheClient, ResolveResponse};
+use std::cell::RefCell;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+thread_local! {
+    static RUNTIME: RefCell<Option<Runtime>> = const { RefCell::new(None) };
+}
+
+impl DIDCacheClient {
+    /// Blocking version of [resolve](Self::resolve), drives the resolve on a current-thread runtime
+    /// Returns [DIDCacheError::ConfigError] if called from within an async runtime (blocking there
+    /// would stall or panic the runtime), use [resolve](Self::resolve) instead
+    pub fn resolve_blocking(&self, did: &str) -> Result<ResolveResponse, DIDCacheError> {
+        if Handle::try_current().is_ok() {
+            return Err(DIDCacheError::ConfigError(
+                "resolve_blocking() can't be called from within an async runtime, use resolve()"
+                    .into(),
+            ));
+        }
+
+        RUNTIME.with(|runtime| {
+            let mut runtime = runtime.borrow_mut();
+            if runtime.is_none() {
+                *runtime = Some(Builder::new_current_thread().enable_all().build().map_err(
+                    |e| {
+                        DIDCacheError::ConfigError(format!(
+                            "Couldn't create a tokio runtime for resolve_blocking(): {}",
+                            e
+                        ))
+                    },
+                )?);
+            }
+            runtime
+                .as_ref()
+                .expect("runtime was just created")
+                .block_on(self.resolve(did))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::DIDCacheError;
+    use crate::test_fixtures::{client, DID_KEY};
+
+    #[test]
+    fn resolve_blocking() {
+        let client = tokio::runtime::Runtime::new().unwrap().block_on(client());
+
+        let response = client.resolve_blocking(DID_KEY).unwrap();
+        assert_eq!(response.doc.id, DID_KEY);
+        assert!(!response.cache_hit);
+
+        // The runtime is reused
+        assert!(client.resolve_blocking(DID_KEY).unwrap().cache_hit);
+    }
+
+    #[tokio::test]
+    async fn resolve_blocking_in_runtime() {
+        let client = client().await;
+
+        assert!(matches!(
+            client.resolve_blocking(DID_KEY),
+            Err(DIDCacheError::ConfigError(msg)) if msg.contains("within an async runtime")
+        ));
+    }
+}
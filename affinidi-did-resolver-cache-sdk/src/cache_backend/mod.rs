@@ -0,0 +1,255 @@
+//! Shared (L2) cache backends
+//!
+//! The local moka cache is an L1 cache in front of an optional shared [CacheBackend].
+//! On a local cache miss the backend is checked before resolving the DID, and resolved
+//! DID Documents (with their resolution metadata) are written back to it.
+//! This lets multiple instances share resolved DIDs.
+//!
+//! Set the backend with [ClientConfigBuilder::with_cache_backend](crate::config::ClientConfigBuilder::with_cache_backend).
+//!
+//! Features:
+//! - **redis**: Enables [RedisCacheBackend](redis::RedisCacheBackend) (not available on wasm32)
+
+use crate::metadata::CacheEntry;
+use async_trait::async_trait;
+use std::{fmt::Debug, time::Duration};
+
+#[cfg(all(feature = "redis", not(target_arch = "wasm32")))]
+pub mod redis;
+
+/// A shared cache that sits behind the local cache
+/// Keys are the hashed DID (same as the local cache)
+///
+/// Backend errors should not fail a resolve: implementations should log them and
+/// return None (get) or drop the write (put).
+#[async_trait]
+pub trait CacheBackend: Send + Sync + Debug {
+    /// Returns the cached DID Document and metadata for the hashed DID, if present
+    async fn get(&self, key: &str) -> Option<CacheEntry>;
+
+    /// Stores a resolved DID Document and metadata for the hashed DID, expiring after ttl
+    async fn put(&self, key: &str, entry: &CacheEntry, ttl: Duration);
+
+    /// Removes the hashed DID, called by [remove](crate::DIDCacheClient::remove)
+    /// Default: does nothing, the entry expires after its ttl
+    async fn remove(&self, _key: &str) {}
+
+    /// Removes all DID Documents, called by [clear](crate::DIDCacheClient::clear)
+    /// Default: does nothing, the entries expire after their ttl
+    async fn clear(&self) {}
+
+    /// Removes all DID Documents resolved with the method (e.g. "web", see
+    /// [CacheEntry::resolved_method_name]), called by [clear_method](crate::DIDCacheClient::clear_method)
+    /// Default: does nothing, the entries expire after their ttl
+    async fn clear_method(&self, _method: &str) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{client_with, DID_KEY};
+    use crate::{cache_key::cache_key, config, DIDMethod};
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    #[derive(Debug, Default)]
+    struct MemoryBackend {
+        docs: Mutex<HashMap<String, CacheEntry>>,
+        ttls: Mutex<HashMap<String, Duration>>,
+    }
+
+    #[async_trait]
+    impl CacheBackend for MemoryBackend {
+        async fn get(&self, key: &str) -> Option<CacheEntry> {
+            self.docs.lock().unwrap().get(key).cloned()
+        }
+
+        async fn put(&self, key: &str, entry: &CacheEntry, ttl: Duration) {
+            self.ttls.lock().unwrap().insert(key.to_string(), ttl);
+            self.docs
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), entry.clone());
+        }
+
+        async fn remove(&self, key: &str) {
+            self.docs.lock().unwrap().remove(key);
+        }
+
+        async fn clear(&self) {
+            self.docs.lock().unwrap().clear();
+        }
+
+        async fn clear_method(&self, method: &str) {
+            self.docs
+                .lock()
+                .unwrap()
+                .retain(|_, entry| entry.resolved_method_name() != method);
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_writes_back_to_backend() {
+        let backend = Arc::new(MemoryBackend::default());
+        let client =
+            client_with(config::ClientConfigBuilder::default().with_cache_backend(backend.clone()))
+                .await;
+
+        let response = client.resolve(DID_KEY).await.unwrap();
+        assert!(!response.cache_hit);
+        assert_eq!(
+            backend
+                .docs
+                .lock()
+                .unwrap()
+                .get(&response.did_hash)
+                .map(|entry| &entry.doc),
+            Some(&response.doc)
+        );
+    }
+
+    #[tokio::test]
+    async fn backend_shared_between_clients() {
+        let backend = Arc::new(MemoryBackend::default());
+        let first =
+            client_with(config::ClientConfigBuilder::default().with_cache_backend(backend.clone()))
+                .await;
+        let second =
+            client_with(config::ClientConfigBuilder::default().with_cache_backend(backend.clone()))
+                .await;
+
+        let resolved = first.resolve(DID_KEY).await.unwrap();
+
+        // Second client misses its local cache, but hits the shared backend
+        let response = second.resolve(DID_KEY).await.unwrap();
+        assert!(response.cache_hit);
+        assert_eq!(response.doc, resolved.doc);
+        assert_eq!(response.resolution_metadata, resolved.resolution_metadata);
+
+        // And the local cache is populated from the backend
+        assert!(second.get_cache().contains_key(&cache_key(DID_KEY)));
+    }
+
+    #[tokio::test]
+    async fn remove_from_backend() {
+        let backend = Arc::new(MemoryBackend::default());
+        let first =
+            client_with(config::ClientConfigBuilder::default().with_cache_backend(backend.clone()))
+                .await;
+        let second =
+            client_with(config::ClientConfigBuilder::default().with_cache_backend(backend.clone()))
+                .await;
+
+        let resolved = first.resolve(DID_KEY).await.unwrap();
+        assert!(first.remove(DID_KEY).await.is_some());
+        assert!(backend.docs.lock().unwrap().is_empty());
+
+        // Not served from the shared backend anymore
+        let response = second.resolve(DID_KEY).await.unwrap();
+        assert!(!response.cache_hit);
+        assert_eq!(response.doc, resolved.doc);
+    }
+
+    #[tokio::test]
+    async fn clear_backend() {
+        let backend = Arc::new(MemoryBackend::default());
+        let client =
+            client_with(config::ClientConfigBuilder::default().with_cache_backend(backend.clone()))
+                .await;
+
+        client.resolve(DID_KEY).await.unwrap();
+        client.clear_method(DIDMethod::WEB).await.unwrap();
+        assert_eq!(backend.docs.lock().unwrap().len(), 1);
+        client.clear_method(DIDMethod::KEY).await.unwrap();
+        assert!(backend.docs.lock().unwrap().is_empty());
+
+        client.resolve(DID_KEY).await.unwrap();
+        client.clear().await;
+        assert!(backend.docs.lock().unwrap().is_empty());
+    }
+
+    /// A did:web that redirects to another DID is still a did:web in the backend
+    #[tokio::test]
+    async fn backend_uses_resolved_method() {
+        let backend = Arc::new(MemoryBackend::default());
+        let resolver = HashMap::from([(
+            "https://example.com/.well-known/did.json".to_string(),
+            serde_json::json!({ "id": DID_KEY })
+                .to_string()
+                .into_bytes(),
+        )]);
+        let client = client_with(
+            config::ClientConfigBuilder::default()
+                .with_cache_backend(backend.clone())
+                .with_web_resolver(Arc::new(resolver))
+                .with_method_ttl(DIDMethod::WEB, 10),
+        )
+        .await;
+
+        let response = client.resolve("did:web:example.com").await.unwrap();
+        assert_eq!(
+            backend.ttls.lock().unwrap()[&response.did_hash],
+            Duration::from_secs(10)
+        );
+
+        client.clear_method(DIDMethod::KEY).await.unwrap();
+        assert_eq!(backend.docs.lock().unwrap().len(), 1);
+        client.clear_method(DIDMethod::WEB).await.unwrap();
+        assert!(backend.docs.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn clear_custom_methods_from_backend() {
+        #[derive(Debug)]
+        struct TestMethod;
+
+        #[async_trait]
+        impl crate::method_resolver::MethodResolver for TestMethod {
+            async fn resolve(
+                &self,
+                did: &ssi::dids::DID,
+            ) -> Result<ssi::dids::Document, crate::errors::DIDCacheError> {
+                Ok(ssi::dids::Document::new(did.to_owned()))
+            }
+        }
+
+        let backend = Arc::new(MemoryBackend::default());
+        let client = client_with(
+            config::ClientConfigBuilder::default()
+                .with_cache_backend(backend.clone())
+                .with_method("test", Arc::new(TestMethod)),
+        )
+        .await;
+        client.resolve("did:test:1234").await.unwrap();
+        client.resolve(DID_KEY).await.unwrap();
+
+        client.invalidate_method(DIDMethod::CUSTOM).await.unwrap();
+        let docs = backend.docs.lock().unwrap();
+        assert_eq!(docs.len(), 1);
+        assert!(docs
+            .values()
+            .all(|entry| entry.resolved_method_name() == "key"));
+    }
+
+    /// A resolve that overlaps a remove doesn't write its result back to the backend
+    #[tokio::test]
+    async fn remove_wins_over_backend_write() {
+        let backend = Arc::new(MemoryBackend::default());
+        let client =
+            client_with(config::ClientConfigBuilder::default().with_cache_backend(backend.clone()))
+                .await;
+
+        let generation = *client.generation.read().await;
+        client.remove(DID_KEY).await;
+        let entry = CacheEntry::from(client.resolve(DID_KEY).await.unwrap().doc);
+        backend.docs.lock().unwrap().clear();
+
+        let did_hash = crate::did_hash(DID_KEY);
+        client
+            ._cache_insert(generation, DID_KEY, &did_hash, "key", &entry, true)
+            .await;
+        assert!(backend.docs.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+//! Redis implementation of [CacheBackend]
+//!
+//! DID Documents and their metadata are stored as JSON strings under `<prefix><did_hash>`
+//! with an expiry.
+//!
+//! Example:
+//! ```ignore
+//! let backend = RedisCacheBackend::new("redis://127.0.0.1/", "did_cache:").await?;
+//! let config = ClientConfigBuilder::default()
+//!     .with_cache_backend(Arc::new(backend))
+//!     .build();
+//! ```
+
+use super::CacheBackend;
+use crate::errors::DIDCacheError;
+use crate::metadata::CacheEntry;
+use async_trait::async_trait;
+use redis::{aio::ConnectionManager, AsyncCommands};
+use std::{fmt, time::Duration};
+use tracing::warn;
+
+/// Shares resolved DID Documents across instances via Redis
+/// The connection is automatically re-established if it drops
+#[derive(Clone)]
+pub struct RedisCacheBackend {
+    connection: ConnectionManager,
+    prefix: String,
+}
+
+impl fmt::Debug for RedisCacheBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisCacheBackend")
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+impl RedisCacheBackend {
+    /// Connects to Redis
+    /// redis_url: e.g. `redis://127.0.0.1/`
+    /// prefix: Prepended to each key, to share a Redis instance with other data
+    pub async fn new(redis_url: &str, prefix: &str) -> Result<Self, DIDCacheError> {
+        let client = redis::Client::open(redis_url).map_err(|e| {
+            DIDCacheError::ConfigError(format!("Invalid redis url ({}): {}", redis_url, e))
+        })?;
+        let connection = ConnectionManager::new(client).await.map_err(|e| {
+            DIDCacheError::TransportError(format!("Couldn't connect to redis: {}", e))
+        })?;
+
+        Ok(Self {
+            connection,
+            prefix: prefix.to_string(),
+        })
+    }
+
+    /// Returns all keys under the prefix (SCAN, so Redis isn't blocked on a large keyspace)
+    async fn _keys(&self) -> Vec<String> {
+        let pattern = [&_escape_glob(&self.prefix), "*"].concat();
+        let mut connection = self.connection.clone();
+        let mut iter = match connection.scan_match::<_, String>(pattern).await {
+            Ok(iter) => iter,
+            Err(e) => {
+                warn!("redis scan failed for prefix ({}): {}", self.prefix, e);
+                return Vec::new();
+            }
+        };
+        let mut keys = Vec::new();
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        keys
+    }
+
+    /// Deletes the given (prefixed) keys
+    async fn _delete(&self, keys: Vec<String>) {
+        if keys.is_empty() {
+            return;
+        }
+        let mut connection = self.connection.clone();
+        if let Err(e) = connection.del::<_, ()>(keys).await {
+            warn!("redis del failed for prefix ({}): {}", self.prefix, e);
+        }
+    }
+}
+
+/// Escapes the glob special characters in a redis key pattern
+fn _escape_glob(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[async_trait]
+impl CacheBackend for RedisCacheBackend {
+    async fn get(&self, key: &str) -> Option<CacheEntry> {
+        let mut connection = self.connection.clone();
+        let value: Option<String> = match connection.get([&self.prefix, key].concat()).await {
+            Ok(value) => value,
+            Err(e) => {
+                warn!("redis get failed for key ({}): {}", key, e);
+                return None;
+            }
+        };
+
+        match serde_json::from_str(&value?) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!(
+                    "Couldn't parse DID Document from redis key ({}): {}",
+                    key, e
+                );
+                None
+            }
+        }
+    }
+
+    async fn put(&self, key: &str, entry: &CacheEntry, ttl: Duration) {
+        let value = match serde_json::to_string(entry) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!("Couldn't serialize DID Document for key ({}): {}", key, e);
+                return;
+            }
+        };
+
+        let mut connection = self.connection.clone();
+        if let Err(e) = connection
+            .set_ex::<_, _, ()>([&self.prefix, key].concat(), value, ttl.as_secs().max(1))
+            .await
+        {
+            warn!("redis set failed for key ({}): {}", key, e);
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        let mut connection = self.connection.clone();
+        if let Err(e) = connection.del::<_, ()>([&self.prefix, key].concat()).await {
+            warn!("redis del failed for key ({}): {}", key, e);
+        }
+    }
+
+    async fn clear(&self) {
+        let keys = self._keys().await;
+        self._delete(keys).await;
+    }
+
+    async fn clear_method(&self, method: &str) {
+        let mut keys = Vec::new();
+        for key in self._keys().await {
+            if let Some(entry) = self.get(&key[self.prefix.len()..]).await {
+                if entry.resolved_method_name() == method {
+                    keys.push(key);
+                }
+            }
+        }
+        self._delete(keys).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::_escape_glob;
+
+    #[test]
+    fn escape_glob() {
+        assert_eq!(_escape_glob("did_cache:"), "did_cache:");
+        assert_eq!(_escape_glob("a*b?[c]\\"), "a\\*b\\?\\[c\\]\\\\");
+    }
+}
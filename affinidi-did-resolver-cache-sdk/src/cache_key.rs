@@ -0,0 +1,90 @@
+//! Keys of the DID Document cache
+//!
+//! DID Documents are cached under the raw 32 byte Blake2s256 hash of the canonical DID
+//! ([DIDHash], see [canonicalize_did](crate::canonicalize_did)), which takes less than half the
+//! memory per entry of the hex encoded hash. It displays as the hex encoded hash, the same
+//! string as [did_hash](crate::did_hash).
+//!
+//! Use [cache_key] to look a DID up in the cache returned by
+//! [get_cache](crate::DIDCacheClient::get_cache) or passed to [with_cache](crate::DIDCacheClient::with_cache).
+
+use crate::canonicalize_did;
+use blake2::{Blake2s256, Digest};
+use std::fmt;
+
+/// Raw Blake2s256 hash of a canonical DID
+/// Displays as the hex encoded hash, the same as [did_hash](crate::did_hash)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DIDHash([u8; 32]);
+
+impl DIDHash {
+    /// Hashes the canonical form of the DID
+    pub fn new(did: &str) -> Self {
+        Self::raw(&canonicalize_did(did))
+    }
+
+    /// Hashes the DID as is, without canonicalizing it
+    pub(crate) fn raw(did: &str) -> Self {
+        let mut hasher = Blake2s256::new();
+        hasher.update(did);
+        DIDHash(hasher.finalize().into())
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for DIDHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Key of the DID Document cache
+pub type CacheKey = DIDHash;
+
+/// Returns the key the DID is cached under
+pub fn cache_key(did: &str) -> CacheKey {
+    DIDHash::new(did)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::did_hash;
+    use crate::test_fixtures::DID_KEY;
+    use std::collections::HashSet;
+
+    #[test]
+    fn hex_matches_did_hash() {
+        let hash = DIDHash::new(DID_KEY);
+        assert_eq!(hash.to_string(), did_hash(DID_KEY));
+        assert_eq!(hash.to_string().len(), 64);
+        assert_eq!(cache_key(DID_KEY).to_string(), did_hash(DID_KEY));
+
+        // Keyed by the canonical DID
+        assert_eq!(DIDHash::new(&format!(" {} ", DID_KEY)), hash);
+    }
+
+    #[test]
+    fn no_collisions() {
+        // Similar DIDs (a differing character or suffix) across methods
+        let dids: Vec<String> = (0..50_000u32)
+            .flat_map(|i| {
+                [
+                    format!("did:web:example.com:user:{}", i),
+                    format!("did:web:host{}.example.com", i),
+                    format!("did:pkh:eip155:1:0x{:040x}", i),
+                    format!("did:ethr:0x{:040x}", u64::from(i) << 20),
+                ]
+            })
+            .collect();
+
+        let hashes: HashSet<DIDHash> = dids.iter().map(|did| DIDHash::new(did)).collect();
+        assert_eq!(hashes.len(), dids.len());
+    }
+}
@@ -21,8 +21,20 @@
 //! ```
 //!
 
+use crate::errors::DIDCacheError;
 #[cfg(feature = "network")]
-use std::time::Duration;
+use crate::networking::{backoff::BackoffConfig, tls::TlsVersion};
+use crate::resolver::{dht::DEFAULT_DHT_GATEWAY, ethr};
+use crate::{
+    cache_backend::CacheBackend,
+    method_resolver::{is_method_name, MethodResolver},
+    web_resolver::WebResolver,
+};
+use crate::{health::DEFAULT_SELF_TEST_DIDS, DIDMethod};
+pub use moka::notification::RemovalCause;
+use ssi::dids::Document;
+use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
+use tracing::warn;
 use wasm_bindgen::prelude::*;
 
 /// Private Configuration for the client.
@@ -34,13 +46,97 @@ pub struct ClientConfig {
     #[cfg(feature = "network")]
     pub(crate) service_address: Option<String>,
     pub(crate) cache_capacity: u32,
+    pub(crate) cache_capacity_set: bool,
+    pub(crate) max_cache_bytes: Option<u64>,
+    pub(crate) cache_initial_capacity: Option<usize>,
+    pub(crate) cache_eviction_policy: CacheEvictionPolicy,
     pub(crate) cache_ttl: u32,
+    pub(crate) method_ttls: HashMap<DIDMethod, u32>,
+    pub(crate) negative_cache_ttl: Option<u32>,
     #[cfg(feature = "network")]
     pub(crate) network_timeout: Duration,
     #[cfg(feature = "network")]
     pub(crate) network_cache_limit_count: u32,
+    #[cfg(feature = "network")]
+    pub(crate) network_busy_timeout: Duration,
+    #[cfg(feature = "network")]
+    pub(crate) command_channel_capacity: usize,
+    #[cfg(feature = "network")]
+    pub(crate) response_channel_capacity: usize,
+    #[cfg(feature = "network")]
+    pub(crate) tls_min_version: Option<TlsVersion>,
+    #[cfg(feature = "network")]
+    pub(crate) tls_cipher_suites: Option<Vec<String>>,
+    #[cfg(feature = "network")]
+    pub(crate) tls_roots: Option<Vec<Vec<u8>>>,
+    #[cfg(feature = "network")]
+    pub(crate) danger_accept_invalid_certs: bool,
+    #[cfg(feature = "network")]
+    pub(crate) backoff: BackoffConfig,
+    #[cfg(feature = "network")]
+    pub(crate) keepalive_interval: Duration,
+    #[cfg(feature = "network")]
+    pub(crate) keepalive_timeout: Duration,
+    #[cfg(feature = "network")]
+    pub(crate) max_frame_bytes: usize,
     pub(crate) max_did_parts: usize,
     pub(crate) max_did_size_in_kb: f64,
+    pub(crate) canonicalize: bool,
+    pub(crate) dht_gateway: String,
+    pub(crate) ethr_rpc: HashMap<String, String>,
+    pub(crate) web_path_overrides: HashMap<String, String>,
+    pub(crate) http_proxy: Option<String>,
+    pub(crate) https_proxy: Option<String>,
+    pub(crate) proxy_auth: Option<ProxyAuth>,
+    pub(crate) offline: bool,
+    pub(crate) batch_concurrency: usize,
+    pub(crate) fixtures: Option<HashMap<String, Document>>,
+    pub(crate) cache_backend: Option<Arc<dyn CacheBackend>>,
+    pub(crate) web_resolver: Option<Arc<dyn WebResolver>>,
+    pub(crate) methods: HashMap<String, Arc<dyn MethodResolver>>,
+    pub(crate) eviction_listener: Option<EvictionListener>,
+    pub(crate) self_test_dids: HashMap<DIDMethod, String>,
+}
+
+/// Which entries the local cache keeps when it is full (see moka's `EvictionPolicy`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CacheEvictionPolicy {
+    /// Favors frequently resolved DIDs: a new DID is only admitted if it has been seen more
+    /// often than the DID it would evict, so a burst of one-off DIDs doesn't flush the hot ones
+    #[default]
+    TinyLfu,
+    /// Favors recently resolved DIDs: new DIDs are always admitted, the least recently used is
+    /// evicted. Suits access patterns where the same DIDs are resolved in short bursts
+    Lru,
+}
+
+/// Basic auth credentials for the proxy, the password is redacted from Debug output
+#[derive(Clone)]
+pub(crate) struct ProxyAuth {
+    pub(crate) username: String,
+    pub(crate) password: String,
+}
+
+impl fmt::Debug for ProxyAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyAuth")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Called with the cache key (the DID hash), the DID Document and why it was removed
+pub type EvictionListenerFn = Arc<dyn Fn(String, Document, RemovalCause) + Send + Sync>;
+
+/// Wraps the eviction listener so [ClientConfig] can still derive Debug
+#[derive(Clone)]
+pub(crate) struct EvictionListener(pub(crate) EvictionListenerFn);
+
+impl fmt::Debug for EvictionListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EvictionListener")
+    }
 }
 
 /// Config Builder to construct options required for the client.
@@ -48,20 +144,93 @@ pub struct ClientConfig {
 ///
 /// - service_address: REQUIRED: The address of the service to connect to.
 /// - cache_capacity: The maximum number of items to store in the local cache (default: 100).
+/// - max_cache_bytes: If set, limits the local cache by total serialized DID Document size, as well as by item count if cache_capacity is set (default: None).
+/// - cache_initial_capacity: Number of entries the local cache allocates space for up front (default: None, grows as needed).
+/// - cache_eviction_policy: Whether the local cache favors frequently or recently resolved DIDs when full (default: TinyLfu).
 /// - cache_ttl: The time-to-live in seconds for each item in the local cache (default: 300 (5 Minutes)).
+/// - method_ttls: Per DID method overrides of cache_ttl in seconds (default: None).
+/// - negative_cache_ttl: If set, failed resolutions are cached for this many seconds (default: None).
 /// - network_timeout: The timeout for network requests in milliseconds (default: 5000 (5 seconds)).
 /// - network_cache_limit_count: The maximum number of items to store in the network cache (default: 100).
+/// - network_busy_timeout: How long resolve() waits for room in a full network cache before returning Busy, in milliseconds (default: 5000 (5 seconds)).
+/// - command_channel_capacity: The capacity of the SDK to network task channel (default: 32).
+/// - response_channel_capacity: The capacity of the network task to SDK channel (default: 32).
+/// - tls_min_version: Minimum TLS version for wss:// connections (default: None, platform default).
+/// - tls_cipher_suites: Cipher suites allowed for wss:// connections (default: None, all supported).
+/// - tls_roots: DER root certificates trusted for wss:// connections instead of the platform roots (default: None).
+/// - danger_accept_invalid_certs: INSECURE, TESTING ONLY: Don't verify the server certificate (default: false).
+/// - backoff: Delay between websocket reconnect attempts (default: linear, 5 second steps up to 60 seconds).
+/// - keepalive_interval: How often a websocket ping is sent in milliseconds, 0 disables it (default: 30000 (30 seconds)).
+/// - keepalive_timeout: How long to wait for the pong in milliseconds before reconnecting (default: 10000 (10 seconds)).
+/// - max_frame_bytes: Largest websocket message accepted from the server in bytes (default: 1048576 (1 MiB)).
+/// - canonicalize: Convert resolved DID Documents to a canonical form (default: false).
+/// - dht_gateway: Gateway (Pkarr relay) used to read did:dht records (default: https://relay.pkarr.org).
+/// - ethr_rpc: Ethereum JSON-RPC endpoint per chain id, used to check did:ethr owners (default: None, not checked).
+/// - web_path_overrides: Base path of the did:web DID Documents per host, instead of the spec's locations (default: None).
+/// - http_proxy, https_proxy: Proxy for did:web and did:dht requests (default: None, HTTP_PROXY/HTTPS_PROXY/NO_PROXY environment variables).
+/// - proxy_auth: Basic auth (username, password) for the proxy (default: None).
+/// - offline: Only resolve self-contained DID methods, never make outbound requests (default: false).
+/// - batch_concurrency: The maximum number of cache misses resolve_batch() resolves at once (default: 10).
+/// - cache_backend: Shared (L2) cache checked after a local cache miss (default: None).
+/// - web_resolver: Source of did:web DID Documents (default: None, fetched over HTTP).
+/// - methods: Custom DID method resolvers by method name, replacing any built-in method (default: None).
+/// - eviction_listener: Called when a DID Document is removed from the local cache (default: None).
+/// - self_test_dids: Sample DID per method resolved by self_test() (default: the methods resolved offline, no did:web).
+/// - fixtures: TESTING ONLY: DID Documents returned for known DIDs instead of resolving them (default: None).
 pub struct ClientConfigBuilder {
     #[cfg(feature = "network")]
     service_address: Option<String>,
     cache_capacity: u32,
+    cache_capacity_set: bool,
+    max_cache_bytes: Option<u64>,
+    cache_initial_capacity: Option<usize>,
+    cache_eviction_policy: CacheEvictionPolicy,
     cache_ttl: u32,
+    method_ttls: HashMap<DIDMethod, u32>,
+    negative_cache_ttl: Option<u32>,
     #[cfg(feature = "network")]
     network_timeout: u32,
     #[cfg(feature = "network")]
     network_cache_limit_count: u32,
+    #[cfg(feature = "network")]
+    network_busy_timeout: u32,
+    #[cfg(feature = "network")]
+    command_channel_capacity: usize,
+    #[cfg(feature = "network")]
+    response_channel_capacity: usize,
+    #[cfg(feature = "network")]
+    tls_min_version: Option<TlsVersion>,
+    #[cfg(feature = "network")]
+    tls_cipher_suites: Option<Vec<String>>,
+    #[cfg(feature = "network")]
+    tls_roots: Option<Vec<Vec<u8>>>,
+    #[cfg(feature = "network")]
+    danger_accept_invalid_certs: bool,
+    #[cfg(feature = "network")]
+    backoff: BackoffConfig,
+    #[cfg(feature = "network")]
+    keepalive_interval: u32,
+    #[cfg(feature = "network")]
+    keepalive_timeout: u32,
+    #[cfg(feature = "network")]
+    max_frame_bytes: usize,
     max_did_parts: usize,
     max_did_size_in_kb: f64,
+    canonicalize: bool,
+    dht_gateway: String,
+    ethr_rpc: HashMap<String, String>,
+    web_path_overrides: HashMap<String, String>,
+    http_proxy: Option<String>,
+    https_proxy: Option<String>,
+    proxy_auth: Option<ProxyAuth>,
+    offline: bool,
+    batch_concurrency: usize,
+    fixtures: Option<HashMap<String, Document>>,
+    cache_backend: Option<Arc<dyn CacheBackend>>,
+    web_resolver: Option<Arc<dyn WebResolver>>,
+    methods: HashMap<String, Arc<dyn MethodResolver>>,
+    eviction_listener: Option<EvictionListenerFn>,
+    self_test_dids: HashMap<DIDMethod, String>,
 }
 
 impl Default for ClientConfigBuilder {
@@ -70,13 +239,59 @@ impl Default for ClientConfigBuilder {
             #[cfg(feature = "network")]
             service_address: None,
             cache_capacity: 100,
+            cache_capacity_set: false,
+            max_cache_bytes: None,
+            cache_initial_capacity: None,
+            cache_eviction_policy: CacheEvictionPolicy::default(),
             cache_ttl: 300,
+            method_ttls: HashMap::new(),
+            negative_cache_ttl: None,
             #[cfg(feature = "network")]
             network_timeout: 5000,
             #[cfg(feature = "network")]
             network_cache_limit_count: 100,
+            #[cfg(feature = "network")]
+            network_busy_timeout: 5000,
+            #[cfg(feature = "network")]
+            command_channel_capacity: 32,
+            #[cfg(feature = "network")]
+            response_channel_capacity: 32,
+            #[cfg(feature = "network")]
+            tls_min_version: None,
+            #[cfg(feature = "network")]
+            tls_cipher_suites: None,
+            #[cfg(feature = "network")]
+            tls_roots: None,
+            #[cfg(feature = "network")]
+            danger_accept_invalid_certs: false,
+            #[cfg(feature = "network")]
+            backoff: BackoffConfig::default(),
+            #[cfg(feature = "network")]
+            keepalive_interval: 30000,
+            #[cfg(feature = "network")]
+            keepalive_timeout: 10000,
+            #[cfg(feature = "network")]
+            max_frame_bytes: 1024 * 1024,
             max_did_parts: 12,
             max_did_size_in_kb: 1.0,
+            canonicalize: false,
+            dht_gateway: DEFAULT_DHT_GATEWAY.to_string(),
+            ethr_rpc: HashMap::new(),
+            web_path_overrides: HashMap::new(),
+            http_proxy: None,
+            https_proxy: None,
+            proxy_auth: None,
+            offline: false,
+            batch_concurrency: 10,
+            fixtures: None,
+            cache_backend: None,
+            web_resolver: None,
+            methods: HashMap::new(),
+            eviction_listener: None,
+            self_test_dids: DEFAULT_SELF_TEST_DIDS
+                .iter()
+                .map(|(method, did)| (method.clone(), did.to_string()))
+                .collect(),
         }
     }
 }
@@ -94,6 +309,38 @@ impl ClientConfigBuilder {
     /// Default: 100 items
     pub fn with_cache_capacity(mut self, cache_capacity: u32) -> Self {
         self.cache_capacity = cache_capacity;
+        self.cache_capacity_set = true;
+        self
+    }
+
+    /// Limit the cache by the total size (in bytes) of the cached DID Documents
+    /// Useful when cached documents vary a lot in size (e.g. large did:peer vs small did:key)
+    ///
+    /// If [with_cache_capacity](Self::with_cache_capacity) is also called, both limits apply and
+    /// whichever is reached first evicts. Otherwise only the byte limit applies
+    ///
+    /// NOTE: The size of a DID Document is approximated by the length of its serde_json
+    ///       serialization (not its in-memory size), computed once per cache insert
+    ///       This costs a serialization of every DID Document added to the cache
+    /// Default: None (use cache_capacity)
+    pub fn with_cache_weight_bytes(mut self, cache_weight_bytes: u64) -> Self {
+        self.max_cache_bytes = Some(cache_weight_bytes);
+        self
+    }
+
+    /// Set how many entries the local cache allocates space for when it is created
+    /// Avoids resizing while a large cache warms up, costs the memory up front
+    /// Default: None (the cache grows as needed)
+    pub fn with_cache_initial_capacity(mut self, initial_capacity: usize) -> Self {
+        self.cache_initial_capacity = Some(initial_capacity);
+        self
+    }
+
+    /// Set which entries the local cache keeps when it is full, see [CacheEvictionPolicy]
+    /// Run `cargo run --release --example cache_bench` to compare policies for an access pattern
+    /// Default: TinyLfu
+    pub fn with_cache_eviction_policy(mut self, policy: CacheEvictionPolicy) -> Self {
+        self.cache_eviction_policy = policy;
         self
     }
 
@@ -104,6 +351,24 @@ impl ClientConfigBuilder {
         self
     }
 
+    /// Set the time-to-live in seconds for DID Documents of one DID method, overriding cache_ttl.
+    /// e.g. a short TTL for did:web (the document can change), a long one for did:key (immutable)
+    /// Methods without an override use cache_ttl
+    /// Default: None
+    pub fn with_method_ttl(mut self, method: DIDMethod, ttl: u32) -> Self {
+        self.method_ttls.insert(method, ttl);
+        self
+    }
+
+    /// Enables negative caching, failed resolutions are cached for negative_cache_ttl seconds.
+    /// Until the entry expires resolve() returns the original error without resolving again.
+    /// See [negative_cache](crate::negative_cache)
+    /// Default: None (failed resolutions aren't cached)
+    pub fn with_negative_cache_ttl(mut self, negative_cache_ttl: u32) -> Self {
+        self.negative_cache_ttl = Some(negative_cache_ttl);
+        self
+    }
+
     /// Set the timeout for network requests in milliseconds.
     /// Default: 5000 (5 seconds)
     #[cfg(feature = "network")]
@@ -120,6 +385,116 @@ impl ClientConfigBuilder {
         self
     }
 
+    /// Set how long (in milliseconds) a resolve waits for room when the network cache is full
+    /// (network_cache_limit_count requests already in flight), before returning
+    /// [Busy](crate::errors::DIDCacheError::Busy). While waiting, it backs off exponentially
+    /// with jitter. 0 returns Busy straight away, like [try_resolve](crate::DIDCacheClient::try_resolve)
+    /// Default: 5000 (5 seconds)
+    #[cfg(feature = "network")]
+    pub fn with_network_busy_timeout(mut self, busy_timeout: u32) -> Self {
+        self.network_busy_timeout = busy_timeout;
+        self
+    }
+
+    /// Set the capacity of the channel used to send requests from the SDK to the network task
+    /// When full, [resolve](crate::DIDCacheClient::resolve) waits for space to become available
+    /// A value of 0 is treated as 1
+    /// Default: 32
+    #[cfg(feature = "network")]
+    pub fn with_command_channel_capacity(mut self, capacity: usize) -> Self {
+        self.command_channel_capacity = capacity;
+        self
+    }
+
+    /// Set the capacity of the channel used by the network task to signal the SDK
+    /// A value of 0 is treated as 1
+    /// Default: 32
+    #[cfg(feature = "network")]
+    pub fn with_response_channel_capacity(mut self, capacity: usize) -> Self {
+        self.response_channel_capacity = capacity;
+        self
+    }
+
+    /// Set the minimum TLS version for wss:// connections
+    /// The server must support it, the connection fails rather than downgrading
+    /// Default: None (whatever the platform TLS library negotiates)
+    #[cfg(feature = "network")]
+    pub fn with_min_tls_version(mut self, version: TlsVersion) -> Self {
+        self.tls_min_version = Some(version);
+        self
+    }
+
+    /// Restrict the cipher suites offered for wss:// connections, by IANA name
+    /// (e.g. `TLS13_AES_256_GCM_SHA384`). See [supported_cipher_suites](crate::networking::tls::supported_cipher_suites)
+    /// Unknown cipher suites are a config error when the client is created
+    /// Default: None (all supported cipher suites)
+    #[cfg(feature = "network")]
+    pub fn with_tls_cipher_suites(mut self, cipher_suites: Vec<String>) -> Self {
+        self.tls_cipher_suites = Some(cipher_suites);
+        self
+    }
+
+    /// Trust only these root certificates (DER encoded) for wss:// connections, instead of the
+    /// platform trust store. e.g. a private CA in an air-gapped environment
+    /// Invalid certificates are a config error when the client is created
+    /// Default: None (platform trust store)
+    #[cfg(feature = "network")]
+    pub fn with_tls_roots(mut self, tls_roots: Vec<Vec<u8>>) -> Self {
+        self.tls_roots = Some(tls_roots);
+        self
+    }
+
+    /// INSECURE, TESTING ONLY: Accept any server certificate for wss:// connections
+    /// (self-signed, expired, wrong host name...).
+    /// This disables server authentication, anyone who can intercept the connection can
+    /// impersonate the DID Cache server and return forged DID Documents.
+    /// Never enable this outside of local testing
+    /// Default: false
+    #[cfg(feature = "network")]
+    pub fn with_danger_accept_invalid_certs(mut self, danger_accept_invalid_certs: bool) -> Self {
+        self.danger_accept_invalid_certs = danger_accept_invalid_certs;
+        self
+    }
+
+    /// Set the backoff between websocket reconnect attempts
+    /// e.g. a short linear backoff for fast server restarts, or exponential with jitter when
+    /// many clients share a server. See [BackoffConfig]
+    /// Default: linear, 5 second steps up to 60 seconds
+    #[cfg(feature = "network")]
+    pub fn with_backoff(mut self, backoff: BackoffConfig) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Set how often a websocket ping is sent to the server in milliseconds.
+    /// Keeps idle connections from being dropped by proxies, and detects a dead connection
+    /// before a resolve fails on it. 0 disables the keepalive
+    /// Default: 30000 (30 seconds)
+    #[cfg(feature = "network")]
+    pub fn with_keepalive_interval(mut self, keepalive_interval: u32) -> Self {
+        self.keepalive_interval = keepalive_interval;
+        self
+    }
+
+    /// Set how long to wait for the pong in milliseconds before the connection is treated as
+    /// dead and reconnected
+    /// Default: 10000 (10 seconds)
+    #[cfg(feature = "network")]
+    pub fn with_keepalive_timeout(mut self, keepalive_timeout: u32) -> Self {
+        self.keepalive_timeout = keepalive_timeout;
+        self
+    }
+
+    /// Set the largest websocket message (and frame) in bytes accepted from the server
+    /// Larger messages are rejected before they are buffered or parsed, and the connection is
+    /// reset. Protects against a malicious or broken server exhausting memory
+    /// Default: 1048576 (1 MiB)
+    #[cfg(feature = "network")]
+    pub fn with_max_frame_bytes(mut self, max_frame_bytes: usize) -> Self {
+        self.max_frame_bytes = max_frame_bytes;
+        self
+    }
+
     /// Set maximum number of parts after splitting method-specific-id on "."
     /// Default: 5 parts
     pub fn with_max_did_parts(mut self, max_did_parts: usize) -> Self {
@@ -127,26 +502,565 @@ impl ClientConfigBuilder {
         self
     }
 
-    /// Set maximum size in KB of did to be resolved as FLOAT
-    /// Default: 5 parts
+    /// Set maximum size in KB (1KB = 1000 bytes) of did to be resolved as FLOAT
+    /// Size is measured in UTF-8 bytes, not characters. A DID exactly at the limit is accepted,
+    /// larger DIDs return [DIDCacheError::DIDTooLarge] with the exact sizes
+    /// Default: 1.0 (1000 bytes)
     pub fn with_max_did_size_in_kb(mut self, max_did_size_in_kb: f64) -> Self {
         self.max_did_size_in_kb = max_did_size_in_kb;
         self
     }
 
+    /// Convert resolved DID Documents to a canonical form before they are cached and returned.
+    /// See [DocumentExt::canonicalize](crate::document::DocumentExt::canonicalize)
+    /// Default: false
+    pub fn with_canonicalize(mut self, canonicalize: bool) -> Self {
+        self.canonicalize = canonicalize;
+        self
+    }
+
+    /// Set the gateway used to read did:dht records from the Mainline DHT.
+    /// Any gateway implementing the Pkarr relay API (`GET <gateway>/<z-base-32 id>`) can be used,
+    /// e.g. a self-hosted relay, or a mock for offline tests.
+    /// Records are verified against the DID, so the gateway doesn't need to be trusted.
+    /// Default: https://relay.pkarr.org
+    pub fn with_dht_gateway(mut self, dht_gateway: &str) -> Self {
+        self.dht_gateway = dht_gateway.to_string();
+        self
+    }
+
+    /// Set the Ethereum JSON-RPC endpoint for a chain, used to look up did:ethr DIDs on that
+    /// chain in the ERC-1056 registry (e.g. an owner change or deactivation)
+    /// chain_id: hex (0x89) or decimal (137) chain id, or a network name (mainnet, goerli, ...)
+    /// Once any endpoint is set, resolving a did:ethr on a chain without one is a
+    /// [DIDCacheError::ConfigError]. An invalid chain id is a config error when built
+    /// Default: None (did:ethr DID Documents are derived from the DID alone, not checked)
+    pub fn with_ethr_rpc(mut self, chain_id: &str, rpc_url: &str) -> Self {
+        self.ethr_rpc
+            .insert(chain_id.to_string(), rpc_url.to_string());
+        self
+    }
+
+    /// Set where a host serves its did:web DID Documents, when it doesn't follow the spec
+    /// The path replaces `/.well-known` for the host's root DID, and DID paths are added under it
+    /// e.g. with `("example.com", "/dids")`:
+    /// - `did:web:example.com` -> `https://example.com/dids/did.json`
+    /// - `did:web:example.com:user:alice` -> `https://example.com/dids/user/alice/did.json`
+    ///
+    /// `"/"` serves the root DID from `/did.json` and leaves DID paths as they are
+    /// host: Domain name, with the port if the DID has one (`localhost:8080`)
+    /// A path with a query (`?`), fragment (`#`) or whitespace is a config error when built
+    /// Default: None (`/.well-known/did.json`, or `/<path>/did.json`)
+    pub fn with_web_path_override(mut self, host: &str, path: &str) -> Self {
+        self.web_path_overrides
+            .insert(host.to_string(), path.to_string());
+        self
+    }
+
+    /// Send http:// requests made while resolving (did:web on localhost, did:dht gateway)
+    /// through this proxy, e.g. `http://proxy.example.com:3128`
+    /// Hosts in the NO_PROXY environment variable still bypass the proxy
+    /// An invalid proxy URL is a config error when the client is created
+    /// Default: None (the HTTP_PROXY environment variable)
+    pub fn with_http_proxy(mut self, proxy: &str) -> Self {
+        self.http_proxy = Some(proxy.to_string());
+        self
+    }
+
+    /// Send https:// requests made while resolving (did:web, did:dht gateway) through this
+    /// proxy (HTTP CONNECT), e.g. `http://proxy.example.com:3128`
+    /// Hosts in the NO_PROXY environment variable still bypass the proxy
+    /// An invalid proxy URL is a config error when the client is created
+    /// Default: None (the HTTPS_PROXY environment variable)
+    pub fn with_https_proxy(mut self, proxy: &str) -> Self {
+        self.https_proxy = Some(proxy.to_string());
+        self
+    }
+
+    /// Authenticate to the http/https proxies with basic auth
+    /// Default: None
+    pub fn with_proxy_basic_auth(mut self, username: &str, password: &str) -> Self {
+        self.proxy_auth = Some(ProxyAuth {
+            username: username.to_string(),
+            password: password.to_string(),
+        });
+        self
+    }
+
+    /// Offline mode: the client never makes outbound requests, e.g. for sandboxed unit tests
+    /// or deterministic builds
+    /// Only self-contained DID methods (did:key, did:jwk, did:peer, did:pkh) are resolved,
+    /// others (did:web, did:dht, did:ethr) return [DIDCacheError::OfflineMethodUnsupported]
+    /// Fixtures, manually added DID Documents and a configured cache backend are still used
+    /// Can't be combined with network mode
+    /// Default: false
+    pub fn offline(mut self) -> Self {
+        self.offline = true;
+        self
+    }
+
+    /// Set the maximum number of cache misses resolved concurrently by
+    /// [resolve_batch](crate::DIDCacheClient::resolve_batch)
+    /// Values below 1 are treated as 1
+    /// Default: 10
+    pub fn with_batch_concurrency(mut self, batch_concurrency: usize) -> Self {
+        self.batch_concurrency = batch_concurrency;
+        self
+    }
+
+    /// TESTING ONLY: Resolve DIDs from an in-memory map of DID to DID Document.
+    /// DIDs in the map are returned as-is without any key derivation or network access,
+    /// DIDs not in the map fall through to normal resolution.
+    /// Useful for fast, hermetic tests with predictable DID Documents.
+    /// Default: None
+    pub fn with_fixture_resolver(mut self, fixtures: HashMap<String, Document>) -> Self {
+        self.fixtures = Some(fixtures);
+        self
+    }
+
+    /// Set a shared cache backend (e.g. Redis) that sits behind the local cache.
+    /// Checked after a local cache miss before resolving, resolved DID Documents are written back
+    /// with the cache_ttl. See [cache_backend](crate::cache_backend)
+    /// Default: None
+    pub fn with_cache_backend(mut self, cache_backend: Arc<dyn CacheBackend>) -> Self {
+        self.cache_backend = Some(cache_backend);
+        self
+    }
+
+    /// Set the source of did:web DID Documents, e.g. a `HashMap` of fixed documents for tests
+    /// did:web can then be resolved in offline mode. See [web_resolver](crate::web_resolver)
+    /// Default: None (the did.json is fetched over HTTP)
+    pub fn with_web_resolver(mut self, web_resolver: Arc<dyn WebResolver>) -> Self {
+        self.web_resolver = Some(web_resolver);
+        self
+    }
+
+    /// Resolve the DIDs of a DID method with this resolver, see [method_resolver](crate::method_resolver)
+    /// Adds a custom method (reported as [DIDMethod::CUSTOM], with its name in
+    /// [ResolveResponse::method_name](crate::ResolveResponse::method_name)) or replaces a built-in one
+    /// name: The method name, `mymethod` for `did:mymethod:...`
+    /// A name that isn't lowercase letters and digits is a config error when built
+    /// NOTE: Custom methods are resolved locally, also in network mode
+    /// Default: None (the built-in methods only)
+    pub fn with_method(mut self, name: &str, resolver: Arc<dyn MethodResolver>) -> Self {
+        self.methods.insert(name.to_string(), resolver);
+        self
+    }
+
+    /// Set a listener that is called whenever a DID Document leaves the local cache
+    /// e.g. to log or emit metrics on expiry and capacity evictions
+    /// The [RemovalCause] tells expiry (Expired) and capacity evictions (Size) apart from
+    /// remove()/clear() (Explicit) and a newer DID Document for the same DID (Replaced)
+    ///
+    /// The listener runs on moka's maintenance task, which also blocks cache housekeeping:
+    /// keep it fast and don't call back into the client from it
+    /// Default: None
+    pub fn with_eviction_listener(mut self, listener: EvictionListenerFn) -> Self {
+        self.eviction_listener = Some(listener);
+        self
+    }
+
+    /// Set the known-good DID that [self_test](crate::DIDCacheClient::self_test) resolves for a
+    /// method, e.g. a did:web on your own host
+    /// Methods without a sample DID aren't self tested, so health checks don't depend on hosts
+    /// you don't run
+    /// Default: a sample for each method that resolves offline (ethr, jwk, key, peer, pkh)
+    pub fn with_self_test_did(mut self, method: DIDMethod, did: &str) -> Self {
+        self.self_test_dids.insert(method, did.to_string());
+        self
+    }
+
+    /// Build the [ClientConfig], rejecting invalid settings with a [DIDCacheError::ConfigError]
+    /// - cache_capacity (or max_cache_bytes if set) must be greater than 0
+    /// - max_did_size_in_kb must be greater than 0
+    /// - service_address must be a ws:// or wss:// URL
+    /// - ethr_rpc chain ids must be chain ids or network names
+    /// - web_path_overrides paths must be URL paths
+    /// - methods names must be DID method names
+    pub fn try_build(self) -> Result<ClientConfig, DIDCacheError> {
+        match self._invalid_settings().into_iter().next() {
+            Some(error) => Err(DIDCacheError::ConfigError(error)),
+            None => Ok(self._build()),
+        }
+    }
+
     /// Build the [ClientConfig].
-    pub fn build(self) -> ClientConfig {
+    /// Invalid settings (see [try_build](Self::try_build)) are replaced by their defaults with a
+    /// warning, except an invalid service_address which is kept (connecting to it will fail)
+    /// and ethr_rpc endpoints with an invalid chain id, invalid web_path_overrides or methods
+    /// with an invalid name, which are dropped
+    pub fn build(mut self) -> ClientConfig {
+        let defaults = ClientConfigBuilder::default();
+        for error in self._invalid_settings() {
+            warn!("{}", error);
+        }
+        if self.cache_capacity == 0 {
+            self.cache_capacity = defaults.cache_capacity;
+        }
+        if self.max_cache_bytes == Some(0) {
+            self.max_cache_bytes = defaults.max_cache_bytes;
+        }
+        if self.max_did_size_in_kb.is_nan() || self.max_did_size_in_kb <= 0.0 {
+            self.max_did_size_in_kb = defaults.max_did_size_in_kb;
+        }
+        #[cfg(feature = "network")]
+        if self.max_frame_bytes == 0 {
+            self.max_frame_bytes = defaults.max_frame_bytes;
+        }
+        // Offline is a guarantee, so it wins over network mode
+        #[cfg(feature = "network")]
+        if self.offline {
+            self.service_address = None;
+        }
+
+        self._build()
+    }
+
+    /// Returns a message for each invalid setting
+    fn _invalid_settings(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.cache_capacity == 0 {
+            errors.push("cache_capacity must be greater than 0".to_string());
+        }
+        if self.max_cache_bytes == Some(0) {
+            errors.push("max_cache_bytes must be greater than 0".to_string());
+        }
+        if self.max_did_size_in_kb.is_nan() || self.max_did_size_in_kb <= 0.0 {
+            errors.push(format!(
+                "max_did_size_in_kb must be greater than 0 (found {}), every DID would be rejected",
+                self.max_did_size_in_kb
+            ));
+        }
+        #[cfg(feature = "network")]
+        if self.max_frame_bytes == 0 {
+            errors.push("max_frame_bytes must be greater than 0".to_string());
+        }
+        for chain in self.ethr_rpc.keys() {
+            if ethr::chain_id(chain).is_none() {
+                errors.push(format!(
+                    "ethr_rpc chain id ({}) must be a chain id (e.g. 0x89) or network name",
+                    chain
+                ));
+            }
+        }
+        for (host, path) in &self.web_path_overrides {
+            if !_is_url_path(path) {
+                errors.push(format!(
+                    "web_path_override ({}) path ({}) must be a URL path (e.g. /dids)",
+                    host, path
+                ));
+            }
+        }
+        for name in self.methods.keys() {
+            if !is_method_name(name) {
+                errors.push(format!(
+                    "method name ({}) must be lowercase letters and digits",
+                    name
+                ));
+            }
+        }
+        #[cfg(feature = "network")]
+        if self.offline && self.service_address.is_some() {
+            errors.push("offline mode can't be used with network mode".to_string());
+        }
+        #[cfg(feature = "network")]
+        if let Some(address) = &self.service_address {
+            let valid = ["ws://", "wss://"].iter().any(|scheme| {
+                address
+                    .get(..scheme.len())
+                    .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+                    && address.len() > scheme.len()
+            });
+            if !valid {
+                errors.push(format!(
+                    "service_address ({}) must be a ws:// or wss:// URL",
+                    address
+                ));
+            }
+        }
+        errors
+    }
+
+    fn _build(self) -> ClientConfig {
         ClientConfig {
             #[cfg(feature = "network")]
             service_address: self.service_address,
             cache_capacity: self.cache_capacity,
+            cache_capacity_set: self.cache_capacity_set,
+            max_cache_bytes: self.max_cache_bytes,
+            cache_initial_capacity: self.cache_initial_capacity,
+            cache_eviction_policy: self.cache_eviction_policy,
             cache_ttl: self.cache_ttl,
+            method_ttls: self.method_ttls,
+            negative_cache_ttl: self.negative_cache_ttl,
             #[cfg(feature = "network")]
             network_timeout: Duration::from_millis(self.network_timeout.into()),
             #[cfg(feature = "network")]
             network_cache_limit_count: self.network_cache_limit_count,
+            #[cfg(feature = "network")]
+            network_busy_timeout: Duration::from_millis(self.network_busy_timeout.into()),
+            #[cfg(feature = "network")]
+            command_channel_capacity: self.command_channel_capacity.max(1),
+            #[cfg(feature = "network")]
+            response_channel_capacity: self.response_channel_capacity.max(1),
+            #[cfg(feature = "network")]
+            tls_min_version: self.tls_min_version,
+            #[cfg(feature = "network")]
+            tls_cipher_suites: self.tls_cipher_suites,
+            #[cfg(feature = "network")]
+            tls_roots: self.tls_roots,
+            #[cfg(feature = "network")]
+            danger_accept_invalid_certs: self.danger_accept_invalid_certs,
+            #[cfg(feature = "network")]
+            backoff: self.backoff,
+            #[cfg(feature = "network")]
+            keepalive_interval: Duration::from_millis(self.keepalive_interval.into()),
+            #[cfg(feature = "network")]
+            keepalive_timeout: Duration::from_millis(self.keepalive_timeout.into()),
+            #[cfg(feature = "network")]
+            max_frame_bytes: self.max_frame_bytes,
             max_did_parts: self.max_did_parts,
             max_did_size_in_kb: self.max_did_size_in_kb,
+            canonicalize: self.canonicalize,
+            dht_gateway: self.dht_gateway,
+            ethr_rpc: self
+                .ethr_rpc
+                .into_iter()
+                .filter_map(|(chain, url)| Some((ethr::chain_key(ethr::chain_id(&chain)?), url)))
+                .collect(),
+            web_path_overrides: self
+                .web_path_overrides
+                .into_iter()
+                .filter(|(_, path)| _is_url_path(path))
+                .map(|(host, path)| (host.to_lowercase(), path))
+                .collect(),
+            http_proxy: self.http_proxy,
+            https_proxy: self.https_proxy,
+            proxy_auth: self.proxy_auth,
+            offline: self.offline,
+            batch_concurrency: self.batch_concurrency.max(1),
+            fixtures: self.fixtures,
+            cache_backend: self.cache_backend,
+            web_resolver: self.web_resolver,
+            methods: self
+                .methods
+                .into_iter()
+                .filter(|(name, _)| is_method_name(name))
+                .collect(),
+            eviction_listener: self.eviction_listener.map(EvictionListener),
+            self_test_dids: self.self_test_dids,
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Time-to-live for DID Documents of a DID method (cache_ttl unless overridden)
+    pub(crate) fn cache_ttl_for(&self, method: &str) -> Duration {
+        let ttl = self
+            .method(method)
+            .ok()
+            .and_then(|method| self.method_ttls.get(&method))
+            .unwrap_or(&self.cache_ttl);
+        Duration::from_secs((*ttl).into())
+    }
+
+    /// The [DIDMethod] of a method name, [DIDMethod::CUSTOM] for a registered custom method
+    pub(crate) fn method(&self, name: &str) -> Result<DIDMethod, DIDCacheError> {
+        DIDMethod::try_from(name).or_else(|e| match self.methods.contains_key(name) {
+            true => Ok(DIDMethod::CUSTOM),
+            false => Err(e),
+        })
+    }
+}
+
+/// Can the path be used as the path of a URL? (no query, fragment or whitespace)
+fn _is_url_path(path: &str) -> bool {
+    !path.contains(['?', '#']) && !path.contains(char::is_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClientConfigBuilder;
+    use crate::{errors::DIDCacheError, method_resolver::MethodResolver, DIDMethod};
+    use ssi::dids::Document;
+    use std::sync::Arc;
+
+    fn _config_error(builder: ClientConfigBuilder) -> String {
+        match builder.try_build() {
+            Err(DIDCacheError::ConfigError(msg)) => msg,
+            other => panic!("expected ConfigError, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn try_build_valid() {
+        assert!(ClientConfigBuilder::default().try_build().is_ok());
+    }
+
+    #[test]
+    fn invalid_cache_capacity() {
+        let builder = || ClientConfigBuilder::default().with_cache_capacity(0);
+        assert_eq!(
+            _config_error(builder()),
+            "cache_capacity must be greater than 0"
+        );
+        assert_eq!(builder().build().cache_capacity, 100);
+
+        let builder = || ClientConfigBuilder::default().with_cache_weight_bytes(0);
+        assert_eq!(
+            _config_error(builder()),
+            "max_cache_bytes must be greater than 0"
+        );
+        assert_eq!(builder().build().max_cache_bytes, None);
+    }
+
+    #[test]
+    fn invalid_max_did_size() {
+        for size in [0.0, -1.0, f64::NAN] {
+            let builder = || ClientConfigBuilder::default().with_max_did_size_in_kb(size);
+            assert!(
+                _config_error(builder()).starts_with("max_did_size_in_kb must be greater than 0")
+            );
+            assert_eq!(builder().build().max_did_size_in_kb, 1.0);
+        }
+    }
+
+    #[cfg(feature = "network")]
+    #[test]
+    fn invalid_max_frame_bytes() {
+        let builder = || ClientConfigBuilder::default().with_max_frame_bytes(0);
+        assert_eq!(
+            _config_error(builder()),
+            "max_frame_bytes must be greater than 0"
+        );
+        assert_eq!(builder().build().max_frame_bytes, 1024 * 1024);
+    }
+
+    #[cfg(feature = "network")]
+    #[test]
+    fn invalid_service_address() {
+        for address in ["http://127.0.0.1:8080", "127.0.0.1:8080", "ws://", ""] {
+            let builder = || ClientConfigBuilder::default().with_network_mode(address);
+            assert_eq!(
+                _config_error(builder()),
+                format!(
+                    "service_address ({}) must be a ws:// or wss:// URL",
+                    address
+                )
+            );
+            // Kept as is, there is no sane default
+            assert_eq!(builder().build().service_address.as_deref(), Some(address));
         }
+
+        for address in [
+            "ws://127.0.0.1:8080/did/v1/ws",
+            "WSS://example.com/did/v1/ws",
+        ] {
+            assert!(ClientConfigBuilder::default()
+                .with_network_mode(address)
+                .try_build()
+                .is_ok());
+        }
+    }
+
+    #[cfg(feature = "network")]
+    #[test]
+    fn offline_with_network_mode() {
+        let builder = || {
+            ClientConfigBuilder::default()
+                .with_network_mode("ws://127.0.0.1:8080/did/v1/ws")
+                .offline()
+        };
+        assert_eq!(
+            _config_error(builder()),
+            "offline mode can't be used with network mode"
+        );
+        // Offline wins, no outbound connection is made
+        let config = builder().build();
+        assert!(config.offline);
+        assert!(config.service_address.is_none());
+    }
+
+    #[cfg(feature = "network")]
+    #[test]
+    fn channel_capacities() {
+        let config = ClientConfigBuilder::default().build();
+        assert_eq!(config.command_channel_capacity, 32);
+        assert_eq!(config.response_channel_capacity, 32);
+
+        let config = ClientConfigBuilder::default()
+            .with_command_channel_capacity(1024)
+            .with_response_channel_capacity(0)
+            .build();
+        assert_eq!(config.command_channel_capacity, 1024);
+        assert_eq!(config.response_channel_capacity, 1);
+    }
+
+    #[test]
+    fn ethr_rpc_chain_ids() {
+        let builder = || {
+            ClientConfigBuilder::default()
+                .with_ethr_rpc("mainnet", "http://mainnet")
+                .with_ethr_rpc("137", "http://polygon")
+                .with_ethr_rpc("polygon", "http://invalid")
+        };
+        assert_eq!(
+            _config_error(builder()),
+            "ethr_rpc chain id (polygon) must be a chain id (e.g. 0x89) or network name"
+        );
+
+        // Keyed by hex chain id, the invalid chain is dropped
+        let config = builder().build();
+        assert_eq!(config.ethr_rpc.len(), 2);
+        assert_eq!(config.ethr_rpc["0x1"], "http://mainnet");
+        assert_eq!(config.ethr_rpc["0x89"], "http://polygon");
+    }
+
+    #[test]
+    fn web_path_overrides() {
+        let builder = || {
+            ClientConfigBuilder::default()
+                .with_web_path_override("Example.COM", "/dids/")
+                .with_web_path_override("example.org", "/dids?v=1")
+        };
+        assert_eq!(
+            _config_error(builder()),
+            "web_path_override (example.org) path (/dids?v=1) must be a URL path (e.g. /dids)"
+        );
+
+        // Hosts are lowercase, the invalid path is dropped
+        let config = builder().build();
+        assert_eq!(config.web_path_overrides.len(), 1);
+        assert_eq!(config.web_path_overrides["example.com"], "/dids/");
+    }
+
+    #[test]
+    fn method_names() {
+        #[derive(Debug)]
+        struct NoMethod;
+
+        #[async_trait::async_trait]
+        impl MethodResolver for NoMethod {
+            async fn resolve(&self, did: &ssi::dids::DID) -> Result<Document, DIDCacheError> {
+                Err(DIDCacheError::NotFound(did.to_string()))
+            }
+        }
+
+        let builder = || {
+            ClientConfigBuilder::default()
+                .with_method("test", Arc::new(NoMethod))
+                .with_method("my-method", Arc::new(NoMethod))
+        };
+        assert_eq!(
+            _config_error(builder()),
+            "method name (my-method) must be lowercase letters and digits"
+        );
+
+        // The invalid name is dropped
+        let config = builder().build();
+        assert_eq!(config.methods.len(), 1);
+        assert_eq!(config.method("test").unwrap(), DIDMethod::CUSTOM);
+        assert_eq!(config.method("key").unwrap(), DIDMethod::KEY);
+        assert!(config.method("other").is_err());
     }
 }
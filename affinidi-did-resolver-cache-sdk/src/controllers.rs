@@ -0,0 +1,134 @@
+//! Resolution of a DID's controller chain
+//!
+//! A DID Document's `controller` can be one or more other DIDs, which may in turn have
+//! their own controllers (e.g. a key DID controlled by an organisation DID).
+//! [DIDCacheClient::resolve_controllers] walks this chain for authorization checks.
+
+use crate::{errors::DIDCacheError, DIDCacheClient};
+use ssi::dids::Document;
+use std::collections::{HashSet, VecDeque};
+use tracing::debug;
+
+/// Maximum number of controller levels followed before giving up
+pub const MAX_CONTROLLER_DEPTH: usize = 5;
+
+impl DIDCacheClient {
+    /// Resolves the controllers of a DID, and their controllers, and so on
+    /// Returns the controller DID Documents in breadth first order (direct controllers first)
+    /// The DID itself is not included, even if it is self-controlled
+    ///
+    /// Each DID is only resolved once, so cycles (A -> B -> A) are handled
+    /// Returns an error if any controller can't be resolved, or if the chain is deeper than
+    /// [MAX_CONTROLLER_DEPTH] as an incomplete chain isn't safe to authorize against
+    pub async fn resolve_controllers(&self, did: &str) -> Result<Vec<Document>, DIDCacheError> {
+        let root = self.resolve(did).await?.doc;
+
+        let mut visited = HashSet::from([did.to_string(), root.id.to_string()]);
+        let mut queue: VecDeque<(String, usize)> = _controllers(&root)
+            .into_iter()
+            .map(|controller| (controller, 1))
+            .collect();
+        let mut controllers = Vec::new();
+
+        while let Some((controller, depth)) = queue.pop_front() {
+            if !visited.insert(controller.clone()) {
+                debug!("controller ({}) already visited, skipping", controller);
+                continue;
+            }
+            if depth > MAX_CONTROLLER_DEPTH {
+                return Err(DIDCacheError::DIDError(format!(
+                    "Controller chain for did ({}) exceeds the max depth of {}",
+                    did, MAX_CONTROLLER_DEPTH
+                )));
+            }
+
+            let doc = self.resolve(&controller).await?.doc;
+            queue.extend(_controllers(&doc).into_iter().map(|next| (next, depth + 1)));
+            controllers.push(doc);
+        }
+
+        Ok(controllers)
+    }
+}
+
+/// Returns the controller DIDs of a DID Document
+fn _controllers(doc: &Document) -> Vec<String> {
+    doc.controller
+        .as_ref()
+        .map(|controllers| {
+            controllers
+                .as_slice()
+                .iter()
+                .map(|controller| controller.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::client_with_docs;
+
+    fn _did(n: usize) -> String {
+        format!("did:web:org{}.example.com", n)
+    }
+
+    fn _doc(did: &str, controllers: &[String]) -> Document {
+        serde_json::from_value(serde_json::json!({
+            "id": did,
+            "controller": controllers,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn resolves_chain() {
+        // 0 is controlled by 1 and 2, 2 is controlled by 3
+        let client = client_with_docs(vec![
+            _doc(&_did(0), &[_did(1), _did(2)]),
+            _doc(&_did(1), &[]),
+            _doc(&_did(2), &[_did(3)]),
+            _doc(&_did(3), &[]),
+        ])
+        .await;
+
+        let controllers = client.resolve_controllers(&_did(0)).await.unwrap();
+        let ids: Vec<String> = controllers.iter().map(|doc| doc.id.to_string()).collect();
+        assert_eq!(ids, vec![_did(1), _did(2), _did(3)]);
+    }
+
+    #[tokio::test]
+    async fn handles_cycles() {
+        // 0 -> 1 -> 2 -> 0, and 1 is self-controlled
+        let client = client_with_docs(vec![
+            _doc(&_did(0), &[_did(1)]),
+            _doc(&_did(1), &[_did(1), _did(2)]),
+            _doc(&_did(2), &[_did(0)]),
+        ])
+        .await;
+
+        let controllers = client.resolve_controllers(&_did(0)).await.unwrap();
+        assert_eq!(controllers.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn no_controllers() {
+        let client = client_with_docs(vec![_doc(&_did(0), &[])]).await;
+
+        assert!(client
+            .resolve_controllers(&_did(0))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn max_depth_exceeded() {
+        let docs = (0..=MAX_CONTROLLER_DEPTH + 1).map(|n| _doc(&_did(n), &[_did(n + 1)]));
+        let client = client_with_docs(docs).await;
+
+        let result = client.resolve_controllers(&_did(0)).await;
+        assert!(matches!(result, Err(DIDCacheError::DIDError(msg)) if msg.contains("max depth")));
+    }
+}
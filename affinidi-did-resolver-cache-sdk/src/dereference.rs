@@ -0,0 +1,209 @@
+//! DID URL dereferencing
+//!
+//! [DIDCacheClient::dereference] resolves the DID of a DID URL (through the cache) and returns
+//! the part of the DID Document the DID URL points at, e.g. a verification method or service.
+//!
+//! See: <https://www.w3.org/TR/did-core/#did-url-dereferencing>
+
+use crate::{errors::DIDCacheError, metadata::DocumentMetadata, DIDCacheClient};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ssi::dids::{Document, DIDURL};
+
+/// The result of dereferencing a DID URL
+/// did: The DID the DID URL is relative to
+/// path, query, fragment: The DID URL components, if present
+/// content: The dereferenced JSON value (the DID Document if there is no fragment)
+/// content_metadata: DID Document Metadata of the resolved DID Document
+/// cache_hit: true if the DID Document came from the cache
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DereferenceResponse {
+    pub did_url: String,
+    pub did: String,
+    pub path: Option<String>,
+    pub query: Option<String>,
+    pub fragment: Option<String>,
+    pub content: Value,
+    pub content_metadata: DocumentMetadata,
+    pub cache_hit: bool,
+}
+
+impl DIDCacheClient {
+    /// Dereferences a DID URL (e.g. `did:example:123#key-1`)
+    /// Resolves the DID using the cache, then selects the verification method or service whose
+    /// id matches the fragment, in either absolute (`did:example:123#key-1`) or relative
+    /// (`#key-1`) form
+    ///
+    /// A DID URL without a fragment, path or query returns the whole DID Document
+    /// Path and query dereferencing is method specific and isn't supported, unless a fragment
+    /// selects the content
+    pub async fn dereference(&self, did_url: &str) -> Result<DereferenceResponse, DIDCacheError> {
+        let did_url = did_url.trim();
+        let parsed = DIDURL::new(did_url.as_bytes()).map_err(|_| {
+            DIDCacheError::DIDError(format!("DID URL ({}) isn't a valid DID URL", did_url))
+        })?;
+
+        let path = Some(parsed.path().as_str())
+            .filter(|p| !p.is_empty())
+            .map(str::to_string);
+        let query = parsed.query().map(|q| q.as_str().to_string());
+        let fragment = parsed.fragment().map(|f| f.as_str().to_string());
+
+        let response = self.resolve(parsed.did().as_str()).await?;
+
+        let content = match &fragment {
+            Some(fragment) => _select_fragment(&response.doc, fragment).ok_or_else(|| {
+                DIDCacheError::NotFound(format!("DID URL ({}) isn't in the DID Document", did_url))
+            })?,
+            None if path.is_some() || query.is_some() => {
+                return Err(DIDCacheError::DIDError(format!(
+                    "DID URL ({}) path and query dereferencing isn't supported",
+                    did_url
+                )))
+            }
+            None => serde_json::to_value(&response.doc).map_err(|e| {
+                DIDCacheError::DIDError(format!("Couldn't serialize DID Document: {}", e))
+            })?,
+        };
+
+        Ok(DereferenceResponse {
+            did_url: did_url.to_string(),
+            did: response.did,
+            path,
+            query,
+            fragment,
+            content,
+            content_metadata: response.document_metadata,
+            cache_hit: response.cache_hit,
+        })
+    }
+}
+
+/// Finds the verification method or service with the fragment as its id
+fn _select_fragment(doc: &Document, fragment: &str) -> Option<Value> {
+    let absolute = format!("{}#{}", doc.id, fragment);
+    let relative = format!("#{}", fragment);
+    let matches = |id: &str| id == absolute || id == relative;
+
+    if let Some(vm) = doc
+        .verification_method
+        .iter()
+        .find(|vm| matches(vm.id.as_str()))
+    {
+        return serde_json::to_value(vm).ok();
+    }
+
+    doc.service
+        .iter()
+        .find(|service| matches(service.id.as_str()))
+        .and_then(|service| serde_json::to_value(service).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{errors::DIDCacheError, test_fixtures::client_with_docs};
+    use serde_json::json;
+    use ssi::dids::Document;
+
+    const DID: &str = "did:web:example.com";
+
+    fn _doc() -> Document {
+        serde_json::from_value(json!({
+            "id": DID,
+            "verificationMethod": [{
+                "id": "did:web:example.com#key-1",
+                "type": "JsonWebKey2020",
+                "controller": DID,
+                "publicKeyJwk": {
+                    "kty": "OKP",
+                    "crv": "Ed25519",
+                    "x": "Pc-Ki0MHOiiinc3tFSsmeTl6rsIyHAMF6fBgd-nx14c"
+                }
+            }],
+            "service": [
+                {
+                    "id": "did:web:example.com#didcomm",
+                    "type": "DIDCommMessaging",
+                    "serviceEndpoint": "https://example.com/didcomm"
+                },
+                {
+                    "id": "did:web:example.com#linked-domain",
+                    "type": "LinkedDomains",
+                    "serviceEndpoint": "https://example.com"
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn verification_method() {
+        let client = client_with_docs([_doc()]).await;
+
+        let response = client
+            .dereference("did:web:example.com#key-1")
+            .await
+            .unwrap();
+        assert_eq!(response.did, DID);
+        assert_eq!(response.fragment.as_deref(), Some("key-1"));
+        assert_eq!(response.content["id"], "did:web:example.com#key-1");
+        assert_eq!(response.content["type"], "JsonWebKey2020");
+    }
+
+    #[tokio::test]
+    async fn services() {
+        let client = client_with_docs([_doc()]).await;
+
+        let response = client
+            .dereference("did:web:example.com#didcomm")
+            .await
+            .unwrap();
+        assert_eq!(response.content["type"], "DIDCommMessaging");
+
+        let response = client
+            .dereference("did:web:example.com#linked-domain")
+            .await
+            .unwrap();
+        assert_eq!(response.content["serviceEndpoint"], "https://example.com");
+        assert!(response.cache_hit);
+    }
+
+    #[tokio::test]
+    async fn no_fragment_returns_document() {
+        let client = client_with_docs([_doc()]).await;
+
+        let response = client.dereference(DID).await.unwrap();
+        assert_eq!(response.content["id"], DID);
+        assert!(response.path.is_none());
+        assert!(response.query.is_none());
+    }
+
+    #[tokio::test]
+    async fn components_parsed() {
+        let client = client_with_docs([_doc()]).await;
+
+        let response = client
+            .dereference("did:web:example.com/path?versionId=1#key-1")
+            .await
+            .unwrap();
+        assert_eq!(response.path.as_deref(), Some("/path"));
+        assert_eq!(response.query.as_deref(), Some("versionId=1"));
+        assert_eq!(response.fragment.as_deref(), Some("key-1"));
+        assert_eq!(response.content["id"], "did:web:example.com#key-1");
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let client = client_with_docs([_doc()]).await;
+
+        assert!(matches!(
+            client.dereference("did:web:example.com#missing").await,
+            Err(DIDCacheError::NotFound(_))
+        ));
+        assert!(matches!(
+            client.dereference("did:web:example.com/path").await,
+            Err(DIDCacheError::DIDError(_))
+        ));
+        assert!(client.dereference("not a did url").await.is_err());
+    }
+}
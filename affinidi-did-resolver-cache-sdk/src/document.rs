@@ -1,13 +1,19 @@
 //! Extends the SSI Crate Document with new methods and functions
 
+use crate::errors::DIDCacheError;
+use serde_json::Value;
 use ssi::{
     dids::{
-        document::{DIDVerificationMethod, ResourceRef},
-        Document, DIDURL,
+        document::{verification_method::ValueOrReference, DIDVerificationMethod, ResourceRef},
+        DIDURLReferenceBuf, Document, DID, DIDURL,
     },
+    jwk::{Base64urlUInt, OctetParams, Params},
+    multicodec::MultiEncoded,
     verification_methods::ProofPurposes,
+    JWK,
 };
-use tracing::warn;
+use std::{collections::BTreeMap, fmt};
+use tracing::{debug, warn};
 
 pub trait DocumentExt {
     /// Does this DID contain authentication verification_method with the given id?
@@ -22,6 +28,13 @@ pub trait DocumentExt {
 
     /// Returns a DID Verification Method if found by ID
     fn get_verification_method(&self, id: &str) -> Option<&DIDVerificationMethod>;
+
+    /// Returns a canonical form of this DID Document
+    /// Two semantically equivalent documents will serialize to identical bytes
+    /// - verification methods and services are ordered by id
+    /// - verification relationships use absolute ids and are ordered by id
+    /// - publicKeyMultibase keys are converted to publicKeyJwk (JsonWebKey2020) where possible
+    fn canonicalize(&self) -> Document;
 }
 
 impl DocumentExt for Document {
@@ -105,49 +118,494 @@ impl DocumentExt for Document {
             None
         }
     }
+
+    fn canonicalize(&self) -> Document {
+        let mut doc = self.clone();
+        let did = self.id.as_did();
+
+        for vm in doc.verification_method.iter_mut() {
+            _canonicalize_vm(vm);
+        }
+        doc.verification_method
+            .sort_by(|a, b| a.id.as_str().cmp(b.id.as_str()));
+
+        let relationships = &mut doc.verification_relationships;
+        for relationship in [
+            &mut relationships.authentication,
+            &mut relationships.assertion_method,
+            &mut relationships.key_agreement,
+            &mut relationships.capability_invocation,
+            &mut relationships.capability_delegation,
+        ] {
+            _canonicalize_relationship(did, relationship);
+        }
+
+        doc.service.sort_by(|a, b| a.id.as_str().cmp(b.id.as_str()));
+
+        doc
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::{config, DIDCacheClient};
+/// Converts a publicKeyMultibase verification method to a publicKeyJwk (JsonWebKey2020)
+/// If the key can't be converted, the verification method is left unchanged
+fn _canonicalize_vm(vm: &mut DIDVerificationMethod) {
+    let Some(Value::String(key)) = vm.properties.get("publicKeyMultibase") else {
+        return;
+    };
 
-    use super::*;
+    match _multibase_jwk(key).map(|jwk| serde_json::to_value(jwk.to_public())) {
+        Some(Ok(jwk)) => {
+            vm.properties.remove("publicKeyMultibase");
+            vm.properties.insert("publicKeyJwk".to_string(), jwk);
+            vm.type_ = "JsonWebKey2020".to_string();
+        }
+        _ => {
+            debug!(
+                "Couldn't convert publicKeyMultibase to JWK for ({}), leaving as is",
+                vm.id
+            );
+        }
+    }
+}
+
+/// Decodes a multicodec publicKeyMultibase to a JWK
+fn _multibase_jwk(key: &str) -> Option<JWK> {
+    let (_, bytes) = multibase::decode(key).ok()?;
+    JWK::from_multicodec(MultiEncoded::new(&bytes).ok()?).ok()
+}
+
+/// Converts a raw base58 public key to a JWK, using the verification method type for the curve
+fn _base58_jwk(type_: &str, key: &str) -> Option<JWK> {
+    let bytes = multibase::Base::Base58Btc.decode(key).ok()?;
+    match type_ {
+        "Ed25519VerificationKey2018" | "Ed25519VerificationKey2020" => {
+            ssi::jwk::ed25519_parse(&bytes).ok()
+        }
+        "X25519KeyAgreementKey2019" | "X25519KeyAgreementKey2020" => {
+            Some(JWK::from(Params::OKP(OctetParams {
+                curve: "X25519".into(),
+                public_key: Base64urlUInt(bytes),
+                private_key: None,
+            })))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the verification methods of a verification relationship
+/// References are looked up in the document's verificationMethod, and are skipped if not found
+pub(crate) fn relationship_methods<'a>(
+    doc: &'a Document,
+    relationship: &'a [ValueOrReference],
+) -> Vec<&'a DIDVerificationMethod> {
+    let did = doc.id.as_did();
+    relationship
+        .iter()
+        .filter_map(|item| match item {
+            ValueOrReference::Value(vm) => Some(vm),
+            ValueOrReference::Reference(_) => {
+                let id = item.id().resolve(did).to_string();
+                let vm = doc
+                    .verification_method
+                    .iter()
+                    .find(|vm| vm.id.as_str() == id);
+                if vm.is_none() {
+                    warn!("verification method ({}) not found in DID Document", id);
+                }
+                vm
+            }
+        })
+        .collect()
+}
+
+/// Resolves a verification method id against the DID Document id
+/// `#key-1` and `key-1` become `<did>#key-1`, full DID URLs are returned as-is
+pub(crate) fn absolute_vm_id(doc: &Document, id: &str) -> String {
+    if let Some(fragment) = id.strip_prefix('#') {
+        [doc.id.as_str(), "#", fragment].concat()
+    } else if id.starts_with("did:") {
+        id.to_string()
+    } else {
+        [doc.id.as_str(), "#", id].concat()
+    }
+}
+
+/// Returns the public key of a verification method as a JWK
+/// Supports publicKeyJwk, (multicodec) publicKeyMultibase and publicKeyBase58 keys
+pub(crate) fn verification_method_jwk(vm: &DIDVerificationMethod) -> Result<JWK, DIDCacheError> {
+    if let Some(jwk) = vm.properties.get("publicKeyJwk") {
+        serde_json::from_value::<JWK>(jwk.clone()).map_err(|e| {
+            DIDCacheError::DIDError(format!("Invalid publicKeyJwk in ({}): {}", vm.id, e))
+        })
+    } else if let Some(Value::String(key)) = vm.properties.get("publicKeyMultibase") {
+        _multibase_jwk(key).ok_or_else(|| {
+            DIDCacheError::DIDError(format!(
+                "Couldn't convert publicKeyMultibase to JWK in ({})",
+                vm.id
+            ))
+        })
+    } else if let Some(Value::String(key)) = vm.properties.get("publicKeyBase58") {
+        _base58_jwk(&vm.type_, key).ok_or_else(|| {
+            DIDCacheError::DIDError(format!(
+                "Couldn't convert publicKeyBase58 ({}) to JWK in ({})",
+                vm.type_, vm.id
+            ))
+        })
+    } else {
+        Err(DIDCacheError::DIDError(format!(
+            "verification method ({}) has no supported public key",
+            vm.id
+        )))
+    }
+    .map(|jwk| jwk.to_public())
+}
+
+/// Converts all references to absolute ids, and orders them by id
+fn _canonicalize_relationship(did: &DID, relationship: &mut [ValueOrReference]) {
+    for item in relationship.iter_mut() {
+        match item {
+            ValueOrReference::Reference(reference) => {
+                let absolute = reference.as_did_reference().resolve(did).into_owned();
+                *reference = DIDURLReferenceBuf::Absolute(absolute);
+            }
+            ValueOrReference::Value(vm) => _canonicalize_vm(vm),
+        }
+    }
+
+    relationship.sort_by(|a, b| {
+        a.id()
+            .resolve(did)
+            .as_str()
+            .cmp(b.id().resolve(did).as_str())
+    });
+}
+
+/// Changes to a set of DID Document items (verification methods, services or relationships)
+/// Items are identified by their absolute id, so reordering is not a change
+/// added: ids only in the new document
+/// removed: ids only in the old document
+/// changed: ids in both documents, with different content
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ItemDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl ItemDiff {
+    /// No items were added, removed or changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Compares two sets of items keyed by absolute id
+    fn new(old: BTreeMap<String, Value>, new: BTreeMap<String, Value>) -> Self {
+        let mut diff = ItemDiff::default();
+
+        for (id, old_value) in &old {
+            match new.get(id) {
+                Some(new_value) if new_value != old_value => diff.changed.push(id.clone()),
+                Some(_) => {}
+                None => diff.removed.push(id.clone()),
+            }
+        }
+        diff.added = new
+            .keys()
+            .filter(|id| !old.contains_key(*id))
+            .cloned()
+            .collect();
+
+        diff
+    }
+}
+
+/// Differences between two versions of a DID Document
+/// See [diff_documents]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DocumentDiff {
+    pub verification_methods: ItemDiff,
+    pub services: ItemDiff,
+    pub authentication: ItemDiff,
+    pub assertion_method: ItemDiff,
+    pub key_agreement: ItemDiff,
+    pub capability_invocation: ItemDiff,
+    pub capability_delegation: ItemDiff,
+}
+
+impl DocumentDiff {
+    /// The documents are equivalent (ignoring ordering)
+    pub fn is_empty(&self) -> bool {
+        self._sections().iter().all(|(_, diff)| diff.is_empty())
+    }
+
+    fn _sections(&self) -> [(&'static str, &ItemDiff); 7] {
+        [
+            ("verificationMethod", &self.verification_methods),
+            ("service", &self.services),
+            ("authentication", &self.authentication),
+            ("assertionMethod", &self.assertion_method),
+            ("keyAgreement", &self.key_agreement),
+            ("capabilityInvocation", &self.capability_invocation),
+            ("capabilityDelegation", &self.capability_delegation),
+        ]
+    }
+}
+
+/// Human readable change report, one line per change
+impl fmt::Display for DocumentDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+
+        for (section, diff) in self._sections() {
+            for id in &diff.added {
+                writeln!(f, "{}: added {}", section, id)?;
+            }
+            for id in &diff.removed {
+                writeln!(f, "{}: removed {}", section, id)?;
+            }
+            for id in &diff.changed {
+                writeln!(f, "{}: changed {}", section, id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compares two versions of a DID Document
+/// Reports added, removed and changed verification methods, services and verification relationships
+/// Items are matched by absolute id, so reordering or relative vs absolute ids are not reported as changes
+pub fn diff_documents(old: &Document, new: &Document) -> DocumentDiff {
+    let old_did = old.id.as_did();
+    let new_did = new.id.as_did();
+
+    let relationship = |old: &[ValueOrReference], new: &[ValueOrReference]| {
+        ItemDiff::new(
+            _relationship_items(old_did, old),
+            _relationship_items(new_did, new),
+        )
+    };
 
-    const TEST_DID: &str = "did:peer:2.Vz6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv.EzQ3shQLqRUza6AMJFbPuMdvFRFWm1wKviQRnQSC1fScovJN4s.SeyJ0IjoiRElEQ29tbU1lc3NhZ2luZyIsInMiOnsidXJpIjoiaHR0cHM6Ly8xMjcuMC4wLjE6NzAzNyIsImEiOlsiZGlkY29tbS92MiJdLCJyIjpbXX19";
+    let old_vrs = &old.verification_relationships;
+    let new_vrs = &new.verification_relationships;
 
-    async fn basic_local_client() -> DIDCacheClient {
-        let config = config::ClientConfigBuilder::default().build();
-        DIDCacheClient::new(config).await.unwrap()
+    DocumentDiff {
+        verification_methods: ItemDiff::new(
+            _items(
+                old_did,
+                old.verification_method
+                    .iter()
+                    .map(|vm| (vm.id.as_str(), vm)),
+            ),
+            _items(
+                new_did,
+                new.verification_method
+                    .iter()
+                    .map(|vm| (vm.id.as_str(), vm)),
+            ),
+        ),
+        services: ItemDiff::new(
+            _items(old_did, old.service.iter().map(|s| (s.id.as_str(), s))),
+            _items(new_did, new.service.iter().map(|s| (s.id.as_str(), s))),
+        ),
+        authentication: relationship(&old_vrs.authentication, &new_vrs.authentication),
+        assertion_method: relationship(&old_vrs.assertion_method, &new_vrs.assertion_method),
+        key_agreement: relationship(&old_vrs.key_agreement, &new_vrs.key_agreement),
+        capability_invocation: relationship(
+            &old_vrs.capability_invocation,
+            &new_vrs.capability_invocation,
+        ),
+        capability_delegation: relationship(
+            &old_vrs.capability_delegation,
+            &new_vrs.capability_delegation,
+        ),
     }
+}
+
+/// Converts a relative id (#fragment) to an absolute id
+fn _absolute_id(did: &DID, id: &str) -> String {
+    if id.starts_with('#') {
+        [did.as_str(), id].concat()
+    } else {
+        id.to_string()
+    }
+}
+
+/// Keys serialized items by absolute id
+/// The id itself is removed from the value, so relative vs absolute ids compare equal
+fn _items<'a, T: serde::Serialize + 'a>(
+    did: &DID,
+    items: impl Iterator<Item = (&'a str, &'a T)>,
+) -> BTreeMap<String, Value> {
+    items
+        .map(|(id, item)| {
+            let mut value = serde_json::to_value(item).unwrap_or(Value::Null);
+            if let Value::Object(map) = &mut value {
+                map.remove("id");
+            }
+            (_absolute_id(did, id), value)
+        })
+        .collect()
+}
+
+/// Keys a verification relationship by absolute id
+/// References have no content, embedded verification methods are compared by value
+fn _relationship_items(did: &DID, relationship: &[ValueOrReference]) -> BTreeMap<String, Value> {
+    relationship
+        .iter()
+        .map(|item| {
+            let id = item.id().resolve(did).to_string();
+            match item {
+                ValueOrReference::Reference(_) => (id, Value::Null),
+                ValueOrReference::Value(vm) => {
+                    let mut value = serde_json::to_value(vm).unwrap_or(Value::Null);
+                    if let Value::Object(map) = &mut value {
+                        map.remove("id");
+                    }
+                    (id, value)
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{client, DID_KEY, DID_PEER};
 
     #[tokio::test]
     async fn key_agreement_id_exists() {
-        let client = basic_local_client().await;
+        let client = client().await;
 
         // Resolve a DID which automatically adds it to the cache
-        let response = client.resolve(TEST_DID).await.unwrap();
+        let response = client.resolve(DID_PEER).await.unwrap();
         assert!(response
             .doc
-            .contains_key_agreement(&[TEST_DID, "#key-2"].concat()));
+            .contains_key_agreement(&[DID_PEER, "#key-2"].concat()));
     }
 
     #[tokio::test]
     async fn key_agreement_id_missing() {
-        let client = basic_local_client().await;
+        let client = client().await;
 
         // Resolve a DID which automatically adds it to the cache
-        let response = client.resolve(TEST_DID).await.unwrap();
+        let response = client.resolve(DID_PEER).await.unwrap();
         assert!(!response
             .doc
-            .contains_key_agreement(&[TEST_DID, "#key-3"].concat()));
+            .contains_key_agreement(&[DID_PEER, "#key-3"].concat()));
+    }
+
+    #[tokio::test]
+    async fn canonicalize_converts_keys_and_references() {
+        let client = client().await;
+
+        let response = client.resolve(DID_KEY).await.unwrap();
+        let doc = response.doc.canonicalize();
+
+        let vm = doc.verification_method.first().unwrap();
+        assert_eq!(vm.type_, "JsonWebKey2020");
+        assert!(!vm.properties.contains_key("publicKeyMultibase"));
+        assert_eq!(vm.properties["publicKeyJwk"]["crv"], "Ed25519");
+
+        for reference in &doc.verification_relationships.authentication {
+            assert!(matches!(
+                reference,
+                ValueOrReference::Reference(DIDURLReferenceBuf::Absolute(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn canonicalize_is_order_independent() {
+        let client = client().await;
+
+        let response = client.resolve(DID_PEER).await.unwrap();
+        let mut reordered = response.doc.clone();
+        reordered.verification_method.reverse();
+        reordered.service.reverse();
+
+        assert_eq!(
+            serde_json::to_string(&response.doc.canonicalize()).unwrap(),
+            serde_json::to_string(&reordered.canonicalize()).unwrap()
+        );
+        // Canonicalizing twice makes no further changes
+        assert_eq!(
+            response.doc.canonicalize(),
+            response.doc.canonicalize().canonicalize()
+        );
+    }
+
+    #[tokio::test]
+    async fn diff_ignores_reordering() {
+        let client = client().await;
+
+        let response = client.resolve(DID_PEER).await.unwrap();
+        let mut reordered = response.doc.clone();
+        reordered.verification_method.reverse();
+        reordered.service.reverse();
+        reordered.verification_relationships.key_agreement.reverse();
+
+        let diff = diff_documents(&response.doc, &reordered);
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "No changes\n");
+
+        // Relative vs absolute references are the same
+        let canonical = response.doc.canonicalize();
+        let diff = diff_documents(&response.doc, &canonical);
+        assert!(diff.authentication.is_empty());
+        assert!(diff.key_agreement.is_empty());
+    }
+
+    #[tokio::test]
+    async fn diff_reports_changes() {
+        let client = client().await;
+
+        let old = client.resolve(DID_PEER).await.unwrap().doc;
+        let mut new = old.clone();
+
+        // Remove the first key and its relationships, change the second key, drop the service
+        let removed = new.verification_method.remove(0);
+        let changed = new.verification_method.first_mut().unwrap();
+        changed
+            .properties
+            .insert("publicKeyJwk".to_string(), Value::Null);
+        let changed_id = changed.id.to_string();
+        new.verification_relationships
+            .authentication
+            .retain(|r| r.id().resolve(new.id.as_did()).as_str() != removed.id.as_str());
+        new.service.clear();
+
+        let diff = diff_documents(&old, &new);
+        assert!(!diff.is_empty());
+        assert_eq!(
+            diff.verification_methods.removed,
+            vec![removed.id.to_string()]
+        );
+        assert_eq!(diff.verification_methods.changed, vec![changed_id.clone()]);
+        assert!(diff.verification_methods.added.is_empty());
+        assert_eq!(diff.authentication.removed, vec![removed.id.to_string()]);
+        assert_eq!(diff.services.removed.len(), 1);
+
+        let report = diff.to_string();
+        assert!(report.contains(&format!("verificationMethod: changed {}", changed_id)));
+
+        // Reverse direction reports additions
+        let diff = diff_documents(&new, &old);
+        assert_eq!(
+            diff.verification_methods.added,
+            vec![removed.id.to_string()]
+        );
+        assert_eq!(diff.services.added.len(), 1);
     }
 
     #[tokio::test]
     async fn invalid_key_agreement() {
-        let client = basic_local_client().await;
+        let client = client().await;
 
         // Resolve a DID which automatically adds it to the cache
-        let response = client.resolve(TEST_DID).await.unwrap();
+        let response = client.resolve(DID_PEER).await.unwrap();
         assert!(!response.doc.contains_key_agreement("BAD_DID:TEST#FAIL"));
     }
 }
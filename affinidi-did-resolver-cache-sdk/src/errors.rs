@@ -1,15 +1,22 @@
 //! Error types for the DID Cache Client SDK
+use std::time::Duration;
 use thiserror::Error;
 use wasm_bindgen::JsValue;
 
 /// DIDCacheError is the error type for the DID Cache Client SDK.
 ///
 /// This error type is used for all errors that can occur in the DID Cache Client SDK.
-#[derive(Error, Debug)]
+#[derive(Error, Clone, Debug)]
 pub enum DIDCacheError {
     /// There was an error in resolving the DID.
     #[error("DID error: {0}")]
     DIDError(String),
+    /// The DID is malformed (e.g. an invalid method specific id).
+    #[error("Invalid DID: {0}")]
+    InvalidDid(String),
+    /// The DID is well formed, but its DID Document doesn't exist.
+    #[error("DID not found: {0}")]
+    NotFound(String),
     /// Unsupported DID Method
     #[error("Unsupported DID method: {0}")]
     UnsupportedMethod(String),
@@ -22,6 +29,110 @@ pub enum DIDCacheError {
     /// A network timeout occurred.
     #[error("Network timeout")]
     NetworkTimeout,
+    /// The resolver is too busy to accept the request right now.
+    #[error("Resolver is busy, try again later")]
+    Busy,
+    /// The caller cancelled the resolve.
+    #[error("Resolve cancelled")]
+    Cancelled,
+    /// The DID has more keys and/or services (method specific id parts) than max_did_parts.
+    #[error("The total number of keys and/or services must be less than or equal to {limit}, but {found} were found.")]
+    TooManyParts { found: usize, limit: usize },
+    /// The DID is larger (in UTF-8 bytes) than max_did_size_in_kb allows.
+    #[error("The DID size of {size_bytes} bytes exceeds the limit of {limit_bytes} bytes.")]
+    DIDTooLarge {
+        size_bytes: usize,
+        limit_bytes: usize,
+    },
+    /// The DID method needs network access, which offline mode doesn't allow.
+    #[error("DID method not supported in offline mode: {0}")]
+    OfflineMethodUnsupported(String),
+    /// The did:key public key type (multicodec) isn't supported.
+    #[error("Unsupported did:key public key type (multicodec 0x{0:x})")]
+    UnsupportedKeyType(u64),
+    /// The DID resolved, but doesn't contain the requested verification method.
+    #[error("Verification method not found: {0}")]
+    VerificationMethodNotFound(String),
+    /// The remote server (network mode) couldn't resolve the DID, code is one of [CODES].
+    /// retry_after is the server's hint of when to retry (e.g. when rate_limited).
+    #[error("Remote error ({code}): {message}")]
+    RemoteError {
+        code: &'static str,
+        message: String,
+        retry_after: Option<Duration>,
+    },
+}
+
+/// Every error code returned by [DIDCacheError::code], these are stable and safe to branch on
+/// `rate_limited` is only sent by the server, when a client exceeds its rate limit
+pub const CODES: [&str; 14] = [
+    "invalid_did",
+    "not_found",
+    "did_too_large",
+    "too_many_parts",
+    "unsupported_method",
+    "offline_method_unsupported",
+    "unsupported_key_type",
+    "verification_method_not_found",
+    "transport",
+    "timeout",
+    "busy",
+    "cancelled",
+    "rate_limited",
+    "config",
+];
+
+impl DIDCacheError {
+    /// Is this error likely to be temporary (network or transport related)?
+    /// Transient errors may succeed if the request is retried.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self.code(),
+            "transport" | "timeout" | "busy" | "rate_limited"
+        )
+    }
+
+    /// How long the server asked the client to wait before retrying, if it said
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            DIDCacheError::RemoteError { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// Stable, machine readable error code (one of [CODES]), branch on this rather than the message
+    pub fn code(&self) -> &'static str {
+        match self {
+            DIDCacheError::DIDError(_) | DIDCacheError::InvalidDid(_) => "invalid_did",
+            DIDCacheError::NotFound(_) => "not_found",
+            DIDCacheError::UnsupportedMethod(_) => "unsupported_method",
+            DIDCacheError::TransportError(_) => "transport",
+            DIDCacheError::ConfigError(_) => "config",
+            DIDCacheError::NetworkTimeout => "timeout",
+            DIDCacheError::Busy => "busy",
+            DIDCacheError::Cancelled => "cancelled",
+            DIDCacheError::TooManyParts { .. } => "too_many_parts",
+            DIDCacheError::DIDTooLarge { .. } => "did_too_large",
+            DIDCacheError::OfflineMethodUnsupported(_) => "offline_method_unsupported",
+            DIDCacheError::UnsupportedKeyType(_) => "unsupported_key_type",
+            DIDCacheError::VerificationMethodNotFound(_) => "verification_method_not_found",
+            DIDCacheError::RemoteError { code, .. } => code,
+        }
+    }
+
+    /// Error received from the remote server (network mode)
+    /// Servers that don't send a code (or send an unknown one) give a TransportError, as before
+    #[cfg(feature = "network")]
+    pub(crate) fn from_remote(code: &str, message: String, retry_after: Option<Duration>) -> Self {
+        match CODES.iter().find(|known| **known == code) {
+            Some(code) => DIDCacheError::RemoteError {
+                code,
+                message,
+                retry_after,
+            },
+            None => DIDCacheError::TransportError(message),
+        }
+    }
 }
 
 // Converts DIDCacheError to JsValue which is required for propagating errors to WASM
@@ -30,3 +141,77 @@ impl From<DIDCacheError> for JsValue {
         JsValue::from(err.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes() {
+        for (error, code) in [
+            (
+                DIDCacheError::DIDError("did isn't to spec!".into()),
+                "invalid_did",
+            ),
+            (
+                DIDCacheError::DIDTooLarge {
+                    size_bytes: 2000,
+                    limit_bytes: 1000,
+                },
+                "did_too_large",
+            ),
+            (DIDCacheError::InvalidDid("bad id".into()), "invalid_did"),
+            (
+                DIDCacheError::NotFound("did:web:example.com".into()),
+                "not_found",
+            ),
+            (
+                DIDCacheError::TooManyParts { found: 2, limit: 1 },
+                "too_many_parts",
+            ),
+            (
+                DIDCacheError::UnsupportedMethod("foo".into()),
+                "unsupported_method",
+            ),
+            (DIDCacheError::TransportError("closed".into()), "transport"),
+            (DIDCacheError::NetworkTimeout, "timeout"),
+            (DIDCacheError::Busy, "busy"),
+            (DIDCacheError::Cancelled, "cancelled"),
+            (
+                DIDCacheError::UnsupportedKeyType(0x1202),
+                "unsupported_key_type",
+            ),
+        ] {
+            assert_eq!(error.code(), code);
+            assert!(CODES.contains(&error.code()));
+        }
+    }
+
+    #[cfg(feature = "network")]
+    #[test]
+    fn remote_errors() {
+        let error = DIDCacheError::from_remote(
+            "unsupported_method",
+            "Unsupported DID method: foo".into(),
+            None,
+        );
+        assert_eq!(error.code(), "unsupported_method");
+        assert!(!error.is_transient());
+        assert_eq!(error.retry_after(), None);
+
+        let error = DIDCacheError::from_remote(
+            "rate_limited",
+            "rate_limited".into(),
+            Some(Duration::from_millis(250)),
+        );
+        assert_eq!(error.code(), "rate_limited");
+        assert!(error.is_transient());
+        assert_eq!(error.retry_after(), Some(Duration::from_millis(250)));
+
+        // Older servers don't send a code
+        let error = DIDCacheError::from_remote("", "DID error: not to spec".into(), None);
+        assert!(
+            matches!(error, DIDCacheError::TransportError(msg) if msg == "DID error: not to spec")
+        );
+    }
+}
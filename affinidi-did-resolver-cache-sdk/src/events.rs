@@ -0,0 +1,145 @@
+//! Resolve events for observability
+//!
+//! Every completed resolve publishes a [ResolveEvent] to a broadcast channel.
+//! Any number of subscribers (metrics, audit logs, dashboards) can listen via
+//! [DIDCacheClient::subscribe_events].
+//!
+//! Publishing never blocks or fails a resolve. Subscribers that fall behind will
+//! receive [RecvError::Lagged](tokio::sync::broadcast::error::RecvError::Lagged) and skip
+//! the oldest events.
+
+use crate::{errors::DIDCacheError, DIDCacheClient, DIDMethod, ResolveResponse};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// How many events are buffered for each subscriber before it starts lagging
+pub(crate) const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Result of a resolve
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResolveOutcome {
+    /// The DID was resolved
+    Success,
+    /// The resolve failed, contains the error message
+    Failure(String),
+}
+
+/// Published for each completed resolve
+/// did: The DID that was requested
+/// method: The DID method, None if it couldn't be determined (e.g. a malformed DID)
+/// cache_hit: Was the DID Document served from the cache?
+/// outcome: Did the resolve succeed?
+/// duration: How long the resolve took
+#[derive(Clone, Debug)]
+pub struct ResolveEvent {
+    pub did: String,
+    pub method: Option<DIDMethod>,
+    pub cache_hit: bool,
+    pub outcome: ResolveOutcome,
+    pub duration: Duration,
+}
+
+impl DIDCacheClient {
+    /// Subscribe to events for every completed resolve
+    /// Only events published after subscribing are received
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ResolveEvent> {
+        self.events.subscribe()
+    }
+
+    /// Publishes a [ResolveEvent] for the result of a resolve
+    /// Does nothing if there are no subscribers, send errors are ignored
+    pub(crate) fn publish_event(
+        &self,
+        did: &str,
+        result: &Result<ResolveResponse, DIDCacheError>,
+        duration: Duration,
+    ) {
+        if self.events.receiver_count() == 0 {
+            return;
+        }
+
+        let event = match result {
+            Ok(response) => ResolveEvent {
+                did: did.to_string(),
+                method: Some(response.method.clone()),
+                cache_hit: response.cache_hit,
+                outcome: ResolveOutcome::Success,
+                duration,
+            },
+            Err(e) => ResolveEvent {
+                did: did.to_string(),
+                method: did
+                    .split(':')
+                    .nth(1)
+                    .and_then(|method| self.config.method(method).ok()),
+                cache_hit: false,
+                outcome: ResolveOutcome::Failure(e.to_string()),
+                duration,
+            },
+        };
+
+        let _ = self.events.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{client, DID_KEY};
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    #[tokio::test]
+    async fn resolve_publishes_events() {
+        let client = client().await;
+        let mut events = client.subscribe_events();
+
+        client.resolve(DID_KEY).await.unwrap();
+        client.resolve(DID_KEY).await.unwrap();
+
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.did, DID_KEY);
+        assert_eq!(event.method, Some(DIDMethod::KEY));
+        assert!(!event.cache_hit);
+        assert_eq!(event.outcome, ResolveOutcome::Success);
+
+        let event = events.recv().await.unwrap();
+        assert!(event.cache_hit);
+    }
+
+    #[tokio::test]
+    async fn failed_resolve_publishes_event() {
+        let client = client().await;
+        let mut events = client.subscribe_events();
+
+        assert!(client.resolve("did:key:invalid").await.is_err());
+
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.method, Some(DIDMethod::KEY));
+        assert!(matches!(event.outcome, ResolveOutcome::Failure(_)));
+    }
+
+    #[tokio::test]
+    async fn multiple_subscribers() {
+        let client = client().await;
+        let mut first = client.subscribe_events();
+        let mut second = client.subscribe_events();
+
+        client.resolve(DID_KEY).await.unwrap();
+
+        assert_eq!(first.recv().await.unwrap().did, DID_KEY);
+        assert_eq!(second.recv().await.unwrap().did, DID_KEY);
+        assert_eq!(first.try_recv().unwrap_err(), TryRecvError::Empty);
+    }
+
+    #[tokio::test]
+    async fn lagged_subscriber_does_not_block_resolve() {
+        let client = client().await;
+        let mut events = client.subscribe_events();
+
+        for _ in 0..EVENT_CHANNEL_CAPACITY + 10 {
+            client.resolve(DID_KEY).await.unwrap();
+        }
+
+        assert!(matches!(events.try_recv(), Err(TryRecvError::Lagged(10))));
+    }
+}
@@ -0,0 +1,177 @@
+//! Cheap existence checks for DIDs
+//!
+//! [DIDCacheClient::exists] answers "does this DID exist?" without building the DID Document
+//! where the method allows it, for bulk checks of many DIDs (e.g. validating issuer lists).
+
+use crate::{
+    cache_key::cache_key, canonicalize_did, errors::DIDCacheError, method_of, parse_did,
+    resolver::web, DIDCacheClient, DIDMethod,
+};
+
+impl DIDCacheClient {
+    /// Checks whether a DID exists, using the cheapest check for each method
+    /// - Cached DIDs, and DIDs in the fixtures (see
+    ///   [with_fixture_resolver](crate::config::ClientConfigBuilder::with_fixture_resolver)), always exist
+    /// - did:web: a HEAD request for the did.json
+    /// - did:key, did:jwk, did:peer, did:pkh: the DID is self-describing, so it exists if it is well formed
+    /// - did:dht: resolved as normal (the signed record is the DID Document)
+    /// - did:ethr: resolved as normal (derived from the address, or checked in the registry if an ethr_rpc is configured)
+    /// - did:example: resolved as normal
+    /// - Custom methods (see [with_method](crate::config::ClientConfigBuilder::with_method)): resolved as normal
+    ///
+    /// The same size limits as [resolve](Self::resolve) apply (max_did_size_in_kb, max_did_parts)
+    ///
+    /// Returns Ok(false) if the DID is well formed but doesn't exist (did:web host returns 404/410,
+    /// or resolving returns a `not_found` error)
+    /// Returns an InvalidDid error if the DID is malformed, and a TransportError if the did:web host is
+    /// unreachable, so callers can tell "doesn't exist" apart from "couldn't check"
+    ///
+    /// NOTE: did:dht and did:example are resolved with [resolve](Self::resolve), so in network mode
+    ///       the remote server resolves them (and the result is cached). The other checks are made
+    ///       locally, even in network mode
+    ///       In offline mode did:web, did:dht and did:ethr return [DIDCacheError::OfflineMethodUnsupported]
+    ///       With a configured [WebResolver](crate::web_resolver::WebResolver), did:web is resolved as normal
+    pub async fn exists(&self, did: &str) -> Result<bool, DIDCacheError> {
+        let did = &canonicalize_did(did);
+        self._check_did_size(did)?;
+        let method = match method_of(did) {
+            Err(DIDCacheError::UnsupportedMethod(name)) => self.config.method(&name)?,
+            method => method?,
+        };
+
+        if self.cache.contains_key(&cache_key(did))
+            || self
+                .config
+                .fixtures
+                .as_ref()
+                .is_some_and(|fixtures| fixtures.contains_key(did))
+        {
+            return Ok(true);
+        }
+
+        let parts: Vec<&str> = did.split(':').collect();
+        self._check_did_parts(&parts)?;
+        let parsed = parse_did(did)?;
+        match method {
+            DIDMethod::WEB
+                if !self.config.offline
+                    && self.config.web_resolver.is_none()
+                    && !self.config.methods.contains_key("web") =>
+            {
+                web::exists(
+                    &self.http_client,
+                    &self.config.web_path_overrides,
+                    &parts[2..].join(":"),
+                )
+                .await
+            }
+            DIDMethod::DHT | DIDMethod::EXAMPLE => _found(self.resolve(did).await),
+            _ => _found(self.local_resolve(parsed).await),
+        }
+    }
+}
+
+/// A resolved DID exists, a `not_found` error (local or from the server) means it doesn't
+fn _found<T>(result: Result<T, DIDCacheError>) -> Result<bool, DIDCacheError> {
+    match result {
+        Ok(_) => Ok(true),
+        Err(e) if e.code() == "not_found" => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::{client, client_with, http_server, web_server, DID_KEY, DID_PEER};
+    use crate::{config, errors::DIDCacheError};
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn self_describing_dids() {
+        let client = client().await;
+
+        assert!(client.exists(DID_KEY).await.unwrap());
+        assert!(client.exists(DID_PEER).await.unwrap());
+        assert!(matches!(
+            client.exists("did:key:z6MkNotAKey").await,
+            Err(DIDCacheError::InvalidDid(_))
+        ));
+        assert!(client.exists("not a did").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn size_limits() {
+        let client =
+            client_with(config::ClientConfigBuilder::default().with_max_did_parts(2)).await;
+
+        let did = ["did:web:", &"a".repeat(1000), ".com"].concat();
+        assert!(matches!(
+            client.exists(&did).await,
+            Err(DIDCacheError::DIDTooLarge { .. })
+        ));
+        assert!(matches!(
+            client.exists(DID_PEER).await,
+            Err(DIDCacheError::TooManyParts { found: 4, limit: 2 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn dht_not_found() {
+        let (did, _) = crate::resolver::dht::tests::signed_record(&[], 1);
+        let gateway = format!("http://127.0.0.1:{}/", http_server("404 Not Found").await.0);
+        let client =
+            client_with(config::ClientConfigBuilder::default().with_dht_gateway(&gateway)).await;
+
+        assert!(!client.exists(&did).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn cached_did() {
+        let doc =
+            serde_json::from_value(serde_json::json!({ "id": "did:web:example.com" })).unwrap();
+        let client = client_with(
+            config::ClientConfigBuilder::default()
+                .with_fixture_resolver(HashMap::from([("did:web:example.com".to_string(), doc)])),
+        )
+        .await;
+        client.resolve("did:web:example.com").await.unwrap();
+
+        assert!(client.exists("did:web:example.com").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn fixture_did_web() {
+        let (did, requests) = web_server("404 Not Found").await;
+        let doc = serde_json::from_value(serde_json::json!({ "id": did })).unwrap();
+        let client = client_with(
+            config::ClientConfigBuilder::default()
+                .with_fixture_resolver(HashMap::from([(did.clone(), doc)])),
+        )
+        .await;
+
+        // The fixture wins, as it does for resolve(), no request is made
+        assert!(client.exists(&did).await.unwrap());
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn web_found() {
+        let did = web_server("200 OK").await.0;
+        assert!(client().await.exists(&did).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn web_not_found() {
+        let did = web_server("404 Not Found").await.0;
+        assert!(!client().await.exists(&did).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn web_server_error() {
+        let did = web_server("500 Internal Server Error").await.0;
+        assert!(matches!(
+            client().await.exists(&did).await,
+            Err(DIDCacheError::TransportError(_))
+        ));
+    }
+}
@@ -0,0 +1,187 @@
+//! Deep health checks for the resolver
+//!
+//! [DIDCacheClient::self_test] resolves a known-good sample DID for each supported method,
+//! bypassing the cache, so readiness probes know each method actually works
+//! (e.g. remote services are reachable), not just that the process is up.
+
+#[cfg(feature = "network")]
+use crate::did_hash;
+use crate::{
+    errors::DIDCacheError,
+    parse_did,
+    resolver::dht::{self, DEFAULT_DHT_GATEWAY},
+    DIDCacheClient, DIDMethod,
+};
+use std::collections::HashMap;
+use tracing::{debug, warn};
+
+/// Known-good sample DIDs for the methods that resolve offline, self tested by default
+/// did:web and did:dht depend on hosts that may not be yours, set their samples with
+/// [with_self_test_did](crate::config::ClientConfigBuilder::with_self_test_did)
+/// did:example is excluded as it must be manually loaded
+pub(crate) const DEFAULT_SELF_TEST_DIDS: [(DIDMethod, &str); 5] = [
+    (
+        DIDMethod::ETHR,
+        "did:ethr:0x1:0xb9c5714089478a327f09197987f16f9e5d936e8a",
+    ),
+    (
+        DIDMethod::JWK,
+        "did:jwk:eyJjcnYiOiJQLTI1NiIsImt0eSI6IkVDIiwieCI6ImFjYklRaXVNczNpOF91c3pFakoydHBUdFJNNEVVM3l6OTFQSDZDZEgyVjAiLCJ5IjoiX0tjeUxqOXZXTXB0bm1LdG00NkdxRHo4d2Y3NEk1TEtncmwyR3pIM25TRSJ9",
+    ),
+    (
+        DIDMethod::KEY,
+        "did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv",
+    ),
+    (
+        DIDMethod::PEER,
+        "did:peer:2.Vz6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv.EzQ3shQLqRUza6AMJFbPuMdvFRFWm1wKviQRnQSC1fScovJN4s.SeyJ0IjoiRElEQ29tbU1lc3NhZ2luZyIsInMiOnsidXJpIjoiaHR0cHM6Ly8xMjcuMC4wLjE6NzAzNyIsImEiOlsiZGlkY29tbS92MiJdLCJyIjpbXX19",
+    ),
+    (
+        DIDMethod::PKH,
+        "did:pkh:solana:4sGjMW1sUnHzSxGspuhpqLDx6wiyjNtZ:CKg5d12Jhpej1JqtmxLJgaFqqeYjxgPqToJ4LBdvG9Ev",
+    ),
+];
+
+impl DIDCacheClient {
+    /// Resolves the known-good sample DID of each method (see
+    /// [with_self_test_did](crate::config::ClientConfigBuilder::with_self_test_did)) and reports
+    /// per-method health. Methods without a sample DID aren't tested, except did:dht when a
+    /// gateway is configured (the gateway is probed directly)
+    /// The cache is bypassed (and not updated) so each method is really exercised
+    /// In network mode this checks the remote server can resolve each method
+    /// Failures (e.g. an unreachable did:web host) are reported, never returned as an error
+    pub async fn self_test(&self) -> HashMap<DIDMethod, Result<(), DIDCacheError>> {
+        let mut results = HashMap::new();
+
+        for (method, did) in &self.config.self_test_dids {
+            let result = self._self_test_did(did).await;
+            match &result {
+                Ok(_) => debug!("self test passed for method ({})", method),
+                Err(e) => warn!("self test failed for method ({}): {}", method, e),
+            }
+            results.insert(method.clone(), result);
+        }
+
+        if let Some(result) = self._self_test_dht_gateway().await {
+            match &result {
+                Ok(_) => debug!("self test passed for DHT gateway"),
+                Err(e) => warn!("self test failed for DHT gateway: {}", e),
+            }
+            results.insert(DIDMethod::DHT, result);
+        }
+
+        results
+    }
+
+    /// Probes the configured DHT gateway if there is no did:dht sample DID
+    /// Skipped in network mode (the remote server does the resolving) and for the default
+    /// public gateway, which isn't ours to poll
+    async fn _self_test_dht_gateway(&self) -> Option<Result<(), DIDCacheError>> {
+        if self.config.self_test_dids.contains_key(&DIDMethod::DHT)
+            || self.config.dht_gateway == DEFAULT_DHT_GATEWAY
+        {
+            return None;
+        }
+        #[cfg(feature = "network")]
+        if self.config.service_address.is_some() {
+            return None;
+        }
+
+        Some(dht::probe_gateway(&self.http_client, &self.config.dht_gateway).await)
+    }
+
+    /// Resolves a DID without touching the cache
+    async fn _self_test_did(&self, did: &str) -> Result<(), DIDCacheError> {
+        #[cfg(feature = "network")]
+        if self.config.service_address.is_some() {
+            let did_hash = did_hash(did);
+            return self
+                .network_resolve(did, &did_hash, false, None, None)
+                .await
+                .map(|_| ());
+        }
+
+        self.local_resolve(parse_did(did)?).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::{client, client_with, http_server};
+    use crate::{config, DIDMethod};
+    use std::{collections::HashMap, sync::Arc};
+
+    #[tokio::test]
+    async fn self_test_reports_each_method() {
+        let client = client().await;
+
+        let results = client.self_test().await;
+        assert_eq!(results.len(), 5);
+
+        // Methods that resolve offline must be healthy
+        for method in [
+            DIDMethod::ETHR,
+            DIDMethod::JWK,
+            DIDMethod::KEY,
+            DIDMethod::PEER,
+            DIDMethod::PKH,
+        ] {
+            assert!(results[&method].is_ok(), "{} failed", method);
+        }
+        // did:web has no default sample, it would depend on someone else's host
+        assert!(!results.contains_key(&DIDMethod::WEB));
+        // Nor is the default (public) DHT gateway probed
+        assert!(!results.contains_key(&DIDMethod::DHT));
+
+        // The cache isn't used
+        assert_eq!(client.get_cache().entry_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn self_test_configured_did() {
+        let did = "did:web:example.com";
+        let resolver = HashMap::from([(
+            "https://example.com/.well-known/did.json".to_string(),
+            serde_json::json!({ "id": did }).to_string().into_bytes(),
+        )]);
+        let client = client_with(
+            config::ClientConfigBuilder::default()
+                .with_web_resolver(Arc::new(resolver))
+                .with_self_test_did(DIDMethod::WEB, did)
+                .with_self_test_did(DIDMethod::KEY, "did:key:z6MkNotAKey"),
+        )
+        .await;
+
+        let results = client.self_test().await;
+        assert!(results[&DIDMethod::WEB].is_ok());
+        // Replaces the default sample
+        assert!(results[&DIDMethod::KEY].is_err());
+    }
+
+    #[tokio::test]
+    async fn self_test_probes_dht_gateway() {
+        let (port, requests) = http_server("404 Not Found").await;
+        let client = client_with(
+            config::ClientConfigBuilder::default()
+                .with_dht_gateway(&format!("http://127.0.0.1:{}", port)),
+        )
+        .await;
+
+        let results = client.self_test().await;
+        assert!(results[&DIDMethod::DHT].is_ok());
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn self_test_reports_failing_dht_gateway() {
+        let (port, _) = http_server("502 Bad Gateway").await;
+        let client = client_with(
+            config::ClientConfigBuilder::default()
+                .with_dht_gateway(&format!("http://127.0.0.1:{}", port)),
+        )
+        .await;
+
+        let results = client.self_test().await;
+        assert!(results[&DIDMethod::DHT].is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! Key agreement (encryption) keys of a DID
+//!
+//! [DIDCacheClient::key_agreement_keys] returns the keyAgreement keys of a DID as JWKs that
+//! can be used directly for ECDH, whatever encoding the DID Document uses for them.
+//!
+//! Some DID methods (e.g. did:key with an Ed25519 key) list an Ed25519 signing key under
+//! keyAgreement. These are converted to the equivalent X25519 key.
+
+use crate::{
+    document::{relationship_methods, verification_method_jwk},
+    errors::DIDCacheError,
+    DIDCacheClient,
+};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use ssi::{
+    jwk::{Base64urlUInt, OctetParams, Params},
+    JWK,
+};
+use tracing::warn;
+
+impl DIDCacheClient {
+    /// Resolves a DID and returns its keyAgreement keys as encryption capable JWKs
+    /// Each JWK has `kid` set to the verification method id
+    /// Returns an empty Vec if the DID has no keyAgreement keys
+    /// Keys that can't be used for key agreement (e.g. RSA) are skipped
+    pub async fn key_agreement_keys(&self, did: &str) -> Result<Vec<JWK>, DIDCacheError> {
+        let doc = self.resolve(did).await?.doc;
+
+        let mut keys = Vec::new();
+        for vm in relationship_methods(&doc, &doc.verification_relationships.key_agreement) {
+            match verification_method_jwk(vm).and_then(_encryption_jwk) {
+                Ok(mut jwk) => {
+                    jwk.key_id = Some(vm.id.to_string());
+                    keys.push(jwk);
+                }
+                Err(e) => warn!("Skipping keyAgreement key ({}): {}", vm.id, e),
+            }
+        }
+
+        Ok(keys)
+    }
+}
+
+/// Checks a JWK can be used for key agreement, converting Ed25519 keys to X25519
+fn _encryption_jwk(jwk: JWK) -> Result<JWK, DIDCacheError> {
+    match &jwk.params {
+        Params::EC(_) => Ok(jwk),
+        Params::OKP(okp) if okp.curve == "X25519" => Ok(jwk),
+        Params::OKP(okp) if okp.curve == "Ed25519" => {
+            let point = <[u8; 32]>::try_from(okp.public_key.0.as_slice())
+                .ok()
+                .and_then(|bytes| CompressedEdwardsY(bytes).decompress())
+                .ok_or_else(|| DIDCacheError::DIDError("Invalid Ed25519 public key".into()))?;
+
+            Ok(JWK::from(Params::OKP(OctetParams {
+                curve: "X25519".into(),
+                public_key: Base64urlUInt(point.to_montgomery().to_bytes().to_vec()),
+                private_key: None,
+            })))
+        }
+        _ => Err(DIDCacheError::DIDError(
+            "Key type can't be used for key agreement".into(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{client, client_with_docs, DID_JWK, DID_KEY};
+
+    const DID_WEB: &str = "did:web:example.com";
+
+    fn _curve(jwk: &JWK) -> String {
+        match &jwk.params {
+            Params::EC(ec) => ec.curve.clone().unwrap(),
+            Params::OKP(okp) => okp.curve.clone(),
+            _ => panic!("unexpected key type"),
+        }
+    }
+
+    #[tokio::test]
+    async fn ed25519_converted_to_x25519() {
+        let client = client().await;
+
+        let keys = client.key_agreement_keys(DID_KEY).await.unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(_curve(&keys[0]), "X25519");
+        assert_eq!(
+            keys[0].key_id.as_deref(),
+            Some([DID_KEY, "#", &DID_KEY[8..]].concat().as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn jwk_key() {
+        let client = client().await;
+
+        let keys = client.key_agreement_keys(DID_JWK).await.unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(_curve(&keys[0]), "P-256");
+    }
+
+    #[tokio::test]
+    async fn base58_keys() {
+        let doc = serde_json::from_value(serde_json::json!({
+            "id": DID_WEB,
+            "verificationMethod": [{
+                "id": "did:web:example.com#key-x25519",
+                "type": "X25519KeyAgreementKey2019",
+                "controller": DID_WEB,
+                "publicKeyBase58": "JhNWeSVLMYccCk7iopQW4guaSJTojqpMEELgSLhKwRr"
+            }],
+            "keyAgreement": ["#key-x25519"]
+        }))
+        .unwrap();
+        let client = client_with_docs([doc]).await;
+
+        let keys = client.key_agreement_keys(DID_WEB).await.unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(_curve(&keys[0]), "X25519");
+        assert_eq!(
+            keys[0].key_id.as_deref(),
+            Some("did:web:example.com#key-x25519")
+        );
+    }
+
+    #[tokio::test]
+    async fn no_key_agreement_keys() {
+        let doc = serde_json::from_value(serde_json::json!({ "id": DID_WEB })).unwrap();
+        let client = client_with_docs([doc]).await;
+
+        assert!(client.key_agreement_keys(DID_WEB).await.unwrap().is_empty());
+    }
+}
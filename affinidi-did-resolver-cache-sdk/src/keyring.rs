@@ -0,0 +1,115 @@
+//! Combined keyring of trusted issuer keys
+//!
+//! [DIDCacheClient::build_keyring] resolves a set of DIDs and collects their assertionMethod
+//! keys as JWKs, keyed by verification method id (kid), so any token signed by one of the
+//! issuers can be verified against a single keyring.
+
+use crate::{
+    document::{relationship_methods, verification_method_jwk},
+    errors::DIDCacheError,
+    DIDCacheClient,
+};
+use ssi::JWK;
+use std::collections::HashMap;
+use tracing::warn;
+
+/// A key in the [Keyring]
+/// did: The DID the key was resolved from
+/// jwk: The public key, with `kid` set to the verification method id
+#[derive(Clone, Debug)]
+pub struct KeyringEntry {
+    pub did: String,
+    pub jwk: JWK,
+}
+
+/// Keys from a set of DIDs, keyed by verification method id (kid)
+/// keys: kid -> key
+/// failures: DIDs that couldn't be resolved, and why
+#[derive(Debug, Default)]
+pub struct Keyring {
+    pub keys: HashMap<String, KeyringEntry>,
+    pub failures: HashMap<String, DIDCacheError>,
+}
+
+impl Keyring {
+    /// Returns the JWK for a kid (verification method id)
+    pub fn get(&self, kid: &str) -> Option<&JWK> {
+        self.keys.get(kid).map(|entry| &entry.jwk)
+    }
+
+    /// Returns the DID a kid (verification method id) belongs to
+    pub fn did_of(&self, kid: &str) -> Option<&str> {
+        self.keys.get(kid).map(|entry| entry.did.as_str())
+    }
+}
+
+impl DIDCacheClient {
+    /// Resolves the DIDs with [resolve_batch](Self::resolve_batch) (so at most batch_concurrency
+    /// at once) and builds a keyring from their assertionMethod keys
+    /// A DID that can't be resolved is recorded in [Keyring::failures] rather than failing the
+    /// whole keyring, so one unreachable issuer doesn't block the others
+    /// Keys that can't be converted to a JWK (e.g. did:ethr blockchainAccountId) are skipped
+    pub async fn build_keyring(&self, dids: &[&str]) -> Result<Keyring, DIDCacheError> {
+        let results = self.resolve_batch(dids).await;
+
+        let mut keyring = Keyring::default();
+        for (did, result) in dids.iter().zip(results) {
+            let response = match result {
+                Ok(response) => response,
+                Err(e) => {
+                    warn!("Couldn't resolve DID ({}) for keyring: {}", did, e);
+                    keyring.failures.insert(did.to_string(), e);
+                    continue;
+                }
+            };
+
+            let doc = &response.doc;
+            for vm in relationship_methods(doc, &doc.verification_relationships.assertion_method) {
+                match verification_method_jwk(vm) {
+                    Ok(mut jwk) => {
+                        let kid = vm.id.to_string();
+                        jwk.key_id = Some(kid.clone());
+                        keyring.keys.insert(
+                            kid,
+                            KeyringEntry {
+                                did: did.to_string(),
+                                jwk,
+                            },
+                        );
+                    }
+                    Err(e) => warn!("Skipping key for keyring: {}", e),
+                }
+            }
+        }
+
+        Ok(keyring)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::{DID_JWK, DID_KEY};
+    use crate::{config, DIDCacheClient};
+
+    #[tokio::test]
+    async fn keyring_from_multiple_dids() {
+        let config = config::ClientConfigBuilder::default().build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        let keyring = client
+            .build_keyring(&[DID_KEY, DID_JWK, "did:unknown:1234"])
+            .await
+            .unwrap();
+
+        assert_eq!(keyring.keys.len(), 2);
+        let kid = [DID_KEY, "#", &DID_KEY[8..]].concat();
+        let jwk = keyring.get(&kid).unwrap();
+        assert_eq!(jwk.key_id.as_deref(), Some(kid.as_str()));
+        assert_eq!(keyring.did_of(&kid), Some(DID_KEY));
+        assert_eq!(keyring.did_of(&[DID_JWK, "#0"].concat()), Some(DID_JWK));
+
+        // The unresolvable DID is reported, not fatal
+        assert_eq!(keyring.failures.len(), 1);
+        assert!(keyring.failures.contains_key("did:unknown:1234"));
+    }
+}
@@ -6,47 +6,95 @@ Used to easily connect to the DID Universal Resolver Cache.
 # Crate features
 As this crate can be used either natively or in a WASM environment, the following features are available:
 * **local**
-    **default** - Enables the local mode of the SDK. This is the default mode.
+  **default** - Enables the local mode of the SDK. This is the default mode.
 * **network**
     * Enables the network mode of the SDK. This mode requires a run-time service address to connect to.
     * This feature is NOT supported in a WASM environment. Will cause a compile error if used in WASM.
+* **redis**
+    * Enables a Redis shared cache backend, see [cache_backend].
 */
 
 #[cfg(all(feature = "network", target_arch = "wasm32"))]
 compile_error!("Cannot enable both features at the same time");
 
-use blake2::{Blake2s256, Digest};
-use config::ClientConfig;
+use cache_key::{cache_key, CacheKey, DIDHash};
+use config::{CacheEvictionPolicy, ClientConfig};
+use document::DocumentExt;
 use errors::DIDCacheError;
-use moka::future::Cache;
+use events::{ResolveEvent, EVENT_CHANNEL_CAPACITY};
+use metadata::{CacheEntry, DocumentMetadata, ResolutionMetadata};
+use method_resolver::MethodResolver;
+use moka::{future::Cache, policy::EvictionPolicy, Expiry};
+use negative_cache::ResolveOutcome;
 #[cfg(feature = "network")]
 use networking::{
-    network::{NetworkTask, WSCommands},
-    WSRequest,
+    network::{NetworkStatus, NetworkTask, WSCommands},
+    tls, WSRequest,
 };
-use ssi::dids::Document;
-#[cfg(feature = "network")]
-use std::sync::Arc;
-use std::{fmt, time::Duration};
+use serde::Serialize;
+use serde_json::Value;
+use ssi::dids::{document::DIDVerificationMethod, Document, DID, DIDURL};
+use stats::CacheCounters;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{broadcast, RwLock};
 #[cfg(feature = "network")]
 use tokio::sync::{mpsc, Mutex};
-use tracing::debug;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, field, span, Instrument, Level, Span};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsValue;
+use web_resolver::{HttpWebResolver, WebResolver};
 
+pub mod batch;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+pub mod cache_backend;
+pub mod cache_key;
 pub mod config;
+pub mod controllers;
+pub mod dereference;
 pub mod document;
 pub mod errors;
+pub mod events;
+pub mod exists;
+pub mod health;
+pub mod key_agreement;
+pub mod keyring;
+pub mod metadata;
+pub mod method_resolver;
+pub mod negative_cache;
 #[cfg(feature = "network")]
 pub mod networking;
+pub mod peer;
 mod resolver;
+pub mod retry;
+pub mod stats;
+#[cfg(test)]
+mod test_fixtures;
+#[cfg(any(feature = "test-util", all(test, feature = "network")))]
+pub mod test_util;
+pub mod web_resolver;
 
+/// Unit of max_did_size_in_kb, a decimal (SI) kilobyte: 1KB = 1000 bytes, not 1024
 const BYTES_PER_KILO_BYTE: f64 = 1000.0;
 
+/// How long resolve_before() waits past the deadline in network mode, so the network request
+/// times out first and is removed from the request list rather than dropped while in flight
+#[cfg(feature = "network")]
+const DEADLINE_GRACE: Duration = Duration::from_millis(100);
+
 /// DID Methods supported by the DID Universal Resolver Cache
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+/// Serializes as the lowercase method name (same as Display)
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
 #[wasm_bindgen]
 pub enum DIDMethod {
+    DHT,
     ETHR,
     JWK,
     KEY,
@@ -54,12 +102,15 @@ pub enum DIDMethod {
     PKH,
     WEB,
     EXAMPLE,
+    /// A method registered with [with_method](config::ClientConfigBuilder::with_method)
+    CUSTOM,
 }
 
 /// Helper function to convert a DIDMethod to a string
 impl fmt::Display for DIDMethod {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            DIDMethod::DHT => write!(f, "dht"),
             DIDMethod::ETHR => write!(f, "ethr"),
             DIDMethod::JWK => write!(f, "jwk"),
             DIDMethod::KEY => write!(f, "key"),
@@ -67,6 +118,7 @@ impl fmt::Display for DIDMethod {
             DIDMethod::PKH => write!(f, "pkh"),
             DIDMethod::WEB => write!(f, "web"),
             DIDMethod::EXAMPLE => write!(f, "example"),
+            DIDMethod::CUSTOM => write!(f, "custom"),
         }
     }
 }
@@ -85,6 +137,7 @@ impl TryFrom<&str> for DIDMethod {
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value.to_lowercase().as_str() {
+            "dht" => Ok(DIDMethod::DHT),
             "ethr" => Ok(DIDMethod::ETHR),
             "jwk" => Ok(DIDMethod::JWK),
             "key" => Ok(DIDMethod::KEY),
@@ -98,12 +151,221 @@ impl TryFrom<&str> for DIDMethod {
     }
 }
 
+/// Returns the method of a DID without resolving or fully validating it
+/// e.g. `did:key:z6Mk...` returns [DIDMethod::KEY]
+/// Only checks the DID has the form `did:<method>:<method specific id>`
+///
+/// Returns [DIDCacheError::DIDError] for malformed DIDs, or [DIDCacheError::UnsupportedMethod]
+pub fn method_of(did: &str) -> Result<DIDMethod, DIDCacheError> {
+    let mut parts = did.trim().splitn(3, ':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("did"), Some(method), Some(id)) if !method.is_empty() && !id.is_empty() => {
+            method.try_into()
+        }
+        _ => Err(DIDCacheError::DIDError(format!(
+            "did ({}) isn't of the form did:<method>:<id>",
+            did
+        ))),
+    }
+}
+
+/// Parses a DID, returns [DIDCacheError::InvalidDid] if it isn't valid DID syntax
+pub(crate) fn parse_did(did: &str) -> Result<&DID, DIDCacheError> {
+    DID::new::<str>(did)
+        .map_err(|_| DIDCacheError::InvalidDid(format!("did ({}) isn't a valid DID", did)))
+}
+
+/// Canonicalizes a DID, so equivalent DIDs share a cache entry
+/// The cache key is hashed over the canonical form
+/// - surrounding whitespace is removed
+/// - any DID URL path, query or fragment is removed (e.g. `did:key:z6Mk...#z6Mk...`)
+/// - the `did` scheme and method name are lowercased
+/// - did:web: the domain name is lowercased (domains are case-insensitive), the path isn't
+/// - did:web: the default https port (`%3A443`) is removed
+/// - did:web: percent-encoding is normalized (uppercase hex, unreserved characters decoded)
+///
+/// Everything else (e.g. multibase key material) is case-sensitive and left as is
+/// e.g. `did:WEB:Example.COM%3a443:Alice` returns `did:web:example.com:Alice`
+pub fn canonicalize_did(did: &str) -> String {
+    let did = did.trim();
+    // '/', '?' and '#' can't appear in a DID, they start the DID URL path, query or fragment
+    let did = match did.find(['/', '?', '#']) {
+        Some(end) => &did[..end],
+        None => did,
+    };
+    let mut parts = did.splitn(3, ':');
+    let (Some(scheme), Some(method), Some(id)) = (parts.next(), parts.next(), parts.next()) else {
+        return did.to_string();
+    };
+
+    let method = method.to_lowercase();
+    let id = if method == "web" {
+        let id = _normalize_percent_encoding(id);
+        let (domain, path) = match id.split_once(':') {
+            Some((domain, path)) => (domain, Some(path)),
+            None => (id.as_str(), None),
+        };
+        let domain = domain.to_lowercase().replace("%3a", "%3A");
+        let domain = domain.strip_suffix("%3A443").unwrap_or(&domain);
+        match path {
+            Some(path) => [domain, ":", path].concat(),
+            None => domain.to_string(),
+        }
+    } else {
+        id.to_string()
+    };
+
+    [&scheme.to_lowercase(), ":", &method, ":", &id].concat()
+}
+
+/// Uppercases percent-encoded hex digits, and decodes percent-encoded unreserved characters
+/// (RFC 3986 section 6.2.2). Invalid escapes are left as is
+fn _normalize_percent_encoding(id: &str) -> String {
+    let bytes = id.as_bytes();
+    let mut normalized = String::with_capacity(id.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = (bytes[i] == b'%')
+            .then(|| id.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(b) if b.is_ascii_alphanumeric() || b"-._~".contains(&b) => {
+                normalized.push(b as char);
+                i += 3;
+            }
+            Some(b) => {
+                normalized.push_str(&format!("%{:02X}", b));
+                i += 3;
+            }
+            None => {
+                let ch = id[i..].chars().next().unwrap();
+                normalized.push(ch);
+                i += ch.len_utf8();
+            }
+        }
+    }
+    normalized
+}
+
+/// Per request options for [resolve_with_options](DIDCacheClient::resolve_with_options)
+/// force_refresh: Don't read the cache, resolve the DID and cache the fresh DID Document
+/// no_store: Don't read or write the cache (implies force_refresh)
+///
+/// The local cache, the negative cache and the cache backend are all skipped.
+/// NOTE: In network mode the remote server may still answer from its own cache
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResolveOptions {
+    pub force_refresh: bool,
+    pub no_store: bool,
+}
+
+impl ResolveOptions {
+    /// Is the cache read for this request?
+    fn read_cache(&self) -> bool {
+        !(self.force_refresh || self.no_store)
+    }
+}
+
+/// method_name: Name of the DID method (e.g. `web`), this tells custom methods apart as they are
+///              all [DIDMethod::CUSTOM]
+/// resolution_metadata: W3C DID Resolution Metadata (e.g. contentType)
+/// document_metadata: W3C DID Document Metadata (e.g. deactivated)
+/// Metadata is cached with the DID Document, so cache hits return the original metadata
+/// resolved_id: The id of the resolved DID Document
+/// is_redirected: Is resolved_id a different DID to the requested one? (e.g. did:web redirect)
+/// age: How long the DID Document has been in the local cache (zero if it was just resolved)
+///      Serialized as `age_ms`, whole milliseconds
+/// raw: The DID Document JSON as it was resolved, for byte-faithful output (e.g. verifying a
+///      signature over the document). Keeps the @context (which doc doesn't have) and the field
+///      order, for DIDs derived locally (e.g. did:key) it's doc serialized
+#[derive(Serialize)]
 pub struct ResolveResponse {
     pub did: String,
     pub method: DIDMethod,
+    pub method_name: String,
     pub did_hash: String,
     pub doc: Document,
+    pub resolved_id: String,
+    pub is_redirected: bool,
+    pub resolution_metadata: ResolutionMetadata,
+    pub document_metadata: DocumentMetadata,
     pub cache_hit: bool,
+    #[serde(rename = "age_ms", serialize_with = "serialize_millis")]
+    pub age: Duration,
+    pub raw: Value,
+}
+
+/// Serializes a Duration as whole milliseconds
+fn serialize_millis<S: serde::Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_u64(duration.as_millis() as u64)
+}
+
+impl ResolveResponse {
+    fn from_entry(
+        did: &str,
+        method: DIDMethod,
+        did_hash: String,
+        entry: CacheEntry,
+        cache_hit: bool,
+    ) -> Self {
+        let resolved_id = entry.doc.id.to_string();
+        let raw = entry
+            .raw
+            .unwrap_or_else(|| serde_json::to_value(&entry.doc).unwrap_or_default());
+        // did is already canonical, so only real differences count as a redirect
+        let is_redirected = canonicalize_did(&resolved_id) != did;
+        let age = if cache_hit {
+            entry.inserted_at.elapsed()
+        } else {
+            Duration::ZERO
+        };
+        ResolveResponse {
+            did: did.to_string(),
+            method,
+            method_name: did.split(':').nth(1).unwrap_or_default().to_string(),
+            did_hash,
+            doc: entry.doc,
+            resolved_id,
+            is_redirected,
+            resolution_metadata: entry.resolution_metadata,
+            document_metadata: entry.document_metadata,
+            cache_hit,
+            age,
+            raw,
+        }
+    }
+
+    /// Returns the verification method with the given id
+    /// id can be a full DID URL (`did:example:123#key-1`), or relative to the DID Document
+    /// (`#key-1` or `key-1`)
+    pub fn get_verification_method(&self, id: &str) -> Option<&DIDVerificationMethod> {
+        let id = document::absolute_vm_id(&self.doc, id);
+        self.doc
+            .verification_method
+            .iter()
+            .find(|vm| vm.id.as_str() == id)
+    }
+
+    /// Returns the keyAgreement verification methods
+    /// Inline verification methods are returned as-is, references are looked up in
+    /// verificationMethod (and skipped if not found)
+    pub fn get_key_agreement(&self) -> Vec<&DIDVerificationMethod> {
+        document::relationship_methods(
+            &self.doc,
+            &self.doc.verification_relationships.key_agreement,
+        )
+    }
+
+    /// Returns the authentication verification methods
+    /// Inline verification methods are returned as-is, references are looked up in
+    /// verificationMethod (and skipped if not found)
+    pub fn get_authentication(&self) -> Vec<&DIDVerificationMethod> {
+        document::relationship_methods(
+            &self.doc,
+            &self.doc.verification_relationships.authentication,
+        )
+    }
 }
 
 // ***************************************************************************
@@ -113,15 +375,30 @@ pub struct ResolveResponse {
 /// cache: Local cache for resolved DIDs
 /// network_task: OPTIONAL: Task to handle network requests
 /// network_rx: OPTIONAL: Channel to listen for responses from the network task
+/// network_status: Shared with the network task, connection state and whether it can accept requests
+/// events: Broadcasts a [ResolveEvent] for each completed resolve
+/// generation: Bumped on every remove/clear, so a resolve that overlaps a remove doesn't re-cache
+/// negative_cache: OPTIONAL: Recently failed resolutions, see [negative_cache]
+/// counters: Cache hit/miss counts, see [cache_stats](Self::cache_stats)
+/// methods: The [MethodResolver] for each DID method name, see [method_resolver]
 #[wasm_bindgen(getter_with_clone)]
 #[derive(Clone)]
 pub struct DIDCacheClient {
     config: ClientConfig,
-    cache: Cache<String, Document>,
+    cache: Cache<CacheKey, CacheEntry>,
     #[cfg(feature = "network")]
     network_task_tx: Option<mpsc::Sender<WSCommands>>,
     #[cfg(feature = "network")]
     network_task_rx: Option<Arc<Mutex<mpsc::Receiver<WSCommands>>>>,
+    #[cfg(feature = "network")]
+    network_status: Arc<NetworkStatus>,
+    events: broadcast::Sender<ResolveEvent>,
+    generation: Arc<RwLock<u64>>,
+    negative_cache: Option<Cache<String, ResolveOutcome>>,
+    counters: Arc<CacheCounters>,
+    http_client: reqwest::Client,
+    methods: Arc<HashMap<String, Arc<dyn MethodResolver>>>,
+    in_flight: resolver::InFlight,
     #[cfg(feature = "did_example")]
     did_example_cache: did_example::DiDExampleCache,
 }
@@ -132,140 +409,445 @@ impl DIDCacheClient {
     /// Returns the initial DID, the hashed DID, and the resolved DID Document
     /// NOTE: The DID Document id may be different to the requested DID due to the DID having been updated.
     ///       The original DID should be in the `also_known_as` field of the DID Document.
+    ///       [ResolveResponse::is_redirected] is set when this happens.
     pub async fn resolve(&self, did: &str) -> Result<ResolveResponse, DIDCacheError> {
-        let did_size_in_kb = did.len() as f64 / BYTES_PER_KILO_BYTE;
+        self.resolve_with_options(did, ResolveOptions::default())
+            .await
+    }
 
-        // If DID's size is greater than 1KB we don't resolve it
-        if did_size_in_kb > self.config.max_did_size_in_kb {
-            return Err(DIDCacheError::DIDError(format!(
-                "The DID size of {:.3}KB exceeds the limit of {1}KB. Please ensure the size is less than {1}KB.",
-                did_size_in_kb, self.config.max_did_size_in_kb
-            )));
+    /// Resolves a DID with per request [ResolveOptions], e.g. to bypass the cache when a
+    /// DID Document is known to have changed (key rotation, revocation)
+    /// Otherwise the same as [resolve](Self::resolve)
+    pub async fn resolve_with_options(
+        &self,
+        did: &str,
+        opts: ResolveOptions,
+    ) -> Result<ResolveResponse, DIDCacheError> {
+        self.resolve_inner(did, false, None, opts, None).await
+    }
+
+    /// Resolves a DID, returning [DIDCacheError::Cancelled] as soon as cancel is cancelled
+    /// (e.g. the user navigated away). A pending network request is removed from the request list
+    /// Otherwise the same as [resolve_with_options](Self::resolve_with_options)
+    pub async fn resolve_with_cancel(
+        &self,
+        did: &str,
+        opts: ResolveOptions,
+        cancel: &CancellationToken,
+    ) -> Result<ResolveResponse, DIDCacheError> {
+        if cancel.is_cancelled() {
+            return Err(DIDCacheError::Cancelled);
         }
+        self.resolve_inner(did, false, None, opts, Some(cancel))
+            .await
+    }
 
-        let parts: Vec<&str> = did.split(':').collect();
-        if parts.len() < 3 {
-            return Err(DIDCacheError::DIDError(format!(
-                "did isn't to spec! did ({})",
-                did
-            )));
+    /// Resolves a DID, but fails fast with [DIDCacheError::Busy] instead of waiting when the
+    /// network task can't accept any more requests (command channel or request list is full)
+    /// Useful for shedding load when overloaded
+    /// NOTE: In local mode this is the same as [resolve](Self::resolve)
+    pub async fn try_resolve(&self, did: &str) -> Result<ResolveResponse, DIDCacheError> {
+        self.resolve_inner(did, true, None, ResolveOptions::default(), None)
+            .await
+    }
+
+    /// Resolves a DID, giving up at the deadline rather than after the configured network_timeout
+    /// Use this when the caller already has an overall deadline (e.g. an incoming request)
+    /// Returns [DIDCacheError::TransportError] (`deadline exceeded`) if the deadline has already
+    /// passed, or passes before the DID is resolved
+    pub async fn resolve_before(
+        &self,
+        did: &str,
+        deadline: Instant,
+    ) -> Result<ResolveResponse, DIDCacheError> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(DIDCacheError::TransportError("deadline exceeded".into()));
         }
 
-        let key_parts: Vec<&str> = parts.last().unwrap().split(".").collect();
-        if key_parts.len() > self.config.max_did_parts {
+        // The deadline is also passed down so a network request is cleaned up when it expires
+        #[cfg(feature = "network")]
+        let remaining = match self.config.service_address {
+            Some(_) => remaining + DEADLINE_GRACE,
+            None => remaining,
+        };
+        tokio::time::timeout(
+            remaining,
+            self.resolve_inner(did, false, Some(deadline), ResolveOptions::default(), None),
+        )
+        .await
+        .map_err(|_| DIDCacheError::TransportError("deadline exceeded".into()))?
+    }
+
+    /// Resolves the DID of a verification method id (e.g. a proof's `verificationMethod`), and
+    /// returns that verification method
+    /// Searches `verificationMethod` and methods embedded in the verification relationships
+    ///
+    /// Returns [DIDCacheError::VerificationMethodNotFound] if the DID resolves but doesn't
+    /// contain the verification method
+    pub async fn resolve_verification_method(
+        &self,
+        vm_id: &str,
+    ) -> Result<DIDVerificationMethod, DIDCacheError> {
+        let vm_id = vm_id.trim();
+        let did_url = DIDURL::new(vm_id.as_bytes()).map_err(|_| {
+            DIDCacheError::DIDError(format!(
+                "verification method id ({}) isn't a valid DID URL",
+                vm_id
+            ))
+        })?;
+        if did_url.fragment().is_none() {
             return Err(DIDCacheError::DIDError(format!(
-                "The total number of keys and/or services must be less than or equal to {:?}, but {:?} were found.",
-                self.config.max_did_parts,
-                parts.len()
+                "verification method id ({}) has no #fragment",
+                vm_id
             )));
         }
 
-        let mut hasher = Blake2s256::new();
-        hasher.update(did);
-        let did_hash = format!("{:x}", hasher.finalize());
+        let response = self.resolve(did_url.did().as_str()).await?;
+        response
+            .doc
+            .get_verification_method(vm_id)
+            .cloned()
+            .ok_or_else(|| DIDCacheError::VerificationMethodNotFound(vm_id.to_string()))
+    }
+
+    /// Resolves an already parsed DID
+    /// Skips splitting and validating the DID string, and uses the parsed method and method specific id
+    /// Otherwise the same as [resolve](Self::resolve), including the cache and size limits
+    pub async fn resolve_parsed(&self, did: &DID) -> Result<ResolveResponse, DIDCacheError> {
+        let start = Instant::now();
+        let span = Self::_resolve_span(did.as_str());
+        let result = async {
+            self._check_did_size(did.as_str())?;
+            let parts = ["did", did.method_name(), did.method_specific_id()];
+            self._resolve(did, &parts, false, None, ResolveOptions::default(), None)
+                .await
+        }
+        .instrument(span.clone())
+        .await;
+        Self::_record_span(&span, &result, start.elapsed());
+        self.publish_event(did.as_str(), &result, start.elapsed());
+        result
+    }
+
+    /// Span around a resolve, with structured did_method and did_hash fields
+    /// cache_hit and latency_ms are recorded once the resolve completes (see [Self::_record_span])
+    fn _resolve_span(did: &str) -> Span {
+        span!(
+            Level::INFO,
+            "resolve",
+            did_method = did.split(':').nth(1).unwrap_or_default(),
+            did_hash = did_hash(did),
+            cache_hit = field::Empty,
+            latency_ms = field::Empty,
+        )
+    }
+
+    /// Records the outcome of a resolve on its span
+    fn _record_span(
+        span: &Span,
+        result: &Result<ResolveResponse, DIDCacheError>,
+        duration: Duration,
+    ) {
+        span.record(
+            "cache_hit",
+            result.as_ref().is_ok_and(|response| response.cache_hit),
+        );
+        span.record("latency_ms", duration.as_millis() as u64);
+    }
+
+    /// Shared implementation of [resolve_with_options](Self::resolve_with_options),
+    /// [resolve_with_cancel](Self::resolve_with_cancel), [try_resolve](Self::try_resolve)
+    /// and [resolve_before](Self::resolve_before)
+    /// Publishes a [ResolveEvent] once the resolve completes
+    /// fail_fast: Return [DIDCacheError::Busy] rather than waiting for the network task
+    /// deadline: Network requests time out at the deadline if it is sooner than network_timeout
+    /// opts: Whether the cache is read and written
+    /// cancel: Resolving stops with [DIDCacheError::Cancelled] when this is cancelled
+    async fn resolve_inner(
+        &self,
+        did: &str,
+        fail_fast: bool,
+        deadline: Option<Instant>,
+        opts: ResolveOptions,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<ResolveResponse, DIDCacheError> {
+        // Surrounding whitespace or case differences would otherwise cause a cache miss against the same DID
+        let did = &canonicalize_did(did);
+
+        let start = Instant::now();
+        let span = Self::_resolve_span(did);
+        let result = async {
+            if did.is_empty() {
+                return Err(DIDCacheError::DIDError("empty DID".into()));
+            }
+
+            self._check_did_size(did)?;
+
+            let parts: Vec<&str> = did.split(':').collect();
+            if parts.len() < 3 {
+                return Err(DIDCacheError::DIDError(format!(
+                    "did isn't to spec! did ({})",
+                    did
+                )));
+            }
+
+            self._resolve(parse_did(did)?, &parts, fail_fast, deadline, opts, cancel)
+                .await
+        }
+        .instrument(span.clone())
+        .await;
+        Self::_record_span(&span, &result, start.elapsed());
+        self.publish_event(did, &result, start.elapsed());
+        result
+    }
+
+    /// Rejects DIDs larger than the configured max_did_size_in_kb with [DIDCacheError::DIDTooLarge]
+    /// resolve() checks the canonical DID, so surrounding whitespace doesn't count
+    fn _check_did_size(&self, did: &str) -> Result<(), DIDCacheError> {
+        // The size limit is measured in UTF-8 bytes (not characters), a DID exactly at the limit is accepted
+        // Compare as whole bytes so floating point error can't reject a DID at the boundary
+        let limit_bytes = (self.config.max_did_size_in_kb * BYTES_PER_KILO_BYTE).round() as usize;
+        if did.len() > limit_bytes {
+            return Err(DIDCacheError::DIDTooLarge {
+                size_bytes: did.len(),
+                limit_bytes,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Rejects DIDs with more keys and/or services (`.` separated parts of the method specific id)
+    /// than max_did_parts with [DIDCacheError::TooManyParts]
+    fn _check_did_parts(&self, parts: &[&str]) -> Result<(), DIDCacheError> {
+        let found = parts.last().map_or(0, |id| id.split('.').count());
+        if found > self.config.max_did_parts {
+            return Err(DIDCacheError::TooManyParts {
+                found,
+                limit: self.config.max_did_parts,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Resolves a DID that has been split into parts (did, method, method specific id...)
+    /// Checks the cache first, then resolves and caches the DID Document (unless opts say otherwise)
+    async fn _resolve(
+        &self,
+        parsed: &DID,
+        parts: &[&str],
+        #[allow(unused_variables)] fail_fast: bool,
+        #[allow(unused_variables)] deadline: Option<Instant>,
+        opts: ResolveOptions,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<ResolveResponse, DIDCacheError> {
+        let did = parsed.as_str();
+        self._check_did_parts(parts)?;
+
+        let did_hash = did_hash(did);
 
         #[cfg(feature = "did_example")]
         // Short-circuit for example DIDs
         if parts[1] == "example" {
             if let Some(doc) = self.did_example_cache.get(did) {
-                return Ok(ResolveResponse {
-                    did: did.to_string(),
-                    method: parts[1].try_into()?,
-                    did_hash: did_hash,
-                    doc: doc.clone(),
-                    cache_hit: true,
-                });
+                return Ok(ResolveResponse::from_entry(
+                    did,
+                    self.config.method(parts[1])?,
+                    did_hash,
+                    doc.clone().into(),
+                    true,
+                ));
             }
         }
 
         // Check if the DID is in the cache
-        if let Some(doc) = self.cache.get(&did_hash).await {
+        let cached = if opts.read_cache() {
+            self.cache.get(&cache_key(did)).await
+        } else {
+            debug!("skipping cache for did ({})", did);
+            None
+        };
+        if let Some(entry) = cached {
             debug!("found did ({}) in cache", did);
-            Ok(ResolveResponse {
-                did: did.to_string(),
-                method: parts[1].try_into()?,
+            self.counters.hit();
+            Ok(ResolveResponse::from_entry(
+                did,
+                self.config.method(parts[1])?,
                 did_hash,
-                doc,
-                cache_hit: true,
-            })
+                entry,
+                true,
+            ))
         } else {
-            debug!("did ({}) NOT in cache hash ({})", did, did_hash);
-            // If the DID is not in the cache, resolve it (local or via network)
+            // A remove/clear while resolving must win over caching the result
+            let generation = *self.generation.read().await;
+
+            if opts.read_cache() {
+                debug!("did ({}) NOT in cache hash ({})", did, did_hash);
+                self.counters.miss();
+
+                // A recent failed resolution is returned without resolving again
+                if let Some(e) = self._negative_get(&did_hash).await {
+                    debug!("found did ({}) in negative cache", did);
+                    return Err(e);
+                }
+            }
+
+            // Check the shared cache backend (L2) before resolving
+            if let Some(backend) = self
+                .config
+                .cache_backend
+                .as_ref()
+                .filter(|_| opts.read_cache())
+            {
+                if let Some(entry) = backend.get(&did_hash).await {
+                    debug!("found did ({}) in cache backend", did);
+                    self._cache_insert(generation, did, &did_hash, parts[1], &entry, false)
+                        .await;
+                    return Ok(ResolveResponse::from_entry(
+                        did,
+                        self.config.method(parts[1])?,
+                        did_hash,
+                        entry,
+                        true,
+                    ));
+                }
+            }
+
+            // If the DID is not in the cache, resolve it (fixture, local or via network)
+            let fixture = self
+                .config
+                .fixtures
+                .as_ref()
+                .and_then(|fixtures| fixtures.get(did));
+
             #[cfg(feature = "network")]
-            let doc = {
-                if self.config.service_address.is_some() {
-                    self.network_resolve(did, &did_hash).await?
+            let result = {
+                if let Some(doc) = fixture {
+                    debug!("resolved did ({}) from fixtures", did);
+                    Ok(CacheEntry::from(doc.clone()))
+                } else if self.config.service_address.is_some()
+                    && !self.config.methods.contains_key(parts[1])
+                {
+                    // Custom methods aren't known to the server, they're resolved locally
+                    self.network_resolve(did, &did_hash, fail_fast, deadline, cancel)
+                        .await
                 } else {
-                    self.local_resolve(did, &parts).await?
+                    _cancellable(cancel, self.local_resolve_shared(parsed, &did_hash)).await
                 }
             };
 
             #[cfg(not(feature = "network"))]
-            let doc = self.local_resolve(did, &parts).await?;
+            let result = if let Some(doc) = fixture {
+                debug!("resolved did ({}) from fixtures", did);
+                Ok(CacheEntry::from(doc.clone()))
+            } else {
+                _cancellable(cancel, self.local_resolve_shared(parsed, &did_hash)).await
+            };
+
+            if result.is_err() && !opts.no_store {
+                self._negative_insert(generation, &did_hash, ResolveOutcome::from(&result))
+                    .await;
+            }
+            let mut entry = result?;
+
+            if self.config.canonicalize {
+                entry.doc = entry.doc.canonicalize();
+            }
 
-            debug!("adding did ({}) to cache ({})", did, did_hash);
-            self.cache.insert(did_hash.clone(), doc.clone()).await;
-            Ok(ResolveResponse {
-                did: did.to_string(),
-                method: parts[1].try_into()?,
+            if !opts.no_store {
+                debug!("adding did ({}) to cache ({})", did, did_hash);
+                self._cache_insert(generation, did, &did_hash, parts[1], &entry, true)
+                    .await;
+            }
+            Ok(ResolveResponse::from_entry(
+                did,
+                self.config.method(parts[1])?,
                 did_hash,
-                doc,
-                cache_hit: false,
-            })
+                entry,
+                false,
+            ))
         }
     }
 
-    /// If you want to interact directly with the DID Document cache
-    /// This will return a clone of the cache (the clone is cheap, and the cache is shared)
-    /// For example, accessing cache statistics or manually inserting a DID Document
-    pub fn get_cache(&self) -> Cache<String, Document> {
-        self.cache.clone()
-    }
-
-    /// Stops the network task if it is running and removes any resources
-    #[cfg(feature = "network")]
-    pub fn stop(&self) {
-        if let Some(tx) = self.network_task_tx.as_ref() {
-            let _ = tx.blocking_send(WSCommands::Exit);
+    /// Caches a resolved DID Document, unless the cache was removed from or cleared since the
+    /// resolve started (generation changed), in which case the remove wins
+    /// The read lock is held while inserting so a remove can't slip in between check and insert
+    /// method: Method of the resolved DID, stored with the entry for [clear_method](Self::clear_method)
+    /// write_back: Also store the entry in the cache backend (false if it was read from there)
+    async fn _cache_insert(
+        &self,
+        generation: u64,
+        did: &str,
+        did_hash: &str,
+        method: &str,
+        entry: &CacheEntry,
+        write_back: bool,
+    ) {
+        let current = self.generation.read().await;
+        if *current == generation {
+            let mut entry = entry.clone();
+            entry.inserted_at = Instant::now();
+            entry.method = self.config.method(method).ok();
+            entry.method_name = Some(method.to_string());
+            if let Some(backend) = self.config.cache_backend.as_ref().filter(|_| write_back) {
+                backend
+                    .put(
+                        did_hash,
+                        &entry,
+                        self.config.cache_ttl_for(entry.resolved_method_name()),
+                    )
+                    .await;
+            }
+            self.cache.insert(cache_key(did), entry).await;
+            // A successful resolution replaces any stale failed resolution
+            self._negative_remove(did_hash).await;
+        } else {
+            debug!(
+                "cache changed while resolving hash ({}), not caching the result",
+                did_hash
+            );
         }
     }
 
-    /// Removes the specified DID from the cache
-    /// Returns the removed DID Document if it was in the cache, or None if it was not
-    pub async fn remove(&self, did: &str) -> Option<Document> {
-        //let did_hash = sha256::digest(did);
-        let mut hasher = Blake2s256::new();
-        hasher.update(did);
-        let did_hash = format!("{:x}", hasher.finalize());
-        self.cache.remove(&did_hash).await
+    /// Bumps the generation, so in-flight resolves don't cache their (possibly stale) result
+    /// Returns the write guard, hold it until the cache has been updated
+    async fn _next_generation(&self) -> tokio::sync::RwLockWriteGuard<'_, u64> {
+        let mut generation = self.generation.write().await;
+        *generation += 1;
+        generation
     }
 
-    /// Add a DID Document to the cache manually
-    pub async fn add_did_document(&mut self, did: &str, doc: Document) {
-        let mut hasher = Blake2s256::new();
-        hasher.update(did);
-        let did_hash = format!("{:x}", hasher.finalize());
-        debug!("manually adding did ({}) hash({}) to cache", did, did_hash);
-        self.cache.insert(did_hash, doc).await;
+    /// If you want to interact directly with the DID Document cache
+    /// This will return a clone of the cache (the clone is cheap, and the cache is shared)
+    /// For example, accessing cache statistics or manually inserting a DID Document
+    /// The cache is keyed by [cache_key]
+    pub fn get_cache(&self) -> Cache<CacheKey, CacheEntry> {
+        self.cache.clone()
     }
-}
 
-/// Following are the WASM bindings for the DIDCacheClient
-#[wasm_bindgen]
-impl DIDCacheClient {
-    /// Create a new DIDCacheClient with configuration generated from [ClientConfigBuilder](config::ClientConfigBuilder)
+    /// Create a new DIDCacheClient that uses an externally built cache
+    /// Lets multiple clients share a cache, or a cache be pre-seeded before the client is created
     ///
-    /// Will return an error if the configuration is invalid.
+    /// The cache settings in the config (cache_capacity, cache_ttl, method_ttls, max_cache_bytes,
+    /// cache_initial_capacity, cache_eviction_policy) are ignored, configure these on the cache instead.
+    /// NOTE: [clear_method](Self::clear_method) needs the cache built with
+    ///       `support_invalidation_closures()`
     ///
-    /// Establishes websocket connection and sets up the cache.
-    // using Self instead of DIDCacheClient leads to E0401 errors in dependent crates
-    // this is due to wasm_bindgen generated code (check via `cargo expand`)
-    pub async fn new(config: ClientConfig) -> Result<DIDCacheClient, DIDCacheError> {
-        // Create the initial cache
-        let cache = Cache::builder()
-            .max_capacity(config.cache_capacity.into())
-            .time_to_live(Duration::from_secs(config.cache_ttl.into()))
-            .build();
+    /// Otherwise the same as [new](Self::new)
+    pub async fn with_cache(
+        config: ClientConfig,
+        cache: Cache<CacheKey, CacheEntry>,
+    ) -> Result<DIDCacheClient, DIDCacheError> {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let negative_cache = negative_cache::build(&config);
+        let http_client = resolver::http_client(&config)?;
+        let web_resolver: Arc<dyn WebResolver> = config
+            .web_resolver
+            .clone()
+            .unwrap_or_else(|| Arc::new(HttpWebResolver(http_client.clone())));
+        let methods = Arc::new(resolver::registry(&config, &http_client, web_resolver));
 
         #[cfg(feature = "network")]
         let mut client = Self {
@@ -273,6 +855,14 @@ impl DIDCacheClient {
             cache,
             network_task_tx: None,
             network_task_rx: None,
+            network_status: Arc::new(NetworkStatus::default()),
+            events,
+            generation: Arc::new(RwLock::new(0)),
+            negative_cache,
+            counters: Arc::new(CacheCounters::default()),
+            http_client,
+            methods,
+            in_flight: Default::default(),
             #[cfg(feature = "did_example")]
             did_example_cache: did_example::DiDExampleCache::new(),
         };
@@ -280,6 +870,13 @@ impl DIDCacheClient {
         let client = Self {
             config,
             cache,
+            events,
+            generation: Arc::new(RwLock::new(0)),
+            negative_cache,
+            counters: Arc::new(CacheCounters::default()),
+            http_client,
+            methods,
+            in_flight: Default::default(),
             #[cfg(feature = "did_example")]
             did_example_cache: did_example::DiDExampleCache::new(),
         };
@@ -289,24 +886,36 @@ impl DIDCacheClient {
             if client.config.service_address.is_some() {
                 // Running in network mode
 
+                // Fail now if the TLS policy can't be met, rather than never connecting
+                let connector = tls::connector(&client.config)?;
+
                 // Channel to communicate from SDK to network task
-                let (sdk_tx, mut task_rx) = mpsc::channel(32);
+                let (sdk_tx, mut task_rx) = mpsc::channel(client.config.command_channel_capacity);
                 // Channel to communicate from network task to SDK
-                let (task_tx, sdk_rx) = mpsc::channel(32);
+                let (task_tx, sdk_rx) = mpsc::channel(client.config.response_channel_capacity);
 
                 client.network_task_tx = Some(sdk_tx);
                 client.network_task_rx = Some(Arc::new(Mutex::new(sdk_rx)));
 
                 // Start the network task
                 let _config = client.config.clone();
-                tokio::spawn(async move {
-                    let _ = NetworkTask::run(_config, &mut task_rx, &task_tx).await;
+                let _status = client.network_status.clone();
+                let task = tokio::spawn(async move {
+                    NetworkTask::run(_config, connector, &mut task_rx, &task_tx, _status).await
                 });
 
                 if let Some(arc_rx) = client.network_task_rx.as_ref() {
                     // Wait for the network task to be ready
                     let mut rx = arc_rx.lock().await;
-                    rx.recv().await.unwrap();
+                    if rx.recv().await.is_none() {
+                        // The network task gave up before connecting (e.g. incompatible server)
+                        return Err(match task.await {
+                            Ok(Err(e)) => e,
+                            _ => DIDCacheError::TransportError(
+                                "Network task exited before connecting".into(),
+                            ),
+                        });
+                    }
                 }
             }
         }
@@ -314,6 +923,256 @@ impl DIDCacheClient {
         Ok(client)
     }
 
+    /// Stops the network task if it is running and removes any resources
+    #[cfg(feature = "network")]
+    pub fn stop(&self) {
+        if let Some(tx) = self.network_task_tx.as_ref() {
+            let _ = tx.blocking_send(WSCommands::Exit);
+        }
+    }
+
+    /// Removes the specified DID from the cache
+    /// Returns the removed DID Document if it was in the cache, or None if it was not
+    ///
+    /// A remove always wins over a resolve that is in flight at the same time: the resolve still
+    /// returns its DID Document, but doesn't cache it, so the removed entry isn't brought back
+    /// The DID is also removed from the shared cache backend, if one is configured
+    pub async fn remove(&self, did: &str) -> Option<Document> {
+        let did_hash = did_hash(did);
+        let _generation = self._next_generation().await;
+        self._negative_remove(&did_hash).await;
+        if let Some(backend) = &self.config.cache_backend {
+            backend.remove(&did_hash).await;
+        }
+        self.cache
+            .remove(&cache_key(did))
+            .await
+            .map(|entry| entry.doc)
+    }
+
+    /// Is the DID in the local cache? Never resolves the DID
+    /// e.g. to decide whether to prefetch it
+    pub async fn contains(&self, did: &str) -> bool {
+        self.cache.contains_key(&cache_key(did))
+    }
+
+    /// Returns the DID Document if it is in the local cache, without resolving the DID
+    /// Doesn't restart the TTL of the entry, and isn't counted as a cache hit
+    pub async fn peek(&self, did: &str) -> Option<Document> {
+        self.cache.get(&cache_key(did)).await.map(|entry| entry.doc)
+    }
+
+    /// Removes all DID Documents from the cache
+    /// e.g. after a bulk key rotation, or to give tests a clean slate
+    /// Like [remove](Self::remove), resolves in flight at the same time won't be cached
+    /// The shared cache backend is cleared too, if one is configured (see [CacheBackend::clear](cache_backend::CacheBackend::clear))
+    pub async fn clear(&self) {
+        let _generation = self._next_generation().await;
+        self._negative_clear().await;
+        if let Some(backend) = &self.config.cache_backend {
+            backend.clear().await;
+        }
+        self.cache.invalidate_all();
+        self.cache.run_pending_tasks().await;
+    }
+
+    /// Removes all DID Documents of a DID method from the cache
+    /// The cache key is a one-way hash, so the method of the resolved DID is stored with each
+    /// entry. Entries added directly (e.g. [add_did_document](Self::add_did_document)) don't have
+    /// one, the method of their DID Document's id is used instead
+    /// The shared cache backend is cleared too, by the same method
+    /// (see [CacheBackend::clear_method](cache_backend::CacheBackend::clear_method))
+    /// NOTE: [DIDMethod::CUSTOM] clears every custom method, see [clear_method_name](Self::clear_method_name)
+    pub async fn clear_method(&self, method: DIDMethod) -> Result<(), DIDCacheError> {
+        let name = method.to_string();
+        // The backend only knows method names, CUSTOM is each configured custom method
+        let backend_names = match method {
+            DIDMethod::CUSTOM => self
+                .config
+                .methods
+                .keys()
+                .filter(|name| DIDMethod::try_from(name.as_str()).is_err())
+                .cloned()
+                .collect(),
+            _ => vec![name.clone()],
+        };
+        self._clear_where(backend_names, move |entry| match &entry.method {
+            Some(entry_method) => *entry_method == method,
+            None => entry.doc.id.method_name() == name,
+        })
+        .await
+    }
+
+    /// Same as [clear_method](Self::clear_method), named after the cache's invalidate methods
+    pub async fn invalidate_method(&self, method: DIDMethod) -> Result<(), DIDCacheError> {
+        self.clear_method(method).await
+    }
+
+    /// Removes all DID Documents of a DID method, by its name (e.g. `mymethod` for
+    /// `did:mymethod:...`), from the cache
+    /// Unlike [clear_method](Self::clear_method) this clears a single custom method
+    pub async fn clear_method_name(&self, method_name: &str) -> Result<(), DIDCacheError> {
+        let name = method_name.to_string();
+        self._clear_where(vec![name.clone()], move |entry| match &entry.method_name {
+            Some(entry_method) => *entry_method == name,
+            None => entry.doc.id.method_name() == name,
+        })
+        .await
+    }
+
+    /// Removes the DID Documents that match from the cache, and those of the methods from the
+    /// cache backend
+    async fn _clear_where(
+        &self,
+        method_names: Vec<String>,
+        matches: impl Fn(&CacheEntry) -> bool + Send + Sync + 'static,
+    ) -> Result<(), DIDCacheError> {
+        let _generation = self._next_generation().await;
+        if let Some(backend) = &self.config.cache_backend {
+            for method_name in &method_names {
+                backend.clear_method(method_name).await;
+            }
+        }
+        self.cache
+            .invalidate_entries_if(move |_, entry| matches(entry))
+            .map_err(|e| DIDCacheError::ConfigError(format!("Couldn't clear cache: {}", e)))?;
+        self.cache.run_pending_tasks().await;
+        // Failed resolutions have no DID Document to take the method from, so drop them all
+        self._negative_clear().await;
+        Ok(())
+    }
+
+    /// Add a DID Document to the cache manually
+    pub async fn add_did_document(&mut self, did: &str, doc: Document) {
+        let did_hash = did_hash(did);
+        debug!("manually adding did ({}) hash({}) to cache", did, did_hash);
+        self._negative_remove(&did_hash).await;
+        self.cache.insert(cache_key(did), doc.into()).await;
+    }
+}
+
+/// Returns the key a DID is cached under: the hex encoded Blake2s256 hash of the canonical DID
+/// (see [canonicalize_did])
+/// The same key is used for the network request list, the cache backend and in [ResolveResponse::did_hash]
+pub fn did_hash(did: &str) -> String {
+    DIDHash::new(did).to_string()
+}
+
+/// Awaits the resolution, unless cancel is cancelled first
+async fn _cancellable<T>(
+    cancel: Option<&CancellationToken>,
+    resolution: impl std::future::Future<Output = Result<T, DIDCacheError>>,
+) -> Result<T, DIDCacheError> {
+    match cancel {
+        Some(cancel) => tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Err(DIDCacheError::Cancelled),
+            result = resolution => result,
+        },
+        None => resolution.await,
+    }
+}
+
+/// Serialized size in bytes of a DID Document
+fn _document_size(doc: &Document) -> u32 {
+    serde_json::to_vec(doc)
+        .map(|bytes| bytes.len().try_into().unwrap_or(u32::MAX))
+        .unwrap_or(u32::MAX)
+}
+
+/// Serialized size in bytes of a cache entry's DID Document(s), used to weigh cache entries
+fn _entry_size(entry: &CacheEntry) -> u32 {
+    let raw = entry.raw.as_ref().map_or(0, |raw| {
+        serde_json::to_vec(raw)
+            .map(|bytes| bytes.len().try_into().unwrap_or(u32::MAX))
+            .unwrap_or(u32::MAX)
+    });
+    _document_size(&entry.doc).saturating_add(raw)
+}
+
+/// Expires each cache entry after the TTL of its DID method (see
+/// [with_method_ttl](config::ClientConfigBuilder::with_method_ttl))
+/// The method is the one stored with the entry (see [CacheEntry::resolved_method_name]), as the
+/// cache key is a hash
+struct MethodExpiry(ClientConfig);
+
+impl Expiry<CacheKey, CacheEntry> for MethodExpiry {
+    fn expire_after_create(
+        &self,
+        _: &CacheKey,
+        entry: &CacheEntry,
+        _: std::time::Instant,
+    ) -> Option<Duration> {
+        Some(self.0.cache_ttl_for(entry.resolved_method_name()))
+    }
+
+    /// Re-caching a DID Document restarts its TTL
+    fn expire_after_update(
+        &self,
+        key: &CacheKey,
+        entry: &CacheEntry,
+        updated_at: std::time::Instant,
+        _: Option<Duration>,
+    ) -> Option<Duration> {
+        self.expire_after_create(key, entry, updated_at)
+    }
+}
+
+/// Following are the WASM bindings for the DIDCacheClient
+#[wasm_bindgen]
+impl DIDCacheClient {
+    /// Create a new DIDCacheClient with configuration generated from [ClientConfigBuilder](config::ClientConfigBuilder)
+    ///
+    /// Will return an error if the configuration is invalid.
+    ///
+    /// Establishes websocket connection and sets up the cache.
+    // using Self instead of DIDCacheClient leads to E0401 errors in dependent crates
+    // this is due to wasm_bindgen generated code (check via `cargo expand`)
+    pub async fn new(config: ClientConfig) -> Result<DIDCacheClient, DIDCacheError> {
+        // Create the initial cache
+        let cache = Cache::builder()
+            .expire_after(MethodExpiry(config.clone()))
+            .eviction_policy(match config.cache_eviction_policy {
+                CacheEvictionPolicy::TinyLfu => EvictionPolicy::tiny_lfu(),
+                CacheEvictionPolicy::Lru => EvictionPolicy::lru(),
+            })
+            // Needed for clear_method()
+            .support_invalidation_closures();
+        let cache = match config.cache_initial_capacity {
+            Some(initial_capacity) => cache.initial_capacity(initial_capacity),
+            None => cache,
+        };
+        let cache = match config.eviction_listener.clone() {
+            Some(listener) => {
+                cache.eviction_listener(move |key: Arc<CacheKey>, entry: CacheEntry, cause| {
+                    (listener.0)(key.to_string(), entry.doc, cause)
+                })
+            }
+            None => cache,
+        };
+        let cache = if let Some(max_cache_bytes) = config.max_cache_bytes {
+            // Weigh each entry by the size of the serialized DID Document
+            // With a count limit as well, every entry weighs at least its share of the byte limit
+            // so no more than cache_capacity entries fit, whichever limit is reached first evicts
+            let min_weight: u32 = if config.cache_capacity_set {
+                max_cache_bytes
+                    .div_ceil(config.cache_capacity.into())
+                    .try_into()
+                    .unwrap_or(u32::MAX)
+            } else {
+                0
+            };
+            cache
+                .weigher(move |_, entry: &CacheEntry| _entry_size(entry).max(min_weight))
+                .max_capacity(max_cache_bytes)
+                .build()
+        } else {
+            cache.max_capacity(config.cache_capacity.into()).build()
+        };
+
+        Self::with_cache(config, cache).await
+    }
+
     pub async fn wasm_resolve(&self, did: &str) -> Result<JsValue, DIDCacheError> {
         let response = self.resolve(did).await?;
 
@@ -326,6 +1185,44 @@ impl DIDCacheClient {
         }
     }
 
+    /// Resolves a DID, returning the whole [ResolveResponse] (e.g. cache_hit, did_hash and
+    /// method) as a JS object rather than only the DID Document
+    /// Maps are returned as plain JS objects
+    pub async fn wasm_resolve_full(&self, did: &str) -> Result<JsValue, DIDCacheError> {
+        let response = self.resolve(did).await?;
+
+        response
+            .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+            .map_err(|err| {
+                DIDCacheError::DIDError(format!("Error serializing resolve response: {}", err))
+            })
+    }
+
+    /// Removes the specified DID from the cache, e.g. after a key rotation
+    /// Returns the removed DID Document, or `undefined` if it was not in the cache
+    /// (removing a DID that isn't cached is a no-op)
+    pub async fn wasm_remove(&self, did: &str) -> Result<JsValue, DIDCacheError> {
+        match self.remove(did).await {
+            Some(doc) => serde_wasm_bindgen::to_value(&doc).map_err(|err| {
+                DIDCacheError::DIDError(format!("Error serializing DID Document: {}", err))
+            }),
+            None => Ok(JsValue::UNDEFINED),
+        }
+    }
+
+    /// Removes all DID Documents from the cache (moka `invalidate_all`)
+    /// A no-op if the cache is already empty
+    pub async fn wasm_clear(&self) {
+        self.clear().await
+    }
+
+    /// Returns the number of DID Documents in the cache
+    /// Pending cache maintenance is run first so the count is accurate
+    pub async fn wasm_cache_size(&self) -> u64 {
+        self.cache.run_pending_tasks().await;
+        self.cache.entry_count()
+    }
+
     #[cfg(feature = "did_example")]
     pub fn add_example_did(&mut self, doc: &str) -> Result<(), DIDCacheError> {
         self.did_example_cache
@@ -337,17 +1234,13 @@ impl DIDCacheClient {
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    const DID_KEY: &str = "did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv";
-
-    async fn basic_local_client() -> DIDCacheClient {
-        let config = config::ClientConfigBuilder::default().build();
-        DIDCacheClient::new(config).await.unwrap()
-    }
+    use crate::test_fixtures::{client, DID_JWK, DID_KEY};
+    use ssi::dids::DIDBuf;
+    use std::{collections::HashMap, str::FromStr};
 
     #[tokio::test]
     async fn remove_existing_cached_did() {
-        let client = basic_local_client().await;
+        let client = client().await;
 
         // Resolve a DID which automatically adds it to the cache
         let response = client.resolve(DID_KEY).await.unwrap();
@@ -355,12 +1248,1028 @@ mod tests {
         assert_eq!(removed_doc, Some(response.doc));
     }
 
+    #[tokio::test]
+    async fn shared_external_cache() {
+        let cache = Cache::new(10);
+        let first = DIDCacheClient::with_cache(
+            config::ClientConfigBuilder::default().build(),
+            cache.clone(),
+        )
+        .await
+        .unwrap();
+        let second = DIDCacheClient::with_cache(
+            config::ClientConfigBuilder::default().build(),
+            cache.clone(),
+        )
+        .await
+        .unwrap();
+
+        let resolved = first.resolve(DID_KEY).await.unwrap();
+        assert!(cache.contains_key(&cache_key(DID_KEY)));
+
+        let response = second.resolve(DID_KEY).await.unwrap();
+        assert!(response.cache_hit);
+        assert_eq!(response.doc, resolved.doc);
+    }
+
+    #[tokio::test]
+    async fn external_cache_eviction_listener() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let cache = Cache::builder()
+            .eviction_listener(move |did_hash: Arc<CacheKey>, _, _| {
+                let _ = tx.send(did_hash.to_string());
+            })
+            .build();
+        let client =
+            DIDCacheClient::with_cache(config::ClientConfigBuilder::default().build(), cache)
+                .await
+                .unwrap();
+
+        client.resolve(DID_KEY).await.unwrap();
+        client.remove(DID_KEY).await;
+        client.get_cache().run_pending_tasks().await;
+        assert_eq!(rx.try_recv().unwrap(), did_hash(DID_KEY));
+    }
+
+    #[tokio::test]
+    async fn cache_eviction_policy() {
+        // Returns how many hot DIDs are still cached after a scan of one-off DIDs
+        async fn hot_after_scan(policy: config::CacheEvictionPolicy) -> usize {
+            let config = config::ClientConfigBuilder::default()
+                .with_cache_capacity(10)
+                .with_cache_initial_capacity(10)
+                .with_cache_eviction_policy(policy)
+                .build();
+            let client = DIDCacheClient::new(config).await.unwrap();
+            let cache = client.get_cache();
+            let entry: CacheEntry = Document::new(DIDBuf::from_str(DID_KEY).unwrap()).into();
+
+            let hot: Vec<String> = (0..10).map(|i| format!("did:web:hot{}.com", i)).collect();
+            for _ in 0..5 {
+                for did in &hot {
+                    if cache.get(&cache_key(did)).await.is_none() {
+                        cache.insert(cache_key(did), entry.clone()).await;
+                    }
+                }
+            }
+            cache.run_pending_tasks().await;
+            for i in 0..10 {
+                cache
+                    .insert(cache_key(&format!("did:web:scan{}.com", i)), entry.clone())
+                    .await;
+            }
+            cache.run_pending_tasks().await;
+
+            hot.iter()
+                .filter(|did| cache.contains_key(&cache_key(did)))
+                .count()
+        }
+
+        // TinyLFU doesn't admit the one-off DIDs, LRU evicts the hot DIDs for them
+        assert_eq!(
+            hot_after_scan(config::CacheEvictionPolicy::TinyLfu).await,
+            10
+        );
+        assert_eq!(hot_after_scan(config::CacheEvictionPolicy::Lru).await, 0);
+    }
+
+    #[tokio::test]
+    async fn pre_seeded_external_cache() {
+        let doc = client().await.resolve(DID_KEY).await.unwrap().doc;
+
+        let cache = Cache::new(10);
+        cache.insert(cache_key(DID_JWK), doc.clone().into()).await;
+
+        let client =
+            DIDCacheClient::with_cache(config::ClientConfigBuilder::default().build(), cache)
+                .await
+                .unwrap();
+
+        // Seeded entries are returned as is, without resolving
+        let response = client.resolve(DID_JWK).await.unwrap();
+        assert!(response.cache_hit);
+        assert_eq!(response.doc, doc);
+    }
+
+    #[tokio::test]
+    async fn resolve_verification_method() {
+        let client = client().await;
+
+        let vm_id = [DID_KEY, "#", &DID_KEY[8..]].concat();
+        let vm = client.resolve_verification_method(&vm_id).await.unwrap();
+        assert_eq!(vm.id, vm_id);
+        assert_eq!(vm.controller, DID_KEY);
+    }
+
+    #[tokio::test]
+    async fn resolve_verification_method_embedded() {
+        let did = "did:web:example.com";
+        let doc: Document = serde_json::from_value(serde_json::json!({
+            "id": did,
+            "authentication": [{
+                "id": "did:web:example.com#auth",
+                "type": "Multikey",
+                "controller": did,
+                "publicKeyMultibase": &DID_KEY[8..],
+            }],
+        }))
+        .unwrap();
+        let config = config::ClientConfigBuilder::default()
+            .with_fixture_resolver(HashMap::from([(did.to_string(), doc)]))
+            .build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        let vm = client
+            .resolve_verification_method("did:web:example.com#auth")
+            .await
+            .unwrap();
+        assert_eq!(vm.type_, "Multikey");
+    }
+
+    #[tokio::test]
+    async fn resolve_verification_method_not_found() {
+        let client = client().await;
+
+        let result = client
+            .resolve_verification_method(&[DID_KEY, "#missing"].concat())
+            .await;
+        assert!(matches!(
+            result,
+            Err(DIDCacheError::VerificationMethodNotFound(_))
+        ));
+
+        // No fragment
+        let result = client.resolve_verification_method(DID_KEY).await;
+        assert!(matches!(result, Err(DIDCacheError::DIDError(_))));
+    }
+
+    #[test]
+    fn method_of_did() {
+        assert_eq!(method_of(DID_KEY).unwrap(), DIDMethod::KEY);
+        assert_eq!(method_of(DID_JWK).unwrap(), DIDMethod::JWK);
+        assert_eq!(method_of(" did:web:example.com ").unwrap(), DIDMethod::WEB);
+
+        assert!(matches!(
+            method_of("did:unknown:1234"),
+            Err(DIDCacheError::UnsupportedMethod(method)) if method == "unknown"
+        ));
+        for malformed in [
+            "",
+            "did",
+            "did:key",
+            "did:key:",
+            "did::1234",
+            "key:did:1234",
+        ] {
+            assert!(
+                matches!(method_of(malformed), Err(DIDCacheError::DIDError(_))),
+                "{}",
+                malformed
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn clear_cache() {
+        let client = client().await;
+
+        client.resolve(DID_KEY).await.unwrap();
+        client.resolve(DID_JWK).await.unwrap();
+        let cache = client.get_cache();
+        cache.run_pending_tasks().await;
+        assert_eq!(cache.entry_count(), 2);
+
+        client.clear().await;
+        assert_eq!(cache.entry_count(), 0);
+
+        // Still resolves after clearing
+        assert!(!client.resolve(DID_KEY).await.unwrap().cache_hit);
+    }
+
+    #[tokio::test]
+    async fn clear_cache_by_method() {
+        let client = client().await;
+
+        client.resolve(DID_KEY).await.unwrap();
+        client.resolve(DID_JWK).await.unwrap();
+
+        client.clear_method(DIDMethod::KEY).await.unwrap();
+
+        let cache = client.get_cache();
+        assert_eq!(cache.entry_count(), 1);
+        assert!(client.resolve(DID_JWK).await.unwrap().cache_hit);
+        assert!(!client.resolve(DID_KEY).await.unwrap().cache_hit);
+    }
+
+    #[tokio::test]
+    async fn clear_method_uses_resolved_method() {
+        // The did:web document has a did:key id, it is still cleared as a did:web
+        let resolver = HashMap::from([(
+            "https://example.com/.well-known/did.json".to_string(),
+            serde_json::json!({ "id": DID_KEY })
+                .to_string()
+                .into_bytes(),
+        )]);
+        let client = DIDCacheClient::new(
+            config::ClientConfigBuilder::default()
+                .with_web_resolver(Arc::new(resolver))
+                .build(),
+        )
+        .await
+        .unwrap();
+        client.resolve("did:web:example.com").await.unwrap();
+
+        client.clear_method(DIDMethod::KEY).await.unwrap();
+        assert!(client.contains("did:web:example.com").await);
+
+        client.clear_method(DIDMethod::WEB).await.unwrap();
+        assert!(!client.contains("did:web:example.com").await);
+    }
+
+    #[tokio::test]
+    async fn resolve_canonicalize() {
+        let config = config::ClientConfigBuilder::default()
+            .with_canonicalize(true)
+            .build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        let response = client.resolve(DID_KEY).await.unwrap();
+        assert_eq!(response.doc, response.doc.canonicalize());
+
+        // Cached copy is also canonical
+        let response = client.resolve(DID_KEY).await.unwrap();
+        assert!(response.cache_hit);
+        assert_eq!(response.doc, response.doc.canonicalize());
+    }
+
+    #[tokio::test]
+    async fn raw_of_derived_document() {
+        let client = client().await;
+
+        let response = client.resolve(DID_KEY).await.unwrap();
+        assert_eq!(response.raw, serde_json::to_value(&response.doc).unwrap());
+        let cached = client.resolve(DID_KEY).await.unwrap();
+        assert!(cached.cache_hit);
+        assert_eq!(cached.raw, response.raw);
+    }
+
+    #[tokio::test]
+    async fn cache_weighed_by_document_size() {
+        let client = client().await;
+        let doc_size = _document_size(&client.resolve(DID_KEY).await.unwrap().doc) as u64;
+
+        // Only enough room for one document
+        let config = config::ClientConfigBuilder::default()
+            .with_cache_weight_bytes(doc_size + 10)
+            .build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        client.resolve(DID_KEY).await.unwrap();
+        client
+            .resolve("did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK")
+            .await
+            .unwrap();
+
+        let cache = client.get_cache();
+        cache.run_pending_tasks().await;
+        assert_eq!(cache.entry_count(), 1);
+        assert!(cache.weighted_size() <= doc_size + 10);
+    }
+
+    #[tokio::test]
+    async fn cache_count_and_byte_limits() {
+        let dids = [
+            DID_KEY,
+            DID_JWK,
+            "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK",
+        ];
+
+        // Plenty of bytes, the count limit is reached first
+        let config = config::ClientConfigBuilder::default()
+            .with_cache_capacity(2)
+            .with_cache_weight_bytes(1_000_000)
+            .build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+        for did in dids {
+            client.resolve(did).await.unwrap();
+        }
+        let cache = client.get_cache();
+        cache.run_pending_tasks().await;
+        assert_eq!(cache.entry_count(), 2);
+
+        // Plenty of entries, the byte limit is reached first
+        let doc_size = _document_size(&client.resolve(DID_KEY).await.unwrap().doc) as u64;
+        let config = config::ClientConfigBuilder::default()
+            .with_cache_capacity(100)
+            .with_cache_weight_bytes(doc_size + 10)
+            .build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+        for did in dids {
+            client.resolve(did).await.unwrap();
+        }
+        let cache = client.get_cache();
+        cache.run_pending_tasks().await;
+        assert_eq!(cache.entry_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn method_ttl_overrides_cache_ttl() {
+        let config = config::ClientConfigBuilder::default()
+            .with_method_ttl(DIDMethod::KEY, 1)
+            .build();
+        assert_eq!(config.cache_ttl_for("key"), Duration::from_secs(1));
+        assert_eq!(config.cache_ttl_for("jwk"), Duration::from_secs(300));
+        assert_eq!(config.cache_ttl_for("unknown"), Duration::from_secs(300));
+
+        let client = DIDCacheClient::new(config).await.unwrap();
+        client.resolve(DID_KEY).await.unwrap();
+        client.resolve(DID_JWK).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let cache = client.get_cache();
+        cache.run_pending_tasks().await;
+        assert_eq!(cache.entry_count(), 1);
+        assert!(!client.resolve(DID_KEY).await.unwrap().cache_hit);
+        assert!(client.resolve(DID_JWK).await.unwrap().cache_hit);
+    }
+
+    #[tokio::test]
+    async fn resolve_from_fixtures() {
+        let fixture_did = "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
+        let fixture_doc = Document::new(DIDBuf::from_str(fixture_did).unwrap());
+
+        let config = config::ClientConfigBuilder::default()
+            .with_fixture_resolver(HashMap::from([(
+                fixture_did.to_string(),
+                fixture_doc.clone(),
+            )]))
+            .build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        let response = client.resolve(fixture_did).await.unwrap();
+        assert_eq!(response.doc, fixture_doc);
+        assert!(!response.cache_hit);
+
+        // Fixtures are cached like any other resolved DID
+        let response = client.resolve(fixture_did).await.unwrap();
+        assert!(response.cache_hit);
+
+        // Unmapped DIDs fall through to real resolution
+        let response = client.resolve(DID_KEY).await.unwrap();
+        assert!(!response.doc.verification_method.is_empty());
+    }
+
+    #[tokio::test]
+    async fn offline_mode() {
+        let fixture_did = "did:web:example.com";
+        let fixture_doc = Document::new(DIDBuf::from_str(fixture_did).unwrap());
+
+        let config = config::ClientConfigBuilder::default()
+            .offline()
+            .with_fixture_resolver(HashMap::from([(
+                fixture_did.to_string(),
+                fixture_doc.clone(),
+            )]))
+            .build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        // Self-contained methods still resolve
+        assert!(client.resolve(DID_KEY).await.is_ok());
+        assert!(client.resolve(DID_JWK).await.is_ok());
+
+        // Fixtures are used before the method check
+        let response = client.resolve(fixture_did).await.unwrap();
+        assert_eq!(response.doc, fixture_doc);
+
+        for did in [
+            "did:web:affinidi.com",
+            "did:dht:o3kbqdahhbbwutu6yrdfgo5ja9q1sdzxemyq3jqvb8gqw4a8mr6y",
+            "did:ethr:0x1:0xb9c5714089478a327f09197987f16f9e5d936e8a",
+        ] {
+            let method = did.split(':').nth(1).unwrap();
+            assert!(
+                matches!(client.resolve(did).await, Err(DIDCacheError::OfflineMethodUnsupported(m)) if m == method)
+            );
+        }
+        assert!(matches!(
+            client.exists("did:web:affinidi.com").await,
+            Err(DIDCacheError::OfflineMethodUnsupported(_))
+        ));
+        assert!(client.exists(DID_KEY).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn resolve_with_options() {
+        let client = client().await;
+        client.resolve(DID_KEY).await.unwrap();
+
+        // A stale DID Document in the cache
+        let stale = Document::new(DIDBuf::from_str(DID_KEY).unwrap());
+        client
+            .get_cache()
+            .insert(cache_key(DID_KEY), stale.clone().into())
+            .await;
+
+        // no_store resolves, but leaves the cache alone
+        let opts = ResolveOptions {
+            no_store: true,
+            ..Default::default()
+        };
+        let response = client.resolve_with_options(DID_KEY, opts).await.unwrap();
+        assert!(!response.cache_hit);
+        assert!(!response.doc.verification_method.is_empty());
+        assert_eq!(client.peek(DID_KEY).await, Some(stale.clone()));
+        assert_eq!(client.resolve(DID_KEY).await.unwrap().doc, stale);
+
+        // force_refresh resolves and replaces the cached copy
+        let opts = ResolveOptions {
+            force_refresh: true,
+            ..Default::default()
+        };
+        let response = client.resolve_with_options(DID_KEY, opts).await.unwrap();
+        assert!(!response.cache_hit);
+        assert_eq!(client.peek(DID_KEY).await, Some(response.doc));
+    }
+
+    #[test]
+    fn did_hash_is_blake2s256() {
+        // Pinned, changing the algorithm invalidates every precomputed key and shared cache
+        assert_eq!(
+            did_hash(DID_KEY),
+            "48a51321773b64f3c522c20f4b02cdc7af39517a6c725412eb1e95f24caf664a"
+        );
+        // Hashes the canonical DID
+        assert_eq!(did_hash(&format!(" {}#key-1", DID_KEY)), did_hash(DID_KEY));
+    }
+
+    #[tokio::test]
+    async fn contains_and_peek() {
+        let client = client().await;
+        assert!(!client.contains(DID_KEY).await);
+        assert!(client.peek(DID_KEY).await.is_none());
+        // Neither resolves the DID
+        assert_eq!(client.get_cache().entry_count(), 0);
+
+        let doc = client.resolve(DID_KEY).await.unwrap().doc;
+
+        // Same canonicalization as resolve()
+        let did = format!(" {}#key-1", DID_KEY.replacen("did:key", "DID:KEY", 1));
+        assert!(client.contains(&did).await);
+        assert_eq!(client.peek(&did).await, Some(doc));
+    }
+
+    fn _is_size_error(result: &Result<ResolveResponse, DIDCacheError>) -> bool {
+        matches!(result, Err(DIDCacheError::DIDTooLarge { .. }))
+    }
+
+    #[tokio::test]
+    async fn did_size_limit_boundary() {
+        let client = client().await;
+
+        // Exactly 1000 bytes is accepted (fails later as it isn't a valid did:key)
+        let did = format!("did:key:z{}", "a".repeat(1000 - 9));
+        assert_eq!(did.len(), 1000);
+        assert!(!_is_size_error(&client.resolve(&did).await));
+
+        // One byte over is rejected, with the exact sizes
+        let did = format!("did:key:z{}", "a".repeat(1000 - 8));
+        let result = client.resolve(&did).await;
+        assert!(matches!(
+            result,
+            Err(DIDCacheError::DIDTooLarge {
+                size_bytes: 1001,
+                limit_bytes: 1000
+            })
+        ));
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "The DID size of 1001 bytes exceeds the limit of 1000 bytes."
+        );
+
+        // Limits that aren't exact in floating point still accept the boundary
+        let config = config::ClientConfigBuilder::default()
+            .with_max_did_size_in_kb(0.3)
+            .build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+        let did = format!("did:key:z{}", "a".repeat(300 - 9));
+        assert!(!_is_size_error(&client.resolve(&did).await));
+    }
+
+    #[tokio::test]
+    async fn did_size_limit_counts_bytes() {
+        let client = client().await;
+
+        // 600 characters, but 1,192 bytes as 'é' is 2 bytes in UTF-8
+        let did = format!("did:web:{}", "é".repeat(600 - 8));
+        assert!(did.chars().count() < 1000);
+        assert!(matches!(
+            client.resolve(&did).await,
+            Err(DIDCacheError::DIDTooLarge {
+                size_bytes: 1192,
+                ..
+            })
+        ));
+
+        // Surrounding whitespace isn't part of the canonical DID
+        let did = format!("  did:key:z{}  ", "a".repeat(1000 - 9));
+        assert!(!_is_size_error(&client.resolve(&did).await));
+    }
+
+    #[tokio::test]
+    async fn resolve_parsed_did() {
+        let client = client().await;
+        let did = DIDBuf::from_str(DID_KEY).unwrap();
+
+        let response = client.resolve_parsed(&did).await.unwrap();
+        assert_eq!(response.did, DID_KEY);
+        assert_eq!(response.method, DIDMethod::KEY);
+        assert!(!response.cache_hit);
+
+        // Shares the cache with resolve
+        let response = client.resolve(DID_KEY).await.unwrap();
+        assert!(response.cache_hit);
+        let response = client.resolve_parsed(&did).await.unwrap();
+        assert!(response.cache_hit);
+    }
+
+    #[tokio::test]
+    async fn resolve_parsed_did_with_colons() {
+        let client = client().await;
+        let did = DIDBuf::from_str("did:pkh:eip155:1:0xb9c5714089478a327f09197987f16f9e5d936e8a")
+            .unwrap();
+
+        let response = client.resolve_parsed(&did).await.unwrap();
+        assert_eq!(response.doc.id, did);
+    }
+
+    #[tokio::test]
+    async fn too_many_parts_reports_key_parts() {
+        let config = config::ClientConfigBuilder::default()
+            .with_max_did_parts(2)
+            .build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        let result = client
+            .resolve("did:peer:2.Vz6Mk.Ez6LS.SeyJ0IjoiZG0ifQ")
+            .await;
+        let Err(e) = result else {
+            panic!("expected TooManyParts");
+        };
+        assert!(matches!(
+            e,
+            DIDCacheError::TooManyParts { found: 4, limit: 2 }
+        ));
+        assert!(e.to_string().contains("but 4 were found"));
+    }
+
+    #[tokio::test]
+    async fn empty_did_rejected() {
+        let client = client().await;
+
+        for did in ["", "   ", "\t\n"] {
+            let result = client.resolve(did).await;
+            assert!(matches!(result, Err(DIDCacheError::DIDError(msg)) if msg == "empty DID"));
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_did_syntax_rejected() {
+        let client = client().await;
+
+        for did in [
+            "did:peer:2.Ez6L bad",
+            "did:jwk:ab^c",
+            "did:key:z6Mk|",
+            "did:pkh:a b",
+        ] {
+            assert!(matches!(
+                client.resolve(did).await,
+                Err(DIDCacheError::InvalidDid(_))
+            ));
+            assert!(matches!(
+                client.exists(did).await,
+                Err(DIDCacheError::InvalidDid(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn surrounding_whitespace_trimmed() {
+        let client = client().await;
+
+        let response = client.resolve(DID_KEY).await.unwrap();
+        assert!(!response.cache_hit);
+
+        let response = client.resolve(&format!("  {}\n", DID_KEY)).await.unwrap();
+        assert!(response.cache_hit);
+        assert_eq!(response.did, DID_KEY);
+
+        assert!(client.remove(&format!(" {}", DID_KEY)).await.is_some());
+    }
+
     #[tokio::test]
     async fn remove_non_existing_cached_did() {
-        let client = basic_local_client().await;
+        let client = client().await;
 
         // We haven't resolved the cache, so it shouldn't be in the cache
         let removed_doc = client.remove(DID_KEY).await;
         assert_eq!(removed_doc, None);
     }
+
+    #[tokio::test]
+    async fn metadata_round_trips_through_cache() {
+        let config = config::ClientConfigBuilder::default().build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        let resolved = client.resolve(DID_KEY).await.unwrap();
+        assert!(!resolved.cache_hit);
+        assert!(resolved.resolution_metadata.content_type.is_some());
+        assert!(resolved.resolution_metadata.error.is_none());
+
+        let cached = client.resolve(DID_KEY).await.unwrap();
+        assert!(cached.cache_hit);
+        assert_eq!(cached.resolution_metadata, resolved.resolution_metadata);
+        assert_eq!(cached.document_metadata, resolved.document_metadata);
+    }
+
+    #[test]
+    fn canonicalize_dids() {
+        assert_eq!(
+            canonicalize_did(" did:WEB:Example.COM:Alice "),
+            "did:web:example.com:Alice"
+        );
+        assert_eq!(
+            canonicalize_did("DID:web:LocalHost%3a8080"),
+            "did:web:localhost%3A8080"
+        );
+        // Default port
+        assert_eq!(
+            canonicalize_did("did:web:example.com%3A443:Alice"),
+            "did:web:example.com:Alice"
+        );
+        assert_eq!(
+            canonicalize_did("did:web:example.com%3A4430"),
+            "did:web:example.com%3A4430"
+        );
+        // Percent-encoding
+        assert_eq!(
+            canonicalize_did("did:web:example.com:user%7ealice%2fkeys:%e2%82%ac%zz"),
+            "did:web:example.com:user~alice%2Fkeys:%E2%82%AC%zz"
+        );
+        assert_eq!(
+            canonicalize_did("did:web:%65xample.com"),
+            "did:web:example.com"
+        );
+        // Only did:web identifiers are case-insensitive
+        assert_eq!(
+            canonicalize_did("did:KEY:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv"),
+            DID_KEY
+        );
+        // DID URL parts aren't part of the DID
+        assert_eq!(
+            canonicalize_did(
+                "did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv#z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv"
+            ),
+            DID_KEY
+        );
+        assert_eq!(
+            canonicalize_did("did:web:example.com/path?query"),
+            "did:web:example.com"
+        );
+        assert_eq!(canonicalize_did("not a did"), "not a did");
+    }
+
+    #[tokio::test]
+    async fn verification_method_lookup() {
+        let did = "did:web:example.com";
+        let vm = |id: &str| {
+            serde_json::json!({
+                "id": id,
+                "type": "JsonWebKey2020",
+                "controller": did,
+                "publicKeyJwk": {
+                    "kty": "OKP",
+                    "crv": "Ed25519",
+                    "x": "Pc-Ki0MHOiiinc3tFSsmeTl6rsIyHAMF6fBgd-nx14c"
+                }
+            })
+        };
+        let doc: Document = serde_json::from_value(serde_json::json!({
+            "id": did,
+            "verificationMethod": [vm("did:web:example.com#key-1"), vm("did:web:example.com#key-2")],
+            "authentication": ["#key-1", "did:web:example.com#missing"],
+            "keyAgreement": ["did:web:example.com#key-2", vm("did:web:example.com#key-3")]
+        }))
+        .unwrap();
+        let config = config::ClientConfigBuilder::default()
+            .with_fixture_resolver(HashMap::from([(did.to_string(), doc)]))
+            .build();
+        let response = DIDCacheClient::new(config)
+            .await
+            .unwrap()
+            .resolve(did)
+            .await
+            .unwrap();
+
+        for id in ["did:web:example.com#key-1", "#key-1", "key-1"] {
+            assert_eq!(
+                response.get_verification_method(id).unwrap().id.as_str(),
+                "did:web:example.com#key-1"
+            );
+        }
+        assert!(response.get_verification_method("#key-3").is_none());
+        assert!(response
+            .get_verification_method("did:web:other.com#key-1")
+            .is_none());
+
+        let ids = |vms: Vec<&DIDVerificationMethod>| {
+            vms.iter().map(|vm| vm.id.to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(response.get_authentication()),
+            ["did:web:example.com#key-1"]
+        );
+        assert_eq!(
+            ids(response.get_key_agreement()),
+            ["did:web:example.com#key-2", "did:web:example.com#key-3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn eviction_listener_receives_cause() {
+        use config::RemovalCause;
+        use std::sync::Mutex;
+
+        let did = "did:web:example.com";
+        let doc: Document = serde_json::from_value(serde_json::json!({ "id": did })).unwrap();
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let listener = evicted.clone();
+        let config = config::ClientConfigBuilder::default()
+            .with_fixture_resolver(HashMap::from([(did.to_string(), doc)]))
+            .with_cache_ttl(1)
+            .with_eviction_listener(Arc::new(move |key, doc: Document, cause| {
+                listener
+                    .lock()
+                    .unwrap()
+                    .push((key, doc.id.to_string(), cause))
+            }))
+            .build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        let resolved = client.resolve(did).await.unwrap();
+        client.remove(did).await;
+        client.get_cache().run_pending_tasks().await;
+        assert_eq!(
+            evicted.lock().unwrap().pop(),
+            Some((
+                resolved.did_hash.clone(),
+                did.to_string(),
+                RemovalCause::Explicit
+            ))
+        );
+
+        client.resolve(did).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        client.get_cache().run_pending_tasks().await;
+        assert_eq!(
+            evicted.lock().unwrap().pop(),
+            Some((resolved.did_hash, did.to_string(), RemovalCause::Expired))
+        );
+    }
+
+    #[tokio::test]
+    async fn redirected_did_document() {
+        let doc: Document = serde_json::from_value(serde_json::json!({
+            "id": "did:web:new.example.com",
+            "alsoKnownAs": ["did:web:old.example.com"]
+        }))
+        .unwrap();
+        let config = config::ClientConfigBuilder::default()
+            .with_fixture_resolver(HashMap::from([(
+                "did:web:old.example.com".to_string(),
+                doc,
+            )]))
+            .build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        let resolved = client.resolve("did:web:old.example.com").await.unwrap();
+        assert_eq!(resolved.did, "did:web:old.example.com");
+        assert_eq!(resolved.resolved_id, "did:web:new.example.com");
+        assert!(resolved.is_redirected);
+
+        // Cache hits keep the redirect
+        let cached = client.resolve("did:web:old.example.com").await.unwrap();
+        assert!(cached.cache_hit);
+        assert!(cached.is_redirected);
+    }
+
+    #[tokio::test]
+    async fn mixed_case_did_web_single_cache_entry() {
+        let doc: Document =
+            serde_json::from_value(serde_json::json!({ "id": "did:web:example.com:Alice" }))
+                .unwrap();
+        let config = config::ClientConfigBuilder::default()
+            .with_fixture_resolver(HashMap::from([(doc.id.to_string(), doc)]))
+            .build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        let first = client.resolve("did:web:Example.COM:Alice").await.unwrap();
+        assert!(!first.cache_hit);
+        assert!(!first.is_redirected);
+        let second = client.resolve("did:web:example.com:Alice").await.unwrap();
+        assert!(second.cache_hit);
+        assert_eq!(first.did_hash, second.did_hash);
+
+        let cache = client.get_cache();
+        cache.run_pending_tasks().await;
+        assert_eq!(cache.entry_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn remove_wins_over_inflight_resolve() {
+        // did:web server that holds the response until told to send it
+        // The port isn't known before the server starts, so the document's id is another did:web
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel();
+        let mut server = test_fixtures::serve(
+            "200 OK",
+            serde_json::json!({ "id": "did:web:localhost%3A1" }).to_string(),
+            Some(release_rx),
+        )
+        .await;
+        let did = format!("did:web:localhost%3A{}", server.port);
+
+        let config = config::ClientConfigBuilder::default().build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        let resolving = {
+            let client = client.clone();
+            let did = did.clone();
+            tokio::spawn(async move { client.resolve(&did).await })
+        };
+
+        // Remove while the resolve is in flight, then let the resolve finish
+        server.requests.recv().await.unwrap();
+        client.remove(&did).await;
+        release_tx.send(()).unwrap();
+
+        let response = resolving.await.unwrap().unwrap();
+        assert_eq!(response.doc.id.as_str(), "did:web:localhost%3A1");
+        assert!(!response.cache_hit);
+
+        // The remove won, the resolved document wasn't cached
+        assert!(client.get_cache().get(&cache_key(&did)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn resolve_response_serializes() {
+        let client = client().await;
+        client.resolve(DID_KEY).await.unwrap();
+        let response = client.resolve(DID_KEY).await.unwrap();
+
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value["did"], DID_KEY);
+        assert_eq!(value["method"], "key");
+        assert_eq!(value["did_hash"], did_hash(DID_KEY));
+        assert_eq!(value["cache_hit"], true);
+        assert_eq!(value["doc"]["id"], DID_KEY);
+        assert!(value["age_ms"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn cache_hit_age() {
+        let client = client().await;
+
+        let response = client.resolve(DID_KEY).await.unwrap();
+        assert!(!response.cache_hit);
+        assert_eq!(response.age, Duration::ZERO);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let response = client.resolve(DID_KEY).await.unwrap();
+        assert!(response.cache_hit);
+        assert!(response.age >= Duration::from_millis(50));
+
+        // A forced refresh restarts the age
+        client
+            .resolve_with_options(
+                DID_KEY,
+                ResolveOptions {
+                    force_refresh: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let response = client.resolve(DID_KEY).await.unwrap();
+        assert!(response.age < Duration::from_millis(50));
+    }
+
+    /// Records the fields of every `resolve` span
+    #[derive(Clone, Default)]
+    struct SpanFields(Arc<std::sync::Mutex<Vec<(String, String)>>>);
+
+    impl field::Visit for SpanFields {
+        fn record_debug(&mut self, field: &field::Field, value: &dyn fmt::Debug) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((field.name().to_string(), format!("{:?}", value)));
+        }
+    }
+
+    impl<S> tracing_subscriber::Layer<S> for SpanFields
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_new_span(
+            &self,
+            attrs: &span::Attributes<'_>,
+            _: &span::Id,
+            _: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if attrs.metadata().name() == "resolve" {
+                attrs.record(&mut self.clone());
+            }
+        }
+
+        fn on_record(
+            &self,
+            id: &span::Id,
+            values: &span::Record<'_>,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if ctx.span(id).is_some_and(|span| span.name() == "resolve") {
+                values.record(&mut self.clone());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_span_fields() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let fields = SpanFields::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(fields.clone()));
+
+        let client = client().await;
+        client.resolve(DID_KEY).await.unwrap();
+        client.resolve(DID_KEY).await.unwrap();
+
+        let fields = fields.0.lock().unwrap().clone();
+        let values = |name: &str| -> Vec<String> {
+            fields
+                .iter()
+                .filter(|(field, _)| field == name)
+                .map(|(_, value)| value.clone())
+                .collect()
+        };
+        assert_eq!(values("did_method"), ["\"key\"", "\"key\""]);
+        assert_eq!(
+            values("did_hash"),
+            vec![format!("{:?}", did_hash(DID_KEY)); 2]
+        );
+        assert_eq!(values("cache_hit"), ["false", "true"]);
+        assert_eq!(values("latency_ms").len(), 2);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod wasm_tests {
+    use super::*;
+    use crate::test_fixtures::DID_KEY;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    async fn wasm_resolve_full_shape() {
+        let client = DIDCacheClient::new(config::ClientConfigBuilder::default().build())
+            .await
+            .unwrap();
+
+        let value = client.wasm_resolve_full(DID_KEY).await.unwrap();
+        let get = |key: &str| js_sys::Reflect::get(&value, &JsValue::from_str(key)).unwrap();
+        assert_eq!(get("did").as_string().as_deref(), Some(DID_KEY));
+        assert_eq!(get("method").as_string().as_deref(), Some("key"));
+        assert_eq!(get("method_name").as_string().as_deref(), Some("key"));
+        assert_eq!(get("did_hash").as_string(), Some(did_hash(DID_KEY)));
+        assert_eq!(get("cache_hit").as_bool(), Some(false));
+        assert!(get("doc").is_object());
+        assert!(get("age_ms").as_f64().is_some());
+    }
+
+    #[wasm_bindgen_test]
+    async fn wasm_remove_and_clear() {
+        let client = DIDCacheClient::new(config::ClientConfigBuilder::default().build())
+            .await
+            .unwrap();
+
+        // No-ops when nothing is cached
+        assert!(client.wasm_remove(DID_KEY).await.unwrap().is_undefined());
+        client.wasm_clear().await;
+
+        client.wasm_resolve(DID_KEY).await.unwrap();
+        assert!(client.wasm_remove(DID_KEY).await.unwrap().is_object());
+        client.wasm_resolve(DID_KEY).await.unwrap();
+        client.wasm_clear().await;
+        assert_eq!(client.wasm_cache_size().await, 0);
+    }
 }
@@ -0,0 +1,227 @@
+//! W3C DID Resolution metadata
+//!
+//! A DID Resolution Result is the DID Document plus DID Resolution Metadata (about the
+//! resolution itself) and DID Document Metadata (about the document, e.g. deactivated).
+//! The metadata is cached alongside the DID Document, so cache hits return the same metadata
+//! as the original resolution.
+//!
+//! See: <https://www.w3.org/TR/did-core/#did-resolution>
+
+use crate::{errors::DIDCacheError, DIDMethod};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ssi::dids::{document, resolution, Document};
+use std::time::Instant;
+
+/// DID Resolution Metadata
+/// content_type: Media type of the returned DID Document representation
+/// error: Error code if the resolution failed (e.g. `notFound`, `invalidDid`)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolutionMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ResolutionMetadata {
+    /// Resolution metadata for a failed resolution, with the W3C error code for the error
+    /// - `methodNotSupported`: the DID method isn't supported (or needs the network in offline mode)
+    /// - `notFound`: the DID Document doesn't exist
+    /// - `unsupportedPublicKeyType`: the did:key public key type isn't supported
+    /// - `invalidDid`: the DID is malformed
+    /// - `internalError`: anything else (e.g. network errors)
+    pub fn from_error(error: &DIDCacheError) -> Self {
+        let code = match error.code() {
+            "unsupported_method" | "offline_method_unsupported" => "methodNotSupported",
+            "not_found" => "notFound",
+            "unsupported_key_type" => "unsupportedPublicKeyType",
+            "invalid_did" | "did_too_large" | "too_many_parts" => "invalidDid",
+            _ => "internalError",
+        };
+
+        ResolutionMetadata {
+            content_type: None,
+            error: Some(code.to_string()),
+        }
+    }
+}
+
+impl From<&resolution::Metadata> for ResolutionMetadata {
+    fn from(metadata: &resolution::Metadata) -> Self {
+        ResolutionMetadata {
+            content_type: metadata.content_type.clone(),
+            error: None,
+        }
+    }
+}
+
+/// DID Document Metadata
+/// Fields are only set if the DID method provides them
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deactivated: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_id: Option<String>,
+}
+
+impl From<&document::Metadata> for DocumentMetadata {
+    fn from(metadata: &document::Metadata) -> Self {
+        DocumentMetadata {
+            deactivated: metadata.deactivated,
+            ..Default::default()
+        }
+    }
+}
+
+/// A DID Document and its resolution metadata, as stored in the cache
+/// inserted_at: When the entry was added to the local cache (not serialized, so an entry read
+///              from a cache backend starts from when it was read)
+/// method: Method of the DID that was resolved, set when cached (not serialized)
+///         This can differ from the DID Document id's method (e.g. a redirect)
+/// method_name: Name of that method, set with method. Serialized (unlike method) so a cache
+///              backend clears and expires the entry by the resolved method
+/// raw: The DID Document JSON as it was fetched (did:web) or received from the server, keeping
+///      the @context and field order. None if the document was derived from the DID
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheEntry {
+    #[serde(rename = "didDocument")]
+    pub doc: Document,
+    #[serde(rename = "didResolutionMetadata", default)]
+    pub resolution_metadata: ResolutionMetadata,
+    #[serde(rename = "didDocumentMetadata", default)]
+    pub document_metadata: DocumentMetadata,
+    #[serde(skip, default = "Instant::now")]
+    pub inserted_at: Instant,
+    #[serde(skip)]
+    pub method: Option<DIDMethod>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method_name: Option<String>,
+    #[serde(
+        rename = "didDocumentRaw",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub raw: Option<Value>,
+}
+
+impl CacheEntry {
+    /// Builds a cache entry from a DID Document and the SSI resolution metadata
+    pub(crate) fn new(
+        doc: Document,
+        metadata: &resolution::Metadata,
+        document_metadata: &document::Metadata,
+    ) -> Self {
+        CacheEntry {
+            doc,
+            resolution_metadata: metadata.into(),
+            document_metadata: document_metadata.into(),
+            inserted_at: Instant::now(),
+            method: None,
+            method_name: None,
+            raw: None,
+        }
+    }
+
+    /// Name of the method the entry was resolved with, or if unknown (e.g. a manually added
+    /// DID Document) the method of the DID Document's id
+    pub fn resolved_method_name(&self) -> &str {
+        self.method_name
+            .as_deref()
+            .unwrap_or_else(|| self.doc.id.method_name())
+    }
+}
+
+/// DID Documents that didn't come from a resolver (fixtures, manually added) have no metadata
+impl From<Document> for CacheEntry {
+    fn from(doc: Document) -> Self {
+        CacheEntry {
+            doc,
+            resolution_metadata: ResolutionMetadata::default(),
+            document_metadata: DocumentMetadata::default(),
+            inserted_at: Instant::now(),
+            method: None,
+            method_name: None,
+            raw: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_codes() {
+        let code = |e: DIDCacheError| ResolutionMetadata::from_error(&e).error.unwrap();
+
+        assert_eq!(
+            code(DIDCacheError::UnsupportedMethod("foo".into())),
+            "methodNotSupported"
+        );
+        assert_eq!(
+            code(DIDCacheError::NotFound("did:web:example.com".into())),
+            "notFound"
+        );
+        assert_eq!(
+            code(DIDCacheError::DIDError("did isn't to spec!".into())),
+            "invalidDid"
+        );
+        assert_eq!(code(DIDCacheError::NetworkTimeout), "internalError");
+    }
+
+    #[test]
+    fn serializes_to_w3c_names() {
+        let entry = CacheEntry {
+            doc: serde_json::from_value(serde_json::json!({ "id": "did:web:example.com" }))
+                .unwrap(),
+            resolution_metadata: ResolutionMetadata {
+                content_type: Some("application/did+json".into()),
+                error: None,
+            },
+            document_metadata: DocumentMetadata {
+                deactivated: Some(true),
+                version_id: Some("2".into()),
+                ..Default::default()
+            },
+            inserted_at: Instant::now(),
+            method: None,
+            method_name: None,
+            raw: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&entry).unwrap(),
+            serde_json::json!({
+                "didDocument": { "id": "did:web:example.com" },
+                "didResolutionMetadata": { "contentType": "application/did+json" },
+                "didDocumentMetadata": { "deactivated": true, "versionId": "2" },
+            })
+        );
+    }
+
+    #[test]
+    fn resolved_method_name_round_trips() {
+        let mut entry = CacheEntry::from(
+            serde_json::from_value::<Document>(serde_json::json!({ "id": "did:key:z6Mk" }))
+                .unwrap(),
+        );
+        assert_eq!(entry.resolved_method_name(), "key");
+
+        // Resolved as a did:web that redirected to a did:key
+        entry.method_name = Some("web".into());
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!(json["methodName"], "web");
+
+        let entry: CacheEntry = serde_json::from_value(json).unwrap();
+        assert_eq!(entry.resolved_method_name(), "web");
+    }
+}
@@ -0,0 +1,173 @@
+//! Pluggable DID method resolvers
+//!
+//! Each DID method is resolved by a [MethodResolver], looked up by method name in the client's
+//! registry. The registry starts with the built-in methods (dht, ethr, jwk, key, peer, pkh, web),
+//! and [ClientConfigBuilder::with_method](crate::config::ClientConfigBuilder::with_method) adds
+//! custom methods, or replaces a built-in one.
+//!
+//! Resolved DID Documents are cached like any other. A custom method's DIDs report
+//! [DIDMethod::CUSTOM](crate::DIDMethod::CUSTOM) as their method, and the method's name as
+//! their [method_name](crate::ResolveResponse::method_name). Clear a single custom method with
+//! [clear_method_name](crate::DIDCacheClient::clear_method_name).
+
+use crate::{errors::DIDCacheError, metadata::CacheEntry};
+use async_trait::async_trait;
+use ssi::dids::{Document, DID};
+use std::fmt::Debug;
+
+/// Resolves the DIDs of one DID method
+/// did: The parsed canonical DID (see [canonicalize_did](crate::canonicalize_did)), e.g. `did:mymethod:1234`
+///
+/// Return a NotFound error if the DID Document doesn't exist, an InvalidDid error if the DID is
+/// malformed, and a TransportError if it couldn't be fetched (so it is retried, see
+/// [is_transient](DIDCacheError::is_transient)).
+#[async_trait]
+pub trait MethodResolver: Send + Sync + Debug {
+    /// Returns the DID Document
+    async fn resolve(&self, did: &DID) -> Result<Document, DIDCacheError>;
+
+    /// Returns the DID Document with its W3C resolution metadata
+    /// The default has no metadata, override this if the method provides any
+    async fn resolve_with_metadata(&self, did: &DID) -> Result<CacheEntry, DIDCacheError> {
+        self.resolve(did).await.map(CacheEntry::from)
+    }
+}
+
+/// Is this a valid DID method name? (lowercase letters and digits, as in the DID syntax)
+pub(crate) fn is_method_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::ClientConfigBuilder,
+        test_fixtures::{client_with, DID_KEY},
+        DIDCacheClient, DIDMethod,
+    };
+    use ssi::dids::DIDBuf;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    /// Resolves any did:test to an empty DID Document, counting resolves
+    #[derive(Debug, Default)]
+    struct TestMethod(AtomicUsize);
+
+    #[async_trait]
+    impl MethodResolver for TestMethod {
+        async fn resolve(&self, did: &DID) -> Result<Document, DIDCacheError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            match did.as_str() {
+                "did:test:missing" => Err(DIDCacheError::NotFound(did.to_string())),
+                _ => Ok(Document::new(did.to_owned())),
+            }
+        }
+    }
+
+    #[test]
+    fn method_names() {
+        assert!(is_method_name("test"));
+        assert!(is_method_name("v2"));
+        assert!(!is_method_name(""));
+        assert!(!is_method_name("Test"));
+        assert!(!is_method_name("my-method"));
+    }
+
+    #[tokio::test]
+    async fn custom_method() {
+        let method = Arc::new(TestMethod::default());
+        let client = DIDCacheClient::new(
+            ClientConfigBuilder::default()
+                .with_method("test", method.clone())
+                .build(),
+        )
+        .await
+        .unwrap();
+
+        let response = client.resolve("did:test:1234").await.unwrap();
+        assert_eq!(response.doc.id, "did:test:1234");
+        assert_eq!(response.method, DIDMethod::CUSTOM);
+        assert_eq!(response.method_name, "test");
+        assert!(client.resolve("did:test:1234").await.unwrap().cache_hit);
+        assert_eq!(method.0.load(Ordering::SeqCst), 1);
+
+        assert!(matches!(
+            client.resolve("did:test:missing").await,
+            Err(DIDCacheError::NotFound(_))
+        ));
+        assert!(client.exists("did:test:1234").await.unwrap());
+        assert!(!client.exists("did:test:missing").await.unwrap());
+
+        client.clear_method(DIDMethod::CUSTOM).await.unwrap();
+        assert!(!client.contains("did:test:1234").await);
+
+        // Other clients don't know the method
+        let client = DIDCacheClient::new(ClientConfigBuilder::default().build())
+            .await
+            .unwrap();
+        assert!(matches!(
+            client.resolve("did:test:1234").await,
+            Err(DIDCacheError::UnsupportedMethod(method)) if method == "test"
+        ));
+    }
+
+    #[tokio::test]
+    async fn clear_single_custom_method() {
+        let client = client_with(
+            ClientConfigBuilder::default()
+                .with_method("test", Arc::new(TestMethod::default()))
+                .with_method("other", Arc::new(TestMethod::default())),
+        )
+        .await;
+        client.resolve("did:test:1234").await.unwrap();
+        let response = client.resolve("did:other:1234").await.unwrap();
+        assert_eq!(response.method, DIDMethod::CUSTOM);
+        assert_eq!(response.method_name, "other");
+
+        client.clear_method_name("test").await.unwrap();
+        assert!(!client.contains("did:test:1234").await);
+        assert!(client.contains("did:other:1234").await);
+
+        // Built-in methods can be cleared by name too
+        client.resolve(DID_KEY).await.unwrap();
+        client.clear_method_name("key").await.unwrap();
+        assert!(!client.contains(DID_KEY).await);
+        assert!(client.contains("did:other:1234").await);
+    }
+
+    #[tokio::test]
+    async fn replaces_builtin_method() {
+        let did = DID_KEY;
+
+        /// Resolves every did:key to the same DID Document
+        #[derive(Debug)]
+        struct FixedKey;
+
+        #[async_trait]
+        impl MethodResolver for FixedKey {
+            async fn resolve(&self, _: &DID) -> Result<Document, DIDCacheError> {
+                Ok(Document::new(
+                    DIDBuf::from_string("did:key:fixed".to_string()).unwrap(),
+                ))
+            }
+        }
+
+        let client = DIDCacheClient::new(
+            ClientConfigBuilder::default()
+                .with_method("key", Arc::new(FixedKey))
+                .build(),
+        )
+        .await
+        .unwrap();
+
+        let response = client.resolve(did).await.unwrap();
+        assert_eq!(response.doc.id, "did:key:fixed");
+        assert_eq!(response.method, DIDMethod::KEY);
+    }
+}
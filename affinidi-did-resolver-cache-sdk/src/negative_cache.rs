@@ -0,0 +1,251 @@
+//! Negative caching of failed resolutions
+//!
+//! Only resolved DID Documents are stored in the main cache, so a DID that doesn't exist (or
+//! is invalid) would hit the network on every resolve.
+//! When enabled with [with_negative_cache_ttl](crate::config::ClientConfigBuilder::with_negative_cache_ttl),
+//! failed resolutions are kept in a secondary cache with their own (shorter) TTL, and resolve()
+//! returns the original error until the entry expires.
+//!
+//! Transient failures (transport errors, timeouts, deadlines, busy, rate limited) and client side
+//! errors (cancelled, config) say nothing about the DID, so they are never cached.
+//! [resolve_with_retry](DIDCacheClient::resolve_with_retry) retries skip the negative cache.
+//!
+//! A successful resolution (e.g. from the shared cache backend, or a manually added DID Document)
+//! removes the negative entry.
+
+use crate::{config::ClientConfig, errors::DIDCacheError, metadata::CacheEntry, DIDCacheClient};
+use moka::future::Cache;
+use ssi::dids::Document;
+use std::time::Duration;
+use tracing::debug;
+
+/// The outcome of resolving a DID
+/// Found: The DID Document was resolved
+/// NotFound: The DID doesn't exist, holds the original error message
+/// Error: Resolving failed for another reason (e.g. host unreachable), holds the original error
+#[derive(Clone, Debug)]
+pub enum ResolveOutcome {
+    Found(Box<Document>),
+    NotFound(String),
+    Error(DIDCacheError),
+}
+
+impl ResolveOutcome {
+    /// Is this outcome worth remembering in the negative cache?
+    /// Only failures about the DID itself are, transient errors (see
+    /// [is_transient](DIDCacheError::is_transient), e.g. a deadline or rate limit) and client side
+    /// errors (cancelled, config) may not happen on the next resolve so aren't cached
+    pub fn is_negative(&self) -> bool {
+        match self {
+            ResolveOutcome::Found(_) => false,
+            ResolveOutcome::NotFound(_) => true,
+            ResolveOutcome::Error(e) => {
+                !(e.is_transient() || matches!(e.code(), "cancelled" | "config"))
+            }
+        }
+    }
+
+    /// Converts the outcome back to the result resolve() returned
+    pub fn into_result(self) -> Result<Document, DIDCacheError> {
+        match self {
+            ResolveOutcome::Found(doc) => Ok(*doc),
+            ResolveOutcome::NotFound(msg) => Err(DIDCacheError::NotFound(msg)),
+            ResolveOutcome::Error(e) => Err(e),
+        }
+    }
+}
+
+impl From<&Result<CacheEntry, DIDCacheError>> for ResolveOutcome {
+    fn from(result: &Result<CacheEntry, DIDCacheError>) -> Self {
+        match result {
+            Ok(entry) => ResolveOutcome::Found(Box::new(entry.doc.clone())),
+            Err(DIDCacheError::NotFound(msg)) => ResolveOutcome::NotFound(msg.clone()),
+            Err(e) => ResolveOutcome::Error(e.clone()),
+        }
+    }
+}
+
+/// Builds the negative cache if a negative_cache_ttl is configured
+pub(crate) fn build(config: &ClientConfig) -> Option<Cache<String, ResolveOutcome>> {
+    config.negative_cache_ttl.map(|ttl| {
+        Cache::builder()
+            .max_capacity(config.cache_capacity.into())
+            .time_to_live(Duration::from_secs(ttl.into()))
+            .build()
+    })
+}
+
+impl DIDCacheClient {
+    /// Returns the cached error for a DID that recently failed to resolve
+    pub(crate) async fn _negative_get(&self, did_hash: &str) -> Option<DIDCacheError> {
+        let outcome = self.negative_cache.as_ref()?.get(did_hash).await?;
+        outcome.into_result().err()
+    }
+
+    /// Remembers a failed resolution, unless the cache was removed from or cleared since the
+    /// resolve started (same as [_cache_insert](Self::_cache_insert))
+    pub(crate) async fn _negative_insert(
+        &self,
+        generation: u64,
+        did_hash: &str,
+        outcome: ResolveOutcome,
+    ) {
+        let Some(negative_cache) = &self.negative_cache else {
+            return;
+        };
+        if !outcome.is_negative() {
+            return;
+        }
+
+        let current = self.generation.read().await;
+        if *current == generation {
+            debug!("caching failed resolution for hash ({})", did_hash);
+            negative_cache.insert(did_hash.to_string(), outcome).await;
+        }
+    }
+
+    /// Removes a negative entry, e.g. once the DID has resolved
+    pub(crate) async fn _negative_remove(&self, did_hash: &str) {
+        if let Some(negative_cache) = &self.negative_cache {
+            negative_cache.invalidate(did_hash).await;
+        }
+    }
+
+    /// Removes all negative entries
+    pub(crate) async fn _negative_clear(&self) {
+        if let Some(negative_cache) = &self.negative_cache {
+            negative_cache.invalidate_all();
+            negative_cache.run_pending_tasks().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResolveOutcome;
+    use crate::test_fixtures::{client, client_with, web_server};
+    use crate::{config, errors::DIDCacheError};
+    use std::{collections::HashMap, sync::atomic::Ordering, time::Duration};
+
+    #[tokio::test]
+    async fn failed_resolution_is_cached() {
+        let (did, requests) = web_server("404 Not Found").await;
+        let client =
+            client_with(config::ClientConfigBuilder::default().with_negative_cache_ttl(60)).await;
+
+        let first = client.resolve(&did).await.err().unwrap().to_string();
+        let second = client.resolve(&did).await.err().unwrap().to_string();
+        assert_eq!(first, second);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn only_failures_about_the_did_are_negative() {
+        assert!(ResolveOutcome::NotFound("test".into()).is_negative());
+        assert!(ResolveOutcome::Error(DIDCacheError::InvalidDid("test".into())).is_negative());
+        assert!(
+            ResolveOutcome::Error(DIDCacheError::UnsupportedMethod("test".into())).is_negative()
+        );
+
+        for e in [
+            DIDCacheError::TransportError("deadline exceeded".into()),
+            DIDCacheError::NetworkTimeout,
+            DIDCacheError::Busy,
+            DIDCacheError::Cancelled,
+            DIDCacheError::ConfigError("test".into()),
+            DIDCacheError::RemoteError {
+                code: "rate_limited",
+                message: "test".into(),
+                retry_after: Some(Duration::from_secs(1)),
+            },
+            DIDCacheError::RemoteError {
+                code: "timeout",
+                message: "test".into(),
+                retry_after: None,
+            },
+        ] {
+            assert!(!ResolveOutcome::Error(e.clone()).is_negative(), "{}", e);
+        }
+        // The remote server saying the DID doesn't exist is about the DID
+        assert!(ResolveOutcome::Error(DIDCacheError::RemoteError {
+            code: "not_found",
+            message: "test".into(),
+            retry_after: None,
+        })
+        .is_negative());
+    }
+
+    #[tokio::test]
+    async fn transient_failure_is_not_cached() {
+        let (did, requests) = web_server("503 Service Unavailable").await;
+        let client =
+            client_with(config::ClientConfigBuilder::default().with_negative_cache_ttl(60)).await;
+
+        assert!(client.resolve(&did).await.is_err());
+        assert!(client.resolve(&did).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn disabled_by_default() {
+        let (did, requests) = web_server("500 Internal Server Error").await;
+        let client = client().await;
+
+        assert!(client.resolve(&did).await.is_err());
+        assert!(client.resolve(&did).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn success_overrides_negative_entry() {
+        let (did, requests) = web_server("404 Not Found").await;
+        let mut client =
+            client_with(config::ClientConfigBuilder::default().with_negative_cache_ttl(60)).await;
+        assert!(client.resolve(&did).await.is_err());
+
+        let doc = serde_json::from_value(serde_json::json!({ "id": did })).unwrap();
+        client.add_did_document(&did, doc).await;
+        assert_eq!(client.resolve(&did).await.unwrap().doc.id.as_str(), did);
+
+        // The negative entry is gone, so once the DID Document expires the DID is resolved again
+        client.get_cache().invalidate_all();
+        assert!(matches!(
+            client.resolve(&did).await,
+            Err(DIDCacheError::NotFound(_))
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn remove_clears_negative_entry() {
+        let (did, requests) = web_server("404 Not Found").await;
+        let client =
+            client_with(config::ClientConfigBuilder::default().with_negative_cache_ttl(60)).await;
+
+        assert!(client.resolve(&did).await.is_err());
+        client.remove(&did).await;
+        assert!(client.resolve(&did).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn fixtures_not_affected() {
+        let doc =
+            serde_json::from_value(serde_json::json!({ "id": "did:web:example.com" })).unwrap();
+        let client = client_with(
+            config::ClientConfigBuilder::default()
+                .with_negative_cache_ttl(60)
+                .with_fixture_resolver(HashMap::from([("did:web:example.com".to_string(), doc)])),
+        )
+        .await;
+
+        assert!(client.resolve("did:web:example.com").await.is_ok());
+        assert!(
+            client
+                .resolve("did:web:example.com")
+                .await
+                .unwrap()
+                .cache_hit
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Reconnect backoff for the websocket connection to the DID Cache server
+//!
+//! When connecting fails (or times out) the network task waits before trying again.
+//! The default is a linear backoff in 5 second steps up to 60 seconds. A multiplier above 1.0
+//! switches to an exponential backoff, and jitter spreads the retries of many clients out so they
+//! don't all reconnect at the same moment after a server restart.
+
+use rand::Rng;
+use std::time::Duration;
+
+/// Backoff between websocket connection attempts
+/// - initial: Delay after the first failed attempt (default: 5 seconds)
+/// - max: Upper bound for any single delay (default: 60 seconds)
+/// - multiplier: 1.0 (or less) is linear, each delay adds `initial`, above 1.0 each delay is
+///   multiplied by it (default: 1.0)
+/// - jitter: Randomize each delay between half and the full delay (default: false)
+#[derive(Clone, Debug, PartialEq)]
+pub struct BackoffConfig {
+    pub initial: Duration,
+    pub max: Duration,
+    pub multiplier: f64,
+    pub jitter: bool,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(5),
+            max: Duration::from_secs(60),
+            multiplier: 1.0,
+            jitter: false,
+        }
+    }
+}
+
+impl BackoffConfig {
+    /// Linear backoff, adding `step` after each failed attempt up to `max`
+    pub fn linear(step: Duration, max: Duration) -> Self {
+        Self {
+            initial: step,
+            max,
+            multiplier: 1.0,
+            jitter: false,
+        }
+    }
+
+    /// Exponential backoff, starting at `initial` and multiplying by `multiplier` after each
+    /// failed attempt up to `max`
+    pub fn exponential(initial: Duration, multiplier: f64, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            multiplier,
+            jitter: false,
+        }
+    }
+
+    /// Randomize each delay between half and the full delay
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Returns the delay before retrying after the given failed attempt (1 based), without jitter
+    pub(crate) fn delay_for_attempt(&self, attempt: u32) -> Duration {
+        let attempt = attempt.max(1);
+        let delay = if self.multiplier > 1.0 {
+            self.initial.as_secs_f64() * self.multiplier.powf(f64::from(attempt - 1))
+        } else {
+            self.initial.as_secs_f64() * f64::from(attempt)
+        };
+
+        // Also catches infinity when the multiplier overflows
+        if delay.is_finite() && delay < self.max.as_secs_f64() {
+            Duration::from_secs_f64(delay)
+        } else {
+            self.max
+        }
+    }
+
+    /// Returns the delay to wait after the given failed attempt (1 based), with jitter if enabled
+    pub(crate) fn next_delay(&self, attempt: u32) -> Duration {
+        let delay = self.delay_for_attempt(attempt);
+        if self.jitter && !delay.is_zero() {
+            rand::rng().random_range(delay / 2..=delay)
+        } else {
+            delay
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BackoffConfig;
+    use std::time::Duration;
+
+    fn delays(config: &BackoffConfig, attempts: u32) -> Vec<u64> {
+        (1..=attempts)
+            .map(|attempt| config.delay_for_attempt(attempt).as_millis() as u64)
+            .collect()
+    }
+
+    #[test]
+    fn default_is_linear_5s_up_to_60s() {
+        let config = BackoffConfig::default();
+        assert_eq!(
+            delays(&config, 14),
+            [
+                5000, 10000, 15000, 20000, 25000, 30000, 35000, 40000, 45000, 50000, 55000, 60000,
+                60000, 60000
+            ]
+        );
+        assert_eq!(config.next_delay(3), Duration::from_secs(15));
+    }
+
+    #[test]
+    fn linear() {
+        let config = BackoffConfig::linear(Duration::from_millis(250), Duration::from_millis(1000));
+        assert_eq!(delays(&config, 6), [250, 500, 750, 1000, 1000, 1000]);
+    }
+
+    #[test]
+    fn exponential() {
+        let config =
+            BackoffConfig::exponential(Duration::from_millis(100), 2.0, Duration::from_secs(1));
+        assert_eq!(delays(&config, 6), [100, 200, 400, 800, 1000, 1000]);
+
+        // Huge attempt counts don't overflow
+        assert_eq!(config.delay_for_attempt(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn jitter_within_bounds() {
+        let config =
+            BackoffConfig::exponential(Duration::from_millis(100), 2.0, Duration::from_secs(1))
+                .with_jitter(true);
+        for attempt in 1..10 {
+            let full = config.delay_for_attempt(attempt);
+            let delay = config.next_delay(attempt);
+            assert!(
+                delay >= full / 2 && delay <= full,
+                "{:?} vs {:?}",
+                delay,
+                full
+            );
+        }
+    }
+}
@@ -6,64 +6,296 @@
 
 use network::WSCommands;
 use rand::{distr::Alphanumeric, Rng};
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{self, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
 use ssi::dids::Document;
-use tokio::{select, sync::oneshot};
+use std::{
+    fmt,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+use tokio::{
+    select,
+    sync::{
+        broadcast,
+        mpsc::{error::TrySendError, Sender},
+        oneshot,
+    },
+    time::{sleep, timeout},
+};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, span, warn, Instrument, Level};
 
-use crate::{errors::DIDCacheError, DIDCacheClient};
+use crate::{
+    cache_key::DIDHash,
+    errors::DIDCacheError,
+    metadata::{CacheEntry, DocumentMetadata, ResolutionMetadata},
+    DIDCacheClient,
+};
+pub mod backoff;
 pub mod network;
 mod request_queue;
+pub mod tls;
+
+pub use request_queue::RequestListStats;
+
+/// Websocket subprotocol for the current WSRequest/WSResponse protocol version
+/// Offered by the SDK on connect, and must be selected by the server
+/// Bump this when the protocol changes in a way older clients or servers can't handle
+pub const WS_PROTOCOL_VERSION: &str = "affinidi-did-resolver.v1";
+
+/// Start of the body of the 400 Bad Request the server answers an unsupported protocol version with
+/// Tells the rejection apart from a 400 sent by a proxy or load balancer
+pub const UNSUPPORTED_PROTOCOL: &str = "Unsupported protocol version";
 
 /// WSRequest is the request format to the websocket connection
 /// did: DID to resolve
+/// request_id: Unique id for this request, echoed back in the response (empty from older clients)
 #[derive(Debug, Deserialize, Serialize)]
 pub struct WSRequest {
     pub did: String,
+    #[serde(default)]
+    pub request_id: String,
+}
+
+/// Blake2s256 hash of the DID exactly as sent in a [WSRequest], the server echoes it in its response
+/// Not canonicalized: older clients key their requests by the hash of the DID as they sent it
+/// This SDK sends canonical DIDs, so for its requests it is the same as [did_hash](crate::did_hash)
+pub fn request_hash(did: &str) -> String {
+    DIDHash::raw(did).to_string()
 }
 
 /// WSResponse is the response format from the websocket connection
-/// did: DID that was resolved
-/// hash: SHA256 Hash of the DID
+/// request_id: The request_id of the [WSRequest] (empty from older servers)
+/// did: DID of the [WSRequest]
+/// hash: Hash of the DID of the [WSRequest] (see [request_hash])
 /// document: The resolved DID Document
+/// resolution_metadata, document_metadata: W3C metadata (default if the server doesn't send it)
+/// raw: The DID Document JSON as the server resolved it, only sent if it differs from document
 #[derive(Debug, Deserialize, Serialize)]
 pub struct WSResponse {
+    #[serde(default)]
+    pub request_id: String,
     pub did: String,
     pub hash: String,
     pub document: Document,
+    #[serde(default)]
+    pub resolution_metadata: ResolutionMetadata,
+    #[serde(default)]
+    pub document_metadata: DocumentMetadata,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<serde_json::Value>,
 }
 
 /// WSResponseError is the response format from the websocket connection if an error occurred server side.
-/// did: DID associated with the error
-/// hash: SHA256 Hash of the DID
+/// request_id: The request_id of the [WSRequest] (empty from older servers)
+/// did: DID of the [WSRequest]
+/// hash: Hash of the DID of the [WSRequest] (see [request_hash])
+/// code: Stable error code, see [DIDCacheError::code] (empty from older servers)
 /// error: Error message
+/// retry_after_ms: OPTIONAL: How long to wait before retrying in milliseconds (e.g. when rate_limited)
 #[derive(Debug, Deserialize, Serialize)]
 pub struct WSResponseError {
+    #[serde(default)]
+    pub request_id: String,
     pub did: String,
     pub hash: String,
+    #[serde(default)]
+    pub code: String,
     pub error: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
+}
+
+/// Snapshot of the websocket connection health
+/// reconnects: Number of times the websocket has been reconnected
+/// last_error: The most recent connection error, if any
+/// connected: Is the websocket currently connected?
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConnectionDiagnostics {
+    pub reconnects: u64,
+    pub last_error: Option<String>,
+    pub connected: bool,
+}
+
+/// Backoff while waiting for room in a full request list, jittered so waiting resolves
+/// don't all retry at once
+const BUSY_BACKOFF: backoff::BackoffConfig = backoff::BackoffConfig {
+    initial: Duration::from_millis(5),
+    max: Duration::from_millis(250),
+    multiplier: 2.0,
+    jitter: true,
+};
+
+/// How many connection events are buffered for each subscriber before it starts lagging
+pub(crate) const CONNECTION_EVENT_CHANNEL_CAPACITY: usize = 16;
+
+/// Changes to the state of the websocket connection
+/// Connected: The websocket connected (or reconnected) to the remote server
+/// Disconnected: The websocket connection was lost or closed
+/// Reconnecting: Connecting failed, retrying after a backoff. attempt is the number of failed attempts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    Connected,
+    Disconnected,
+    Reconnecting { attempt: u32 },
 }
 
 /// WSResponseType is the type of response received from the websocket connection
 /// Response: A successful response
 /// Error: An error response
-#[derive(Debug, Deserialize, Serialize)]
+/// Unknown: A response type this version doesn't know (e.g. added by a newer server)
+///          Only produced when deserializing, it can't be serialized
+#[derive(Debug, Serialize)]
 pub enum WSResponseType {
-    Response(WSResponse),
+    Response(Box<WSResponse>),
     Error(WSResponseError),
+    #[serde(skip_serializing)]
+    Unknown(WSUnknownResponse),
+}
+
+/// A response of an unknown type, with the fields needed to match it to its request
+/// response_type: The type (tag) of the response
+/// request_id, hash: Taken from the response if present, otherwise empty
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WSUnknownResponse {
+    pub response_type: String,
+    pub request_id: String,
+    pub hash: String,
+}
+
+impl<'de> Deserialize<'de> for WSResponseType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TypeVisitor;
+
+        impl<'de> Visitor<'de> for TypeVisitor {
+            type Value = WSResponseType;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a websocket response ({\"<type>\": {...}})")
+            }
+
+            // A type without content
+            fn visit_str<E: de::Error>(self, response_type: &str) -> Result<Self::Value, E> {
+                Ok(WSResponseType::Unknown(WSUnknownResponse {
+                    response_type: response_type.to_string(),
+                    ..Default::default()
+                }))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let response_type: String = map
+                    .next_key()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let response = match response_type.as_str() {
+                    "Response" => WSResponseType::Response(map.next_value()?),
+                    "Error" => WSResponseType::Error(map.next_value()?),
+                    _ => {
+                        let content: serde_json::Value = map.next_value()?;
+                        let field = |name: &str| {
+                            content
+                                .get(name)
+                                .and_then(serde_json::Value::as_str)
+                                .unwrap_or_default()
+                                .to_string()
+                        };
+                        WSResponseType::Unknown(WSUnknownResponse {
+                            request_id: field("request_id"),
+                            hash: field("hash"),
+                            response_type,
+                        })
+                    }
+                };
+                if map.next_key::<de::IgnoredAny>()?.is_some() {
+                    return Err(de::Error::custom("expected a single response type"));
+                }
+                Ok(response)
+            }
+        }
+
+        deserializer.deserialize_any(TypeVisitor)
+    }
 }
 
 impl DIDCacheClient {
+    /// Returns the health of the websocket connection to the remote server
+    /// In local mode this is always the default (not connected, no errors)
+    pub fn connection_diagnostics(&self) -> ConnectionDiagnostics {
+        self.network_status.diagnostics()
+    }
+
+    /// Returns how many resolves were sent to the network task, and how many of them were
+    /// deduplicated into a request already in flight, e.g. for tuning network_cache_limit_count
+    /// None in local mode
+    pub fn network_stats(&self) -> Option<RequestListStats> {
+        self.network_task_tx
+            .as_ref()
+            .map(|_| self.network_status.request_list.stats())
+    }
+
+    /// Is the network task's request list full (network_cache_limit_count requests in flight)?
+    /// While it is, resolves wait (see
+    /// [with_network_busy_timeout](crate::config::ClientConfigBuilder::with_network_busy_timeout))
+    /// and [try_resolve](Self::try_resolve) returns [DIDCacheError::Busy], so callers can back off
+    /// Always false in local mode
+    pub fn network_busy(&self) -> bool {
+        self.network_task_tx.is_some() && self.network_status.full.load(Ordering::Relaxed)
+    }
+
+    /// Waits with an exponential, jittered backoff until the request list has room
+    /// Returns [DIDCacheError::Busy] if it is still full at busy_deadline
+    async fn _wait_while_busy(
+        &self,
+        busy_deadline: Instant,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<(), DIDCacheError> {
+        let mut attempt = 0;
+        while self.network_status.full.load(Ordering::Relaxed) {
+            let remaining = busy_deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                debug!("network task request list is still full, returning busy");
+                return Err(DIDCacheError::Busy);
+            }
+            attempt += 1;
+            let delay = BUSY_BACKOFF.next_delay(attempt).min(remaining);
+            match cancel {
+                Some(cancel) => select! {
+                    _ = cancel.cancelled() => return Err(DIDCacheError::Cancelled),
+                    _ = sleep(delay) => {}
+                },
+                None => sleep(delay).await,
+            }
+        }
+        Ok(())
+    }
+
+    /// Subscribe to changes in the websocket connection state
+    /// Only events published after subscribing are received, use [connection_diagnostics](Self::connection_diagnostics)
+    /// for the current state. In local mode no events are published
+    pub fn subscribe_connection_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.network_status.events.subscribe()
+    }
+
     /// Resolve a DID via the network
     /// Returns the resolved DID Document, or an error
     ///
     /// Send the request, and wait for the response
+    /// fail_fast: If true, returns [DIDCacheError::Busy] if the network task can't accept the request,
+    ///            otherwise waits up to network_busy_timeout (or the deadline) for it to
+    /// deadline: If sooner than network_timeout, the request times out at the deadline instead
+    /// cancel: If cancelled while waiting, the request is removed and [DIDCacheError::Cancelled] returned
     pub(crate) async fn network_resolve(
         &self,
         did: &str,
         did_hash: &str,
-    ) -> Result<Document, DIDCacheError> {
-        let _span = span!(Level::DEBUG, "network_resolve");
+        fail_fast: bool,
+        deadline: Option<Instant>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<CacheEntry, DIDCacheError> {
+        // Child of the resolve span, covers the websocket round trip
+        let _span = span!(Level::DEBUG, "network_resolve", did_hash = did_hash);
         async move {
             debug!("resolving did ({}) via network hash ({})", did, did_hash);
 
@@ -82,39 +314,104 @@ impl DIDCacheClient {
             .collect();
 
             // 1. Send the request to the network task, which will then send via websocket to the remote server
-            network_task_tx
-                .send(WSCommands::Send(tx, unique_id.clone(), WSRequest { did: did.into() }))
-                .await
-                .map_err(|e| {
-                    DIDCacheError::TransportError(format!(
-                        "Couldn't send request to network_task. Reason: {}",
-                        e
-                    ))
+            // The request_id is only used if this is the first request for the DID, otherwise
+            // the response to the request already in flight is shared
+            let request = WSCommands::Send(
+                tx,
+                unique_id.clone(),
+                WSRequest {
+                    did: did.into(),
+                    request_id: unique_id.clone(),
+                },
+            );
+            if fail_fast {
+                if self.network_status.full.load(Ordering::Relaxed) {
+                    debug!("network task request list is full, returning busy");
+                    return Err(DIDCacheError::Busy);
+                }
+                network_task_tx.try_send(request).map_err(|e| match e {
+                    TrySendError::Full(_) => DIDCacheError::Busy,
+                    TrySendError::Closed(_) => DIDCacheError::TransportError(
+                        "Couldn't send request to network_task. Reason: channel closed".into(),
+                    ),
                 })?;
+            } else {
+                let mut busy_deadline = Instant::now() + self.config.network_busy_timeout;
+                if let Some(deadline) = deadline {
+                    busy_deadline = busy_deadline.min(deadline);
+                }
+                self._wait_while_busy(busy_deadline, cancel).await?;
+
+                // The command channel can still be full (e.g. a burst of requests)
+                let remaining = busy_deadline.saturating_duration_since(Instant::now());
+                match timeout(remaining, network_task_tx.send(request)).await {
+                    Ok(sent) => sent.map_err(|e| {
+                        DIDCacheError::TransportError(format!(
+                            "Couldn't send request to network_task. Reason: {}",
+                            e
+                        ))
+                    })?,
+                    Err(_) => {
+                        debug!("network task command channel is still full, returning busy");
+                        return Err(DIDCacheError::Busy);
+                    }
+                }
+            }
+
+            // Removes the request from the request list unless a response is received, also if
+            // this future is dropped
+            let mut pending = PendingRequest {
+                network_task_tx: &network_task_tx,
+                request_id: unique_id,
+                did_hash,
+                answered: false,
+            };
 
             // 2. Wait for the response from the network task
 
+            // Wait for network_timeout, or until the caller's deadline if that is sooner
+            let (timeout, deadline_reached) = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    (remaining.min(self.config.network_timeout), remaining < self.config.network_timeout)
+                }
+                None => (self.config.network_timeout, false),
+            };
+
             // Setup the timer for the wait, doesn't do anything till `await` is called in the select! macro
-            let sleep = tokio::time::sleep(self.config.network_timeout);
+            let sleep = tokio::time::sleep(timeout);
             tokio::pin!(sleep);
 
+            let cancelled = async {
+                match cancel {
+                    Some(cancel) => cancel.cancelled().await,
+                    None => std::future::pending().await,
+                }
+            };
+
                 select! {
+                    _ = cancelled => {
+                        debug!("Resolve cancelled did_hash ({})", did_hash);
+                        Err(DIDCacheError::Cancelled)
+                    }
                     _ = &mut sleep => {
                         warn!("Timeout reached, no message received did_hash ({})", did_hash);
-                        network_task_tx.send(WSCommands::TimeOut(unique_id, did_hash.to_string())).await.map_err(|err| {
-                            DIDCacheError::TransportError(format!("Could not send timeout message to ws_handler: {:?}", err))
-                        })?;
-                         Err(DIDCacheError::NetworkTimeout)
+                        if deadline_reached {
+                            Err(DIDCacheError::TransportError("deadline exceeded".into()))
+                        } else {
+                            Err(DIDCacheError::NetworkTimeout)
+                        }
                     }
                     value = rx => {
+                        pending.answered = value.is_ok();
                         match value {
-                            Ok(WSCommands::ResponseReceived(doc)) => {
+                            Ok(WSCommands::ResponseReceived(entry)) => {
                                 debug!("Received response from network task ({})", did_hash);
-                                 Ok(*doc)
+                                 Ok(*entry)
                             }
-                            Ok(WSCommands::ErrorReceived(msg)) => {
+                            Ok(WSCommands::ErrorReceived(e)) => {
                                 warn!("Received error response from network task");
-                                 Err(DIDCacheError::TransportError(msg))
+                                 Err(e)
                             }
                             Ok(_) => {
                                 debug!("Received unexpected response from network task");
@@ -132,3 +429,405 @@ impl DIDCacheClient {
         .await
     }
 }
+
+/// A request sent to the network task that is waiting for its response
+/// When dropped without a response (timeout, cancellation or the resolve being dropped) the
+/// request is removed from the request list. This never waits: if the command channel is full
+/// the request stays listed until the server answers it
+struct PendingRequest<'a> {
+    network_task_tx: &'a Sender<WSCommands>,
+    request_id: String,
+    did_hash: &'a str,
+    answered: bool,
+}
+
+impl Drop for PendingRequest<'_> {
+    fn drop(&mut self) {
+        if self.answered {
+            return;
+        }
+        let timeout = WSCommands::TimeOut(
+            std::mem::take(&mut self.request_id),
+            self.did_hash.to_string(),
+        );
+        if let Err(e) = self.network_task_tx.try_send(timeout) {
+            warn!(
+                "Couldn't remove request did_hash ({}) from the network task: {}",
+                self.did_hash, e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        network::{NetworkStatus, NetworkTask},
+        ConnectionDiagnostics, ConnectionEvent,
+    };
+    use crate::test_fixtures::DID_KEY;
+    use crate::{config, errors::DIDCacheError, DIDCacheClient, ResolveOptions};
+    use moka::future::Cache;
+    use std::{
+        sync::{atomic::Ordering, Arc},
+        time::{Duration, Instant},
+    };
+    use tokio::sync::{mpsc, Mutex};
+    use tokio_util::sync::CancellationToken;
+
+    /// Creates a network client that isn't connected to a network task
+    /// Returns the receiving end of the command channel so the test controls when it is read
+    fn _disconnected_network_client(
+        channel_capacity: usize,
+    ) -> (DIDCacheClient, mpsc::Receiver<super::WSCommands>) {
+        let config = config::ClientConfigBuilder::default()
+            .with_network_mode("ws://127.0.0.1:1/did/v1/ws")
+            .build();
+        let (task_tx, task_rx) = mpsc::channel(channel_capacity);
+        let (_, sdk_rx) = mpsc::channel(1);
+
+        let client = DIDCacheClient {
+            config,
+            cache: Cache::new(10),
+            network_task_tx: Some(task_tx),
+            network_task_rx: Some(Arc::new(Mutex::new(sdk_rx))),
+            network_status: Arc::new(NetworkStatus::default()),
+            events: tokio::sync::broadcast::channel(1).0,
+            generation: Default::default(),
+            negative_cache: None,
+            counters: Default::default(),
+            http_client: Default::default(),
+            methods: Default::default(),
+            in_flight: Default::default(),
+            #[cfg(feature = "did_example")]
+            did_example_cache: did_example::DiDExampleCache::new(),
+        };
+
+        (client, task_rx)
+    }
+
+    #[tokio::test]
+    async fn try_resolve_busy_when_channel_full() {
+        let (client, _task_rx) = _disconnected_network_client(1);
+
+        // Fill the channel, nothing is reading from it
+        client
+            .network_task_tx
+            .as_ref()
+            .unwrap()
+            .try_send(super::WSCommands::Connected)
+            .unwrap();
+
+        let result = client.try_resolve(DID_KEY).await;
+        assert!(matches!(result, Err(DIDCacheError::Busy)));
+    }
+
+    #[tokio::test]
+    async fn try_resolve_busy_when_request_list_full() {
+        let (client, _task_rx) = _disconnected_network_client(10);
+        client.network_status.full.store(true, Ordering::Relaxed);
+
+        let result = client.try_resolve(DID_KEY).await;
+        assert!(matches!(result, Err(DIDCacheError::Busy)));
+    }
+
+    #[tokio::test]
+    async fn resolve_waits_while_request_list_full() {
+        let (mut client, mut task_rx) = _disconnected_network_client(10);
+        client.config.network_timeout = Duration::from_millis(100);
+        client.network_status.full.store(true, Ordering::Relaxed);
+
+        // Room becomes available before the busy timeout, so the request is sent
+        let status = client.network_status.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            status.full.store(false, Ordering::Relaxed);
+        });
+        let result = client.resolve(DID_KEY).await;
+        assert!(matches!(result, Err(DIDCacheError::NetworkTimeout)));
+        assert!(matches!(
+            task_rx.recv().await,
+            Some(super::WSCommands::Send(..))
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolve_busy_after_busy_timeout() {
+        let (mut client, mut task_rx) = _disconnected_network_client(10);
+        client.config.network_busy_timeout = Duration::from_millis(100);
+        client.network_status.full.store(true, Ordering::Relaxed);
+        assert!(client.network_busy());
+
+        let start = Instant::now();
+        let result = client.resolve(DID_KEY).await;
+        assert!(matches!(result, Err(DIDCacheError::Busy)));
+        assert!(start.elapsed() < client.config.network_timeout);
+        assert!(task_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn resolve_busy_when_channel_stays_full() {
+        let (mut client, _task_rx) = _disconnected_network_client(1);
+        client.config.network_busy_timeout = Duration::from_millis(100);
+        client
+            .network_task_tx
+            .as_ref()
+            .unwrap()
+            .try_send(super::WSCommands::Connected)
+            .unwrap();
+
+        let result = client.resolve(DID_KEY).await;
+        assert!(matches!(result, Err(DIDCacheError::Busy)));
+    }
+
+    #[tokio::test]
+    async fn resolve_before_deadline_exceeded() {
+        let (client, mut task_rx) = _disconnected_network_client(10);
+
+        // Nothing answers the request, so the deadline passes long before network_timeout
+        let start = Instant::now();
+        let result = client
+            .resolve_before(DID_KEY, start + Duration::from_millis(100))
+            .await;
+
+        assert!(
+            matches!(result, Err(DIDCacheError::TransportError(msg)) if msg == "deadline exceeded")
+        );
+        assert!(start.elapsed() < client.config.network_timeout);
+
+        // The request timed out at the deadline, rather than being dropped in the request list
+        let (sent, removed) = _sent_and_removed(&mut task_rx).await;
+        assert_eq!(sent, removed);
+    }
+
+    #[tokio::test]
+    async fn resolve_before_doesnt_wait_for_full_channel() {
+        // The request fills the channel, so it can't be removed at the deadline
+        let (client, mut task_rx) = _disconnected_network_client(1);
+
+        let start = Instant::now();
+        let result = client
+            .resolve_before(DID_KEY, start + Duration::from_millis(100))
+            .await;
+        assert!(
+            matches!(result, Err(DIDCacheError::TransportError(msg)) if msg == "deadline exceeded")
+        );
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        assert!(matches!(
+            task_rx.recv().await,
+            Some(super::WSCommands::Send(..))
+        ));
+        assert!(task_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn dropped_resolve_removes_request() {
+        let (client, mut task_rx) = _disconnected_network_client(10);
+
+        // The caller gives up without a deadline or cancellation token
+        let result =
+            tokio::time::timeout(Duration::from_millis(100), client.resolve(DID_KEY)).await;
+        assert!(result.is_err());
+
+        let (sent, removed) = _sent_and_removed(&mut task_rx).await;
+        assert_eq!(sent, removed);
+    }
+
+    #[tokio::test]
+    async fn resolve_before_deadline_already_passed() {
+        let (client, mut task_rx) = _disconnected_network_client(10);
+
+        let result = client.resolve_before(DID_KEY, Instant::now()).await;
+        assert!(
+            matches!(result, Err(DIDCacheError::TransportError(msg)) if msg == "deadline exceeded")
+        );
+
+        // No request was sent
+        assert!(task_rx.try_recv().is_err());
+    }
+
+    /// Returns the request id of the request sent to the network task, and the id the
+    /// network task was then told to remove
+    async fn _sent_and_removed(
+        task_rx: &mut mpsc::Receiver<super::WSCommands>,
+    ) -> (String, String) {
+        let Some(super::WSCommands::Send(_, sent, _)) = task_rx.recv().await else {
+            panic!("request wasn't sent");
+        };
+        let Some(super::WSCommands::TimeOut(removed, _)) = task_rx.recv().await else {
+            panic!("request wasn't removed");
+        };
+        (sent, removed)
+    }
+
+    #[tokio::test]
+    async fn resolve_timeout_removes_request() {
+        let (mut client, mut task_rx) = _disconnected_network_client(10);
+        client.config.network_timeout = Duration::from_millis(100);
+
+        // Nothing answers the request
+        let result = client.resolve(DID_KEY).await;
+        assert!(matches!(result, Err(DIDCacheError::NetworkTimeout)));
+
+        let (sent, removed) = _sent_and_removed(&mut task_rx).await;
+        assert_eq!(sent, removed);
+    }
+
+    #[tokio::test]
+    async fn resolve_with_cancel() {
+        let (client, mut task_rx) = _disconnected_network_client(10);
+        let cancel = CancellationToken::new();
+
+        let _cancel = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            _cancel.cancel();
+        });
+        let start = Instant::now();
+        let result = client
+            .resolve_with_cancel(DID_KEY, ResolveOptions::default(), &cancel)
+            .await;
+        assert!(matches!(result, Err(DIDCacheError::Cancelled)));
+        assert!(start.elapsed() < client.config.network_timeout);
+
+        let (sent, removed) = _sent_and_removed(&mut task_rx).await;
+        assert_eq!(sent, removed);
+
+        // Already cancelled, nothing is sent
+        let result = client
+            .resolve_with_cancel(DID_KEY, ResolveOptions::default(), &cancel)
+            .await;
+        assert!(matches!(result, Err(DIDCacheError::Cancelled)));
+        assert!(task_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn unknown_response_types() {
+        let parse = |json: &str| serde_json::from_str::<super::WSResponseType>(json);
+
+        let Ok(super::WSResponseType::Unknown(unknown)) =
+            parse(r#"{"Progress": {"request_id": "1", "hash": "abc", "percent": 50}}"#)
+        else {
+            panic!("expected an unknown response");
+        };
+        assert_eq!(unknown.response_type, "Progress");
+        assert_eq!(unknown.request_id, "1");
+        assert_eq!(unknown.hash, "abc");
+
+        // Unknown types without (matching) content
+        for json in [
+            r#""Heartbeat""#,
+            r#"{"Heartbeat": null}"#,
+            r#"{"Heartbeat": [1]}"#,
+        ] {
+            let Ok(super::WSResponseType::Unknown(unknown)) = parse(json) else {
+                panic!("expected an unknown response from {}", json);
+            };
+            assert_eq!(unknown.response_type, "Heartbeat");
+            assert!(unknown.request_id.is_empty());
+        }
+
+        // Known types still have to be valid, and malformed JSON is still an error
+        assert!(parse(r#"{"Error": {"did": "did:key:z6Mk"}}"#).is_err());
+        assert!(parse(r#"{"Response": {}, "Error": {}}"#).is_err());
+        assert!(parse("{}").is_err());
+        assert!(parse("{\"Error\"").is_err());
+
+        let error = parse(r#"{"Error": {"did": "did:key:z6Mk", "hash": "abc", "error": "x"}}"#);
+        assert!(matches!(error, Ok(super::WSResponseType::Error(_))));
+    }
+
+    #[test]
+    fn response_raw_is_optional() {
+        let parse = |json: &str| match serde_json::from_str(json).unwrap() {
+            super::WSResponseType::Response(response) => response,
+            _ => panic!("expected a response"),
+        };
+        let document = r#""document": {"id": "did:web:example.com"}"#;
+
+        // Older servers don't send raw
+        let response = parse(&format!(
+            r#"{{"Response": {{"did": "did:web:example.com", "hash": "abc", {}}}}}"#,
+            document
+        ));
+        assert!(response.raw.is_none());
+        assert!(!serde_json::to_string(&response).unwrap().contains("raw"));
+
+        let response = parse(&format!(
+            r#"{{"Response": {{"did": "did:web:example.com", "hash": "abc", {}, "raw": {{"id": "did:web:example.com", "extra": 1}}}}}}"#,
+            document
+        ));
+        assert_eq!(response.raw.unwrap()["extra"], 1);
+    }
+
+    #[tokio::test]
+    async fn network_stats() {
+        let (client, _task_rx) = _disconnected_network_client(1);
+        assert_eq!(
+            client.network_stats(),
+            Some(super::RequestListStats::default())
+        );
+
+        let local = DIDCacheClient::new(config::ClientConfigBuilder::default().build())
+            .await
+            .unwrap();
+        assert!(local.network_stats().is_none());
+    }
+
+    #[tokio::test]
+    async fn connection_diagnostics_default() {
+        let (client, _task_rx) = _disconnected_network_client(1);
+
+        assert_eq!(
+            client.connection_diagnostics(),
+            ConnectionDiagnostics::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn connection_diagnostics_records_connect_error() {
+        let (client, _task_rx) = _disconnected_network_client(1);
+        let (_sdk_tx, mut task_rx) = mpsc::channel(1);
+        let (task_tx, _sdk_rx) = mpsc::channel(1);
+
+        // Nothing is listening on this port, the connection is refused
+        let config = client.config.clone();
+        let status = client.network_status.clone();
+        let task = tokio::spawn(async move {
+            let _ = NetworkTask::run(config, None, &mut task_rx, &task_tx, status).await;
+        });
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        task.abort();
+
+        let diagnostics = client.connection_diagnostics();
+        assert!(!diagnostics.connected);
+        assert_eq!(diagnostics.reconnects, 0);
+        assert!(diagnostics
+            .last_error
+            .unwrap()
+            .starts_with("Error connecting to websocket"));
+    }
+
+    #[tokio::test]
+    async fn connection_events_reconnecting() {
+        let (client, _task_rx) = _disconnected_network_client(1);
+        let mut events = client.subscribe_connection_events();
+        let (_sdk_tx, mut task_rx) = mpsc::channel(1);
+        let (task_tx, _sdk_rx) = mpsc::channel(1);
+
+        // Nothing is listening on this port, every attempt fails
+        let config = client.config.clone();
+        let status = client.network_status.clone();
+        let task = tokio::spawn(async move {
+            let _ = NetworkTask::run(config, None, &mut task_rx, &task_tx, status).await;
+        });
+
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        task.abort();
+        assert_eq!(event, ConnectionEvent::Reconnecting { attempt: 1 });
+    }
+}
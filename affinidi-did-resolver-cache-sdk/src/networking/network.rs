@@ -6,26 +6,48 @@
 //! The remote server communicates via a websocket connection.
 //!
 
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
-use crate::{config::ClientConfig, errors::DIDCacheError, WSRequest};
-use blake2::{Blake2s256, Digest};
+use super::{UNSUPPORTED_PROTOCOL, WS_PROTOCOL_VERSION};
+use crate::{
+    config::ClientConfig, did_hash, errors::DIDCacheError, metadata::CacheEntry, WSRequest,
+};
 use futures_util::{SinkExt, StreamExt};
-use ssi::dids::Document;
 use tokio::{
     net::TcpStream,
     select,
     sync::{
+        broadcast,
         mpsc::{Receiver, Sender},
         oneshot,
     },
-    time::sleep,
+    time::{interval_at, sleep, sleep_until, Instant, MissedTickBehavior},
 };
 #[cfg(feature = "network")]
-use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
-use tracing::{debug, error, span, warn, Instrument, Level};
+use tokio_tungstenite::{
+    connect_async_tls_with_config,
+    tungstenite::{
+        client::IntoClientRequest,
+        error::CapacityError,
+        error::{ProtocolError, SubProtocolError},
+        http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderValue, StatusCode},
+        protocol::WebSocketConfig,
+        Error as WsError, Message,
+    },
+    Connector, MaybeTlsStream, WebSocketStream,
+};
+use tracing::{debug, error, info, span, warn, Instrument, Level};
 
-use super::{request_queue::RequestList, WSResponseType};
+use super::{
+    request_queue::{RequestList, RequestListCounters},
+    ConnectionDiagnostics, ConnectionEvent, WSResponseType, CONNECTION_EVENT_CHANNEL_CAPACITY,
+};
 
 /// WSCommands are the commands that can be sent between the SDK and the network task
 /// Connected: Signals that the websocket is connected
@@ -40,13 +62,68 @@ pub(crate) enum WSCommands {
     Connected,
     Exit,
     Send(Responder, String, WSRequest),
-    ResponseReceived(Box<Document>),
-    ErrorReceived(String),
+    ResponseReceived(Box<CacheEntry>),
+    ErrorReceived(DIDCacheError),
     TimeOut(String, String),
 }
 
 pub(crate) type Responder = oneshot::Sender<WSCommands>;
 
+/// State of the network task that is shared with the SDK
+/// full: The request list is full, new requests can't be accepted
+/// connected: The websocket is currently connected
+/// reconnects: How many times the websocket has been reconnected
+/// last_error: The most recent connection error
+/// events: Broadcasts a [ConnectionEvent] when the connection state changes
+/// request_list: Request list counters, see [network_stats](crate::DIDCacheClient::network_stats)
+#[derive(Debug)]
+pub(crate) struct NetworkStatus {
+    pub(crate) full: AtomicBool,
+    connected: AtomicBool,
+    reconnects: AtomicU64,
+    last_error: Mutex<Option<String>>,
+    pub(crate) events: broadcast::Sender<ConnectionEvent>,
+    pub(crate) request_list: Arc<RequestListCounters>,
+}
+
+impl Default for NetworkStatus {
+    fn default() -> Self {
+        NetworkStatus {
+            full: AtomicBool::new(false),
+            connected: AtomicBool::new(false),
+            reconnects: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+            events: broadcast::channel(CONNECTION_EVENT_CHANNEL_CAPACITY).0,
+            request_list: Arc::default(),
+        }
+    }
+}
+
+impl NetworkStatus {
+    /// Send errors (no subscribers) are ignored
+    fn publish(&self, event: ConnectionEvent) {
+        let _ = self.events.send(event);
+    }
+
+    fn set_error(&self, error: String) {
+        if let Ok(mut last_error) = self.last_error.lock() {
+            *last_error = Some(error);
+        }
+    }
+
+    pub(crate) fn diagnostics(&self) -> ConnectionDiagnostics {
+        ConnectionDiagnostics {
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            last_error: self
+                .last_error
+                .lock()
+                .map(|last_error| last_error.clone())
+                .unwrap_or_default(),
+            connected: self.connected.load(Ordering::Relaxed),
+        }
+    }
+}
+
 /// NetworkTask handles the communication with the network.
 /// This runs as a separate task in the background.
 ///
@@ -55,21 +132,32 @@ pub(crate) type Responder = oneshot::Sender<WSCommands>;
 /// task_rx_channel: Rc<Receiver<WSCommands>> - PRIVATE. Channel to receive commands from the SDK
 /// task_tx_channel: Sender<WSCommands> - PRIVATE. Channel to send commands to the SDK
 /// websocket: Option<Rc<WebSocketStream<MaybeTlsStream<TcpStream>>>> - PRIVATE. The websocket connection itself
+/// status: Arc<NetworkStatus> - Shared with the SDK, connection state and if the request list is full
+/// ping_sent: When the outstanding keepalive ping was sent, None if the pong has been received
+/// last_pong: When the last keepalive pong was received
 pub(crate) struct NetworkTask {
     config: ClientConfig,
+    connector: Option<Connector>,
     service_address: String,
     cache: RequestList,
     sdk_tx: Sender<WSCommands>,
+    status: Arc<NetworkStatus>,
+    ping_sent: Option<Instant>,
+    last_pong: Option<Instant>,
 }
 
 impl NetworkTask {
+    /// connector: TLS connector for the configured TLS policy, None uses the default connector
     pub async fn run(
         config: ClientConfig,
+        connector: Option<Connector>,
         sdk_rx: &mut Receiver<WSCommands>,
         sdk_tx: &Sender<WSCommands>,
+        status: Arc<NetworkStatus>,
     ) -> Result<(), DIDCacheError> {
         let _span = span!(Level::INFO, "network_task");
-        async move {
+        let request_list = status.request_list.clone();
+        let result = async move {
             debug!("Starting...");
 
             let service_address = if let Some(service_address) = &config.service_address {
@@ -80,33 +168,74 @@ impl NetworkTask {
                 ));
             };
 
-            let cache = RequestList::new(&config);
+            let cache = RequestList::new(&config, status.request_list.clone());
 
             let mut network_task = NetworkTask {
                 config,
+                connector,
                 service_address,
                 cache,
                 sdk_tx: sdk_tx.clone(),
+                status,
+                ping_sent: None,
+                last_pong: None,
             };
 
             let mut websocket = network_task.ws_connect().await?;
 
+            // Keepalive pings, disabled when the interval is 0
+            let keepalive_enabled = !network_task.config.keepalive_interval.is_zero();
+            let keepalive_period = network_task
+                .config
+                .keepalive_interval
+                .max(Duration::from_millis(1));
+            let mut keepalive = interval_at(Instant::now() + keepalive_period, keepalive_period);
+            keepalive.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
             loop {
+                let pong_deadline = network_task
+                    .ping_sent
+                    .map(|sent| sent + network_task.config.keepalive_timeout);
+
                 select! {
                     value = websocket.next() => {
                         if network_task.ws_recv(value).is_err() {
-                            // Reset the connection
-                            websocket = network_task.ws_connect().await?;
+                            websocket = network_task.ws_reconnect().await?;
+                            keepalive.reset();
                         }
                     },
-                    value = sdk_rx.recv(), if !network_task.cache.is_full() => {
+                    _ = keepalive.tick(), if keepalive_enabled && network_task.ping_sent.is_none() => {
+                        debug!("Sending keepalive ping");
+                        if let Err(e) = websocket.send(Message::Ping(Vec::new().into())).await {
+                            warn!("Couldn't send keepalive ping: {}", e);
+                            network_task.status.set_error(format!("Couldn't send keepalive ping: {}", e));
+                            websocket = network_task.ws_reconnect().await?;
+                            keepalive.reset();
+                        } else {
+                            network_task.ping_sent = Some(Instant::now());
+                        }
+                    },
+                    _ = sleep_until(pong_deadline.unwrap_or_else(Instant::now)), if pong_deadline.is_some() => {
+                        // No pong, the connection is dead (e.g. silently dropped by a proxy)
+                        warn!(
+                            "Keepalive pong not received, reconnecting. Last pong: {:?} ago",
+                            network_task.last_pong.map(|pong| pong.elapsed())
+                        );
+                        network_task.status.set_error("Keepalive pong not received".into());
+                        websocket = network_task.ws_reconnect().await?;
+                        keepalive.reset();
+                    },
+                    // Always read commands, so TimeOut and Exit are handled while the list is full
+                    value = sdk_rx.recv() => {
                         if let Some(cmd) = value {
                             match cmd {
+                                WSCommands::Send(channel, _, _) if network_task.cache.is_full() => {
+                                    // The SDK waits while the list is full, this only races with it
+                                    debug!("Request list is full, returning busy");
+                                    let _ = channel.send(WSCommands::ErrorReceived(DIDCacheError::Busy));
+                                }
                                 WSCommands::Send(channel, uid, request) => {
-                                    let mut hasher = Blake2s256::new();
-                                    hasher.update(request.did.clone());
-                                    let did_hash = format!("{:x}", hasher.finalize());
-                                    if network_task.cache.insert(did_hash, &uid, channel) {
+                                    if network_task.cache.insert(did_hash(&request.did), &uid, channel) {
                                         let _ = network_task.ws_send(&mut websocket, &request).await;
                                     }
                                 }
@@ -115,6 +244,8 @@ impl NetworkTask {
                                 }
                                 WSCommands::Exit => {
                                     debug!("Exiting...");
+                                    network_task.status.connected.store(false, Ordering::Relaxed);
+                                    network_task.status.publish(ConnectionEvent::Disconnected);
                                     return Ok(());
                                 }
                                 _ => {
@@ -124,38 +255,95 @@ impl NetworkTask {
                         }
                     }
                 }
+
+                // Let the SDK know if new requests can't be accepted
+                network_task
+                    .status
+                    .full
+                    .store(network_task.cache.is_full(), Ordering::Relaxed);
             }
         }
-        .instrument(_span)
-        .await
+        .instrument(_span.clone())
+        .await;
+
+        let stats = request_list.stats();
+        _span.in_scope(|| {
+            info!(
+                "Exited: total_inserts({}) deduped_inserts({}) dedup_rate({:.1}%) peak_concurrent({})",
+                stats.total_inserts,
+                stats.deduped_inserts,
+                stats.dedup_rate() * 100.0,
+                stats.peak_concurrent
+            )
+        });
+        result
+    }
+
+    /// Drops the current connection and connects again
+    async fn ws_reconnect(
+        &mut self,
+    ) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, DIDCacheError> {
+        self.status.connected.store(false, Ordering::Relaxed);
+        self.status.reconnects.fetch_add(1, Ordering::Relaxed);
+        self.status.publish(ConnectionEvent::Disconnected);
+        self.ping_sent = None;
+        self.ws_connect().await
     }
 
     /// Creates the connection to the remote server via a websocket
     /// If timeouts or errors occur, it will backoff and retry
-    /// NOTE: Increases in 5 second increments up to 60 seconds
+    /// NOTE: The delay between attempts is set by [BackoffConfig](super::backoff::BackoffConfig)
+    /// (default: 5 second increments up to 60 seconds)
+    /// Fails without retrying if the server doesn't support [WS_PROTOCOL_VERSION]
     async fn ws_connect(
         &self,
     ) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, DIDCacheError> {
-        async fn _handle_backoff(backoff: Duration) -> Duration {
-            let b = if backoff.as_secs() < 60 {
-                backoff.saturating_add(Duration::from_secs(5))
-            } else {
-                backoff
-            };
+        async fn _handle_backoff(
+            status: &NetworkStatus,
+            config: &ClientConfig,
+            attempt: u32,
+        ) -> u32 {
+            status.publish(ConnectionEvent::Reconnecting { attempt });
+            let b = config.backoff.next_delay(attempt);
 
-            debug!("connect backoff: {} Seconds", b.as_secs());
+            debug!("connect backoff: {}ms (attempt {})", b.as_millis(), attempt);
             sleep(b).await;
-            b
+            attempt.saturating_add(1)
         }
 
         let _span = span!(Level::DEBUG, "ws_connect", server = self.service_address);
         async move {
             // Connect to the DID cache server
-            let mut backoff = Duration::from_secs(1);
+            let mut request = self
+                .service_address
+                .as_str()
+                .into_client_request()
+                .map_err(|e| {
+                    DIDCacheError::ConfigError(format!(
+                        "Invalid service address ({}): {}",
+                        self.service_address, e
+                    ))
+                })?;
+            request.headers_mut().insert(
+                SEC_WEBSOCKET_PROTOCOL,
+                HeaderValue::from_static(WS_PROTOCOL_VERSION),
+            );
+
+            // Oversized messages are rejected before they are buffered
+            let ws_config = WebSocketConfig::default()
+                .max_message_size(Some(self.config.max_frame_bytes))
+                .max_frame_size(Some(self.config.max_frame_bytes));
+
+            let mut attempt = 1;
             loop {
                 debug!("Starting websocket connection");
 
-                let connection = connect_async(&self.service_address);
+                let connection = connect_async_tls_with_config(
+                    request.clone(),
+                    Some(ws_config),
+                    false,
+                    self.connector.clone(),
+                );
                 let timeout = tokio::time::sleep(self.config.network_timeout);
 
                 select! {
@@ -163,19 +351,34 @@ impl NetworkTask {
                         match conn {
                             Ok((conn, _)) => {
                                 debug!("Websocket connected");
-                                self.sdk_tx.send(WSCommands::Connected).await.unwrap();
+                                self.status.connected.store(true, Ordering::Relaxed);
+                                self.status.publish(ConnectionEvent::Connected);
+                                // Only the first connection is waited for, the SDK doesn't read
+                                // the signal again, so it mustn't block once the channel is full
+                                let _ = self.sdk_tx.try_send(WSCommands::Connected);
                                 return Ok(conn)
                             }
+                            Err(e) if _incompatible_protocol(&e) => {
+                                let error = format!(
+                                    "DID Cache server doesn't support protocol version ({}): {}",
+                                    WS_PROTOCOL_VERSION, e
+                                );
+                                error!("{}", error);
+                                self.status.set_error(error.clone());
+                                return Err(DIDCacheError::TransportError(error));
+                            }
                             Err(e) => {
                                 error!("Error connecting to websocket: {:?}", e);
-                                backoff = _handle_backoff(backoff).await;
+                                self.status.set_error(format!("Error connecting to websocket: {}", e));
+                                attempt = _handle_backoff(&self.status, &self.config, attempt).await;
                             }
                         }
                     }
                     _ = timeout => {
                         // Start backing off and retry
                         warn!("Connect timeout reached");
-                        backoff = _handle_backoff(backoff).await;
+                        self.status.set_error("Connect timeout reached".into());
+                        attempt = _handle_backoff(&self.status, &self.config, attempt).await;
                     }
                 }
             }
@@ -205,6 +408,16 @@ impl NetworkTask {
         }
     }
 
+    /// Removes the request a response belongs to, returning the channels waiting for it
+    /// Older servers don't echo the request_id, so fall back to matching by DID hash
+    fn _remove_request(&mut self, request_id: &str, did_hash: &str) -> Option<Vec<Responder>> {
+        if request_id.is_empty() {
+            self.cache.remove(did_hash, None)
+        } else {
+            self.cache.remove_request(request_id)
+        }
+    }
+
     /// Processes inbound websocket messages from the remote server
     fn ws_recv(
         &mut self,
@@ -218,11 +431,25 @@ impl NetworkTask {
                         match response {
                             Ok(WSResponseType::Response(response)) => {
                                 debug!("Received response: {:?}", response.hash);
-                                if let Some(channels) = self.cache.remove(&response.hash, None) {
+                                if let Some(channels) =
+                                    self._remove_request(&response.request_id, &response.hash)
+                                {
                                     // Loop through and notify each registered channel
                                     for channel in channels {
                                         let _ = channel.send(WSCommands::ResponseReceived(
-                                            Box::new(response.document.clone()),
+                                            Box::new(CacheEntry {
+                                                doc: response.document.clone(),
+                                                resolution_metadata: response
+                                                    .resolution_metadata
+                                                    .clone(),
+                                                document_metadata: response
+                                                    .document_metadata
+                                                    .clone(),
+                                                inserted_at: std::time::Instant::now(),
+                                                method: None,
+                                                method_name: None,
+                                                raw: response.raw.clone(),
+                                            }),
                                         ));
                                     }
                                 } else {
@@ -234,27 +461,80 @@ impl NetworkTask {
                                     "Received error: did hash({}) Error: {:?}",
                                     response.hash, response.error
                                 );
-                                if let Some(channels) = self.cache.remove(&response.hash, None) {
+                                if let Some(channels) =
+                                    self._remove_request(&response.request_id, &response.hash)
+                                {
                                     for channel in channels {
                                         let _ = channel.send(WSCommands::ErrorReceived(
-                                            response.error.clone(),
+                                            DIDCacheError::from_remote(
+                                                &response.code,
+                                                response.error.clone(),
+                                                response.retry_after_ms.map(Duration::from_millis),
+                                            ),
                                         ));
                                     }
                                 } else {
                                     warn!("Response not found in request list: {}", response.hash);
                                 }
                             }
+                            Ok(WSResponseType::Unknown(response)) => {
+                                // A newer server, fail the request now rather than at its timeout
+                                warn!(
+                                    "Received unknown response type ({}), is the server newer than this client? did hash({})",
+                                    response.response_type, response.hash
+                                );
+                                if response.request_id.is_empty() && response.hash.is_empty() {
+                                    return Ok(());
+                                }
+                                if let Some(channels) =
+                                    self._remove_request(&response.request_id, &response.hash)
+                                {
+                                    for channel in channels {
+                                        let _ = channel.send(WSCommands::ErrorReceived(
+                                            DIDCacheError::TransportError(format!(
+                                                "Unknown response type ({}) from server",
+                                                response.response_type
+                                            )),
+                                        ));
+                                    }
+                                }
+                            }
                             Err(e) => {
-                                warn!("Error parsing message: {:?}", e);
+                                warn!("Malformed message from server: {:?}", e);
                             }
                         }
+                    } else if let Message::Pong(_) = msg {
+                        debug!("Received keepalive pong");
+                        self.ping_sent = None;
+                        self.last_pong = Some(Instant::now());
+                    } else if let Message::Ping(_) = msg {
+                        // tungstenite answers pings itself
+                        debug!("Received ping");
+                    } else if let Message::Close(frame) = msg {
+                        warn!("Websocket closed by server: {:?}", frame);
+                        self.status.set_error("Websocket closed by server".into());
+                        return Err(DIDCacheError::TransportError(
+                            "Websocket closed by server".into(),
+                        ));
                     } else {
                         warn!("Received non-text message, ignoring: {}", msg);
                     }
                 }
+                Err(WsError::Capacity(CapacityError::MessageTooLong { size, max_size })) => {
+                    // The rest of the message is still on the wire, reset the connection
+                    let error = format!(
+                        "Message from server ({} bytes) exceeds max_frame_bytes ({} bytes)",
+                        size, max_size
+                    );
+                    error!("{}", error);
+                    self.status.set_error(error.clone());
+                    return Err(DIDCacheError::TransportError(error));
+                }
                 Err(e) => {
                     // Can't receive messages, reset the connection
                     error!("Error receiving message: {:?}", e);
+                    self.status
+                        .set_error(format!("Error receiving message: {}", e));
                     return Err(DIDCacheError::TransportError(format!(
                         "Error receiving message: {:?}",
                         e
@@ -262,9 +542,344 @@ impl NetworkTask {
                 }
             }
         } else {
-            warn!("No message received");
+            // The stream has ended, the server closed the connection
+            warn!("Websocket closed by server");
+            self.status.set_error("Websocket closed by server".into());
+            return Err(DIDCacheError::TransportError(
+                "Websocket closed by server".into(),
+            ));
         }
 
         Ok(())
     }
 }
+
+/// Did the server reject the connection because it doesn't support [WS_PROTOCOL_VERSION]?
+/// The server either refuses the upgrade (400 Bad Request with an [UNSUPPORTED_PROTOCOL] body),
+/// or is an older server that doesn't select a subprotocol at all
+/// Any other 400 (e.g. from a proxy or load balancer) is retried
+fn _incompatible_protocol(error: &WsError) -> bool {
+    match error {
+        WsError::Http(response) => {
+            response.status() == StatusCode::BAD_REQUEST
+                && response
+                    .body()
+                    .as_deref()
+                    .is_some_and(|body| body.starts_with(UNSUPPORTED_PROTOCOL.as_bytes()))
+        }
+        WsError::Protocol(ProtocolError::SecWebSocketSubProtocolError(
+            SubProtocolError::NoSubProtocol | SubProtocolError::InvalidSubProtocol,
+        )) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::ClientConfigBuilder, test_fixtures::DID_KEY, test_util::MockCacheServer};
+    use ssi::dids::{DIDBuf, Document};
+    use std::collections::HashMap;
+    use tokio::sync::mpsc;
+    use tokio_tungstenite::tungstenite::http::Response;
+
+    /// Runs the network task for a while, returns the number of reconnects
+    async fn run_keepalive(address: &str, keepalive_interval: u32) -> u64 {
+        let config = ClientConfigBuilder::default()
+            .with_network_mode(address)
+            .with_keepalive_interval(keepalive_interval)
+            .with_keepalive_timeout(100)
+            .build();
+        let status = Arc::new(NetworkStatus::default());
+        let (_sdk_tx, mut task_rx) = mpsc::channel(10);
+        let (task_tx, _sdk_rx) = mpsc::channel(10);
+
+        let task_status = status.clone();
+        let task = tokio::spawn(async move {
+            let _ = NetworkTask::run(config, None, &mut task_rx, &task_tx, task_status).await;
+        });
+        sleep(Duration::from_millis(700)).await;
+        task.abort();
+
+        status.reconnects.load(Ordering::Relaxed)
+    }
+
+    #[tokio::test]
+    async fn keepalive_answered() {
+        let server = MockCacheServer::start(HashMap::new()).await;
+        assert_eq!(run_keepalive(server.address(), 50).await, 0);
+        assert_eq!(server.connections(), 1);
+    }
+
+    #[tokio::test]
+    async fn keepalive_pong_timeout_reconnects() {
+        let server = MockCacheServer::start(HashMap::new()).await;
+        server.set_unresponsive(true);
+        assert!(run_keepalive(server.address(), 50).await > 0);
+        assert!(server.connections() > 1);
+    }
+
+    #[tokio::test]
+    async fn keepalive_disabled() {
+        let server = MockCacheServer::start(HashMap::new()).await;
+        server.set_unresponsive(true);
+        assert_eq!(run_keepalive(server.address(), 0).await, 0);
+        assert_eq!(server.connections(), 1);
+    }
+
+    #[tokio::test]
+    async fn oversized_message_reconnects() {
+        let server = MockCacheServer::start(HashMap::new()).await;
+        server.set_greeting(&"x".repeat(2048));
+
+        let config = ClientConfigBuilder::default()
+            .with_network_mode(server.address())
+            .with_max_frame_bytes(1024)
+            .with_backoff(crate::networking::backoff::BackoffConfig::linear(
+                Duration::from_millis(50),
+                Duration::from_millis(50),
+            ))
+            .build();
+        let status = Arc::new(NetworkStatus::default());
+        let (_sdk_tx, mut task_rx) = mpsc::channel(10);
+        let (task_tx, _sdk_rx) = mpsc::channel(10);
+
+        let task_status = status.clone();
+        let task = tokio::spawn(async move {
+            let _ = NetworkTask::run(config, None, &mut task_rx, &task_tx, task_status).await;
+        });
+        sleep(Duration::from_millis(300)).await;
+        task.abort();
+
+        assert!(status.reconnects.load(Ordering::Relaxed) > 0);
+        assert_eq!(
+            status.diagnostics().last_error.as_deref(),
+            Some("Message from server (2048 bytes) exceeds max_frame_bytes (1024 bytes)")
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_response_fails_request() {
+        let server = MockCacheServer::start(HashMap::new()).await;
+        server.set_unknown_response(DID_KEY, "Queued");
+
+        let config = ClientConfigBuilder::default()
+            .with_network_mode(server.address())
+            .with_network_timeout(10000)
+            .build();
+        let client = crate::DIDCacheClient::new(config).await.unwrap();
+
+        // Fails on the response, not at the network timeout
+        let start = Instant::now();
+        let result = client.resolve(DID_KEY).await;
+        assert!(matches!(
+            result,
+            Err(DIDCacheError::TransportError(msg)) if msg == "Unknown response type (Queued) from server"
+        ));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn full_request_list_returns_busy() {
+        // Reads requests, but doesn't answer them in time
+        let server = MockCacheServer::start(HashMap::new()).await;
+        server.set_delay(Duration::from_secs(60));
+        let config = ClientConfigBuilder::default()
+            .with_network_mode(server.address())
+            .with_network_timeout(10000)
+            .with_network_cache_limit_count(1)
+            .with_network_busy_timeout(200)
+            .build();
+        let client = Arc::new(crate::DIDCacheClient::new(config).await.unwrap());
+
+        // The list is full once it holds more than network_cache_limit_count requests
+        for did in ["did:web:one.example.com", "did:web:two.example.com"] {
+            let client = client.clone();
+            tokio::spawn(async move { client.resolve(did).await });
+        }
+        for _ in 0..50 {
+            if client.network_busy() {
+                break;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        assert!(client.network_busy());
+
+        let start = Instant::now();
+        let result = client.resolve("did:web:three.example.com").await;
+        assert!(matches!(result, Err(DIDCacheError::Busy)));
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(client.network_stats().unwrap().total_inserts, 2);
+    }
+
+    #[tokio::test]
+    async fn full_request_list_drains_on_timeout() {
+        let dids = [
+            "did:web:one.example.com",
+            "did:web:two.example.com",
+            "did:web:three.example.com",
+        ];
+        let docs = dids.map(|did| {
+            let doc = Document::new(DIDBuf::from_string(did.to_string()).unwrap());
+            (did.to_string(), doc)
+        });
+        let server = MockCacheServer::start(HashMap::from(docs)).await;
+        // Never answers the requests
+        server.set_unresponsive(true);
+        let config = ClientConfigBuilder::default()
+            .with_network_mode(server.address())
+            .with_keepalive_interval(0)
+            .with_network_timeout(200)
+            .with_network_cache_limit_count(1)
+            .with_network_busy_timeout(100)
+            .build();
+        let client = Arc::new(crate::DIDCacheClient::new(config).await.unwrap());
+
+        // Fills the list, both time out
+        let requests: Vec<_> = dids[..2]
+            .iter()
+            .map(|&did| {
+                let client = client.clone();
+                tokio::spawn(async move { client.resolve(did).await })
+            })
+            .collect();
+        for request in requests {
+            assert!(matches!(
+                request.await.unwrap(),
+                Err(DIDCacheError::NetworkTimeout)
+            ));
+        }
+
+        // The timed out requests were removed from the list
+        for _ in 0..50 {
+            if !client.network_busy() {
+                break;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!client.network_busy());
+
+        server.set_unresponsive(false);
+        server.disconnect_all();
+        for _ in 0..50 {
+            if server.connections() == 2 {
+                break;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        assert!(client.resolve(dids[2]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn reconnects_with_full_response_channel() {
+        let did = "did:web:example.com";
+        let doc = Document::new(DIDBuf::from_string(did.to_string()).unwrap());
+        let server = MockCacheServer::start(HashMap::from([(did.to_string(), doc)])).await;
+        let config = ClientConfigBuilder::default()
+            .with_network_mode(server.address())
+            .with_response_channel_capacity(1)
+            .build();
+        let client = crate::DIDCacheClient::new(config).await.unwrap();
+
+        // Each reconnect signals the SDK, which only reads the first signal
+        for connections in 2..=4 {
+            server.disconnect_all();
+            for _ in 0..100 {
+                if server.connections() == connections {
+                    break;
+                }
+                sleep(Duration::from_millis(20)).await;
+            }
+            assert_eq!(server.connections(), connections);
+        }
+        assert!(client.resolve(did).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn connection_events() {
+        let server = MockCacheServer::start(HashMap::new()).await;
+
+        let config = ClientConfigBuilder::default()
+            .with_network_mode(server.address())
+            .with_keepalive_interval(0)
+            .build();
+        let status = Arc::new(NetworkStatus::default());
+        let mut events = status.events.subscribe();
+        let (sdk_tx, mut task_rx) = mpsc::channel(10);
+        let (task_tx, _sdk_rx) = mpsc::channel(10);
+
+        let task_status = status.clone();
+        let task = tokio::spawn(async move {
+            let _ = NetworkTask::run(config, None, &mut task_rx, &task_tx, task_status).await;
+        });
+
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(
+                tokio::time::timeout(Duration::from_secs(5), events.recv())
+                    .await
+                    .unwrap()
+                    .unwrap(),
+            );
+            // Drop the first connection, keep the next one open
+            if received.len() == 1 {
+                server.disconnect_all();
+            }
+        }
+        assert_eq!(
+            received,
+            vec![
+                ConnectionEvent::Connected,
+                ConnectionEvent::Disconnected,
+                ConnectionEvent::Connected
+            ]
+        );
+
+        sdk_tx.send(WSCommands::Exit).await.unwrap();
+        assert_eq!(
+            tokio::time::timeout(Duration::from_secs(5), events.recv())
+                .await
+                .unwrap()
+                .unwrap(),
+            ConnectionEvent::Disconnected
+        );
+        let _ = task.await;
+    }
+
+    #[test]
+    fn incompatible_protocol_errors() {
+        let rejected = Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(Some(
+                format!(
+                    "{}, expected ({})",
+                    UNSUPPORTED_PROTOCOL, WS_PROTOCOL_VERSION
+                )
+                .into(),
+            ))
+            .unwrap();
+        assert!(_incompatible_protocol(&WsError::Http(rejected)));
+        assert!(_incompatible_protocol(&WsError::Protocol(
+            ProtocolError::SecWebSocketSubProtocolError(SubProtocolError::NoSubProtocol)
+        )));
+
+        // Other connection errors are retried, including a 400 from a proxy
+        let bad_request = Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(Some(b"Bad Request".to_vec()))
+            .unwrap();
+        assert!(!_incompatible_protocol(&WsError::Http(bad_request)));
+        let no_body = Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(None)
+            .unwrap();
+        assert!(!_incompatible_protocol(&WsError::Http(no_body)));
+        let unavailable = Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .body(None)
+            .unwrap();
+        assert!(!_incompatible_protocol(&WsError::Http(unavailable)));
+        assert!(!_incompatible_protocol(&WsError::ConnectionClosed));
+    }
+}
@@ -3,46 +3,109 @@
 
 use super::network::Responder;
 use crate::config::ClientConfig;
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+};
 use tracing::debug;
 
+/// Snapshot of the network request list, see [network_stats](crate::DIDCacheClient::network_stats)
+/// total_inserts: Number of resolves handed to the network task
+/// deduped_inserts: Resolves that joined a request already in flight for the same DID,
+///   rather than sending a new request to the server
+/// peak_concurrent: Most requests in flight at once (compare with network_cache_limit_count)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestListStats {
+    pub total_inserts: u64,
+    pub deduped_inserts: u64,
+    pub peak_concurrent: u32,
+}
+
+impl RequestListStats {
+    /// Fraction of inserts that were deduplicated (0.0 if there haven't been any)
+    pub fn dedup_rate(&self) -> f64 {
+        if self.total_inserts > 0 {
+            self.deduped_inserts as f64 / self.total_inserts as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Request list counters, shared with the SDK (see [NetworkStatus](super::network::NetworkStatus))
+#[derive(Debug, Default)]
+pub(crate) struct RequestListCounters {
+    total_inserts: AtomicU64,
+    deduped_inserts: AtomicU64,
+    peak_concurrent: AtomicU32,
+}
+
+impl RequestListCounters {
+    pub(crate) fn stats(&self) -> RequestListStats {
+        RequestListStats {
+            total_inserts: self.total_inserts.load(Ordering::Relaxed),
+            deduped_inserts: self.deduped_inserts.load(Ordering::Relaxed),
+            peak_concurrent: self.peak_concurrent.load(Ordering::Relaxed),
+        }
+    }
+}
+
 /// List of lookups that are in progress.Note the list is not in any order.
-/// NOTE: SHA256 Hash of the DID is used as the key for the list
-/// - list: The list of requests waiting for a response from the server (key: DID Hash, value: Vec[(Unique ID, Responder Channel)]
+/// NOTE: The request_id sent to the server is used as the key for the list
+/// - list: The list of requests waiting for a response from the server (key: request_id, value: (DID Hash, Vec[(Unique ID, Responder Channel)]))
+/// - request_ids: The request_id of the request in flight for each DID hash
 /// - list_full: Is the list full based on limits?
 /// - limit_count: The maximum number of items to store in the request list
 /// - total_count: The total number of items in the list
+/// - counters: Insert, deduplication and peak counts, readable by the SDK
 ///
 /// NOTE: Handles duplicate DID resolver requests, by matching them in the list by the DID hash, adds elements using
-///       the unique ID as an identifier.
+///       the unique ID as an identifier. The unique ID of the first request is the request_id.
 pub(crate) struct RequestList {
-    list: HashMap<String, Vec<(String, Responder)>>,
+    list: HashMap<String, (String, Vec<(String, Responder)>)>,
+    request_ids: HashMap<String, String>,
     list_full: bool,
     limit_count: u32,
     total_count: u32,
+    counters: Arc<RequestListCounters>,
 }
 
 impl RequestList {
     /// Create a new request list
-    pub fn new(config: &ClientConfig) -> Self {
+    pub fn new(config: &ClientConfig, counters: Arc<RequestListCounters>) -> Self {
         debug!(
             "created request list limit_count({})",
             config.network_cache_limit_count
         );
         Self {
             list: HashMap::new(),
+            request_ids: HashMap::new(),
             list_full: false,
             limit_count: config.network_cache_limit_count,
             total_count: 0,
+            counters,
         }
     }
 
     /// Insert a new request into the list
-    /// Returns: true if the request is new, false if it is a duplicate (no need to send to server)
+    /// Returns: true if the request is new (send it to the server with uid as the request_id),
+    /// false if it is a duplicate (no need to send to server)
     pub fn insert(&mut self, key: String, uid: &str, channel: Responder) -> bool {
-        // If the key exists, append the value to the list
-        if let Some(element) = self.list.get_mut(&key) {
+        self.counters.total_inserts.fetch_add(1, Ordering::Relaxed);
+
+        // If a request for the DID is in flight, append the value to its list
+        if let Some((_, element)) = self
+            .request_ids
+            .get(&key)
+            .and_then(|request_id| self.list.get_mut(request_id))
+        {
             element.push((uid.to_string(), channel));
+            self.counters
+                .deduped_inserts
+                .fetch_add(1, Ordering::Relaxed);
             debug!(
                 "Duplicate resolver request, adding to queue to await response. id ({})",
                 key
@@ -50,18 +113,24 @@ impl RequestList {
             false
         } else {
             // Otherwise, create a new list with the value
-            self.list
-                .insert(key.clone(), vec![(uid.to_string(), channel)]);
+            self.list.insert(
+                uid.to_string(),
+                (key.clone(), vec![(uid.to_string(), channel)]),
+            );
+            self.request_ids.insert(key.clone(), uid.to_string());
 
             self.total_count += 1;
+            self.counters
+                .peak_concurrent
+                .fetch_max(self.total_count, Ordering::Relaxed);
 
             if self.total_count > self.limit_count {
                 self.list_full = true;
             }
 
             debug!(
-                "Request inserted: id({}) list_count({})",
-                key, self.total_count
+                "Request inserted: id({}) request_id({}) list_count({})",
+                key, uid, self.total_count
             );
             true
         }
@@ -69,14 +138,19 @@ impl RequestList {
 
     /// Remove a response from the list returning the value
     /// ^^ This is why we don't need a get() function...
+    /// key is the DID hash, for responses use [remove_request](Self::remove_request) instead
     /// If uid isn't provided, then all channels for given key are removed
     /// If uid is provided, then we just remove that channel for that key (which if empty will delete the key)
     pub(crate) fn remove(&mut self, key: &str, uid: Option<String>) -> Option<Vec<Responder>> {
         // Get the Responder Channels from the list
         // Request must be in the list itself!
+        let Some(request_id) = self.request_ids.get(key).cloned() else {
+            debug!("Request not found: hash({})", key);
+            return None;
+        };
 
         if let Some(uid) = uid {
-            let response = if let Some(channels) = self.list.get_mut(key) {
+            let response = if let Some((_, channels)) = self.list.get_mut(&request_id) {
                 // Find the index of the element to remove
                 let index = channels.iter().position(|(id, _)| *id == uid);
 
@@ -101,33 +175,37 @@ impl RequestList {
             };
 
             // If the list is empty, remove the key
-            if let Some(channels) = self.list.get(key) {
+            if let Some((_, channels)) = self.list.get(&request_id) {
                 if channels.is_empty() {
-                    self.list.remove(key);
-                    self.total_count -= 1;
-                    self.list_full = false;
+                    self.remove_request(&request_id);
                 }
             }
 
             response
         } else {
             // Remove all channels for the key
-            if let Some(channels) = self.list.remove(key) {
-                self.total_count -= 1;
-                self.list_full = false;
-
-                debug!(
-                    "Request removed: hash({}) channels_waiting({}) remaining_list_count({})",
-                    key,
-                    channels.len(),
-                    self.total_count
-                );
-
-                Some(channels.into_iter().map(|(_, channel)| channel).collect())
-            } else {
-                debug!("Request not found: hash({})", key);
-                None
-            }
+            self.remove_request(&request_id)
+        }
+    }
+
+    /// Removes the request with the given request_id, returning all channels waiting for it
+    pub(crate) fn remove_request(&mut self, request_id: &str) -> Option<Vec<Responder>> {
+        if let Some((key, channels)) = self.list.remove(request_id) {
+            self.request_ids.remove(&key);
+            self._decrement();
+
+            debug!(
+                "Request removed: hash({}) request_id({}) channels_waiting({}) remaining_list_count({})",
+                key,
+                request_id,
+                channels.len(),
+                self.total_count
+            );
+
+            Some(channels.into_iter().map(|(_, channel)| channel).collect())
+        } else {
+            debug!("Request not found: request_id({})", request_id);
+            None
         }
     }
 
@@ -135,27 +213,34 @@ impl RequestList {
     pub(crate) fn is_full(&self) -> bool {
         self.list_full
     }
+
+    /// Decrements total_count after a key is removed, and re-checks if the list is full
+    /// Saturates at zero, a miscount mustn't wrap around and leave the list looking empty forever
+    fn _decrement(&mut self) {
+        debug_assert!(self.total_count > 0, "request list total_count underflow");
+        self.total_count = self.total_count.saturating_sub(1);
+        self.list_full = self.total_count > self.limit_count;
+    }
 }
 #[cfg(test)]
 mod tests {
+    use crate::test_fixtures::DID_KEY;
 
     use std::collections::HashMap;
 
-    use blake2::{Blake2s256, Digest};
     use rand::{distr::Alphanumeric, Rng};
     use tokio::sync::oneshot::{self, Sender};
 
     use crate::{
-        config,
+        config, did_hash,
         networking::{network::WSCommands, request_queue::RequestList},
     };
-    const DID_KEY: &str = "did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv";
     const DID_KEY_2: &str = "did:key:z6Mkp89diy1PZkbUBDTpiqZBotddb1VV7JnY8qiZMGErUbFe";
 
     #[tokio::test]
     async fn new_works() {
         let config = config::ClientConfigBuilder::default().build();
-        let request_list = RequestList::new(&config);
+        let request_list = RequestList::new(&config, Default::default());
 
         assert!(!request_list.list_full);
         assert_eq!(request_list.total_count, 0);
@@ -164,12 +249,12 @@ mod tests {
     #[tokio::test]
     async fn insert_works_returns_true() {
         let config = config::ClientConfigBuilder::default().build();
-        let mut request_list = RequestList::new(&config);
+        let mut request_list = RequestList::new(&config, Default::default());
 
         let (tx, _) = oneshot::channel::<WSCommands>();
 
         let unique_id: String = _unique_id();
-        let did_hash = _hash_did(DID_KEY);
+        let did_hash = did_hash(DID_KEY);
 
         let insert_result = request_list.insert(did_hash.clone(), &unique_id, tx);
 
@@ -179,13 +264,13 @@ mod tests {
     #[tokio::test]
     async fn insert_works_returns_false_duplicates() {
         let config = config::ClientConfigBuilder::default().build();
-        let mut request_list = RequestList::new(&config);
+        let mut request_list = RequestList::new(&config, Default::default());
 
         let (tx, _) = oneshot::channel::<WSCommands>();
         let (tx2, _) = oneshot::channel::<WSCommands>();
 
         let unique_id: String = _unique_id();
-        let did_hash = _hash_did(DID_KEY);
+        let did_hash = did_hash(DID_KEY);
 
         let insert_result = request_list.insert(did_hash.clone(), &unique_id, tx);
         let insert_result2 = request_list.insert(did_hash.clone(), &unique_id, tx2);
@@ -199,7 +284,7 @@ mod tests {
         let config = config::ClientConfigBuilder::default()
             .with_network_cache_limit_count(1)
             .build();
-        let mut request_list = RequestList::new(&config);
+        let mut request_list = RequestList::new(&config, Default::default());
 
         let (tx, _) = oneshot::channel::<WSCommands>();
         let (tx2, _) = oneshot::channel::<WSCommands>();
@@ -207,8 +292,8 @@ mod tests {
         let unique_id: String = _unique_id();
         let unique_id_2: String = _unique_id();
 
-        let did_hash = _hash_did(DID_KEY);
-        let did_hash_2 = _hash_did(DID_KEY_2);
+        let did_hash_2 = did_hash(DID_KEY_2);
+        let did_hash = did_hash(DID_KEY);
 
         let insert_result = request_list.insert(did_hash.clone(), &unique_id, tx);
         let insert_result2 = request_list.insert(did_hash_2.clone(), &unique_id_2, tx2);
@@ -223,27 +308,27 @@ mod tests {
     #[tokio::test]
     async fn remove_key_not_found() {
         let config = config::ClientConfigBuilder::default().build();
-        let mut request_list = RequestList::new(&config);
+        let mut request_list = RequestList::new(&config, Default::default());
 
-        let result = request_list.remove(&_hash_did(DID_KEY), None);
+        let result = request_list.remove(&did_hash(DID_KEY), None);
         assert!(result.is_none());
     }
 
     #[tokio::test]
     async fn remove_key_not_found_passing_uuid() {
         let config = config::ClientConfigBuilder::default().build();
-        let mut request_list = RequestList::new(&config);
+        let mut request_list = RequestList::new(&config, Default::default());
 
-        let result = request_list.remove(&_hash_did(DID_KEY), Some("".to_string()));
+        let result = request_list.remove(&did_hash(DID_KEY), Some("".to_string()));
         assert!(result.is_none());
     }
 
     #[tokio::test]
     async fn remove_key_not_found_passing_uuid_wrong_did() {
         let config = config::ClientConfigBuilder::default().build();
-        let mut request_list = RequestList::new(&config);
+        let mut request_list = RequestList::new(&config, Default::default());
 
-        let result = request_list.remove(&_hash_did("wrongdid"), Some("".to_string()));
+        let result = request_list.remove(&did_hash("wrongdid"), Some("".to_string()));
         assert!(result.is_none());
     }
 
@@ -251,18 +336,17 @@ mod tests {
     async fn remove_passing_uuid_works() {
         let (mut request_list, did_to_uuid) = _fill_request_list([DID_KEY].to_vec(), true, Some(1));
 
-        let num_of_channels_before_remove =
-            request_list.list.get(&_hash_did(DID_KEY)).unwrap().len();
+        let num_of_channels_before_remove = _waiting(&request_list, DID_KEY);
         let total_count_before_remove = request_list.total_count;
         let ids = did_to_uuid.get(DID_KEY).unwrap();
 
         request_list
-            .remove(&_hash_did(DID_KEY), ids.first().cloned())
+            .remove(&did_hash(DID_KEY), ids.first().cloned())
             .unwrap();
 
         assert_eq!(
             num_of_channels_before_remove - 1,
-            request_list.list.get(&_hash_did(DID_KEY)).unwrap().len()
+            _waiting(&request_list, DID_KEY)
         );
         assert_eq!(total_count_before_remove, request_list.total_count);
     }
@@ -271,22 +355,97 @@ mod tests {
     async fn remove_without_passing_uuid_to_remove_all_works() {
         let (mut request_list, _) = _fill_request_list([DID_KEY].to_vec(), true, Some(4));
 
-        request_list.remove(&_hash_did(DID_KEY), None).unwrap();
+        request_list.remove(&did_hash(DID_KEY), None).unwrap();
+
+        assert_eq!(request_list.total_count, 0);
+    }
+
+    #[tokio::test]
+    async fn remove_same_key_twice() {
+        let config = config::ClientConfigBuilder::default()
+            .with_network_cache_limit_count(0)
+            .build();
+        let mut request_list = RequestList::new(&config, Default::default());
+        let did_hash = did_hash(DID_KEY);
 
+        request_list.insert(did_hash.clone(), &_unique_id(), oneshot::channel().0);
+        assert!(request_list.is_full());
+
+        assert!(request_list.remove(&did_hash, None).is_some());
+        assert!(request_list.remove(&did_hash, None).is_none());
+        assert_eq!(request_list.total_count, 0);
+        assert!(!request_list.is_full());
+
+        // Same again, removing by unique id
+        let unique_id = _unique_id();
+        request_list.insert(did_hash.clone(), &unique_id, oneshot::channel().0);
+        assert!(request_list
+            .remove(&did_hash, Some(unique_id.clone()))
+            .is_some());
+        assert!(request_list.remove(&did_hash, Some(unique_id)).is_none());
         assert_eq!(request_list.total_count, 0);
+        assert!(!request_list.is_full());
     }
 
     #[tokio::test]
     async fn remove_works() {
         let (mut request_list, _) = _fill_request_list([DID_KEY].to_vec(), false, None);
 
-        request_list.remove(&_hash_did(DID_KEY), None).unwrap();
+        request_list.remove(&did_hash(DID_KEY), None).unwrap();
+    }
+
+    #[tokio::test]
+    async fn remove_request_returns_duplicates() {
+        let config = config::ClientConfigBuilder::default().build();
+        let mut request_list = RequestList::new(&config, Default::default());
+        let did_hash = did_hash(DID_KEY);
+        let request_id = _unique_id();
+
+        assert!(request_list.insert(did_hash.clone(), &request_id, oneshot::channel().0));
+        assert!(!request_list.insert(did_hash.clone(), &_unique_id(), oneshot::channel().0));
+
+        // Unknown request ids don't match by DID hash
+        assert!(request_list.remove_request(&did_hash).is_none());
+
+        assert_eq!(request_list.remove_request(&request_id).unwrap().len(), 2);
+        assert_eq!(request_list.total_count, 0);
+        assert!(request_list.remove(&did_hash, None).is_none());
+
+        // A new request for the same DID gets a new request_id
+        let request_id_2 = _unique_id();
+        assert!(request_list.insert(did_hash.clone(), &request_id_2, oneshot::channel().0));
+        assert!(request_list.remove_request(&request_id).is_none());
+        assert_eq!(request_list.remove_request(&request_id_2).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn counts_inserts_and_duplicates() {
+        let config = config::ClientConfigBuilder::default().build();
+        let counters = std::sync::Arc::new(super::RequestListCounters::default());
+        let mut request_list = RequestList::new(&config, counters.clone());
+        assert_eq!(counters.stats().dedup_rate(), 0.0);
+
+        let request_id = _unique_id();
+        request_list.insert(did_hash(DID_KEY), &request_id, oneshot::channel().0);
+        request_list.insert(did_hash(DID_KEY), &_unique_id(), oneshot::channel().0);
+        request_list.insert(did_hash(DID_KEY), &_unique_id(), oneshot::channel().0);
+        request_list.insert(did_hash(DID_KEY_2), &_unique_id(), oneshot::channel().0);
+
+        // The peak stays after requests complete
+        request_list.remove_request(&request_id);
+        request_list.insert(did_hash(DID_KEY), &_unique_id(), oneshot::channel().0);
+
+        let stats = counters.stats();
+        assert_eq!(stats.total_inserts, 5);
+        assert_eq!(stats.deduped_inserts, 2);
+        assert_eq!(stats.peak_concurrent, 2);
+        assert!((stats.dedup_rate() - 0.4).abs() < f64::EPSILON);
     }
 
-    fn _hash_did(did: &str) -> String {
-        let mut hasher = Blake2s256::new();
-        hasher.update(did);
-        format!("{:x}", hasher.clone().finalize())
+    /// Number of channels waiting for a response for the DID
+    fn _waiting(request_list: &RequestList, did: &str) -> usize {
+        let request_id = request_list.request_ids.get(&did_hash(did)).unwrap();
+        request_list.list.get(request_id).unwrap().1.len()
     }
 
     fn _unique_id() -> String {
@@ -305,7 +464,7 @@ mod tests {
         fn get_hash_and_id(did: &str) -> (String, String, Sender<WSCommands>) {
             (
                 _unique_id(),
-                _hash_did(did),
+                did_hash(did),
                 oneshot::channel::<WSCommands>().0,
             )
         }
@@ -315,7 +474,7 @@ mod tests {
         let mut did_to_uuid_map: HashMap<String, Vec<String>> = HashMap::new();
 
         let config = config::ClientConfigBuilder::default().build();
-        let mut request_list = RequestList::new(&config);
+        let mut request_list = RequestList::new(&config, Default::default());
 
         for did in dids {
             let (unique_id, did_hash, tx) = get_hash_and_id(did);
@@ -0,0 +1,297 @@
+//! TLS policy for wss:// connections to the DID Cache server
+//!
+//! By default the websocket uses the platform TLS library and negotiates whatever it allows.
+//! Setting a minimum TLS version or cipher suites switches to a rustls connector that only
+//! offers what the policy allows, so a server that can't meet it fails the handshake rather
+//! than the connection being downgraded.
+//!
+//! The rustls connector is also used to trust custom root certificates (e.g. a private CA),
+//! or, for local testing only, to accept any server certificate.
+
+use crate::{config::ClientConfig, errors::DIDCacheError};
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    version, DigitallySignedStruct, RootCertStore, SignatureScheme, SupportedProtocolVersion,
+};
+use std::sync::Arc;
+use tokio_tungstenite::Connector;
+use tracing::warn;
+
+/// Minimum TLS version accepted for wss:// connections
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TlsVersion {
+    /// TLS 1.2 and TLS 1.3
+    Tls12,
+    /// TLS 1.3 only
+    Tls13,
+}
+
+static TLS12_AND_TLS13: [&SupportedProtocolVersion; 2] = [&version::TLS13, &version::TLS12];
+static TLS13_ONLY: [&SupportedProtocolVersion; 1] = [&version::TLS13];
+
+impl TlsVersion {
+    fn protocol_versions(&self) -> &'static [&'static SupportedProtocolVersion] {
+        match self {
+            TlsVersion::Tls12 => &TLS12_AND_TLS13,
+            TlsVersion::Tls13 => &TLS13_ONLY,
+        }
+    }
+}
+
+/// Cipher suites that can be used in [with_tls_cipher_suites](crate::config::ClientConfigBuilder::with_tls_cipher_suites)
+/// Names are the IANA names (e.g. `TLS13_AES_256_GCM_SHA384`)
+pub fn supported_cipher_suites() -> Vec<&'static str> {
+    ring::default_provider()
+        .cipher_suites
+        .iter()
+        .filter_map(|suite| suite.suite().as_str())
+        .collect()
+}
+
+/// Builds the websocket TLS connector for the configured TLS policy
+/// Returns None if no policy is set (use the default connector)
+/// Returns a ConfigError if the policy can't be satisfied (unknown cipher suites, or no cipher
+/// suites left for the allowed TLS versions)
+pub(crate) fn connector(config: &ClientConfig) -> Result<Option<Connector>, DIDCacheError> {
+    if config.tls_min_version.is_none()
+        && config.tls_cipher_suites.is_none()
+        && config.tls_roots.is_none()
+        && !config.danger_accept_invalid_certs
+    {
+        return Ok(None);
+    }
+
+    let versions = config
+        .tls_min_version
+        .unwrap_or(TlsVersion::Tls12)
+        .protocol_versions();
+
+    let mut provider = ring::default_provider();
+    if let Some(names) = &config.tls_cipher_suites {
+        if let Some(unknown) = names
+            .iter()
+            .find(|name| !supported_cipher_suites().contains(&name.as_str()))
+        {
+            return Err(DIDCacheError::ConfigError(format!(
+                "TLS cipher suite ({}) isn't supported. Supported: {}",
+                unknown,
+                supported_cipher_suites().join(", ")
+            )));
+        }
+        provider.cipher_suites.retain(|suite| {
+            suite
+                .suite()
+                .as_str()
+                .is_some_and(|name| names.iter().any(|n| n == name))
+        });
+    }
+    provider
+        .cipher_suites
+        .retain(|suite| versions.contains(&suite.version()));
+    if provider.cipher_suites.is_empty() {
+        return Err(DIDCacheError::ConfigError(
+            "No TLS cipher suites are allowed for the minimum TLS version".into(),
+        ));
+    }
+
+    let roots = _root_store(config)?;
+
+    let provider = Arc::new(provider);
+    let mut tls_config = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(versions)
+        .map_err(|e| DIDCacheError::ConfigError(format!("Invalid TLS policy: {}", e)))?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    if config.danger_accept_invalid_certs {
+        warn!("TLS server certificates are NOT verified (danger_accept_invalid_certs), only use this for local testing!");
+        tls_config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(AcceptAnyServerCert(provider)));
+    }
+
+    Ok(Some(Connector::Rustls(Arc::new(tls_config))))
+}
+
+/// Root certificates to verify the server with, the configured tls_roots or the platform roots
+fn _root_store(config: &ClientConfig) -> Result<RootCertStore, DIDCacheError> {
+    let mut roots = RootCertStore::empty();
+    if let Some(tls_roots) = &config.tls_roots {
+        for (index, der) in tls_roots.iter().enumerate() {
+            roots
+                .add(CertificateDer::from(der.as_slice()))
+                .map_err(|e| {
+                    DIDCacheError::ConfigError(format!(
+                        "Invalid TLS root certificate ({}): {}",
+                        index, e
+                    ))
+                })?;
+        }
+    } else {
+        let native_certs = rustls_native_certs::load_native_certs();
+        for e in native_certs.errors {
+            warn!("Couldn't load a native root certificate: {}", e);
+        }
+        roots.add_parsable_certificates(native_certs.certs);
+    }
+    Ok(roots)
+}
+
+/// INSECURE: Accepts any server certificate, see
+/// [with_danger_accept_invalid_certs](crate::config::ClientConfigBuilder::with_danger_accept_invalid_certs)
+/// Handshake signatures are still checked, so the server must hold the certificate's key
+#[derive(Debug)]
+struct AcceptAnyServerCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyServerCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ClientConfigBuilder;
+
+    #[test]
+    fn no_policy_uses_default_connector() {
+        let config = ClientConfigBuilder::default().build();
+        assert!(connector(&config).unwrap().is_none());
+    }
+
+    #[test]
+    fn tls13_only() {
+        let config = ClientConfigBuilder::default()
+            .with_min_tls_version(TlsVersion::Tls13)
+            .build();
+
+        let Some(Connector::Rustls(tls_config)) = connector(&config).unwrap() else {
+            panic!("expected a rustls connector");
+        };
+        // Only TLS 1.3 cipher suites are offered
+        let suites = &tls_config.crypto_provider().cipher_suites;
+        assert!(!suites.is_empty());
+        assert!(suites
+            .iter()
+            .all(|suite| suite.version() == &version::TLS13));
+    }
+
+    /// Self-signed P-256 CA certificate (DER, base64)
+    const TEST_CA: &str = "MIIBjzCCATWgAwIBAgIUStx/zL7bTR2V5lbQyY6adVMFwl0wCgYIKoZIzj0EAwIwHDEaMBgGA1UEAwwRRElEIENhY2hlIFRlc3QgQ0EwIBcNMjYxMDE3MDYwNzE3WhgPMjEyNjA5MjMwNjA3MTdaMBwxGjAYBgNVBAMMEURJRCBDYWNoZSBUZXN0IENBMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEhSnN40uQvCEtFwzmiNbqgtj1DlMKdh3D/jDKLccrWTvdjMfuMotzybiTULEkBg0EHNOQAlHLui+2SHeqVJpf66NTMFEwHQYDVR0OBBYEFLg4EMY7hEKFdjnOpC04/yBNfYsMMB8GA1UdIwQYMBaAFLg4EMY7hEKFdjnOpC04/yBNfYsMMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDSAAwRQIhAPbS/fQ/zNz0vgpe+OpcnI6z+NNKBvSQjru126LRWGkAAiAB6BvCU2d+SSRqR6O3dmB2OROTINn0bk9BFvylBVuTFg==";
+
+    #[test]
+    fn custom_tls_roots() {
+        use base64::prelude::*;
+
+        let config = ClientConfigBuilder::default()
+            .with_tls_roots(vec![BASE64_STANDARD.decode(TEST_CA).unwrap()])
+            .build();
+        assert!(matches!(connector(&config), Ok(Some(Connector::Rustls(_)))));
+
+        // Only the configured root is trusted
+        assert_eq!(_root_store(&config).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn invalid_tls_root() {
+        let config = ClientConfigBuilder::default()
+            .with_tls_roots(vec![b"not a certificate".to_vec()])
+            .build();
+
+        assert!(matches!(
+            connector(&config),
+            Err(DIDCacheError::ConfigError(msg)) if msg.starts_with("Invalid TLS root certificate (0)")
+        ));
+    }
+
+    #[test]
+    fn accept_invalid_certs() {
+        use base64::prelude::*;
+
+        let config = ClientConfigBuilder::default()
+            .with_danger_accept_invalid_certs(true)
+            .build();
+        assert!(matches!(connector(&config), Ok(Some(Connector::Rustls(_)))));
+
+        // A self-signed certificate for another host is accepted
+        let cert = BASE64_STANDARD.decode(TEST_CA).unwrap();
+        let verifier = AcceptAnyServerCert(Arc::new(ring::default_provider()));
+        assert!(verifier
+            .verify_server_cert(
+                &CertificateDer::from(cert.as_slice()),
+                &[],
+                &ServerName::try_from("did-cache.example.com").unwrap(),
+                &[],
+                UnixTime::now(),
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn unknown_cipher_suite() {
+        let config = ClientConfigBuilder::default()
+            .with_tls_cipher_suites(vec!["TLS_RSA_WITH_RC4_128_MD5".into()])
+            .build();
+
+        assert!(matches!(
+            connector(&config),
+            Err(DIDCacheError::ConfigError(msg)) if msg.contains("TLS_RSA_WITH_RC4_128_MD5")
+        ));
+    }
+
+    #[test]
+    fn cipher_suites_not_allowed_by_version() {
+        // A TLS 1.2 only cipher suite, with TLS 1.3 required
+        let config = ClientConfigBuilder::default()
+            .with_min_tls_version(TlsVersion::Tls13)
+            .with_tls_cipher_suites(vec!["TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384".into()])
+            .build();
+
+        assert!(matches!(
+            connector(&config),
+            Err(DIDCacheError::ConfigError(_))
+        ));
+    }
+}
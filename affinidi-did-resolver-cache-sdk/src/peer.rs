@@ -0,0 +1,131 @@
+//! Creating did:peer:2 DIDs
+//!
+//! [DIDCacheClient::create_did_peer] builds a did:peer:2 from existing public keys and services.
+//! Keys are never generated, so the same input always creates the same DID.
+//!
+//! See: <https://identity.foundation/peer-did-method-spec/>
+
+use crate::{errors::DIDCacheError, DIDCacheClient};
+use did_peer::{DIDPeer, DIDPeerCreateKeys};
+pub use did_peer::{
+    DIDPeerKeys as PeerKeyPurpose, DIDPeerService as PeerService, PeerServiceEndPoint,
+    PeerServiceEndPointLong, PeerServiceEndPointShort,
+};
+
+/// A public key of a did:peer:2
+/// purpose: Verification (authentication and assertionMethod) or Encryption (keyAgreement)
+/// public_key_multibase: Multibase encoded public key, as in a did:key (`z6Mk...`)
+#[derive(Clone)]
+pub struct PeerKey {
+    pub purpose: PeerKeyPurpose,
+    pub public_key_multibase: String,
+}
+
+impl PeerKey {
+    /// Creates a key from a did:key, or its multibase encoded public key
+    pub fn new(purpose: PeerKeyPurpose, key: &str) -> Self {
+        PeerKey {
+            purpose,
+            public_key_multibase: key.strip_prefix("did:key:").unwrap_or(key).to_string(),
+        }
+    }
+}
+
+impl DIDCacheClient {
+    /// Creates a did:peer:2 DID from public keys and services
+    /// Elements are in canonical order: verification keys (`.V`), then encryption keys (`.E`),
+    /// then services (`.S`), keys of the same purpose keep the order they were given in
+    /// Returns a DIDError if a key isn't a supported multibase encoded public key
+    pub fn create_did_peer(
+        keys: &[PeerKey],
+        services: &[PeerService],
+    ) -> Result<String, DIDCacheError> {
+        let create_keys: Vec<DIDPeerCreateKeys> = keys
+            .iter()
+            .filter(|key| matches!(key.purpose, PeerKeyPurpose::Verification))
+            .chain(
+                keys.iter()
+                    .filter(|key| matches!(key.purpose, PeerKeyPurpose::Encryption)),
+            )
+            .map(|key| DIDPeerCreateKeys {
+                purpose: key.purpose.clone(),
+                type_: None,
+                public_key_multibase: Some(key.public_key_multibase.clone()),
+            })
+            .collect();
+        let services = services.to_vec();
+
+        let (did, _) =
+            DIDPeer::create_peer_did(&create_keys, (!services.is_empty()).then_some(&services))
+                .map_err(|e| DIDCacheError::DIDError(format!("Couldn't create did:peer: {}", e)))?;
+
+        DIDPeer::validate(&did)
+            .map_err(|e| DIDCacheError::DIDError(format!("Couldn't create did:peer: {}", e)))?;
+
+        Ok(did)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use ssi::{dids::DIDKey, JWK};
+
+    #[tokio::test]
+    async fn create_did_peer_resolves() {
+        let verification = DIDKey::generate(&JWK::generate_ed25519().unwrap())
+            .unwrap()
+            .to_string();
+        let encryption = DIDKey::generate(&JWK::generate_p256()).unwrap().to_string();
+        let keys = [
+            PeerKey::new(PeerKeyPurpose::Encryption, &encryption),
+            PeerKey::new(PeerKeyPurpose::Verification, &verification),
+        ];
+        let services = [PeerService {
+            _type: "dm".into(),
+            service_end_point: PeerServiceEndPoint::Short(PeerServiceEndPointShort {
+                uri: "https://example.com/didcomm".into(),
+                a: vec!["didcomm/v2".into()],
+                r: vec![],
+            }),
+            id: None,
+        }];
+
+        let did = DIDCacheClient::create_did_peer(&keys, &services).unwrap();
+        let elements: Vec<&str> = did.split('.').collect();
+        assert_eq!(elements[0], "did:peer:2");
+        assert_eq!(elements[1], format!("V{}", &verification[8..]));
+        assert_eq!(elements[2], format!("E{}", &encryption[8..]));
+        assert!(elements[3].starts_with('S'));
+
+        // Deterministic
+        assert_eq!(
+            DIDCacheClient::create_did_peer(&keys, &services).unwrap(),
+            did
+        );
+
+        let client = DIDCacheClient::new(config::ClientConfigBuilder::default().build())
+            .await
+            .unwrap();
+        let doc = client.resolve(&did).await.unwrap().doc;
+        assert_eq!(doc.id.as_str(), did);
+        assert_eq!(doc.verification_method.len(), 2);
+        assert_eq!(doc.verification_relationships.authentication.len(), 1);
+        assert_eq!(doc.verification_relationships.key_agreement.len(), 1);
+        assert_eq!(doc.service.len(), 1);
+        assert_eq!(
+            doc.service[0].type_.first().map(String::as_str),
+            Some("DIDCommMessaging")
+        );
+    }
+
+    #[test]
+    fn create_did_peer_invalid_key() {
+        let result = DIDCacheClient::create_did_peer(
+            &[PeerKey::new(PeerKeyPurpose::Verification, "not a key")],
+            &[],
+        );
+        assert!(matches!(result, Err(DIDCacheError::DIDError(_))));
+    }
+}
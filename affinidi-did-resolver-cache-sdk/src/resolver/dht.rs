@@ -0,0 +1,446 @@
+//! did:dht resolution
+//!
+//! A did:dht DID Document is a DNS packet, stored as a BEP44 mutable item on the Mainline DHT
+//! under the Ed25519 identity key that the DID encodes (z-base-32).
+//! Records are fetched through an HTTP gateway (Pkarr relay API) rather than joining the DHT
+//! directly, which also works in WASM. The record is verified against the identity key, so the
+//! gateway doesn't need to be trusted.
+//!
+//! See: <https://did-dht.com>
+
+use crate::{
+    errors::DIDCacheError,
+    metadata::{CacheEntry, DocumentMetadata, ResolutionMetadata},
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use ed25519_dalek::{Signature, VerifyingKey};
+use reqwest::StatusCode;
+use serde_json::{json, Map, Value};
+use simple_dns::{rdata::RData, Packet};
+use std::{collections::HashMap, time::Instant};
+
+/// Pkarr relay used to read did:dht records if no other gateway is configured
+pub(crate) const DEFAULT_DHT_GATEWAY: &str = "https://relay.pkarr.org";
+
+const Z_BASE_32: &[u8; 32] = b"ybndrfg8ejkmcpqxot1uwisza345h769";
+
+/// BEP44 limits the value (DNS packet) to 1000 bytes
+const MAX_VALUE_SIZE: usize = 1000;
+
+/// Resolves a did:dht by fetching the signed DNS packet from the gateway
+/// id: The method specific id (z-base-32 encoded Ed25519 public key)
+pub(crate) async fn resolve(
+    client: &reqwest::Client,
+    did: &str,
+    id: &str,
+    gateway: &str,
+) -> Result<CacheEntry, DIDCacheError> {
+    let public_key = identity_key(id)?;
+
+    let url = format!("{}/{}", gateway.trim_end_matches('/'), id);
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| DIDCacheError::TransportError(format!("GET ({}) failed: {}", url, e)))?;
+    match response.status() {
+        status if status.is_success() => {}
+        StatusCode::NOT_FOUND => {
+            return Err(DIDCacheError::NotFound(format!(
+                "did:dht ({}) DID document not found",
+                did
+            )))
+        }
+        status => {
+            return Err(DIDCacheError::TransportError(format!(
+                "GET ({}) returned status ({})",
+                url, status
+            )))
+        }
+    }
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| DIDCacheError::TransportError(format!("GET ({}) failed: {}", url, e)))?;
+
+    let (seq, packet) = verify_record(&public_key, &body)?;
+    let doc = parse_document(did, packet)?;
+
+    Ok(CacheEntry {
+        doc,
+        resolution_metadata: ResolutionMetadata {
+            content_type: Some("application/did+json".into()),
+            error: None,
+        },
+        document_metadata: DocumentMetadata {
+            version_id: Some(seq.to_string()),
+            ..Default::default()
+        },
+        inserted_at: Instant::now(),
+        method: None,
+        method_name: None,
+        raw: None,
+    })
+}
+
+/// Checks the gateway is reachable and answering, without needing a published did:dht
+/// Looks up a key nobody holds (all zeros), so a 404 is the healthy answer
+pub(crate) async fn probe_gateway(
+    client: &reqwest::Client,
+    gateway: &str,
+) -> Result<(), DIDCacheError> {
+    let url = format!("{}/{}", gateway.trim_end_matches('/'), "y".repeat(52));
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| DIDCacheError::TransportError(format!("GET ({}) failed: {}", url, e)))?;
+    match response.status() {
+        status if status.is_success() || status == StatusCode::NOT_FOUND => Ok(()),
+        status => Err(DIDCacheError::TransportError(format!(
+            "GET ({}) returned status ({})",
+            url, status
+        ))),
+    }
+}
+
+/// Decodes the z-base-32 method specific id to the Ed25519 identity key
+fn identity_key(id: &str) -> Result<VerifyingKey, DIDCacheError> {
+    let invalid = || DIDCacheError::InvalidDid(format!("did:dht id ({}) isn't a valid key", id));
+
+    let mut bytes = Vec::with_capacity(32);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in id.bytes() {
+        let value = Z_BASE_32.iter().position(|&z| z == c).ok_or_else(invalid)?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    let bytes: [u8; 32] = bytes.try_into().map_err(|_| invalid())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| invalid())
+}
+
+/// Checks the BEP44 signature of a gateway response (`signature || seq || value`)
+/// Returns the sequence number and the DNS packet
+fn verify_record<'a>(
+    public_key: &VerifyingKey,
+    record: &'a [u8],
+) -> Result<(u64, &'a [u8]), DIDCacheError> {
+    if record.len() < 72 || record.len() > 72 + MAX_VALUE_SIZE {
+        return Err(DIDCacheError::InvalidDid(format!(
+            "did:dht record has an invalid size ({} bytes)",
+            record.len()
+        )));
+    }
+
+    let signature = Signature::from_slice(&record[..64])
+        .map_err(|e| DIDCacheError::InvalidDid(format!("did:dht record signature: {}", e)))?;
+    let seq = u64::from_be_bytes(record[64..72].try_into().unwrap());
+    let value = &record[72..];
+
+    // BEP44 signs the bencoded seq and value
+    let mut signed = format!("3:seqi{}e1:v{}:", seq, value.len()).into_bytes();
+    signed.extend_from_slice(value);
+    public_key.verify_strict(&signed, &signature).map_err(|_| {
+        DIDCacheError::InvalidDid("did:dht record signature doesn't match the DID".into())
+    })?;
+
+    Ok((seq, value))
+}
+
+/// Builds the DID Document from the TXT records of the DNS packet
+/// `_did` is the root record (relationships), `_k<n>` keys, `_s<n>` services,
+/// `_cnt` controllers and `_aka` alsoKnownAs
+fn parse_document(did: &str, packet: &[u8]) -> Result<ssi::dids::Document, DIDCacheError> {
+    let packet = Packet::parse(packet)
+        .map_err(|e| DIDCacheError::InvalidDid(format!("did:dht DNS packet: {}", e)))?;
+
+    let mut records: HashMap<String, String> = HashMap::new();
+    for answer in &packet.answers {
+        let RData::TXT(txt) = &answer.rdata else {
+            continue;
+        };
+        let labels = answer.name.get_labels();
+        let name = match labels {
+            [first, ..] if first.to_string() == "_did" => "_did".to_string(),
+            [first, second, ..] if second.to_string() == "_did" => first.to_string(),
+            _ => continue,
+        };
+        let value = String::try_from(txt.clone())
+            .map_err(|e| DIDCacheError::InvalidDid(format!("did:dht TXT record: {}", e)))?;
+        records.insert(name, value);
+    }
+
+    let root = records.get("_did").ok_or_else(|| {
+        DIDCacheError::InvalidDid(format!("did:dht ({}) has no _did root record", did))
+    })?;
+    let root = properties(root);
+
+    // Record names (k0, s0) to ids in the DID Document
+    let mut ids = HashMap::new();
+    let mut verification_methods = Vec::new();
+    for name in list(root.get("vm").copied()) {
+        let key = properties(records.get(&format!("_{}", name)).ok_or_else(|| {
+            DIDCacheError::InvalidDid(format!("did:dht ({}) is missing record ({})", did, name))
+        })?);
+        let vm_id = format!("{}#{}", did, key.get("id").copied().unwrap_or(name));
+        verification_methods.push(json!({
+            "id": vm_id,
+            "type": "JsonWebKey2020",
+            "controller": key.get("c").copied().unwrap_or(did),
+            "publicKeyJwk": key_jwk(did, &key)?,
+        }));
+        ids.insert(name.to_string(), vm_id);
+    }
+
+    let mut doc = Map::new();
+    doc.insert("id".into(), did.into());
+    if let Some(controllers) = records.get("_cnt") {
+        let controllers: Vec<&str> = list(Some(controllers)).collect();
+        doc.insert("controller".into(), json!(controllers));
+    }
+    if let Some(aka) = records.get("_aka") {
+        let aka: Vec<&str> = list(Some(aka)).collect();
+        doc.insert("alsoKnownAs".into(), json!(aka));
+    }
+    doc.insert("verificationMethod".into(), verification_methods.into());
+
+    for (property, relationship) in [
+        ("auth", "authentication"),
+        ("asm", "assertionMethod"),
+        ("agm", "keyAgreement"),
+        ("inv", "capabilityInvocation"),
+        ("del", "capabilityDelegation"),
+    ] {
+        let methods: Vec<&String> = list(root.get(property).copied())
+            .filter_map(|name| ids.get(name))
+            .collect();
+        if !methods.is_empty() {
+            doc.insert(relationship.into(), json!(methods));
+        }
+    }
+
+    let mut services = Vec::new();
+    for name in list(root.get("svc").copied()) {
+        let Some(service) = records.get(&format!("_{}", name)) else {
+            continue;
+        };
+        let service = properties(service);
+        let endpoints: Vec<&str> = list(service.get("se").copied()).collect();
+        services.push(json!({
+            "id": format!("{}#{}", did, service.get("id").copied().unwrap_or(name)),
+            "type": service.get("t").copied().unwrap_or_default(),
+            "serviceEndpoint": if endpoints.len() == 1 { json!(endpoints[0]) } else { json!(endpoints) },
+        }));
+    }
+    if !services.is_empty() {
+        doc.insert("service".into(), services.into());
+    }
+
+    serde_json::from_value(Value::Object(doc))
+        .map_err(|e| DIDCacheError::InvalidDid(format!("did:dht ({}) DID Document: {}", did, e)))
+}
+
+/// Converts a key record (`id=0;t=0;k=<base64url key>`) to a JWK
+/// Key types: 0 = Ed25519, 1 = secp256k1, 2 = secp256r1, 3 = X25519
+fn key_jwk(did: &str, key: &HashMap<&str, &str>) -> Result<Value, DIDCacheError> {
+    let invalid = |reason: &str| {
+        DIDCacheError::InvalidDid(format!("did:dht ({}) has an invalid key: {}", did, reason))
+    };
+
+    let bytes = BASE64_URL_SAFE_NO_PAD
+        .decode(key.get("k").ok_or_else(|| invalid("no key"))?)
+        .map_err(|e| invalid(&e.to_string()))?;
+
+    let jwk = match key.get("t").copied().unwrap_or("0") {
+        "0" => ssi::jwk::ed25519_parse(&bytes),
+        "1" => ssi::jwk::secp256k1_parse(&bytes),
+        "2" => ssi::jwk::p256_parse(&bytes),
+        "3" => {
+            return Ok(json!({
+                "kty": "OKP",
+                "crv": "X25519",
+                "x": BASE64_URL_SAFE_NO_PAD.encode(&bytes),
+            }))
+        }
+        t => return Err(invalid(&format!("unsupported key type ({})", t))),
+    }
+    .map_err(|e| invalid(&e.to_string()))?;
+
+    serde_json::to_value(jwk).map_err(|e| invalid(&e.to_string()))
+}
+
+/// Splits a TXT record (`a=1;b=2`) into its properties
+fn properties(record: &str) -> HashMap<&str, &str> {
+    record
+        .split(';')
+        .filter_map(|property| property.split_once('='))
+        .collect()
+}
+
+/// Splits a comma separated property value
+fn list(value: Option<&str>) -> impl Iterator<Item = &str> {
+    value
+        .into_iter()
+        .flat_map(|value| value.split(','))
+        .filter(|item| !item.is_empty())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use simple_dns::{rdata::TXT, Name, ResourceRecord, CLASS};
+
+    /// z-base-32 encodes a key, to build the DID for a test key
+    pub(crate) fn encode_id(bytes: &[u8]) -> String {
+        let mut id = String::new();
+        let (mut buffer, mut bits) = (0u32, 0);
+        for &byte in bytes {
+            buffer = (buffer << 8) | byte as u32;
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                id.push(Z_BASE_32[(buffer >> bits) as usize & 31] as char);
+            }
+        }
+        if bits > 0 {
+            id.push(Z_BASE_32[(buffer << (5 - bits)) as usize & 31] as char);
+        }
+        id
+    }
+
+    /// Builds a signed gateway record for the TXT records, returns the DID and the record
+    pub(crate) fn signed_record(records: &[(&str, &str)], seq: u64) -> (String, Vec<u8>) {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let id = encode_id(signing_key.verifying_key().as_bytes());
+
+        let mut packet = Packet::new_reply(0);
+        let names: Vec<String> = records
+            .iter()
+            .map(|(name, _)| format!("{}.{}", name, id))
+            .collect();
+        for ((_, value), name) in records.iter().zip(&names) {
+            packet.answers.push(ResourceRecord::new(
+                Name::new_unchecked(name),
+                CLASS::IN,
+                7200,
+                RData::TXT(TXT::try_from(*value).unwrap()),
+            ));
+        }
+        let value = packet.build_bytes_vec().unwrap();
+
+        let mut signed = format!("3:seqi{}e1:v{}:", seq, value.len()).into_bytes();
+        signed.extend_from_slice(&value);
+        let mut record = signing_key.sign(&signed).to_bytes().to_vec();
+        record.extend_from_slice(&seq.to_be_bytes());
+        record.extend_from_slice(&value);
+
+        (format!("did:dht:{}", id), record)
+    }
+
+    pub(crate) fn identity_key_record() -> String {
+        let key = SigningKey::from_bytes(&[7; 32]).verifying_key();
+        format!(
+            "id=0;t=0;k={}",
+            BASE64_URL_SAFE_NO_PAD.encode(key.as_bytes())
+        )
+    }
+
+    #[test]
+    fn z_base_32_round_trip() {
+        let key = SigningKey::from_bytes(&[1; 32]).verifying_key();
+        let id = encode_id(key.as_bytes());
+        assert_eq!(id.len(), 52);
+        assert_eq!(identity_key(&id).unwrap(), key);
+        assert!(identity_key("not-z-base-32").is_err());
+        assert!(identity_key(&id[..40]).is_err());
+    }
+
+    #[test]
+    fn document_from_records() {
+        let k0 = identity_key_record();
+        let (did, record) = signed_record(
+            &[
+                (
+                    "_did",
+                    "v=0;vm=k0,k1;auth=k0;asm=k0;agm=k1;inv=k0;del=k0;svc=s0",
+                ),
+                ("_k0._did", &k0),
+                (
+                    "_k1._did",
+                    "id=enc;t=3;k=hSDwCYkwp1R0i33ctD73Wg2_Og0mOBr066SpjqqbTmo",
+                ),
+                (
+                    "_s0._did",
+                    "id=dwn;t=DecentralizedWebNode;se=https://example.com/dwn",
+                ),
+                ("_aka._did", "did:web:example.com"),
+            ],
+            3,
+        );
+
+        let id = did.strip_prefix("did:dht:").unwrap();
+        let (seq, packet) = verify_record(&identity_key(id).unwrap(), &record).unwrap();
+        assert_eq!(seq, 3);
+
+        let doc = parse_document(&did, packet).unwrap();
+        let doc = serde_json::to_value(&doc).unwrap();
+        assert_eq!(doc["verificationMethod"][0]["id"], format!("{}#0", did));
+        assert_eq!(
+            doc["verificationMethod"][0]["publicKeyJwk"]["crv"],
+            "Ed25519"
+        );
+        assert_eq!(
+            doc["verificationMethod"][1]["publicKeyJwk"]["crv"],
+            "X25519"
+        );
+        assert_eq!(doc["authentication"][0], format!("{}#0", did));
+        assert_eq!(doc["keyAgreement"][0], format!("{}#enc", did));
+        assert_eq!(doc["service"][0]["id"], format!("{}#dwn", did));
+        assert_eq!(
+            doc["service"][0]["serviceEndpoint"],
+            "https://example.com/dwn"
+        );
+        assert_eq!(doc["alsoKnownAs"][0], "did:web:example.com");
+    }
+
+    /// Serves the record for gateway requests, returns the gateway URL
+    async fn gateway(record: Vec<u8>) -> String {
+        let server = crate::test_fixtures::serve("200 OK", record, None).await;
+        format!("http://127.0.0.1:{}/", server.port)
+    }
+
+    #[tokio::test]
+    async fn resolve_through_gateway() {
+        let k0 = identity_key_record();
+        let (did, record) = signed_record(&[("_did", "v=0;vm=k0;auth=k0"), ("_k0._did", &k0)], 5);
+        let config = crate::config::ClientConfigBuilder::default()
+            .with_dht_gateway(&gateway(record).await)
+            .build();
+        let client = crate::DIDCacheClient::new(config).await.unwrap();
+
+        let response = client.resolve(&did).await.unwrap();
+        assert_eq!(response.method, crate::DIDMethod::DHT);
+        assert_eq!(response.doc.id.as_str(), did);
+        assert_eq!(response.doc.verification_method.len(), 1);
+        assert_eq!(response.document_metadata.version_id.as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn tampered_record_rejected() {
+        let k0 = identity_key_record();
+        let (did, mut record) = signed_record(&[("_did", "v=0;vm=k0"), ("_k0._did", &k0)], 1);
+        let last = record.len() - 1;
+        record[last] ^= 1;
+
+        let id = did.strip_prefix("did:dht:").unwrap();
+        assert!(verify_record(&identity_key(id).unwrap(), &record).is_err());
+        assert!(verify_record(&identity_key(id).unwrap(), &record[..50]).is_err());
+    }
+}
@@ -0,0 +1,336 @@
+//! did:ethr resolution
+//!
+//! The DID Document is derived from the address (or public key) in the DID by the SSI library,
+//! which makes no requests. If JSON-RPC endpoints are configured (see
+//! [with_ethr_rpc](crate::config::ClientConfigBuilder::with_ethr_rpc)) the identity is also
+//! looked up in the ERC-1056 registry (EthereumDIDRegistry) on the DID's chain:
+//! - a changed owner replaces the controller's blockchainAccountId
+//! - an owner of 0x0 means the DID is deactivated
+//!
+//! NOTE: Delegates and attributes (registry events) aren't read, only the owner
+//!
+//! See: <https://github.com/decentralized-identity/ethr-did-resolver/blob/master/doc/did-method-spec.md>
+
+use crate::{errors::DIDCacheError, metadata::CacheEntry};
+use serde_json::{json, Value};
+use ssi::dids::{DIDEthr, DIDResolver, Document, DID};
+use std::collections::HashMap;
+use tracing::error;
+
+/// Address of the EthereumDIDRegistry, the same on mainnet and the other supported chains
+const REGISTRY: &str = "0xdca7ef03e98e0dc2b855be647c39abe984fcf21b";
+
+/// Function selector of `identityOwner(address)`
+const IDENTITY_OWNER: &str = "8733d4e8";
+
+const ZERO_ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+
+/// Parses a network name (mainnet, goerli, ...) or chain id (0x89, 137) to the chain id
+pub(crate) fn chain_id(network: &str) -> Option<u64> {
+    match network {
+        "mainnet" => Some(1),
+        "morden" => Some(2),
+        "ropsten" => Some(3),
+        "rinkeby" => Some(4),
+        "goerli" => Some(5),
+        "kovan" => Some(42),
+        hex if hex.starts_with("0x") => u64::from_str_radix(&hex[2..], 16).ok(),
+        decimal => decimal.parse().ok(),
+    }
+}
+
+/// Key of a chain in [ClientConfig](crate::config::ClientConfig)'s ethr_rpc (e.g. 0x89)
+pub(crate) fn chain_key(chain_id: u64) -> String {
+    format!("0x{:x}", chain_id)
+}
+
+/// Resolves a did:ethr, checking the owner in the registry if ethr_rpc isn't empty
+/// The method specific id is `[network:]address` or `[network:]public key`
+pub(crate) async fn resolve(
+    client: &reqwest::Client,
+    ethr_rpc: &HashMap<String, String>,
+    did: &DID,
+) -> Result<CacheEntry, DIDCacheError> {
+    let id = did.method_specific_id();
+    let mut entry = match DIDEthr.resolve(did).await {
+        Ok(res) => CacheEntry::new(
+            res.document.into_document(),
+            &res.metadata,
+            &res.document_metadata,
+        ),
+        Err(e) => {
+            error!("Error: {:?}", e);
+            return Err(super::_from_resolution_error("ethr", e));
+        }
+    };
+
+    if ethr_rpc.is_empty() {
+        return Ok(entry);
+    }
+
+    // SSI already rejected DIDs with an invalid network
+    let network = id.split_once(':').map_or("mainnet", |(network, _)| network);
+    let chain_id = chain_id(network).ok_or_else(|| {
+        DIDCacheError::InvalidDid(format!("invalid did:ethr network ({})", network))
+    })?;
+    let rpc = ethr_rpc.get(&chain_key(chain_id)).ok_or_else(|| {
+        DIDCacheError::ConfigError(format!(
+            "No ethr_rpc endpoint configured for chain {} ({}), can't resolve {}",
+            chain_key(chain_id),
+            network,
+            did
+        ))
+    })?;
+
+    let address = controller_address(&entry.doc).ok_or_else(|| {
+        DIDCacheError::InvalidDid(format!("did:ethr ({}) has no controller address", did))
+    })?;
+    let owner = identity_owner(client, rpc, &address).await?;
+
+    if owner == ZERO_ADDRESS {
+        deactivate(&mut entry.doc);
+        entry.document_metadata.deactivated = Some(true);
+    } else if !owner.eq_ignore_ascii_case(&address) {
+        change_owner(&mut entry.doc, &format!("eip155:{}:{}", chain_id, owner));
+    }
+    Ok(entry)
+}
+
+/// Address of the #controller verification method (CAIP-10 `eip155:<chain id>:<address>`)
+fn controller_address(doc: &Document) -> Option<String> {
+    doc.verification_method
+        .iter()
+        .find(|vm| vm.id.fragment().is_some_and(|f| f.as_str() == "controller"))?
+        .properties
+        .get("blockchainAccountId")?
+        .as_str()?
+        .rsplit(':')
+        .next()
+        .map(str::to_string)
+}
+
+/// Calls `identityOwner(address)` on the registry, returns the owner's address (lowercase)
+async fn identity_owner(
+    client: &reqwest::Client,
+    rpc: &str,
+    address: &str,
+) -> Result<String, DIDCacheError> {
+    let data = format!(
+        "0x{}{:0>64}",
+        IDENTITY_OWNER,
+        address.trim_start_matches("0x").to_lowercase()
+    );
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "eth_call",
+        "params": [{ "to": REGISTRY, "data": data }, "latest"],
+    });
+
+    let transport = |e: String| DIDCacheError::TransportError(format!("eth_call ({}): {}", rpc, e));
+    let response = client
+        .post(rpc)
+        .header("content-type", "application/json")
+        .body(request.to_string())
+        .send()
+        .await
+        .map_err(|e| transport(e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(transport(format!(
+            "returned status ({})",
+            response.status()
+        )));
+    }
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| transport(e.to_string()))?;
+    let body: Value = serde_json::from_slice(&body).map_err(|e| transport(e.to_string()))?;
+
+    if let Some(error) = body.get("error") {
+        return Err(transport(format!("returned error ({})", error)));
+    }
+    // The address is the last 20 bytes of the 32 byte word (64 hex digits)
+    match body
+        .get("result")
+        .and_then(Value::as_str)
+        .and_then(|result| result.strip_prefix("0x"))
+    {
+        Some(word) if word.len() == 64 && word.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            Ok(format!("0x{}", word[24..].to_lowercase()))
+        }
+        _ => Err(transport(format!("unexpected response ({})", body))),
+    }
+}
+
+/// The owner was set to 0x0: the DID has no keys left
+fn deactivate(doc: &mut Document) {
+    doc.verification_method.clear();
+    let relationships = &mut doc.verification_relationships;
+    relationships.authentication.clear();
+    relationships.assertion_method.clear();
+    relationships.key_agreement.clear();
+    relationships.capability_invocation.clear();
+    relationships.capability_delegation.clear();
+}
+
+/// Points the verification methods at the new owner's account
+/// The public key of a public key DID belongs to the old owner, so its method is removed
+fn change_owner(doc: &mut Document, account_id: &str) {
+    let did = doc.id.clone();
+    let is_key = |fragment: Option<&str>| fragment == Some("controllerKey");
+
+    doc.verification_method
+        .retain(|vm| !is_key(vm.id.fragment().map(|f| f.as_str())));
+    for vm in &mut doc.verification_method {
+        if vm.properties.contains_key("blockchainAccountId") {
+            vm.properties
+                .insert("blockchainAccountId".into(), account_id.into());
+        }
+    }
+
+    let relationships = &mut doc.verification_relationships;
+    for references in [
+        &mut relationships.authentication,
+        &mut relationships.assertion_method,
+    ] {
+        references.retain(|r| !is_key(r.id().resolve(&did).fragment().map(|f| f.as_str())));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::ClientConfigBuilder,
+        test_fixtures::{client_with, serve},
+        DIDCacheClient,
+    };
+    use tokio::sync::mpsc;
+
+    const ADDRESS: &str = "0xb9c5714089478a327f09197987f16f9e5d936e8a";
+    const DID_POLYGON: &str = "did:ethr:0x89:0xb9c5714089478a327f09197987f16f9e5d936e8a";
+    const DID_PUBLIC_KEY: &str =
+        "did:ethr:0x89:0x03fdd57adec3d438ea237fe46b33ee1e016eda6b585c3e27ea66686c2ea5358479";
+
+    /// Serves JSON-RPC requests with the owner
+    /// Returns the URL, and the requests as they are received
+    async fn rpc(owner: &str) -> (String, mpsc::UnboundedReceiver<String>) {
+        rpc_result(&format!("0x{:0>64}", owner.trim_start_matches("0x"))).await
+    }
+
+    /// Serves JSON-RPC requests with the (raw) eth_call result
+    async fn rpc_result(result: &str) -> (String, mpsc::UnboundedReceiver<String>) {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "result": result }).to_string();
+        let server = serve("200 OK", body, None).await;
+        (format!("http://127.0.0.1:{}", server.port), server.requests)
+    }
+
+    /// The JSON-RPC body of a request
+    fn rpc_body(request: &str) -> Value {
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        serde_json::from_str(body).unwrap()
+    }
+
+    async fn client(chain_id: &str, rpc: &str) -> DIDCacheClient {
+        client_with(ClientConfigBuilder::default().with_ethr_rpc(chain_id, rpc)).await
+    }
+
+    #[test]
+    fn chain_ids() {
+        assert_eq!(chain_id("mainnet"), Some(1));
+        assert_eq!(chain_id("0x89"), Some(137));
+        assert_eq!(chain_id("137"), Some(137));
+        assert_eq!(chain_id("polygon"), None);
+        assert_eq!(chain_key(137), "0x89");
+    }
+
+    #[tokio::test]
+    async fn unchanged_owner() {
+        let (url, mut requests) = rpc(ADDRESS).await;
+        let client = client("137", &url).await;
+
+        let response = client.resolve(DID_POLYGON).await.unwrap();
+        assert_eq!(response.doc.verification_method.len(), 2);
+        assert_eq!(
+            response.doc.verification_method[0].properties["blockchainAccountId"],
+            format!("eip155:137:{}", ADDRESS)
+        );
+
+        let request = rpc_body(&requests.recv().await.unwrap());
+        assert_eq!(request["method"], "eth_call");
+        assert_eq!(request["params"][0]["to"], REGISTRY);
+        assert_eq!(
+            request["params"][0]["data"],
+            format!("0x8733d4e8{:0>64}", &ADDRESS[2..])
+        );
+    }
+
+    #[tokio::test]
+    async fn changed_owner() {
+        let owner = "0x1111111111111111111111111111111111111111";
+        let (url, _requests) = rpc(owner).await;
+        let client = client("0x89", &url).await;
+
+        let response = client.resolve(DID_PUBLIC_KEY).await.unwrap();
+        let doc = response.doc;
+        assert_eq!(doc.verification_method.len(), 1);
+        assert_eq!(
+            doc.verification_method[0].properties["blockchainAccountId"],
+            format!("eip155:137:{}", owner)
+        );
+        assert_eq!(doc.verification_relationships.authentication.len(), 1);
+        assert_eq!(doc.verification_relationships.assertion_method.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn deactivated() {
+        let (url, _requests) = rpc(ZERO_ADDRESS).await;
+        let client = client("0x89", &url).await;
+
+        let response = client.resolve(DID_POLYGON).await.unwrap();
+        assert!(response.doc.verification_method.is_empty());
+        assert!(response
+            .doc
+            .verification_relationships
+            .authentication
+            .is_empty());
+        assert_eq!(response.document_metadata.deactivated, Some(true));
+    }
+
+    #[tokio::test]
+    async fn malformed_result() {
+        for result in [
+            // Right length, but not hex
+            format!("0x{}", "z".repeat(64)),
+            // Right byte length, but the address would start inside a multi-byte character
+            format!("0x{}é{}", "0".repeat(23), "0".repeat(39)),
+            format!("0x{}", "0".repeat(62)),
+            "0".repeat(66),
+        ] {
+            let (url, _requests) = rpc_result(&result).await;
+            let client = client("0x89", &url).await;
+
+            assert!(
+                matches!(
+                    client.resolve(DID_POLYGON).await,
+                    Err(DIDCacheError::TransportError(_))
+                ),
+                "{}",
+                result
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn chain_without_rpc() {
+        let client = client("mainnet", "http://127.0.0.1:1").await;
+
+        match client.resolve(DID_POLYGON).await {
+            Err(DIDCacheError::ConfigError(msg)) => {
+                assert!(msg.starts_with("No ethr_rpc endpoint configured for chain 0x89"))
+            }
+            other => panic!("expected ConfigError, got {:?}", other.map(|_| ())),
+        }
+    }
+}
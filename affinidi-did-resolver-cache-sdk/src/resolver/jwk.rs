@@ -0,0 +1,157 @@
+//! did:jwk verification relationships
+//!
+//! The SSI library references the key from every verification relationship, whatever the key
+//! type. The did:jwk specification limits them by the intended use of the key:
+//! - `"use": "enc"`, or an X25519/X448 key: keyAgreement only
+//! - `"use": "sig"`, or an Ed25519/Ed448 key: everything except keyAgreement
+//! - Otherwise (e.g. P-256, P-384 and secp256k1 keys with no `use`): every relationship
+//!
+//! The SSI library can't encode X25519 keys as a Multikey, so their DID Document is built here.
+//!
+//! See: <https://github.com/quartzjer/did-jwk/blob/main/spec.md>
+
+use crate::errors::DIDCacheError;
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use serde_json::{json, Value};
+use ssi::{
+    dids::Document,
+    multicodec::{self, MultiEncodedBuf},
+};
+
+/// Intended use of a did:jwk key
+#[derive(Debug, PartialEq, Eq)]
+enum KeyUse {
+    Encryption,
+    Signing,
+    Any,
+}
+
+/// Decodes the JWK in the method specific id, None if it isn't base64url encoded JSON
+fn decode(id: &str) -> Option<Value> {
+    BASE64_URL_SAFE_NO_PAD
+        .decode(id)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+}
+
+/// Works out the intended use from the JWK in the method specific id
+/// An id that doesn't decode is treated as Any, the SSI library has already validated it
+fn key_use(id: &str) -> KeyUse {
+    let Some(jwk) = decode(id) else {
+        return KeyUse::Any;
+    };
+
+    match (
+        jwk.get("use").and_then(Value::as_str),
+        jwk.get("crv").and_then(Value::as_str),
+    ) {
+        (Some("enc"), _) | (None, Some("X25519" | "X448")) => KeyUse::Encryption,
+        (Some("sig"), _) | (None, Some("Ed25519" | "Ed448")) => KeyUse::Signing,
+        _ => KeyUse::Any,
+    }
+}
+
+/// Builds the DID Document of an X25519 did:jwk, as the SSI library can't
+/// Returns None if the did:jwk isn't an X25519 key, so the SSI library resolves it
+/// did: The full did:jwk DID
+/// id: The method specific id (everything after `did:jwk:`)
+pub(crate) fn x25519_document(did: &str, id: &str) -> Option<Result<Document, DIDCacheError>> {
+    let jwk = decode(id)?;
+    if jwk.get("kty").and_then(Value::as_str) != Some("OKP")
+        || jwk.get("crv").and_then(Value::as_str) != Some("X25519")
+    {
+        return None;
+    }
+
+    let invalid = |reason: &str| DIDCacheError::InvalidDid(format!("did:jwk ({}) {}", did, reason));
+    if jwk.get("d").is_some() {
+        return Some(Err(invalid("contains a private key")));
+    }
+    let key = match jwk
+        .get("x")
+        .and_then(Value::as_str)
+        .and_then(|x| BASE64_URL_SAFE_NO_PAD.decode(x).ok())
+    {
+        Some(key) if key.len() == 32 => key,
+        _ => return Some(Err(invalid("X25519 public key is invalid"))),
+    };
+    let public_key_multibase = multibase::encode(
+        multibase::Base::Base58Btc,
+        MultiEncodedBuf::encode_bytes(multicodec::X25519_PUB, &key).as_bytes(),
+    );
+
+    let vm_id = format!("{}#0", did);
+    Some(
+        serde_json::from_value(json!({
+            "@context": [
+                "https://www.w3.org/ns/did/v1",
+                "https://w3id.org/security/multikey/v1"
+            ],
+            "id": did,
+            "verificationMethod": [{
+                "id": vm_id,
+                "type": "Multikey",
+                "controller": did,
+                "publicKeyMultibase": public_key_multibase,
+            }],
+            "keyAgreement": [vm_id],
+        }))
+        .map_err(|e| invalid(&format!("couldn't build DID Document: {}", e))),
+    )
+}
+
+/// Removes the verification relationships the key of the did:jwk can't be used for
+/// id: The method specific id (everything after `did:jwk:`)
+pub(crate) fn restrict_relationships(doc: &mut Document, id: &str) {
+    let relationships = &mut doc.verification_relationships;
+    match key_use(id) {
+        KeyUse::Encryption => {
+            relationships.authentication.clear();
+            relationships.assertion_method.clear();
+            relationships.capability_invocation.clear();
+            relationships.capability_delegation.clear();
+        }
+        KeyUse::Signing => relationships.key_agreement.clear(),
+        KeyUse::Any => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(jwk: Value) -> String {
+        BASE64_URL_SAFE_NO_PAD.encode(jwk.to_string())
+    }
+
+    #[test]
+    fn key_uses() {
+        let x = "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo";
+        assert_eq!(
+            key_use(&encode(
+                serde_json::json!({"kty": "OKP", "crv": "X25519", "x": x})
+            )),
+            KeyUse::Encryption
+        );
+        assert_eq!(
+            key_use(&encode(
+                serde_json::json!({"kty": "OKP", "crv": "Ed25519", "x": x})
+            )),
+            KeyUse::Signing
+        );
+        // An explicit use wins
+        assert_eq!(
+            key_use(&encode(
+                serde_json::json!({"kty": "EC", "crv": "P-256", "use": "enc", "x": x, "y": x})
+            )),
+            KeyUse::Encryption
+        );
+        assert_eq!(
+            key_use(&encode(
+                serde_json::json!({"kty": "EC", "crv": "P-256", "x": x, "y": x})
+            )),
+            KeyUse::Any
+        );
+        assert_eq!(key_use("not base64!"), KeyUse::Any);
+    }
+}
@@ -0,0 +1,101 @@
+//! did:key multicodec validation
+//!
+//! The SSI library resolves did:key to a Multikey verification method without checking the
+//! key type, so unsupported or private key codecs resolve into unusable DID Documents.
+//! Validating up front gives a clear error instead.
+//!
+//! See: <https://w3c-ccg.github.io/did-method-key/>
+
+use crate::errors::DIDCacheError;
+use ssi::{
+    multicodec::{self, MultiEncodedBuf},
+    JWK,
+};
+
+/// did:key public key multicodecs that can be resolved
+pub(crate) const SUPPORTED_CODECS: [(u64, &str); 7] = [
+    (multicodec::ED25519_PUB, "Ed25519"),
+    (multicodec::SECP256K1_PUB, "secp256k1"),
+    (multicodec::P256_PUB, "P-256"),
+    (multicodec::P384_PUB, "P-384"),
+    (multicodec::BLS12_381_G2_PUB, "BLS12-381 G2"),
+    (multicodec::RSA_PUB, "RSA"),
+    (multicodec::JWK_JCS_PUB, "JWK (JCS)"),
+];
+
+/// Is this multicodec a key type at all? (the `key` tagged codecs of the multicodec table)
+/// Anything else isn't a did:key, rather than an unsupported type of key
+fn is_key_codec(codec: u64) -> bool {
+    matches!(codec, 0xe7..=0xef | 0x1200..=0x13ff | multicodec::JWK_JCS_PUB)
+}
+
+/// Validates the method specific id of a did:key DID (everything after `did:key:`)
+/// Checks the multicodec is a supported public key type, and that the key itself parses
+/// Returns the multicodec of the key, or [DIDCacheError::UnsupportedKeyType] for other key types
+pub(crate) fn validate_key(id: &str) -> Result<u64, DIDCacheError> {
+    let (_, data) = multibase::decode(id).map_err(|e| {
+        DIDCacheError::InvalidDid(format!("did:key ({}) isn't valid multibase: {}", id, e))
+    })?;
+    let encoded = MultiEncodedBuf::new(data).map_err(|e| {
+        DIDCacheError::InvalidDid(format!("did:key ({}) isn't a valid multicodec: {}", id, e))
+    })?;
+
+    let codec = encoded.codec();
+    let Some((_, name)) = SUPPORTED_CODECS.iter().find(|(c, _)| *c == codec) else {
+        if is_key_codec(codec) {
+            return Err(DIDCacheError::UnsupportedKeyType(codec));
+        }
+        return Err(DIDCacheError::InvalidDid(format!(
+            "did:key multicodec (0x{:x}) isn't a key type",
+            codec
+        )));
+    };
+
+    JWK::from_multicodec(&encoded).map_err(|e| {
+        DIDCacheError::InvalidDid(format!("did:key {} public key is invalid: {}", name, e))
+    })?;
+
+    Ok(codec)
+}
+
+/// Returns true if keys of this multicodec can be used for key agreement
+/// BLS12-381 keys are signature only
+pub(crate) fn supports_key_agreement(codec: u64) -> bool {
+    codec != multicodec::BLS12_381_G2_PUB
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_keys_rejected() {
+        let jwk = JWK::generate_p256();
+        let encoded = jwk.to_multicodec().unwrap();
+        let (_, key) = encoded.parts();
+        // Re-encode the key bytes as a private key
+        let private = MultiEncodedBuf::encode_bytes(multicodec::P256_PRIV, key);
+        let id = multibase::encode(multibase::Base::Base58Btc, private.as_bytes());
+
+        assert!(matches!(
+            validate_key(&id),
+            Err(DIDCacheError::UnsupportedKeyType(multicodec::P256_PRIV))
+        ));
+    }
+
+    #[test]
+    fn invalid_key_bytes() {
+        let encoded = MultiEncodedBuf::encode_bytes(multicodec::P384_PUB, &[2; 10]);
+        let id = multibase::encode(multibase::Base::Base58Btc, encoded.as_bytes());
+
+        assert!(matches!(
+            validate_key(&id),
+            Err(DIDCacheError::InvalidDid(msg)) if msg.contains("P-384 public key is invalid")
+        ));
+    }
+
+    #[test]
+    fn invalid_multibase() {
+        assert!(validate_key("not-multibase!").is_err());
+    }
+}
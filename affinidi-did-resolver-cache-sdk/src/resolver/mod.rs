@@ -1,133 +1,420 @@
-use crate::{errors::DIDCacheError, DIDCacheClient};
+use crate::{
+    config::ClientConfig, errors::DIDCacheError, metadata::CacheEntry,
+    method_resolver::MethodResolver, web_resolver::WebResolver, DIDCacheClient,
+};
+use async_trait::async_trait;
 use did_peer::DIDPeer;
-use ssi::dids::{DIDEthr, DIDKey, DIDResolver, DIDWeb, Document, DID, DIDJWK, DIDPKH};
-use tracing::error;
+use ssi::dids::{
+    resolution::{self, ErrorKind},
+    DIDKey, DIDResolver, Document, DID, DIDJWK, DIDPKH,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::OnceCell;
+use tracing::{debug, error};
+
+pub(crate) mod dht;
+pub(crate) mod ethr;
+mod jwk;
+mod key;
+mod pkh;
+pub(crate) mod web;
+
+/// Builds the HTTP client used to resolve did:web, did:dht and did:ethr (JSON-RPC)
+/// Uses the configured proxies, otherwise the HTTP_PROXY/HTTPS_PROXY/NO_PROXY environment variables
+pub(crate) fn http_client(config: &ClientConfig) -> Result<reqwest::Client, DIDCacheError> {
+    #[allow(unused_mut)]
+    let mut builder = reqwest::Client::builder().user_agent(concat!(
+        env!("CARGO_PKG_NAME"),
+        "/",
+        env!("CARGO_PKG_VERSION")
+    ));
+
+    // Proxies aren't supported in the browser
+    #[cfg(not(target_arch = "wasm32"))]
+    for (scheme, proxy) in [
+        (
+            "http",
+            config.http_proxy.as_deref().map(reqwest::Proxy::http),
+        ),
+        (
+            "https",
+            config.https_proxy.as_deref().map(reqwest::Proxy::https),
+        ),
+    ] {
+        let Some(proxy) = proxy else { continue };
+        let mut proxy = proxy
+            .map_err(|e| DIDCacheError::ConfigError(format!("Invalid {} proxy: {}", scheme, e)))?;
+        if let Some(auth) = &config.proxy_auth {
+            proxy = proxy.basic_auth(&auth.username, &auth.password);
+        }
+        builder = builder.proxy(proxy.no_proxy(reqwest::NoProxy::from_env()));
+    }
+
+    builder
+        .build()
+        .map_err(|e| DIDCacheError::ConfigError(format!("Couldn't build HTTP client: {}", e)))
+}
+
+/// Local resolutions in flight, keyed by DID hash
+/// Concurrent resolves of the same DID share the one resolution and its result
+pub(crate) type InFlight =
+    Arc<Mutex<HashMap<String, Arc<OnceCell<Result<CacheEntry, DIDCacheError>>>>>>;
+
+/// Does resolving this DID method make outbound requests?
+fn _requires_network(method: &str) -> bool {
+    matches!(method, "dht" | "ethr" | "web")
+}
+
+/// Maps an SSI resolution error to the matching DIDCacheError
+/// Internal errors of methods that make outbound requests are treated as transport errors
+fn _from_resolution_error(method: &str, error: resolution::Error) -> DIDCacheError {
+    match error.kind() {
+        ErrorKind::MethodNotSupported => DIDCacheError::UnsupportedMethod(method.to_string()),
+        ErrorKind::NotFound => DIDCacheError::NotFound(error.to_string()),
+        ErrorKind::InvalidData | ErrorKind::InvalidMethodSpecificId => {
+            DIDCacheError::InvalidDid(error.to_string())
+        }
+        ErrorKind::Internal if _requires_network(method) => {
+            DIDCacheError::TransportError(error.to_string())
+        }
+        _ => DIDCacheError::InvalidDid(error.to_string()),
+    }
+}
 
 impl DIDCacheClient {
-    /// Resolves a DID to a DID Document
-    pub(crate) async fn local_resolve(
+    /// [local_resolve](Self::local_resolve), but concurrent calls for the same DID are coalesced
+    /// Only the first call resolves the DID, the rest wait for and share its result
+    /// If the first call is cancelled, one of the waiting calls resolves the DID instead
+    pub(crate) async fn local_resolve_shared(
         &self,
-        did: &str,
-        parts: &[&str],
-    ) -> Result<Document, DIDCacheError> {
-        // Match the DID method
-
-        match parts[1] {
-            "ethr" => {
-                let method = DIDEthr;
-
-                match method.resolve(DID::new::<str>(did).unwrap()).await {
-                    Ok(res) => Ok(res.document.into_document()),
-                    Err(e) => {
-                        error!("Error: {:?}", e);
-                        Err(DIDCacheError::DIDError(e.to_string()))
-                    }
-                }
+        did: &DID,
+        did_hash: &str,
+    ) -> Result<CacheEntry, DIDCacheError> {
+        let cell = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(did_hash.to_string())
+            .or_default()
+            .clone();
+
+        let result = cell
+            .get_or_init(|| async {
+                debug!("resolving did ({}) locally", did);
+                self.local_resolve(did).await
+            })
+            .await
+            .clone();
+
+        // Done, so later resolves start a new resolution (unless a newer one is already running)
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        if in_flight
+            .get(did_hash)
+            .is_some_and(|current| Arc::ptr_eq(current, &cell))
+        {
+            in_flight.remove(did_hash);
+        }
+
+        result
+    }
+
+    /// Resolves a DID to a DID Document, with its resolution metadata
+    /// Dispatches to the [MethodResolver] registered for the DID's method
+    pub(crate) async fn local_resolve(&self, did: &DID) -> Result<CacheEntry, DIDCacheError> {
+        let method = did.method_name();
+        // A configured web resolver (or a replaced built-in method) doesn't need the network
+        if self.config.offline
+            && _requires_network(method)
+            && !(method == "web" && self.config.web_resolver.is_some())
+            && !self.config.methods.contains_key(method)
+        {
+            return Err(DIDCacheError::OfflineMethodUnsupported(method.to_string()));
+        }
+
+        match self.methods.get(method) {
+            Some(resolver) => resolver.resolve_with_metadata(did).await,
+            None => Err(DIDCacheError::UnsupportedMethod(method.to_string())),
+        }
+    }
+}
+
+/// Builds the method registry: the built-in methods, then the configured custom methods
+/// (which replace a built-in method of the same name)
+pub(crate) fn registry(
+    config: &ClientConfig,
+    http_client: &reqwest::Client,
+    web_resolver: Arc<dyn WebResolver>,
+) -> HashMap<String, Arc<dyn MethodResolver>> {
+    let builtins: [(&str, Arc<dyn MethodResolver>); 7] = [
+        (
+            "dht",
+            Arc::new(DhtMethod {
+                client: http_client.clone(),
+                gateway: config.dht_gateway.clone(),
+            }),
+        ),
+        (
+            "ethr",
+            Arc::new(EthrMethod {
+                client: http_client.clone(),
+                ethr_rpc: config.ethr_rpc.clone(),
+            }),
+        ),
+        ("jwk", Arc::new(JwkMethod)),
+        ("key", Arc::new(KeyMethod)),
+        ("peer", Arc::new(PeerMethod)),
+        ("pkh", Arc::new(PkhMethod)),
+        (
+            "web",
+            Arc::new(WebMethod {
+                web_resolver,
+                path_overrides: config.web_path_overrides.clone(),
+            }),
+        ),
+    ];
+
+    builtins
+        .into_iter()
+        .map(|(name, method)| (name.to_string(), method))
+        .chain(config.methods.clone())
+        .collect()
+}
+
+/// did:dht, read through the configured gateway
+#[derive(Debug)]
+struct DhtMethod {
+    client: reqwest::Client,
+    gateway: String,
+}
+
+#[async_trait]
+impl MethodResolver for DhtMethod {
+    async fn resolve(&self, did: &DID) -> Result<Document, DIDCacheError> {
+        self.resolve_with_metadata(did).await.map(|entry| entry.doc)
+    }
+
+    async fn resolve_with_metadata(&self, did: &DID) -> Result<CacheEntry, DIDCacheError> {
+        dht::resolve(
+            &self.client,
+            did.as_str(),
+            did.method_specific_id(),
+            &self.gateway,
+        )
+        .await
+    }
+}
+
+/// did:ethr, checked in the registry if JSON-RPC endpoints are configured
+#[derive(Debug)]
+struct EthrMethod {
+    client: reqwest::Client,
+    ethr_rpc: HashMap<String, String>,
+}
+
+#[async_trait]
+impl MethodResolver for EthrMethod {
+    async fn resolve(&self, did: &DID) -> Result<Document, DIDCacheError> {
+        self.resolve_with_metadata(did).await.map(|entry| entry.doc)
+    }
+
+    async fn resolve_with_metadata(&self, did: &DID) -> Result<CacheEntry, DIDCacheError> {
+        ethr::resolve(&self.client, &self.ethr_rpc, did).await
+    }
+}
+
+/// did:jwk
+#[derive(Debug)]
+struct JwkMethod;
+
+#[async_trait]
+impl MethodResolver for JwkMethod {
+    async fn resolve(&self, did: &DID) -> Result<Document, DIDCacheError> {
+        self.resolve_with_metadata(did).await.map(|entry| entry.doc)
+    }
+
+    async fn resolve_with_metadata(&self, did: &DID) -> Result<CacheEntry, DIDCacheError> {
+        let id = did.method_specific_id();
+        if let Some(doc) = jwk::x25519_document(did.as_str(), id) {
+            let mut entry = CacheEntry::from(doc?);
+            entry.resolution_metadata.content_type = Some("application/did+ld+json".into());
+            return Ok(entry);
+        }
+
+        match DIDJWK.resolve(did).await {
+            Ok(res) => {
+                // SSI Library uses the key for every relationship, whatever the key type
+                let mut doc = res.document.into_document();
+                jwk::restrict_relationships(&mut doc, id);
+                Ok(CacheEntry::new(doc, &res.metadata, &res.document_metadata))
             }
-            "jwk" => {
-                let method = DIDJWK;
-
-                match method.resolve(DID::new::<str>(did).unwrap()).await {
-                    Ok(res) => Ok(res.document.into_document()),
-                    Err(e) => {
-                        error!("Error: {:?}", e);
-                        Err(DIDCacheError::DIDError(e.to_string()))
-                    }
-                }
+            Err(e) => {
+                error!("Error: {:?}", e);
+                Err(_from_resolution_error("jwk", e))
             }
-            "key" => {
-                let method = DIDKey;
-
-                match method.resolve(DID::new::<str>(did).unwrap()).await {
-                    Ok(res) => {
-                        // SSI Library isn't populating keyAgreement, manually add it if it's empty
-                        if res
-                            .document
-                            .verification_relationships
-                            .key_agreement
-                            .is_empty()
-                        {
-                            let key_id =
-                                res.document.verification_relationships.authentication[0].clone();
-
-                            let mut doc = res.document.into_document();
-                            doc.verification_relationships.key_agreement.push(key_id);
-
-                            Ok(doc)
-                        } else {
-                            Ok(res.document.into_document())
-                        }
-                    }
-                    Err(e) => {
-                        error!("Error: {:?}", e);
-                        Err(DIDCacheError::DIDError(e.to_string()))
-                    }
+        }
+    }
+}
+
+/// did:key
+#[derive(Debug)]
+struct KeyMethod;
+
+#[async_trait]
+impl MethodResolver for KeyMethod {
+    async fn resolve(&self, did: &DID) -> Result<Document, DIDCacheError> {
+        self.resolve_with_metadata(did).await.map(|entry| entry.doc)
+    }
+
+    async fn resolve_with_metadata(&self, did: &DID) -> Result<CacheEntry, DIDCacheError> {
+        // Catch unsupported key types before SSI resolves them into an unusable document
+        let codec = key::validate_key(did.method_specific_id())?;
+
+        match DIDKey.resolve(did).await {
+            Ok(res) => {
+                // SSI Library isn't populating keyAgreement, manually add it if it's empty
+                if key::supports_key_agreement(codec)
+                    && res
+                        .document
+                        .verification_relationships
+                        .key_agreement
+                        .is_empty()
+                {
+                    let key_id = res.document.verification_relationships.authentication[0].clone();
+
+                    let mut doc = res.document.into_document();
+                    doc.verification_relationships.key_agreement.push(key_id);
+
+                    Ok(CacheEntry::new(doc, &res.metadata, &res.document_metadata))
+                } else {
+                    Ok(CacheEntry::new(
+                        res.document.into_document(),
+                        &res.metadata,
+                        &res.document_metadata,
+                    ))
                 }
             }
-            "peer" => {
-                let method = DIDPeer;
-
-                match method.resolve(DID::new::<str>(did).unwrap()).await {
-                    Ok(res) => {
-                        // DID Peer will resolve to MultiKey, which confuses key matching
-                        // Expand the keys to raw keys
-                        DIDPeer::expand_keys(&res.document.into_document())
-                            .await
-                            .map_err(|e| DIDCacheError::DIDError(e.to_string()))
-                    }
-                    Err(e) => {
-                        error!("Error: {:?}", e);
-                        Err(DIDCacheError::DIDError(e.to_string()))
-                    }
-                }
+            Err(e) => {
+                // The key already parsed, so the SSI library doesn't support this key type
+                error!("Error: {:?}", e);
+                Err(DIDCacheError::UnsupportedKeyType(codec))
             }
-            "pkh" => {
-                let method = DIDPKH;
-
-                match method.resolve(DID::new::<str>(did).unwrap()).await {
-                    Ok(res) => Ok(res.document.into_document()),
-                    Err(e) => {
-                        error!("Error: {:?}", e);
-                        Err(DIDCacheError::DIDError(e.to_string()))
-                    }
-                }
+        }
+    }
+}
+
+/// did:peer
+#[derive(Debug)]
+struct PeerMethod;
+
+#[async_trait]
+impl MethodResolver for PeerMethod {
+    async fn resolve(&self, did: &DID) -> Result<Document, DIDCacheError> {
+        self.resolve_with_metadata(did).await.map(|entry| entry.doc)
+    }
+
+    async fn resolve_with_metadata(&self, did: &DID) -> Result<CacheEntry, DIDCacheError> {
+        match DIDPeer.resolve(did).await {
+            Ok(res) => {
+                // DID Peer will resolve to MultiKey, which confuses key matching
+                // Expand the keys to raw keys
+                let doc = DIDPeer::expand_keys(&res.document.into_document())
+                    .await
+                    .map_err(|e| DIDCacheError::InvalidDid(e.to_string()))?;
+                Ok(CacheEntry::new(doc, &res.metadata, &res.document_metadata))
             }
-            "web" => {
-                let method = DIDWeb;
-
-                match method.resolve(DID::new::<str>(did).unwrap()).await {
-                    Ok(res) => Ok(res.document.into_document()),
-                    Err(e) => {
-                        error!("Error: {:?}", e);
-                        Err(DIDCacheError::DIDError(e.to_string()))
-                    }
-                }
+            Err(e) => {
+                error!("Error: {:?}", e);
+                Err(_from_resolution_error("peer", e))
+            }
+        }
+    }
+}
+
+/// did:pkh
+#[derive(Debug)]
+struct PkhMethod;
+
+#[async_trait]
+impl MethodResolver for PkhMethod {
+    async fn resolve(&self, did: &DID) -> Result<Document, DIDCacheError> {
+        self.resolve_with_metadata(did).await.map(|entry| entry.doc)
+    }
+
+    async fn resolve_with_metadata(&self, did: &DID) -> Result<CacheEntry, DIDCacheError> {
+        // Catch malformed or unsupported CAIP-10 account ids before they reach SSI
+        pkh::validate_account_id(did.method_specific_id())?;
+
+        // SSI types the verification methods per chain (EcdsaSecp256k1RecoveryMethod2020 for
+        // eip155 and bip122, Ed25519VerificationKey2018 + SolanaMethod2021 for solana)
+        match DIDPKH.resolve(did).await {
+            Ok(res) => Ok(CacheEntry::new(
+                res.document.into_document(),
+                &res.metadata,
+                &res.document_metadata,
+            )),
+            Err(e) => {
+                error!("Error: {:?}", e);
+                Err(_from_resolution_error("pkh", e))
             }
-            _ => Err(DIDCacheError::DIDError(format!(
-                "DID Method ({}) not supported",
-                parts[1]
-            ))),
         }
     }
 }
 
+/// did:web, fetched with the [WebResolver]
+#[derive(Debug)]
+struct WebMethod {
+    web_resolver: Arc<dyn WebResolver>,
+    path_overrides: HashMap<String, String>,
+}
+
+#[async_trait]
+impl MethodResolver for WebMethod {
+    async fn resolve(&self, did: &DID) -> Result<Document, DIDCacheError> {
+        self.resolve_with_metadata(did).await.map(|entry| entry.doc)
+    }
+
+    async fn resolve_with_metadata(&self, did: &DID) -> Result<CacheEntry, DIDCacheError> {
+        web::resolve(
+            self.web_resolver.as_ref(),
+            &self.path_overrides,
+            did.as_str(),
+            did.method_specific_id(),
+        )
+        .await
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{config, DIDCacheClient};
+    use crate::test_fixtures::{DID_JWK, DID_KEY, DID_PEER};
+    use crate::{config, errors::DIDCacheError, DIDCacheClient};
+    use ssi::{
+        dids::{DIDKey, DID, DIDJWK},
+        multicodec::{self, MultiEncodedBuf},
+        JWK,
+    };
 
     const DID_ETHR: &str = "did:ethr:0x1:0xb9c5714089478a327f09197987f16f9e5d936e8a";
-    const DID_JWK: &str= "did:jwk:eyJjcnYiOiJQLTI1NiIsImt0eSI6IkVDIiwieCI6ImFjYklRaXVNczNpOF91c3pFakoydHBUdFJNNEVVM3l6OTFQSDZDZEgyVjAiLCJ5IjoiX0tjeUxqOXZXTXB0bm1LdG00NkdxRHo4d2Y3NEk1TEtncmwyR3pIM25TRSJ9";
-    const DID_KEY: &str = "did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv";
-    const DID_PEER: &str = "did:peer:2.Vz6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv.EzQ3shQLqRUza6AMJFbPuMdvFRFWm1wKviQRnQSC1fScovJN4s.SeyJ0IjoiRElEQ29tbU1lc3NhZ2luZyIsInMiOnsidXJpIjoiaHR0cHM6Ly8xMjcuMC4wLjE6NzAzNyIsImEiOlsiZGlkY29tbS92MiJdLCJyIjpbXX19";
+    // BLS12-381 G2 test vector from the did:key spec
+    const DID_KEY_BLS12381_G2: &str = "did:key:zUC7EK3ZakmukHhuncwkbySmomv3FmrkmS36E4Ks5rsb6VQSRpoCrx6Hb8e2Nk6UvJFSdyw9NK1scFXJp21gNNYFjVWNgaqyGnkyhtagagCpQb5B7tagJu3HDbjQ8h5ypoHjwBb";
     const DID_PKH: &str =  "did:pkh:solana:4sGjMW1sUnHzSxGspuhpqLDx6wiyjNtZ:CKg5d12Jhpej1JqtmxLJgaFqqeYjxgPqToJ4LBdvG9Ev";
+    const DID_PKH_EIP155: &str = "did:pkh:eip155:1:0xb9c5714089478a327f09197987f16f9e5d936e8a";
+    const DID_PKH_BIP122: &str =
+        "did:pkh:bip122:000000000019d6689c085ae165831e93:128Lkh3S7CkDTBZ8W7BbpsN3YYizJMp8p6";
 
     #[tokio::test]
     async fn local_resolve_ethr() {
         let config = config::ClientConfigBuilder::default().build();
         let client = DIDCacheClient::new(config).await.unwrap();
 
-        let parts: Vec<&str> = DID_ETHR.split(':').collect();
-        let did_document = client.local_resolve(DID_ETHR, &parts).await.unwrap();
+        let did_document = client
+            .local_resolve(DID::new(DID_ETHR).unwrap())
+            .await
+            .unwrap()
+            .doc;
         let verification_relationships = did_document.verification_relationships;
 
         assert_eq!(did_document.id, DID_ETHR);
@@ -143,8 +430,11 @@ mod tests {
         let config = config::ClientConfigBuilder::default().build();
         let client = DIDCacheClient::new(config).await.unwrap();
 
-        let parts: Vec<&str> = DID_JWK.split(':').collect();
-        let did_document = client.local_resolve(DID_JWK, &parts).await.unwrap();
+        let did_document = client
+            .local_resolve(DID::new(DID_JWK).unwrap())
+            .await
+            .unwrap()
+            .doc;
         let verification_relationships = did_document.verification_relationships;
 
         assert_eq!(did_document.id, DID_JWK);
@@ -162,13 +452,108 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn resolution_errors() {
+        let client = DIDCacheClient::new(config::ClientConfigBuilder::default().build())
+            .await
+            .unwrap();
+
+        let did = "did:jwk:not-a-jwk";
+        assert!(matches!(
+            client.local_resolve(DID::new(did).unwrap()).await,
+            Err(DIDCacheError::InvalidDid(_))
+        ));
+
+        assert!(matches!(
+            super::_from_resolution_error("jwk", ssi::dids::resolution::Error::NotFound),
+            DIDCacheError::NotFound(_)
+        ));
+        assert!(matches!(
+            super::_from_resolution_error(
+                "ethr",
+                ssi::dids::resolution::Error::internal("connection refused")
+            ),
+            DIDCacheError::TransportError(_)
+        ));
+        assert!(matches!(
+            super::_from_resolution_error(
+                "jwk",
+                ssi::dids::resolution::Error::internal("unsupported curve")
+            ),
+            DIDCacheError::InvalidDid(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn local_resolve_jwk_okp() {
+        let client = DIDCacheClient::new(config::ClientConfigBuilder::default().build())
+            .await
+            .unwrap();
+
+        // Signing only
+        let did = DIDJWK::generate(&JWK::generate_ed25519().unwrap().to_public()).to_string();
+        let doc = client
+            .local_resolve(DID::new(&did).unwrap())
+            .await
+            .unwrap()
+            .doc;
+        let relationships = doc.verification_relationships;
+        assert_eq!(relationships.authentication.len(), 1);
+        assert_eq!(relationships.assertion_method.len(), 1);
+        assert!(relationships.key_agreement.is_empty());
+        assert!(doc.verification_method[0].properties["publicKeyMultibase"]
+            .as_str()
+            .unwrap()
+            .starts_with("z6Mk"));
+
+        // Key agreement only
+        let mut jwk = JWK::generate_ed25519().unwrap().to_public();
+        let ssi::jwk::Params::OKP(params) = &mut jwk.params else {
+            panic!("Ed25519 is an OKP key");
+        };
+        params.curve = "X25519".into();
+        let did = DIDJWK::generate(&jwk).to_string();
+        let doc = client
+            .local_resolve(DID::new(&did).unwrap())
+            .await
+            .unwrap()
+            .doc;
+        let relationships = doc.verification_relationships;
+        assert!(relationships.authentication.is_empty());
+        assert!(relationships.assertion_method.is_empty());
+        assert!(relationships.capability_invocation.is_empty());
+        assert!(relationships.capability_delegation.is_empty());
+        assert_eq!(relationships.key_agreement.len(), 1);
+        assert!(doc.verification_method[0].properties["publicKeyMultibase"]
+            .as_str()
+            .unwrap()
+            .starts_with("z6LS"));
+
+        // EC keys can be used for anything
+        for jwk in [JWK::generate_secp256k1(), JWK::generate_p384()] {
+            let did = DIDJWK::generate(&jwk.to_public()).to_string();
+            let relationships = client
+                .local_resolve(DID::new(&did).unwrap())
+                .await
+                .unwrap()
+                .doc
+                .verification_relationships;
+            assert_eq!(relationships.authentication.len(), 1);
+            assert_eq!(relationships.key_agreement.len(), 1);
+        }
+    }
+
     #[tokio::test]
     async fn local_resolve_key() {
         let config = config::ClientConfigBuilder::default().build();
         let client = DIDCacheClient::new(config).await.unwrap();
 
         let parts: Vec<&str> = DID_KEY.split(':').collect();
-        let did_document = client.local_resolve(DID_KEY, &parts).await.unwrap();
+        let did_document = client
+            .local_resolve(DID::new(DID_KEY).unwrap())
+            .await
+            .unwrap()
+            .doc;
         let verification_relationships = did_document.verification_relationships;
 
         assert_eq!(did_document.id, DID_KEY);
@@ -182,13 +567,163 @@ mod tests {
             parts.last().unwrap().to_string()
         );
     }
+
+    #[tokio::test]
+    async fn local_resolve_key_curves() {
+        let config = config::ClientConfigBuilder::default().build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        for (curve, jwk) in [
+            ("Ed25519", JWK::generate_ed25519().unwrap()),
+            ("secp256k1", JWK::generate_secp256k1()),
+            ("P-256", JWK::generate_p256()),
+            ("P-384", JWK::generate_p384()),
+        ] {
+            let did = DIDKey::generate(&jwk).unwrap().to_string();
+            let parts: Vec<&str> = did.split(':').collect();
+            let did_document = client
+                .local_resolve(DID::new(&did).unwrap())
+                .await
+                .unwrap()
+                .doc;
+            let verification_relationships = did_document.verification_relationships;
+
+            assert_eq!(did_document.id.as_str(), did, "{}", curve);
+            assert_eq!(verification_relationships.authentication.len(), 1);
+            assert_eq!(verification_relationships.assertion_method.len(), 1);
+            assert_eq!(verification_relationships.key_agreement.len(), 1);
+            assert_eq!(did_document.verification_method.len(), 1);
+            assert_eq!(
+                did_document.verification_method.first().unwrap().properties["publicKeyMultibase"],
+                parts.last().unwrap().to_string(),
+                "{}",
+                curve
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn local_resolve_key_secp256k1_p384_jwk() {
+        // did:key specification test vectors, and freshly generated keys
+        let mut dids = vec![
+            (
+                "secp256k1",
+                "did:key:zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme".to_string(),
+                None,
+            ),
+            (
+                "P-384",
+                "did:key:z82Lm1MpAkeJcix9K8TMiLd5NMAhnwkjjCBeWHXyu3U4oT2MVJJKXkcVBgjGhnLBn2Kaau9"
+                    .to_string(),
+                None,
+            ),
+        ];
+        for (curve, jwk) in [
+            ("secp256k1", JWK::generate_secp256k1()),
+            ("P-384", JWK::generate_p384()),
+        ] {
+            let did = DIDKey::generate(&jwk).unwrap().to_string();
+            dids.push((curve, did, Some(jwk.to_public())));
+        }
+
+        // publicKeyJwk is derived when the DID Document is canonicalized
+        let config = config::ClientConfigBuilder::default()
+            .with_canonicalize(true)
+            .build();
+        let canonical_client = DIDCacheClient::new(config).await.unwrap();
+        let client = DIDCacheClient::new(config::ClientConfigBuilder::default().build())
+            .await
+            .unwrap();
+
+        for (curve, did, expected_jwk) in dids {
+            let parts: Vec<&str> = did.split(':').collect();
+            let vm = client
+                .local_resolve(DID::new(&did).unwrap())
+                .await
+                .unwrap()
+                .doc
+                .verification_method[0]
+                .clone();
+            assert_eq!(vm.properties["publicKeyMultibase"], parts[2], "{}", curve);
+
+            let vm = canonical_client
+                .resolve(&did)
+                .await
+                .unwrap()
+                .doc
+                .verification_method[0]
+                .clone();
+            assert_eq!(vm.type_, "JsonWebKey2020", "{}", curve);
+            let jwk: JWK = serde_json::from_value(vm.properties["publicKeyJwk"].clone()).unwrap();
+            assert_eq!(vm.properties["publicKeyJwk"]["crv"], curve);
+            // The JWK encodes back to the same did:key
+            assert_eq!(DIDKey::generate(&jwk).unwrap().as_str(), did, "{}", curve);
+            if let Some(expected_jwk) = expected_jwk {
+                assert_eq!(jwk, expected_jwk, "{}", curve);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn local_resolve_key_bls12381_g2() {
+        let config = config::ClientConfigBuilder::default().build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        let parts: Vec<&str> = DID_KEY_BLS12381_G2.split(':').collect();
+        let did_document = client
+            .local_resolve(DID::new(DID_KEY_BLS12381_G2).unwrap())
+            .await
+            .unwrap()
+            .doc;
+        let verification_relationships = did_document.verification_relationships;
+
+        assert_eq!(verification_relationships.assertion_method.len(), 1);
+        // Signature only key
+        assert!(verification_relationships.key_agreement.is_empty());
+        assert_eq!(
+            did_document.verification_method.first().unwrap().properties["publicKeyMultibase"],
+            parts.last().unwrap().to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn local_resolve_key_unsupported_curves() {
+        let config = config::ClientConfigBuilder::default().build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        for (curve, key_codec, len) in [
+            ("P-521", multicodec::P521_PUB, 67),
+            ("X25519", multicodec::X25519_PUB, 32),
+            ("BLS12-381 G1", multicodec::BLS12_381_G1_PUB, 48),
+        ] {
+            let encoded = MultiEncodedBuf::encode_bytes(key_codec, &vec![2; len]);
+            let did = [
+                "did:key:",
+                &multibase::encode(multibase::Base::Base58Btc, encoded.as_bytes()),
+            ]
+            .concat();
+
+            assert!(
+                matches!(
+                    client.local_resolve(DID::new(&did).unwrap()).await,
+                    Err(DIDCacheError::UnsupportedKeyType(codec)) if codec == key_codec
+                ),
+                "{} should not be supported",
+                curve
+            );
+        }
+    }
+
     #[tokio::test]
     async fn local_resolve_peer() {
         let config = config::ClientConfigBuilder::default().build();
         let client = DIDCacheClient::new(config).await.unwrap();
 
-        let parts: Vec<&str> = DID_PEER.split(':').collect();
-        let did_document = client.local_resolve(DID_PEER, &parts).await.unwrap();
+        let did_document = client
+            .local_resolve(DID::new(DID_PEER).unwrap())
+            .await
+            .unwrap()
+            .doc;
         let verification_relationships = did_document.verification_relationships;
         let verification_method = did_document.verification_method;
         let service = did_document.service;
@@ -229,7 +764,11 @@ mod tests {
         let client = DIDCacheClient::new(config).await.unwrap();
         let parts: Vec<&str> = DID_PKH.split(':').collect();
 
-        let did_document = client.local_resolve(DID_PKH, &parts).await.unwrap();
+        let did_document = client
+            .local_resolve(DID::new(DID_PKH).unwrap())
+            .await
+            .unwrap()
+            .doc;
         let verification_relationships = did_document.verification_relationships;
         let verification_method = did_document.verification_method;
         let vm_properties_first = verification_method.first().unwrap().properties.clone();
@@ -241,6 +780,14 @@ mod tests {
         assert_eq!(verification_relationships.assertion_method.len(), 2);
 
         assert_eq!(verification_method.len(), 2);
+        assert_eq!(
+            verification_method.first().unwrap().type_,
+            "Ed25519VerificationKey2018"
+        );
+        assert_eq!(
+            verification_method.last().unwrap().type_,
+            "SolanaMethod2021"
+        );
         assert_eq!(
             vm_properties_first["publicKeyBase58"],
             parts.last().unwrap().to_string()
@@ -255,4 +802,82 @@ mod tests {
         );
         assert!(vm_properties_last["publicKeyJwk"].is_object(),);
     }
+
+    #[tokio::test]
+    async fn local_resolve_pkh_eip155() {
+        let config = config::ClientConfigBuilder::default().build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        let did_document = client
+            .local_resolve(DID::new(DID_PKH_EIP155).unwrap())
+            .await
+            .unwrap()
+            .doc;
+        let verification_method = did_document.verification_method;
+
+        assert_eq!(did_document.id, DID_PKH_EIP155);
+        assert_eq!(verification_method.len(), 1);
+        assert_eq!(
+            verification_method[0].id,
+            format!("{}#blockchainAccountId", DID_PKH_EIP155)
+        );
+        assert_eq!(
+            verification_method[0].type_,
+            "EcdsaSecp256k1RecoveryMethod2020"
+        );
+        assert_eq!(
+            verification_method[0].properties["blockchainAccountId"],
+            "eip155:1:0xb9c5714089478a327f09197987f16f9e5d936e8a"
+        );
+        assert_eq!(
+            did_document.verification_relationships.authentication.len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn local_resolve_pkh_bip122() {
+        let config = config::ClientConfigBuilder::default().build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        let did_document = client
+            .local_resolve(DID::new(DID_PKH_BIP122).unwrap())
+            .await
+            .unwrap()
+            .doc;
+        let verification_method = did_document.verification_method;
+
+        assert_eq!(did_document.id, DID_PKH_BIP122);
+        assert_eq!(verification_method.len(), 1);
+        assert_eq!(
+            verification_method[0].id,
+            format!("{}#blockchainAccountId", DID_PKH_BIP122)
+        );
+        // Bitcoin addresses are secp256k1 keys, recovered from the signature like Ethereum
+        assert_eq!(
+            verification_method[0].type_,
+            "EcdsaSecp256k1RecoveryMethod2020"
+        );
+        assert_eq!(
+            verification_method[0].properties["blockchainAccountId"],
+            "bip122:000000000019d6689c085ae165831e93:128Lkh3S7CkDTBZ8W7BbpsN3YYizJMp8p6"
+        );
+        assert_eq!(
+            did_document
+                .verification_relationships
+                .assertion_method
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn local_resolve_pkh_unsupported_namespace() {
+        let config = config::ClientConfigBuilder::default().build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+        let did = "did:pkh:cosmos:cosmoshub-3:cosmos1t2uflqwqe0fsj0shcfkrvpukewcw40yjj6hdc0";
+
+        let result = client.local_resolve(DID::new(did).unwrap()).await;
+        assert!(matches!(result, Err(DIDCacheError::InvalidDid(_))));
+    }
 }
@@ -0,0 +1,198 @@
+//! did:pkh CAIP-10 account id validation
+//!
+//! The SSI library accepts loosely formatted account ids for some namespaces, and rejects
+//! unsupported namespaces with a generic error. Validating up front gives a clear error
+//! rather than a malformed DID Document.
+//!
+//! See: <https://github.com/ChainAgnostic/CAIPs/blob/main/CAIPs/caip-10.md>
+
+use crate::errors::DIDCacheError;
+use multibase::Base;
+
+/// did:pkh namespaces that can be resolved
+pub(crate) const SUPPORTED_NAMESPACES: [&str; 5] = ["eip155", "solana", "bip122", "tezos", "aleo"];
+
+/// Validates the CAIP-10 account id of a did:pkh DID (everything after `did:pkh:`)
+/// Checks the generic CAIP-10 structure, then the namespace specific rules
+pub(crate) fn validate_account_id(account_id: &str) -> Result<(), DIDCacheError> {
+    let parts: Vec<&str> = account_id.split(':').collect();
+    if parts.len() != 3 {
+        return Err(DIDCacheError::InvalidDid(format!(
+            "did:pkh account id ({}) must be <namespace>:<reference>:<address>",
+            account_id
+        )));
+    }
+    let (namespace, reference, address) = (parts[0], parts[1], parts[2]);
+
+    // CAIP-2 namespace: [-a-z0-9]{3,8}
+    if !(3..=8).contains(&namespace.len())
+        || !namespace
+            .chars()
+            .all(|c| c == '-' || c.is_ascii_lowercase() || c.is_ascii_digit())
+    {
+        return Err(DIDCacheError::InvalidDid(format!(
+            "did:pkh namespace ({}) is invalid",
+            namespace
+        )));
+    }
+
+    // CAIP-2 reference: [-_a-zA-Z0-9]{1,32}
+    if !(1..=32).contains(&reference.len())
+        || !reference
+            .chars()
+            .all(|c| c == '-' || c == '_' || c.is_ascii_alphanumeric())
+    {
+        return Err(DIDCacheError::InvalidDid(format!(
+            "did:pkh chain reference ({}) is invalid",
+            reference
+        )));
+    }
+
+    // CAIP-10 address: [-.%a-zA-Z0-9]{1,128}
+    if !(1..=128).contains(&address.len())
+        || !address
+            .chars()
+            .all(|c| c == '-' || c == '.' || c == '%' || c.is_ascii_alphanumeric())
+    {
+        return Err(DIDCacheError::InvalidDid(format!(
+            "did:pkh account address ({}) is invalid",
+            address
+        )));
+    }
+
+    match namespace {
+        "eip155" => _validate_eip155(reference, address),
+        "solana" => _validate_solana(reference, address),
+        "bip122" => _validate_bip122(reference, address),
+        // Validated by the SSI library
+        "tezos" | "aleo" => Ok(()),
+        _ => Err(DIDCacheError::InvalidDid(format!(
+            "did:pkh namespace ({}) is not supported. Supported namespaces: {}",
+            namespace,
+            SUPPORTED_NAMESPACES.join(", ")
+        ))),
+    }
+}
+
+/// eip155: Decimal chain id, 0x prefixed 20 byte hex address
+fn _validate_eip155(reference: &str, address: &str) -> Result<(), DIDCacheError> {
+    if !reference.chars().all(|c| c.is_ascii_digit()) {
+        return Err(DIDCacheError::InvalidDid(format!(
+            "did:pkh eip155 chain id ({}) must be decimal",
+            reference
+        )));
+    }
+
+    match address.strip_prefix("0x") {
+        Some(hex) if hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit()) => Ok(()),
+        _ => Err(DIDCacheError::InvalidDid(format!(
+            "did:pkh eip155 address ({}) must be 0x followed by 40 hex characters",
+            address
+        ))),
+    }
+}
+
+/// solana: Truncated genesis hash reference, base58 encoded 32 byte ed25519 public key address
+fn _validate_solana(reference: &str, address: &str) -> Result<(), DIDCacheError> {
+    if reference.len() != 32 || Base::Base58Btc.decode(reference).is_err() {
+        return Err(DIDCacheError::InvalidDid(format!(
+            "did:pkh solana reference ({}) must be a 32 character base58 genesis hash",
+            reference
+        )));
+    }
+
+    match Base::Base58Btc.decode(address) {
+        Ok(key) if key.len() == 32 => Ok(()),
+        _ => Err(DIDCacheError::InvalidDid(format!(
+            "did:pkh solana address ({}) must be a base58 encoded 32 byte public key",
+            address
+        ))),
+    }
+}
+
+/// bip122: 32 hex character genesis block hash reference, base58 or bech32 address
+fn _validate_bip122(reference: &str, address: &str) -> Result<(), DIDCacheError> {
+    if reference.len() != 32 || !reference.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(DIDCacheError::InvalidDid(format!(
+            "did:pkh bip122 reference ({}) must be 32 hex characters",
+            reference
+        )));
+    }
+
+    if !(26..=62).contains(&address.len()) || !address.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(DIDCacheError::InvalidDid(format!(
+            "did:pkh bip122 address ({}) is invalid",
+            address
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_account_id;
+
+    #[test]
+    fn solana() {
+        assert!(validate_account_id(
+            "solana:4sGjMW1sUnHzSxGspuhpqLDx6wiyjNtZ:CKg5d12Jhpej1JqtmxLJgaFqqeYjxgPqToJ4LBdvG9Ev"
+        )
+        .is_ok());
+
+        // Address isn't a 32 byte key
+        assert!(
+            validate_account_id("solana:4sGjMW1sUnHzSxGspuhpqLDx6wiyjNtZ:CKg5d12Jhpej").is_err()
+        );
+        // Reference isn't base58
+        assert!(validate_account_id(
+            "solana:0sGjMW1sUnHzSxGspuhpqLDx6wiyjNtZ:CKg5d12Jhpej1JqtmxLJgaFqqeYjxgPqToJ4LBdvG9Ev"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn eip155() {
+        assert!(validate_account_id("eip155:1:0xb9c5714089478a327f09197987f16f9e5d936e8a").is_ok());
+
+        assert!(validate_account_id("eip155:1:b9c5714089478a327f09197987f16f9e5d936e8a").is_err());
+        assert!(validate_account_id("eip155:1:0xb9c5714089478a327f").is_err());
+        assert!(
+            validate_account_id("eip155:main:0xb9c5714089478a327f09197987f16f9e5d936e8a").is_err()
+        );
+    }
+
+    #[test]
+    fn bip122() {
+        assert!(validate_account_id(
+            "bip122:000000000019d6689c085ae165831e93:128Lkh3S7CkDTBZ8W7BbpsN3YYizJMp8p6"
+        )
+        .is_ok());
+
+        assert!(validate_account_id(
+            "bip122:000000000019d6689c085ae1:128Lkh3S7CkDTBZ8W7BbpsN3YYizJMp8p6"
+        )
+        .is_err());
+        assert!(validate_account_id("bip122:000000000019d6689c085ae165831e93:128Lkh").is_err());
+    }
+
+    #[test]
+    fn unsupported_namespace() {
+        let err =
+            validate_account_id("cosmos:cosmoshub-3:cosmos1t2uflqwqe0fsj0shcfkrvpukewcw40yjj6hdc0")
+                .unwrap_err();
+        assert!(err.to_string().contains("not supported"));
+    }
+
+    #[test]
+    fn malformed_caip10() {
+        assert!(validate_account_id("eip155:0xb9c5714089478a327f09197987f16f9e5d936e8a").is_err());
+        assert!(
+            validate_account_id("EIP155:1:0xb9c5714089478a327f09197987f16f9e5d936e8a").is_err()
+        );
+        assert!(
+            validate_account_id("eip155:1:0xb9c5714089478a327f09197987f16f9e5d936e8a:extra")
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,396 @@
+//! did:web resolution
+//!
+//! The did.json is fetched with the configured [WebResolver] (by default the client's HTTP
+//! client, so configured proxies are used), and parsed by the SSI library.
+//!
+//! Hosts that serve their DID Documents somewhere else than the spec says can be given a path
+//! override (see [with_web_path_override](crate::config::ClientConfigBuilder::with_web_path_override)).
+//!
+//! See: <https://w3c-ccg.github.io/did-method-web/#read-resolve>
+
+use crate::{
+    errors::DIDCacheError,
+    metadata::{CacheEntry, ResolutionMetadata},
+    web_resolver::WebResolver,
+};
+use reqwest::StatusCode;
+use serde_json::Value;
+use ssi::dids::{document::representation::MediaType, Document};
+use std::{collections::HashMap, time::Instant};
+
+/// Returns the did.json URL of a did:web method specific id (everything after `did:web:`)
+/// e.g. `example.com:user:alice` -> `https://example.com/user/alice/did.json`
+/// A port is percent encoded (`localhost%3A8080` or `localhost%3a8080`)
+/// localhost uses http, for testing
+/// path_overrides: Base path per host (lowercase `host[:port]`), see
+/// [with_web_path_override](crate::config::ClientConfigBuilder::with_web_path_override)
+pub(crate) fn did_web_url(
+    id: &str,
+    path_overrides: &HashMap<String, String>,
+) -> Result<String, DIDCacheError> {
+    let mut parts = id.split(':');
+    let domain = match parts.next() {
+        Some(domain) if !domain.is_empty() => match domain.split_once('%') {
+            Some((host, port)) if port.len() > 2 && port[..2].eq_ignore_ascii_case("3A") => {
+                format!("{}:{}", host, &port[2..])
+            }
+            Some(_) => {
+                return Err(DIDCacheError::InvalidDid(format!(
+                    "did:web ({}) domain name can only percent encode the port separator (%3A)",
+                    id
+                )))
+            }
+            None => domain.to_string(),
+        },
+        _ => {
+            return Err(DIDCacheError::InvalidDid(format!(
+                "did:web ({}) has no domain name",
+                id
+            )))
+        }
+    };
+
+    let path: Vec<&str> = parts.collect();
+    if path.iter().any(|segment| segment.is_empty()) {
+        return Err(DIDCacheError::InvalidDid(format!(
+            "did:web ({}) has an empty path segment",
+            id
+        )));
+    }
+    let path = match path_overrides.get(&domain.to_lowercase()) {
+        // The override replaces /.well-known, and is the parent of any DID path
+        Some(base) => base
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .chain(path)
+            .collect::<Vec<&str>>()
+            .join("/"),
+        None if path.is_empty() => ".well-known".to_string(),
+        None => path.join("/"),
+    };
+    let path = if path.is_empty() {
+        path
+    } else {
+        format!("{}/", path)
+    };
+
+    let proto = if domain.starts_with("localhost") {
+        "http"
+    } else {
+        "https"
+    };
+
+    Ok(format!("{}://{}/{}did.json", proto, domain, path))
+}
+
+/// Resolves a did:web by fetching its did.json with the [WebResolver]
+/// id: The method specific id (everything after `did:web:`)
+pub(crate) async fn resolve(
+    web_resolver: &dyn WebResolver,
+    path_overrides: &HashMap<String, String>,
+    did: &str,
+    id: &str,
+) -> Result<CacheEntry, DIDCacheError> {
+    let body = web_resolver
+        .fetch(&did_web_url(id, path_overrides)?)
+        .await?;
+
+    // A JSON-LD production has an @context, anything else is treated as application/did+json
+    let raw = serde_json::from_slice::<Value>(&body).ok();
+    let media_type = match &raw {
+        Some(value) if value.get("@context").is_some() => MediaType::JsonLd,
+        _ => MediaType::Json,
+    };
+    let doc = Document::from_bytes(media_type, &body)
+        .map_err(|e| {
+            DIDCacheError::InvalidDid(format!("did:web ({}) invalid DID document: {}", did, e))
+        })?
+        .into_document();
+
+    Ok(CacheEntry {
+        doc,
+        resolution_metadata: ResolutionMetadata {
+            content_type: Some(media_type.to_string()),
+            error: None,
+        },
+        document_metadata: Default::default(),
+        inserted_at: Instant::now(),
+        method: None,
+        method_name: None,
+        raw,
+    })
+}
+
+/// Checks the did.json of a did:web exists with a HEAD request
+/// Returns false if the host says it doesn't exist (404 or 410)
+/// Returns a TransportError if the host can't be reached or returns any other error
+pub(crate) async fn exists(
+    client: &reqwest::Client,
+    path_overrides: &HashMap<String, String>,
+    id: &str,
+) -> Result<bool, DIDCacheError> {
+    let url = did_web_url(id, path_overrides)?;
+
+    let response = client
+        .head(&url)
+        .send()
+        .await
+        .map_err(|e| DIDCacheError::TransportError(format!("HEAD ({}) failed: {}", url, e)))?;
+
+    match response.status() {
+        status if status.is_success() => Ok(true),
+        StatusCode::NOT_FOUND | StatusCode::GONE => Ok(false),
+        status => Err(DIDCacheError::TransportError(format!(
+            "HEAD ({}) returned status ({})",
+            url, status
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::ClientConfigBuilder,
+        test_fixtures::{client, client_with, serve},
+        DIDCacheClient,
+    };
+    use ssi::dids::DID;
+
+    /// Serves a DID Document for `did`
+    /// Returns the port, and the requests (lowercased) as they are received
+    async fn _http_server(did: &str) -> (u16, impl std::future::Future<Output = String>) {
+        let mut server = serve("200 OK", serde_json::json!({ "id": did }).to_string(), None).await;
+        let request = async move { server.requests.recv().await.unwrap().to_lowercase() };
+        (server.port, request)
+    }
+
+    #[tokio::test]
+    async fn resolve_did_web() {
+        // Port 1 isn't listening, the DID is served by the test server
+        let (port, request) = _http_server("did:web:localhost%3A1").await;
+        let did = format!("did:web:localhost%3A{}", port);
+        let client = client().await;
+
+        let response = client.resolve(&did).await.unwrap();
+        assert_eq!(response.doc.id, "did:web:localhost%3A1");
+        assert_eq!(
+            response.resolution_metadata.content_type.as_deref(),
+            Some("application/did+json")
+        );
+        assert!(request
+            .await
+            .starts_with("get /.well-known/did.json http/1.1"));
+    }
+
+    #[tokio::test]
+    async fn resolve_did_web_through_proxy() {
+        let did = "did:web:localhost%3A1";
+        let (port, request) = _http_server(did).await;
+        let client = client_with(
+            ClientConfigBuilder::default()
+                .with_http_proxy(&format!("http://127.0.0.1:{}", port))
+                .with_proxy_basic_auth("user", "pass"),
+        )
+        .await;
+
+        // Port 1 isn't listening, so the request must have gone through the proxy
+        let response = client.resolve(did).await.unwrap();
+        assert_eq!(response.doc.id, did);
+
+        let request = request.await;
+        assert!(request.starts_with("get http://localhost:1/.well-known/did.json http/1.1"));
+        // base64("user:pass")
+        assert!(request.contains("proxy-authorization: basic dxnlcjpwyxnz"));
+    }
+
+    #[tokio::test]
+    async fn invalid_proxy() {
+        let config = ClientConfigBuilder::default()
+            .with_https_proxy("not a url")
+            .build();
+        assert!(matches!(
+            DIDCacheClient::new(config).await,
+            Err(DIDCacheError::ConfigError(msg)) if msg.starts_with("Invalid https proxy")
+        ));
+    }
+
+    #[test]
+    fn urls() {
+        let url = |id: &str| did_web_url(id, &HashMap::new());
+        assert_eq!(
+            url("example.com").unwrap(),
+            "https://example.com/.well-known/did.json"
+        );
+        assert_eq!(
+            url("example.com:alice").unwrap(),
+            "https://example.com/alice/did.json"
+        );
+        assert_eq!(
+            url("example.com:user:alice").unwrap(),
+            "https://example.com/user/alice/did.json"
+        );
+        assert_eq!(
+            url("localhost%3A8080").unwrap(),
+            "http://localhost:8080/.well-known/did.json"
+        );
+        assert_eq!(
+            url("example.com%3a8443:user:alice").unwrap(),
+            "https://example.com:8443/user/alice/did.json"
+        );
+        // Percent encoded path segments are kept as they are
+        assert_eq!(
+            url("example.com:user:alice%20smith").unwrap(),
+            "https://example.com/user/alice%20smith/did.json"
+        );
+        assert!(url("").is_err());
+        assert!(url("example.com%3A").is_err());
+        assert!(url("example%2Ecom").is_err());
+        assert!(url("example.com::alice").is_err());
+        // A trailing colon is an empty path segment
+        assert!(url("example.com:alice:").is_err());
+    }
+
+    #[test]
+    fn path_override_urls() {
+        let overrides = HashMap::from([
+            ("example.com".to_string(), "/dids/".to_string()),
+            ("example.org".to_string(), "/".to_string()),
+            ("localhost:8080".to_string(), "api/v1/dids".to_string()),
+        ]);
+        let url = |id: &str| did_web_url(id, &overrides).unwrap();
+
+        assert_eq!(url("example.com"), "https://example.com/dids/did.json");
+        assert_eq!(
+            url("example.com:user:alice"),
+            "https://example.com/dids/user/alice/did.json"
+        );
+        // The root DID moves to /did.json, DID paths stay where the spec puts them
+        assert_eq!(url("example.org"), "https://example.org/did.json");
+        assert_eq!(
+            url("example.org:alice"),
+            "https://example.org/alice/did.json"
+        );
+        // Matched with the port, either case of the percent encoded separator
+        assert_eq!(
+            url("localhost%3a8080"),
+            "http://localhost:8080/api/v1/dids/did.json"
+        );
+        assert_eq!(
+            url("localhost%3A8081"),
+            "http://localhost:8081/.well-known/did.json"
+        );
+        // Only the host, not its subdomains
+        assert_eq!(
+            url("www.example.com"),
+            "https://www.example.com/.well-known/did.json"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_with_path_override() {
+        let did = "did:web:Example.com";
+        let resolver = HashMap::from([(
+            "https://example.com/did.json".to_string(),
+            serde_json::json!({ "id": "did:web:example.com" })
+                .to_string()
+                .into_bytes(),
+        )]);
+        let client = DIDCacheClient::new(
+            ClientConfigBuilder::default()
+                .with_web_resolver(std::sync::Arc::new(resolver))
+                .with_web_path_override("EXAMPLE.com", "/")
+                .build(),
+        )
+        .await
+        .unwrap();
+
+        let response = client.resolve(did).await.unwrap();
+        assert_eq!(response.doc.id, "did:web:example.com");
+    }
+
+    #[tokio::test]
+    async fn local_resolve_port_and_path() {
+        let did = "did:web:example.com%3A8443:user:alice";
+        let doc = serde_json::json!({ "id": did }).to_string().into_bytes();
+        let resolver = std::collections::HashMap::from([(
+            "https://example.com:8443/user/alice/did.json".to_string(),
+            doc,
+        )]);
+        let client = DIDCacheClient::new(
+            ClientConfigBuilder::default()
+                .with_web_resolver(std::sync::Arc::new(resolver))
+                .build(),
+        )
+        .await
+        .unwrap();
+
+        let entry = client.local_resolve(DID::new(did).unwrap()).await.unwrap();
+        assert_eq!(entry.doc.id, did);
+    }
+
+    #[tokio::test]
+    async fn raw_keeps_fetched_json() {
+        let did = "did:web:example.com";
+        let body = r#"{"@context":["https://www.w3.org/ns/did/v1","https://w3id.org/security/suites/jws-2020/v1"],"id":"did:web:example.com","zeta":"kept","alsoKnownAs":["https://example.com"]}"#;
+        let resolver = std::collections::HashMap::from([(
+            "https://example.com/.well-known/did.json".to_string(),
+            body.as_bytes().to_vec(),
+        )]);
+        let client = DIDCacheClient::new(
+            ClientConfigBuilder::default()
+                .with_web_resolver(std::sync::Arc::new(resolver))
+                .build(),
+        )
+        .await
+        .unwrap();
+
+        // The @context (which doc doesn't have) and field order are kept, on cache hits too
+        for cache_hit in [false, true] {
+            let response = client.resolve(did).await.unwrap();
+            assert_eq!(response.cache_hit, cache_hit);
+            assert_eq!(serde_json::to_string(&response.raw).unwrap(), body);
+            assert!(!serde_json::to_string(&response.doc)
+                .unwrap()
+                .contains("@context"));
+        }
+    }
+
+    /// Counts fetches, and takes a while so concurrent resolves overlap
+    #[derive(Debug, Default)]
+    struct SlowCountingResolver(std::sync::atomic::AtomicUsize);
+
+    #[async_trait::async_trait]
+    impl WebResolver for SlowCountingResolver {
+        async fn fetch(&self, _: &str) -> Result<Vec<u8>, DIDCacheError> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            Ok(serde_json::json!({ "id": "did:web:example.com" })
+                .to_string()
+                .into_bytes())
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_resolves_fetch_once() {
+        let resolver = std::sync::Arc::new(SlowCountingResolver::default());
+        let client = DIDCacheClient::new(
+            ClientConfigBuilder::default()
+                .with_web_resolver(resolver.clone())
+                .build(),
+        )
+        .await
+        .unwrap();
+
+        let results =
+            futures_util::future::join_all((0..20).map(|_| client.resolve("did:web:example.com")))
+                .await;
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(resolver.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // Once finished, the DID is resolved again if it isn't cached
+        client.remove("did:web:example.com").await;
+        client.resolve("did:web:example.com").await.unwrap();
+        assert_eq!(resolver.0.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+}
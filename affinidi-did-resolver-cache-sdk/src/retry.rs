@@ -0,0 +1,264 @@
+//! Retry support for DID resolution
+//!
+//! Use [RetryPolicy] with [DIDCacheClient::resolve_with_retry] to automatically retry transient
+//! resolution failures (network timeouts, transport errors) with an exponential backoff.
+//! When the server says how long to wait (e.g. `rate_limited` with a retry after), that is used
+//! as the next delay instead.
+//!
+//! Example:
+//! ```ignore
+//! let policy = RetryPolicy::default()
+//!     .with_max_attempts(5)
+//!     .with_base_delay(Duration::from_millis(250));
+//! let response = client.resolve_with_retry("did:web:example.com", &policy).await?;
+//! ```
+
+use crate::{canonicalize_did, did_hash, errors::DIDCacheError, DIDCacheClient, ResolveResponse};
+use std::{fmt, sync::Arc, time::Duration};
+use tracing::{debug, warn};
+
+type RetryablePredicate = Arc<dyn Fn(&DIDCacheError) -> bool + Send + Sync>;
+
+/// Policy that controls how [DIDCacheClient::resolve_with_retry] retries failed resolutions
+///
+/// - max_attempts: Total number of attempts, including the first (default: 3)
+/// - base_delay: Delay before the first retry, doubles on each subsequent retry (default: 100ms)
+/// - max_delay: Upper bound for any single delay (default: 5 seconds)
+/// - retryable: Decides which errors are retried (default: [DIDCacheError::is_transient])
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    retryable: RetryablePredicate,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            retryable: Arc::new(DIDCacheError::is_transient),
+        }
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("base_delay", &self.base_delay)
+            .field("max_delay", &self.max_delay)
+            .finish()
+    }
+}
+
+impl RetryPolicy {
+    /// Set the total number of attempts (including the first attempt)
+    /// A value of 0 is treated as 1
+    /// Default: 3
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Set the delay before the first retry, each subsequent retry doubles the delay
+    /// Default: 100ms
+    pub fn with_base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Set the maximum delay between any two attempts
+    /// Default: 5 seconds
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Override which errors are considered retryable
+    /// Default: [DIDCacheError::is_transient]
+    pub fn with_retryable<F>(mut self, retryable: F) -> Self
+    where
+        F: Fn(&DIDCacheError) -> bool + Send + Sync + 'static,
+    {
+        self.retryable = Arc::new(retryable);
+        self
+    }
+
+    /// Returns the delay to wait after the given failed attempt (1 based)
+    pub(crate) fn delay_for_attempt(&self, attempt: u32) -> Duration {
+        let factor = 2_u32.saturating_pow(attempt.saturating_sub(1));
+        self.base_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+
+    /// Returns the delay to wait after a failed attempt, the error's
+    /// [retry_after](DIDCacheError::retry_after) if the server sent one (not capped by max_delay)
+    pub(crate) fn delay_after(&self, attempt: u32, error: &DIDCacheError) -> Duration {
+        error
+            .retry_after()
+            .unwrap_or_else(|| self.delay_for_attempt(attempt))
+    }
+}
+
+impl DIDCacheClient {
+    /// Resolves a DID, retrying failures that the [RetryPolicy] considers retryable
+    /// Non-retryable errors (e.g. a malformed DID or unsupported method) are returned immediately
+    /// Returns the error from the last attempt if all attempts fail
+    /// Retries skip the negative cache, so a failure cached by the first attempt (or another
+    /// resolve) isn't just returned again
+    pub async fn resolve_with_retry(
+        &self,
+        did: &str,
+        policy: &RetryPolicy,
+    ) -> Result<ResolveResponse, DIDCacheError> {
+        let max_attempts = policy.max_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            match self.resolve(did).await {
+                Ok(response) => return Ok(response),
+                Err(e) if attempt < max_attempts && (policy.retryable)(&e) => {
+                    let delay = policy.delay_after(attempt, &e);
+                    warn!(
+                        "resolve attempt ({}/{}) failed for did ({}), retrying in {}ms. Reason: {}",
+                        attempt,
+                        max_attempts,
+                        did,
+                        delay.as_millis(),
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    self._negative_remove(&did_hash(&canonicalize_did(did)))
+                        .await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    debug!(
+                        "resolve failed for did ({}) after ({}) attempt(s)",
+                        did, attempt
+                    );
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use crate::test_fixtures::{client, client_with, web_server, DID_KEY};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn delay_doubles_and_is_capped() {
+        let policy = RetryPolicy::default()
+            .with_base_delay(Duration::from_millis(100))
+            .with_max_delay(Duration::from_millis(350));
+
+        assert_eq!(policy.delay_for_attempt(1), Duration::from_millis(100));
+        assert_eq!(policy.delay_for_attempt(2), Duration::from_millis(200));
+        assert_eq!(policy.delay_for_attempt(3), Duration::from_millis(350));
+        assert_eq!(policy.delay_for_attempt(40), Duration::from_millis(350));
+    }
+
+    #[test]
+    fn retry_after_is_the_next_delay() {
+        let policy = RetryPolicy::default().with_max_delay(Duration::from_millis(350));
+
+        let rate_limited = DIDCacheError::RemoteError {
+            code: "rate_limited",
+            message: "rate_limited".into(),
+            retry_after: Some(Duration::from_secs(2)),
+        };
+        assert_eq!(policy.delay_after(1, &rate_limited), Duration::from_secs(2));
+        assert_eq!(
+            policy.delay_after(2, &DIDCacheError::Busy),
+            Duration::from_millis(200)
+        );
+    }
+
+    #[test]
+    fn default_retryable_errors() {
+        let policy = RetryPolicy::default();
+
+        assert!((policy.retryable)(&DIDCacheError::NetworkTimeout));
+        assert!((policy.retryable)(&DIDCacheError::TransportError(
+            "test".into()
+        )));
+        assert!(!(policy.retryable)(&DIDCacheError::DIDError("test".into())));
+        assert!(!(policy.retryable)(&DIDCacheError::UnsupportedMethod(
+            "test".into()
+        )));
+    }
+
+    #[tokio::test]
+    async fn resolve_with_retry_works() {
+        let client = client().await;
+
+        let response = client
+            .resolve_with_retry(DID_KEY, &RetryPolicy::default())
+            .await
+            .unwrap();
+        assert_eq!(response.did, DID_KEY);
+    }
+
+    #[tokio::test]
+    async fn non_retryable_error_is_not_retried() {
+        let client = client().await;
+        let checked = Arc::new(AtomicU32::new(0));
+        let _checked = checked.clone();
+
+        let policy = RetryPolicy::default()
+            .with_max_attempts(5)
+            .with_retryable(move |e| {
+                _checked.fetch_add(1, Ordering::Relaxed);
+                e.is_transient()
+            });
+
+        let result = client.resolve_with_retry("did:key", &policy).await;
+        assert!(matches!(result, Err(DIDCacheError::DIDError(_))));
+        assert_eq!(checked.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_up_to_max_attempts() {
+        let client = client().await;
+        let checked = Arc::new(AtomicU32::new(0));
+        let _checked = checked.clone();
+
+        let policy = RetryPolicy::default()
+            .with_max_attempts(3)
+            .with_base_delay(Duration::from_millis(1))
+            .with_retryable(move |_| {
+                _checked.fetch_add(1, Ordering::Relaxed);
+                true
+            });
+
+        let result = client.resolve_with_retry("did:key", &policy).await;
+        assert!(result.is_err());
+        // The predicate isn't consulted after the final attempt
+        assert_eq!(checked.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn retries_skip_negative_cache() {
+        let (did, requests) = web_server("404 Not Found").await;
+        let client =
+            client_with(config::ClientConfigBuilder::default().with_negative_cache_ttl(60)).await;
+
+        let policy = RetryPolicy::default()
+            .with_max_attempts(3)
+            .with_base_delay(Duration::from_millis(1))
+            .with_retryable(|_| true);
+
+        let result = client.resolve_with_retry(&did, &policy).await;
+        assert!(matches!(result, Err(DIDCacheError::NotFound(_))));
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,109 @@
+//! Cache statistics
+//!
+//! moka only records hit/miss statistics on its own when built with extra options, so the
+//! client counts its own hits and misses around the cache lookup in resolve().
+
+use crate::DIDCacheClient;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Snapshot of the DID Document cache
+/// hits: Number of resolves answered from the cache
+/// misses: Number of resolves that weren't in the cache
+/// entry_count: Number of DID Documents in the cache (approximate)
+/// estimated_size: Weighted size of the cache, total serialized DID Document bytes when
+///   max_cache_bytes is set, otherwise the same as entry_count (approximate)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entry_count: u64,
+    pub estimated_size: u64,
+}
+
+impl CacheStats {
+    /// Fraction of cache lookups that were hits (0.0 if there haven't been any)
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total > 0 {
+            self.hits as f64 / total as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Hit and miss counters, shared by clones of the client
+#[derive(Debug, Default)]
+pub(crate) struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheCounters {
+    pub(crate) fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl DIDCacheClient {
+    /// Returns hit/miss counts and the size of the DID Document cache
+    /// Cheap and lock-free (atomic loads), safe to call as often as needed, e.g. for metrics
+    /// NOTE: entry_count and estimated_size lag slightly behind recent inserts and removes
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            entry_count: self.cache.entry_count(),
+            estimated_size: self.cache.weighted_size(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::DID_KEY;
+    use crate::{config, DIDCacheClient};
+
+    #[tokio::test]
+    async fn counts_hits_and_misses() {
+        let client = DIDCacheClient::new(config::ClientConfigBuilder::default().build())
+            .await
+            .unwrap();
+        assert_eq!(client.cache_stats().hit_rate(), 0.0);
+
+        client.resolve(DID_KEY).await.unwrap();
+        client.resolve(DID_KEY).await.unwrap();
+        client.resolve(DID_KEY).await.unwrap();
+        client.get_cache().run_pending_tasks().await;
+
+        let stats = client.cache_stats();
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.entry_count, 1);
+        assert_eq!(stats.estimated_size, 1);
+        assert!((stats.hit_rate() - 2.0 / 3.0).abs() < f64::EPSILON);
+
+        // Clones share the counters
+        client.clone().resolve(DID_KEY).await.unwrap();
+        assert_eq!(client.cache_stats().hits, 3);
+    }
+
+    #[tokio::test]
+    async fn estimated_size_in_bytes() {
+        let config = config::ClientConfigBuilder::default()
+            .with_cache_weight_bytes(1_000_000)
+            .build();
+        let client = DIDCacheClient::new(config).await.unwrap();
+
+        client.resolve(DID_KEY).await.unwrap();
+        client.get_cache().run_pending_tasks().await;
+
+        let stats = client.cache_stats();
+        assert_eq!(stats.entry_count, 1);
+        assert!(stats.estimated_size > 100);
+    }
+}
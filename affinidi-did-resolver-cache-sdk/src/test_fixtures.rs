@@ -0,0 +1,124 @@
+//! DIDs, clients and servers shared by the unit tests
+
+use crate::{config::ClientConfigBuilder, DIDCacheClient};
+use ssi::dids::Document;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+};
+
+pub(crate) const DID_KEY: &str = "did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv";
+pub(crate) const DID_KEY_2: &str = "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
+pub(crate) const DID_JWK: &str = "did:jwk:eyJjcnYiOiJQLTI1NiIsImt0eSI6IkVDIiwieCI6ImFjYklRaXVNczNpOF91c3pFakoydHBUdFJNNEVVM3l6OTFQSDZDZEgyVjAiLCJ5IjoiX0tjeUxqOXZXTXB0bm1LdG00NkdxRHo4d2Y3NEk1TEtncmwyR3pIM25TRSJ9";
+pub(crate) const DID_PEER: &str = "did:peer:2.Vz6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv.EzQ3shQLqRUza6AMJFbPuMdvFRFWm1wKviQRnQSC1fScovJN4s.SeyJ0IjoiRElEQ29tbU1lc3NhZ2luZyIsInMiOnsidXJpIjoiaHR0cHM6Ly8xMjcuMC4wLjE6NzAzNyIsImEiOlsiZGlkY29tbS92MiJdLCJyIjpbXX19";
+
+/// Client with the default (local) config
+pub(crate) async fn client() -> DIDCacheClient {
+    client_with(ClientConfigBuilder::default()).await
+}
+
+/// Client with the given config
+pub(crate) async fn client_with(config: ClientConfigBuilder) -> DIDCacheClient {
+    DIDCacheClient::new(config.build()).await.unwrap()
+}
+
+/// Client that resolves the DID Documents (by their id) from fixtures
+pub(crate) async fn client_with_docs(docs: impl IntoIterator<Item = Document>) -> DIDCacheClient {
+    let fixtures: HashMap<String, Document> = docs
+        .into_iter()
+        .map(|doc| (doc.id.to_string(), doc))
+        .collect();
+    client_with(ClientConfigBuilder::default().with_fixture_resolver(fixtures)).await
+}
+
+/// A running [serve] test server
+/// served: Number of requests received
+/// requests: Each request (head and body) as received, sent before it is answered
+pub(crate) struct HttpServer {
+    pub(crate) port: u16,
+    pub(crate) served: Arc<AtomicUsize>,
+    pub(crate) requests: mpsc::UnboundedReceiver<String>,
+}
+
+/// Serves HTTP requests with the given status line and (JSON) body
+/// If release is given, the first response waits for it (e.g. to act while a resolve is in flight)
+pub(crate) async fn serve(
+    status: &'static str,
+    body: impl Into<Vec<u8>>,
+    release: Option<oneshot::Receiver<()>>,
+) -> HttpServer {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let served = Arc::new(AtomicUsize::new(0));
+    let counter = served.clone();
+    let (requests_tx, requests) = mpsc::unbounded_channel();
+    let body = body.into();
+    let mut release = release;
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            let _ = requests_tx.send(_read_request(&mut socket).await);
+            if let Some(release) = release.take() {
+                let _ = release.await;
+            }
+            let head = format!(
+                "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                status,
+                body.len()
+            );
+            let _ = socket.write_all(&[head.as_bytes(), &body].concat()).await;
+        }
+    });
+    HttpServer {
+        port,
+        served,
+        requests,
+    }
+}
+
+/// Reads a request, its head and (content-length) body
+async fn _read_request(socket: &mut TcpStream) -> String {
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    loop {
+        let n = socket.read(&mut buf).await.unwrap_or(0);
+        request.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&request);
+        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+            let length = head
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, value)| value.trim().parse().ok())
+                .unwrap_or(0);
+            if body.len() >= length {
+                return text.to_string();
+            }
+        }
+        if n == 0 {
+            return text.to_string();
+        }
+    }
+}
+
+/// Serves HTTP requests with the given status line (and no body)
+/// Returns the port and the number of requests served
+pub(crate) async fn http_server(status: &'static str) -> (u16, Arc<AtomicUsize>) {
+    let server = serve(status, "", None).await;
+    (server.port, server.served)
+}
+
+/// [http_server] as a did:web
+pub(crate) async fn web_server(status: &'static str) -> (String, Arc<AtomicUsize>) {
+    let (port, requests) = http_server(status).await;
+    (format!("did:web:localhost%3A{}", port), requests)
+}
@@ -0,0 +1,369 @@
+//! Test helpers, enabled with the `test-util` feature
+//!
+//! [MockCacheServer] is an in-memory stand-in for the DID Cache server, so the network path
+//! of the SDK can be tested without running the server. It speaks the same websocket protocol
+//! ([WS_PROTOCOL_VERSION]) and answers from a fixed set of DID Documents.
+//!
+//! ```rust,ignore
+//! let server = MockCacheServer::start(HashMap::from([(did.to_string(), doc)])).await;
+//! let config = ClientConfigBuilder::default()
+//!     .with_network_mode(server.address())
+//!     .build();
+//! let client = DIDCacheClient::new(config).await?;
+//! ```
+
+use crate::{
+    errors::DIDCacheError,
+    networking::{
+        request_hash, WSRequest, WSResponse, WSResponseError, WSResponseType, WS_PROTOCOL_VERSION,
+    },
+};
+use futures_util::{SinkExt, StreamExt};
+use ssi::dids::Document;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    select,
+    time::sleep,
+};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderValue},
+    Message,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+/// State shared between the [MockCacheServer] handle and its connections
+/// disconnect: Cancelled to drop every open connection, then replaced for new connections
+/// unknown_types: Response type (unknown to this client) to answer with, by DID
+#[derive(Default)]
+struct MockState {
+    documents: Mutex<HashMap<String, Document>>,
+    errors: Mutex<HashMap<String, DIDCacheError>>,
+    unknown_types: Mutex<HashMap<String, String>>,
+    delay: Mutex<Duration>,
+    greeting: Mutex<Option<String>>,
+    unresponsive: AtomicBool,
+    disconnect: Mutex<CancellationToken>,
+    connections: AtomicU64,
+    requests: AtomicU64,
+}
+
+/// In-memory DID Cache server for tests, listening on a random local port
+/// Answers each request with the preloaded DID Document, or a `not_found` error for unknown DIDs
+/// Behaviour can be changed while clients are connected, e.g. to test error responses
+/// ([set_error](Self::set_error)), timeouts ([set_delay](Self::set_delay)), keepalives
+/// ([set_unresponsive](Self::set_unresponsive)) and reconnects
+/// ([disconnect_all](Self::disconnect_all))
+/// The server stops when dropped
+pub struct MockCacheServer {
+    address: String,
+    state: Arc<MockState>,
+    shutdown: CancellationToken,
+}
+
+impl MockCacheServer {
+    /// Starts the server with the DID Documents (keyed by DID) it resolves
+    pub async fn start(documents: HashMap<String, Document>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("couldn't bind the mock server");
+        let address = format!(
+            "ws://{}/did/v1/ws",
+            listener.local_addr().expect("mock server address")
+        );
+
+        let state = Arc::new(MockState {
+            documents: Mutex::new(documents),
+            ..Default::default()
+        });
+        let shutdown = CancellationToken::new();
+
+        let (_state, _shutdown) = (state.clone(), shutdown.clone());
+        tokio::spawn(async move {
+            loop {
+                select! {
+                    _ = _shutdown.cancelled() => break,
+                    accepted = listener.accept() => {
+                        let Ok((stream, _)) = accepted else { continue };
+                        tokio::spawn(_handle_connection(stream, _state.clone(), _shutdown.clone()));
+                    }
+                }
+            }
+        });
+
+        MockCacheServer {
+            address,
+            state,
+            shutdown,
+        }
+    }
+
+    /// The ws:// address to use with [with_network_mode](crate::config::ClientConfigBuilder::with_network_mode)
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Adds (or replaces) a DID Document
+    pub fn insert(&self, did: &str, document: Document) {
+        _lock(&self.state.documents).insert(did.to_string(), document);
+    }
+
+    /// Answers requests for the DID with this error (its [code](DIDCacheError::code) and message)
+    /// The client receives it as a [RemoteError](DIDCacheError::RemoteError)
+    pub fn set_error(&self, did: &str, error: DIDCacheError) {
+        _lock(&self.state.errors).insert(did.to_string(), error);
+    }
+
+    /// Answers requests for the DID with a response type this client doesn't know, as a newer
+    /// server would, e.g. `Queued`
+    pub fn set_unknown_response(&self, did: &str, response_type: &str) {
+        _lock(&self.state.unknown_types).insert(did.to_string(), response_type.to_string());
+    }
+
+    /// Waits this long before answering each request, e.g. to trigger client timeouts
+    pub fn set_delay(&self, delay: Duration) {
+        *_lock(&self.state.delay) = delay;
+    }
+
+    /// Sends this text message first on every new connection, e.g. one over max_frame_bytes
+    pub fn set_greeting(&self, text: &str) {
+        *_lock(&self.state.greeting) = Some(text.to_string());
+    }
+
+    /// New connections are accepted but never read from, so requests and pings are never
+    /// answered (tungstenite answers pings when reading)
+    pub fn set_unresponsive(&self, unresponsive: bool) {
+        self.state
+            .unresponsive
+            .store(unresponsive, Ordering::Relaxed);
+    }
+
+    /// Drops every open connection without a close frame, clients are expected to reconnect
+    pub fn disconnect_all(&self) {
+        _lock(&self.state.disconnect).cancel();
+        *_lock(&self.state.disconnect) = CancellationToken::new();
+    }
+
+    /// Number of websocket connections accepted
+    pub fn connections(&self) -> u64 {
+        self.state.connections.load(Ordering::Relaxed)
+    }
+
+    /// Number of resolve requests received
+    pub fn requests(&self) -> u64 {
+        self.state.requests.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for MockCacheServer {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
+
+/// Locks the mutex, a panicking test mustn't poison the server for the others
+fn _lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Selects [WS_PROTOCOL_VERSION] like the DID Cache server does
+#[allow(clippy::result_large_err)]
+fn _select_protocol(_: &Request, mut response: Response) -> Result<Response, ErrorResponse> {
+    response.headers_mut().insert(
+        SEC_WEBSOCKET_PROTOCOL,
+        HeaderValue::from_static(WS_PROTOCOL_VERSION),
+    );
+    Ok(response)
+}
+
+/// Answers requests on one connection until it is closed, disconnected or the server stops
+async fn _handle_connection(stream: TcpStream, state: Arc<MockState>, shutdown: CancellationToken) {
+    let Ok(mut websocket) = tokio_tungstenite::accept_hdr_async(stream, _select_protocol).await
+    else {
+        warn!("mock server: websocket handshake failed");
+        return;
+    };
+    state.connections.fetch_add(1, Ordering::Relaxed);
+    let disconnect = _lock(&state.disconnect).clone();
+
+    let greeting = _lock(&state.greeting).clone();
+    if let Some(greeting) = greeting {
+        if websocket.send(Message::text(greeting)).await.is_err() {
+            return;
+        }
+    }
+    if state.unresponsive.load(Ordering::Relaxed) {
+        select! {
+            _ = shutdown.cancelled() => {},
+            _ = disconnect.cancelled() => {},
+        }
+        return;
+    }
+
+    loop {
+        let message = select! {
+            _ = shutdown.cancelled() => break,
+            _ = disconnect.cancelled() => break,
+            message = websocket.next() => message,
+        };
+        let request = match message {
+            Some(Ok(Message::Text(text))) => match serde_json::from_str::<WSRequest>(&text) {
+                Ok(request) => request,
+                Err(e) => {
+                    warn!("mock server: invalid request: {}", e);
+                    continue;
+                }
+            },
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+            Some(Ok(_)) => continue,
+        };
+        state.requests.fetch_add(1, Ordering::Relaxed);
+        debug!("mock server: resolving ({})", request.did);
+
+        let delay = *_lock(&state.delay);
+        if !delay.is_zero() {
+            sleep(delay).await;
+        }
+
+        if websocket
+            .send(Message::text(_respond(&state, request)))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+/// The response message for a request: an unknown response type, a configured error, the DID
+/// Document, or not_found
+fn _respond(state: &MockState, request: WSRequest) -> String {
+    let hash = request_hash(&request.did);
+    if let Some(response_type) = _lock(&state.unknown_types).get(&request.did) {
+        return serde_json::json!({
+            response_type: { "request_id": request.request_id, "hash": hash }
+        })
+        .to_string();
+    }
+
+    let error = _lock(&state.errors).get(&request.did).cloned();
+    let document = _lock(&state.documents).get(&request.did).cloned();
+
+    let error = match (error, document) {
+        (None, Some(document)) => {
+            return _serialize(WSResponseType::Response(Box::new(WSResponse {
+                request_id: request.request_id,
+                did: request.did,
+                hash,
+                document,
+                resolution_metadata: Default::default(),
+                document_metadata: Default::default(),
+                raw: None,
+            })))
+        }
+        (Some(error), _) => error,
+        (None, None) => DIDCacheError::NotFound(request.did.clone()),
+    };
+    _serialize(WSResponseType::Error(WSResponseError {
+        request_id: request.request_id,
+        did: request.did,
+        hash,
+        code: error.code().to_string(),
+        error: error.to_string(),
+        retry_after_ms: error.retry_after().map(|delay| delay.as_millis() as u64),
+    }))
+}
+
+fn _serialize(response: WSResponseType) -> String {
+    serde_json::to_string(&response).expect("serializable response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::ClientConfigBuilder, DIDCacheClient};
+    use ssi::dids::DIDBuf;
+
+    const DID_WEB: &str = "did:web:example.com";
+
+    async fn _client(server: &MockCacheServer, timeout: u32) -> DIDCacheClient {
+        let config = ClientConfigBuilder::default()
+            .with_network_mode(server.address())
+            .with_network_timeout(timeout)
+            .with_backoff(crate::networking::backoff::BackoffConfig::linear(
+                Duration::from_millis(50),
+                Duration::from_millis(50),
+            ))
+            .build();
+        DIDCacheClient::new(config).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn error_responses() {
+        let server = MockCacheServer::start(HashMap::new()).await;
+        let client = _client(&server, 5000).await;
+
+        let result = client.resolve(DID_WEB).await;
+        assert!(matches!(
+            result,
+            Err(DIDCacheError::RemoteError {
+                code: "not_found",
+                ..
+            })
+        ));
+
+        server.set_error(
+            DID_WEB,
+            DIDCacheError::TransportError("host unreachable".into()),
+        );
+        let result = client.resolve(DID_WEB).await;
+        assert!(matches!(
+            result,
+            Err(DIDCacheError::RemoteError {
+                code: "transport",
+                ..
+            })
+        ));
+        assert_eq!(server.requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn delayed_response_times_out() {
+        let doc = Document::new(DIDBuf::from_string(DID_WEB.to_string()).unwrap());
+        let server = MockCacheServer::start(HashMap::from([(DID_WEB.to_string(), doc)])).await;
+        let client = _client(&server, 100).await;
+
+        server.set_delay(Duration::from_millis(500));
+        let result = client.resolve(DID_WEB).await;
+        assert!(matches!(result, Err(DIDCacheError::NetworkTimeout)));
+    }
+
+    #[tokio::test]
+    async fn reconnects_after_disconnect() {
+        let doc = Document::new(DIDBuf::from_string(DID_WEB.to_string()).unwrap());
+        let server = MockCacheServer::start(HashMap::from([(DID_WEB.to_string(), doc)])).await;
+        let client = _client(&server, 5000).await;
+        client.resolve(DID_WEB).await.unwrap();
+
+        server.disconnect_all();
+        for _ in 0..50 {
+            if server.connections() == 2 {
+                break;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(server.connections(), 2);
+
+        client.remove(DID_WEB).await;
+        assert!(!client.resolve(DID_WEB).await.unwrap().cache_hit);
+    }
+}
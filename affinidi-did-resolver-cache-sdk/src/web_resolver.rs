@@ -0,0 +1,115 @@
+//! Pluggable source of did:web DID Documents
+//!
+//! did:web resolution fetches a did.json over HTTPS. By default the client's HTTP client is
+//! used (so configured proxies apply), but a [WebResolver] can be set with
+//! [ClientConfigBuilder::with_web_resolver](crate::config::ClientConfigBuilder::with_web_resolver)
+//! e.g. to serve fixed documents in tests without running an HTTPS server.
+//!
+//! A `HashMap<String, Vec<u8>>` of did.json URL to document bytes is a ready made [WebResolver].
+
+use crate::errors::DIDCacheError;
+use async_trait::async_trait;
+use reqwest::{header, StatusCode};
+use ssi::dids::document::representation::MediaType;
+use std::{collections::HashMap, fmt::Debug};
+
+/// Fetches the did.json of a did:web
+/// url: The did.json URL, e.g. `https://example.com/.well-known/did.json`
+///
+/// Return a NotFound error if the document doesn't exist, and a TransportError if it couldn't
+/// be fetched.
+#[async_trait]
+pub trait WebResolver: Send + Sync + Debug {
+    /// Returns the raw did.json
+    async fn fetch(&self, url: &str) -> Result<Vec<u8>, DIDCacheError>;
+}
+
+/// Fixed documents keyed by did.json URL, anything else is not found
+#[async_trait]
+impl WebResolver for HashMap<String, Vec<u8>> {
+    async fn fetch(&self, url: &str) -> Result<Vec<u8>, DIDCacheError> {
+        self.get(url)
+            .cloned()
+            .ok_or_else(|| DIDCacheError::NotFound(format!("DID document ({}) not found", url)))
+    }
+}
+
+/// The default [WebResolver], a GET request with the client's HTTP client
+#[derive(Debug)]
+pub(crate) struct HttpWebResolver(pub(crate) reqwest::Client);
+
+#[async_trait]
+impl WebResolver for HttpWebResolver {
+    async fn fetch(&self, url: &str) -> Result<Vec<u8>, DIDCacheError> {
+        let response = self
+            .0
+            .get(url)
+            .header(header::ACCEPT, MediaType::Json.to_string())
+            .send()
+            .await
+            .map_err(|e| DIDCacheError::TransportError(format!("GET ({}) failed: {}", url, e)))?;
+        match response.status() {
+            status if status.is_success() => {}
+            StatusCode::NOT_FOUND => {
+                return Err(DIDCacheError::NotFound(format!(
+                    "DID document ({}) not found",
+                    url
+                )))
+            }
+            status => {
+                return Err(DIDCacheError::TransportError(format!(
+                    "GET ({}) returned status ({})",
+                    url, status
+                )))
+            }
+        }
+
+        response
+            .bytes()
+            .await
+            .map(|body| body.to_vec())
+            .map_err(|e| DIDCacheError::TransportError(format!("GET ({}) failed: {}", url, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::ClientConfigBuilder, DIDCacheClient};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn resolve_did_web_from_map() {
+        let did = "did:web:example.com";
+        let doc = serde_json::json!({
+            "@context": "https://www.w3.org/ns/did/v1",
+            "id": did,
+        });
+        let resolver = HashMap::from([(
+            "https://example.com/.well-known/did.json".to_string(),
+            doc.to_string().into_bytes(),
+        )]);
+
+        // Offline, so nothing can be fetched over the network
+        let client = DIDCacheClient::new(
+            ClientConfigBuilder::default()
+                .offline()
+                .with_web_resolver(Arc::new(resolver))
+                .build(),
+        )
+        .await
+        .unwrap();
+
+        let response = client.resolve(did).await.unwrap();
+        assert_eq!(response.doc.id, did);
+        assert_eq!(
+            response.resolution_metadata.content_type.as_deref(),
+            Some("application/did+ld+json")
+        );
+
+        let result = client.resolve("did:web:example.org").await;
+        assert!(
+            matches!(result, Err(DIDCacheError::NotFound(msg)) if msg == "DID document (https://example.org/.well-known/did.json) not found")
+        );
+    }
+}
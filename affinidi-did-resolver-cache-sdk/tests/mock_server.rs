@@ -0,0 +1,30 @@
+//! Resolving through the SDK's network path against [MockCacheServer]
+//!
+//! cargo test --features test-util --test mock_server
+use affinidi_did_resolver_cache_sdk::{
+    config::ClientConfigBuilder, test_util::MockCacheServer, DIDCacheClient,
+};
+use ssi::dids::{DIDBuf, Document};
+use std::collections::HashMap;
+
+const DID_WEB: &str = "did:web:example.com";
+
+#[tokio::test]
+async fn resolve_preloaded_did() {
+    let doc = Document::new(DIDBuf::from_string(DID_WEB.to_string()).unwrap());
+    let server = MockCacheServer::start(HashMap::from([(DID_WEB.to_string(), doc.clone())])).await;
+
+    let config = ClientConfigBuilder::default()
+        .with_network_mode(server.address())
+        .build();
+    let client = DIDCacheClient::new(config).await.unwrap();
+
+    let response = client.resolve(DID_WEB).await.unwrap();
+    assert_eq!(response.doc, doc);
+    assert!(!response.cache_hit);
+    assert_eq!(server.requests(), 1);
+
+    // Cached by the client, the server isn't asked again
+    assert!(client.resolve(DID_WEB).await.unwrap().cache_hit);
+    assert_eq!(server.requests(), 1);
+}
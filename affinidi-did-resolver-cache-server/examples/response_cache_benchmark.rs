@@ -0,0 +1,88 @@
+//! Compares building websocket responses with and without the response cache
+//! Simulates a high hit-rate workload: a small set of hot DIDs, each requested many times
+//! Benchmark reference: (x86_64 Linux, release build, 100 did:key's, 200k requests)
+//! - without response cache ~213k req/s, with response cache ~439k req/s (~2x)
+//!
+//! cargo run --release --example response_cache_benchmark
+use affinidi_did_resolver_cache_sdk::{
+    config::ClientConfigBuilder, networking::WSRequest, DIDCacheClient,
+};
+use affinidi_did_resolver_cache_server::{
+    config::Config,
+    response_cache::{build_caches, response_frame},
+};
+use ssi::{dids::DIDKey, JWK};
+use std::time::Instant;
+
+const HOT_DIDS: usize = 100;
+const REQUESTS: usize = 200_000;
+
+async fn run(client: &DIDCacheClient, config: &Config, dids: &[String]) -> f64 {
+    let (_, responses) = build_caches(config);
+    let start = Instant::now();
+    let mut bytes = 0;
+    for i in 0..REQUESTS {
+        let request = WSRequest {
+            did: dids[i % dids.len()].clone(),
+            request_id: String::new(),
+        };
+        let response = client.resolve(&request.did).await.unwrap();
+        bytes += response_frame(responses.as_ref(), &response, &request)
+            .await
+            .len();
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "  response_cache({}): {} requests in {:.3}s ({:.0} req/s, {} bytes sent)",
+        config.cache_responses,
+        REQUESTS,
+        elapsed,
+        REQUESTS as f64 / elapsed,
+        bytes
+    );
+    elapsed
+}
+
+#[tokio::main]
+async fn main() {
+    let dids: Vec<String> = (0..HOT_DIDS)
+        .map(|_| {
+            DIDKey::generate(&JWK::generate_ed25519().unwrap())
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+
+    let client = DIDCacheClient::new(ClientConfigBuilder::default().build())
+        .await
+        .unwrap();
+    // Warm the resolver cache, so every request below is a cache hit
+    for did in &dids {
+        client.resolve(did).await.unwrap();
+    }
+
+    println!(
+        "Resolving {} hot DIDs {} times in total",
+        HOT_DIDS, REQUESTS
+    );
+    let uncached = run(
+        &client,
+        &Config {
+            cache_responses: false,
+            ..Default::default()
+        },
+        &dids,
+    )
+    .await;
+    let cached = run(
+        &client,
+        &Config {
+            cache_responses: true,
+            ..Default::default()
+        },
+        &dids,
+    )
+    .await;
+
+    println!("Response cache speedup: {:.2}x", uncached / cached);
+}
@@ -5,6 +5,7 @@ use std::{
     env, fmt,
     fs::File,
     io::{self, BufRead},
+    net::SocketAddr,
     path::Path,
     time::Duration,
 };
@@ -17,6 +18,8 @@ struct CacheConfig {
     pub capacity_count: String,
     #[serde(default)]
     pub expire: String,
+    #[serde(default)]
+    pub response_cache: String,
 }
 
 impl Default for CacheConfig {
@@ -24,6 +27,7 @@ impl Default for CacheConfig {
         CacheConfig {
             capacity_count: "1000".into(),
             expire: "300".into(),
+            response_cache: "false".into(),
         }
     }
 }
@@ -34,20 +38,52 @@ impl Default for CacheConfig {
 struct ConfigRaw {
     pub log_level: String,
     pub listen_address: String,
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+    #[serde(default)]
+    pub websocket_path: Option<String>,
     pub enable_http_endpoint: String,
     pub enable_websocket_endpoint: String,
+    #[serde(default)]
+    pub enable_metrics_endpoint: String,
+    #[serde(default)]
+    pub max_frame_bytes: String,
+    #[serde(default)]
+    pub shutdown_grace_period: String,
+    #[serde(default)]
+    pub rate_limit_per_second: String,
+    #[serde(default)]
+    pub rate_limit_burst: String,
+    #[serde(default)]
+    pub deep_health_cache_ttl: String,
+    #[serde(default)]
+    pub deep_health_web_did: String,
     pub statistics_interval: String,
     pub cache: CacheConfig,
 }
 
+/// path_prefix: Routes are nested under this path, empty serves them from the root
+/// websocket_path: Path of the websocket endpoint, under path_prefix
+/// deep_health_cache_ttl: How long a deep health check result is reused
+/// deep_health_web_did: did:web the deep health check resolves, None skips did:web
 pub struct Config {
     pub log_level: LevelFilter,
     pub listen_address: String,
+    pub path_prefix: String,
+    pub websocket_path: String,
     pub enable_http_endpoint: bool,
     pub enable_websocket_endpoint: bool,
+    pub enable_metrics_endpoint: bool,
+    pub max_frame_bytes: usize,
+    pub shutdown_grace_period: Duration,
+    pub rate_limit_per_second: f64,
+    pub rate_limit_burst: u32,
+    pub deep_health_cache_ttl: Duration,
+    pub deep_health_web_did: Option<String>,
     pub statistics_interval: Duration,
     pub cache_capacity_count: u32,
     pub cache_expire: u32,
+    pub cache_responses: bool,
 }
 
 impl fmt::Debug for Config {
@@ -55,14 +91,30 @@ impl fmt::Debug for Config {
         f.debug_struct("Config")
             .field("log_level", &self.log_level)
             .field("listen_address", &self.listen_address)
+            .field("path_prefix", &self.path_prefix)
+            .field("websocket_path", &self.websocket_path)
             .field("enable_http_endpoint", &self.enable_http_endpoint)
             .field("enable_websocket_endpoint", &self.enable_websocket_endpoint)
+            .field("enable_metrics_endpoint", &self.enable_metrics_endpoint)
+            .field("max_frame_bytes", &self.max_frame_bytes)
+            .field(
+                "shutdown_grace_period",
+                &format!("{} seconds", self.shutdown_grace_period.as_secs()),
+            )
+            .field("rate_limit_per_second", &self.rate_limit_per_second)
+            .field("rate_limit_burst", &self.rate_limit_burst)
+            .field(
+                "deep_health_cache_ttl",
+                &format!("{} seconds", self.deep_health_cache_ttl.as_secs()),
+            )
+            .field("deep_health_web_did", &self.deep_health_web_did)
             .field(
                 "statistics_interval",
                 &format!("{} seconds", self.statistics_interval.as_secs()),
             )
             .field("cache_capacity_count", &self.cache_capacity_count)
             .field("cache_expire", &format!("{} seconds", self.cache_expire))
+            .field("cache_responses", &self.cache_responses)
             .finish()
     }
 }
@@ -72,22 +124,58 @@ impl Default for Config {
         Config {
             log_level: LevelFilter::INFO,
             listen_address: "".into(),
+            path_prefix: DEFAULT_PATH_PREFIX.into(),
+            websocket_path: DEFAULT_WEBSOCKET_PATH.into(),
             enable_http_endpoint: true,
             enable_websocket_endpoint: true,
+            enable_metrics_endpoint: true,
+            max_frame_bytes: 65536,
+            shutdown_grace_period: Duration::from_secs(10),
+            rate_limit_per_second: 0.0,
+            rate_limit_burst: 100,
+            deep_health_cache_ttl: Duration::from_secs(30),
+            deep_health_web_did: None,
             statistics_interval: Duration::from_secs(60),
             cache_capacity_count: CacheConfig::default()
                 .capacity_count
                 .parse()
                 .unwrap_or(1000),
             cache_expire: CacheConfig::default().expire.parse().unwrap_or(300),
+            cache_responses: false,
         }
     }
 }
 
+const DEFAULT_PATH_PREFIX: &str = "/did/v1";
+const DEFAULT_WEBSOCKET_PATH: &str = "/ws";
+
+/// Normalizes a route path to a leading `/` and no trailing `/`
+/// `/` (or an empty path) becomes empty
+fn _normalize_path(path: &str) -> String {
+    let path = path.trim().trim_matches('/');
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", path)
+    }
+}
+
 impl TryFrom<ConfigRaw> for Config {
     type Error = CacheError;
 
     fn try_from(raw: ConfigRaw) -> Result<Self, Self::Error> {
+        if let Err(e) = raw.listen_address.parse::<SocketAddr>() {
+            return Err(CacheError::ConfigError(
+                "NA".into(),
+                format!("Invalid listen_address ({}): {}", raw.listen_address, e),
+            ));
+        }
+
+        let websocket_path = match raw.websocket_path.as_deref().map(_normalize_path) {
+            Some(path) if !path.is_empty() => path,
+            _ => DEFAULT_WEBSOCKET_PATH.into(),
+        };
+
         Ok(Config {
             log_level: match raw.log_level.as_str() {
                 "trace" => LevelFilter::TRACE,
@@ -98,11 +186,36 @@ impl TryFrom<ConfigRaw> for Config {
                 _ => LevelFilter::INFO,
             },
             listen_address: raw.listen_address,
+            path_prefix: raw
+                .path_prefix
+                .as_deref()
+                .map(_normalize_path)
+                .unwrap_or_else(|| DEFAULT_PATH_PREFIX.into()),
+            websocket_path,
             enable_http_endpoint: raw.enable_http_endpoint.parse().unwrap_or(true),
             enable_websocket_endpoint: raw.enable_websocket_endpoint.parse().unwrap_or(true),
+            enable_metrics_endpoint: raw.enable_metrics_endpoint.parse().unwrap_or(true),
+            max_frame_bytes: match raw.max_frame_bytes.parse() {
+                Ok(0) | Err(_) => 65536,
+                Ok(max_frame_bytes) => max_frame_bytes,
+            },
+            shutdown_grace_period: Duration::from_secs(
+                raw.shutdown_grace_period.parse().unwrap_or(10),
+            ),
+            rate_limit_per_second: match raw.rate_limit_per_second.parse::<f64>() {
+                Ok(per_second) if per_second.is_finite() && per_second > 0.0 => per_second,
+                _ => 0.0,
+            },
+            rate_limit_burst: raw.rate_limit_burst.parse().unwrap_or(100),
+            deep_health_cache_ttl: Duration::from_secs(
+                raw.deep_health_cache_ttl.parse().unwrap_or(30),
+            ),
+            deep_health_web_did: Some(raw.deep_health_web_did.trim().to_string())
+                .filter(|did| !did.is_empty()),
             statistics_interval: Duration::from_secs(raw.statistics_interval.parse().unwrap_or(60)),
             cache_capacity_count: raw.cache.capacity_count.parse().unwrap_or(1000),
             cache_expire: raw.cache.expire.parse().unwrap_or(300),
+            cache_responses: raw.cache.response_cache.parse().unwrap_or(false),
         })
     }
 }
@@ -191,7 +304,7 @@ pub fn init(reload_handle: Option<Handle<LevelFilter, Registry>>) -> Result<Conf
     let config = read_config_file("conf/cache-conf.toml")?;
 
     // Setup logging
-    if reload_handle.is_some() {
+    if let Some(reload_handle) = reload_handle {
         let level: LevelFilter = match config.log_level.as_str() {
             "trace" => LevelFilter::TRACE,
             "debug" => LevelFilter::DEBUG,
@@ -208,7 +321,6 @@ pub fn init(reload_handle: Option<Handle<LevelFilter, Registry>>) -> Result<Conf
             }
         };
         reload_handle
-            .unwrap()
             .modify(|filter| *filter = level)
             .map_err(|e| CacheError::InternalError("NA".into(), e.to_string()))?;
         event!(Level::INFO, "Log level set to ({})", config.log_level);
@@ -226,3 +338,99 @@ pub fn init(reload_handle: Option<Handle<LevelFilter, Registry>>) -> Result<Conf
         Err(err) => Err(err),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_from_raw() {
+        let raw: ConfigRaw = toml::from_str(
+            r#"
+            log_level = "debug"
+            listen_address = "0.0.0.0:8080"
+            enable_http_endpoint = "false"
+            enable_websocket_endpoint = "true"
+            statistics_interval = "10"
+
+            [cache]
+            capacity_count = "500"
+            expire = "not a number"
+            "#,
+        )
+        .unwrap();
+
+        let config = Config::try_from(raw).unwrap();
+        assert_eq!(config.log_level, LevelFilter::DEBUG);
+        assert_eq!(config.listen_address, "0.0.0.0:8080");
+        assert_eq!(config.path_prefix, "/did/v1");
+        assert_eq!(config.websocket_path, "/ws");
+        assert!(!config.enable_http_endpoint);
+        assert!(config.enable_websocket_endpoint);
+        // Not in the file
+        assert!(config.enable_metrics_endpoint);
+        assert_eq!(config.max_frame_bytes, 65536);
+        assert_eq!(config.shutdown_grace_period, Duration::from_secs(10));
+        // Rate limiting is off unless configured
+        assert_eq!(config.rate_limit_per_second, 0.0);
+        assert_eq!(config.rate_limit_burst, 100);
+        assert_eq!(config.deep_health_cache_ttl, Duration::from_secs(30));
+        assert_eq!(config.deep_health_web_did, None);
+        assert_eq!(config.statistics_interval, Duration::from_secs(10));
+        assert_eq!(config.cache_capacity_count, 500);
+        // Invalid values fall back to the defaults
+        assert_eq!(config.cache_expire, 300);
+        assert!(!config.cache_responses);
+    }
+
+    #[test]
+    fn config_paths() {
+        let parse = |settings: &str| {
+            let raw: ConfigRaw = toml::from_str(&format!(
+                r#"
+                log_level = "info"
+                enable_http_endpoint = "true"
+                enable_websocket_endpoint = "true"
+                statistics_interval = "10"
+                {}
+
+                [cache]
+                "#,
+                settings
+            ))
+            .unwrap();
+            Config::try_from(raw)
+        };
+
+        let config = parse(
+            r#"listen_address = "127.0.0.1:9000"
+            path_prefix = "resolver/v2/"
+            websocket_path = "socket""#,
+        )
+        .unwrap();
+        assert_eq!(config.path_prefix, "/resolver/v2");
+        assert_eq!(config.websocket_path, "/socket");
+
+        // An empty prefix serves the routes from the root
+        let config = parse(
+            r#"listen_address = "127.0.0.1:9000"
+            path_prefix = "/"
+            websocket_path = ''"#,
+        )
+        .unwrap();
+        assert_eq!(config.path_prefix, "");
+        assert_eq!(config.websocket_path, "/ws");
+
+        assert!(matches!(
+            parse(r#"listen_address = "localhost""#),
+            Err(CacheError::ConfigError(_, error)) if error.starts_with("Invalid listen_address")
+        ));
+    }
+
+    #[test]
+    fn shipped_config_file_parses() {
+        let raw = read_config_file("conf/cache-conf.toml").unwrap();
+        let config = Config::try_from(raw).unwrap();
+        assert!(!config.listen_address.is_empty());
+    }
+}
@@ -1,45 +1,272 @@
 use crate::SharedData;
+use affinidi_did_resolver_cache_sdk::{errors::DIDCacheError, metadata::ResolutionMetadata};
 use axum::{
     extract::{Path, State},
+    response::{IntoResponse, Response},
     Json,
 };
-use http::StatusCode;
-use serde_json::{json, Value};
-use tracing::error;
+use http::{header, HeaderMap, HeaderValue, StatusCode};
+use serde_json::json;
+use ssi::dids::document::{
+    representation::{self, MediaType},
+    Represented,
+};
+use tracing::{error, warn};
 
 pub async fn resolver_handler(
     State(state): State<SharedData>,
     Path(did): Path<String>,
-) -> (StatusCode, Json<Value>) {
+    headers: HeaderMap,
+) -> Response {
+    // Content negotiation happens before resolving, no point resolving what we can't return
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok());
+    let Some(media_type) = negotiate_representation(accept) else {
+        warn!(
+            "Can't satisfy Accept header ({}) for DID ({})",
+            accept.unwrap_or_default(),
+            did
+        );
+        return (
+            StatusCode::NOT_ACCEPTABLE,
+            Json(json!({ "error": "representationNotSupported" })),
+        )
+            .into_response();
+    };
+
     match state.resolver.resolve(&did).await {
-        Ok(doc) => match serde_json::to_value(doc.doc) {
-            Ok(value) => {
-                if doc.cache_hit {
+        Ok(doc) => {
+            let options = representation::Options::from_media_type(media_type, Default::default);
+            let represented: Represented = doc.doc.into_representation(options);
+            match serde_json::to_vec(&represented) {
+                Ok(body) => {
                     let mut stats = state.stats.lock().await;
-                    stats.increment_cache_hit();
                     stats.increment_resolver_success();
+                    if doc.cache_hit {
+                        stats.increment_cache_hit();
+                    }
                     stats.increment_did_method_success(doc.method);
+                    drop(stats);
+                    (
+                        StatusCode::OK,
+                        [(
+                            header::CONTENT_TYPE,
+                            HeaderValue::from_static(media_type.name()),
+                        )],
+                        body,
+                    )
+                        .into_response()
+                }
+                Err(e) => {
+                    let mut stats = state.stats.lock().await;
+                    stats.increment_resolver_error();
+                    error!("Error serializing DID ({}) document: {:?}", did, e);
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(json!({ "error": e.to_string() })),
+                    )
+                        .into_response()
                 }
-                (StatusCode::OK, Json(value))
-            }
-            Err(e) => {
-                let mut stats = state.stats.lock().await;
-                stats.increment_resolver_error();
-                error!("Error serializing DID ({}) document: {:?}", did, e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "error": e.to_string() })),
-                )
             }
-        },
+        }
         Err(e) => {
             let mut stats = state.stats.lock().await;
             stats.increment_resolver_error();
             error!("Error resolving DID ({}): {:?}", did, e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({ "error": e.to_string() })),
-            )
+            error_response(&e)
+        }
+    }
+}
+
+/// Maps a resolve error to the DID Resolution HTTP(S) binding status code
+/// The body has the W3C error code (e.g. `notFound`) and the error message
+/// Transport errors (an upstream host failed) are a 502 Bad Gateway
+fn error_response(error: &DIDCacheError) -> Response {
+    let code = ResolutionMetadata::from_error(error)
+        .error
+        .unwrap_or_default();
+    let status = match code.as_str() {
+        "invalidDid" | "unsupportedPublicKeyType" => StatusCode::BAD_REQUEST,
+        "notFound" => StatusCode::NOT_FOUND,
+        "methodNotSupported" => StatusCode::NOT_IMPLEMENTED,
+        _ if error.code() == "transport" => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(json!({ "error": code, "message": error.to_string() })),
+    )
+        .into_response()
+}
+
+/// Picks the DID Document representation to return based on the Accept header
+/// Follows the DID Resolution HTTP(S) binding:
+/// - `application/did+ld+json` returns JSON-LD (with `@context`)
+/// - `application/did+json` returns plain JSON (without `@context`)
+/// - `application/ld+json` (any profile), `*/*`, `application/*` or no header default to JSON-LD
+///
+/// Media ranges are tried in order of their quality value (q), ranges with q=0 are ignored.
+/// Returns None if no acceptable representation can be returned
+pub(crate) fn negotiate_representation(accept: Option<&str>) -> Option<MediaType> {
+    let Some(accept) = accept.filter(|accept| !accept.trim().is_empty()) else {
+        return Some(MediaType::JsonLd);
+    };
+
+    let mut ranges: Vec<(&str, f32)> = accept
+        .split(',')
+        .filter_map(|range| {
+            let mut params = range.split(';').map(str::trim);
+            let media_range = params.next()?;
+            let quality = params
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+            Some((media_range, quality))
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect();
+    // Stable sort keeps the header order for equal quality values
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranges.iter().find_map(
+        |(media_range, _)| match media_range.to_ascii_lowercase().as_str() {
+            "application/did+ld+json" | "application/ld+json" | "application/*" | "*/*" => {
+                Some(MediaType::JsonLd)
+            }
+            "application/did+json" => Some(MediaType::Json),
+            _ => None,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use affinidi_did_resolver_cache_sdk::{config::ClientConfigBuilder, DIDCacheClient};
+    use axum::body::to_bytes;
+    use ssi::dids::document::representation::MediaType;
+    use std::sync::Arc;
+
+    const DID_KEY: &str = "did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv";
+
+    async fn _shared_data() -> SharedData {
+        SharedData {
+            service_start_timestamp: chrono::Utc::now(),
+            stats: Arc::new(Default::default()),
+            resolver: DIDCacheClient::new(ClientConfigBuilder::default().build())
+                .await
+                .unwrap(),
+            response_cache: None,
+            max_frame_bytes: 65536,
+            shutdown: Default::default(),
+            rate_limiter: Default::default(),
+            deep_health: Default::default(),
         }
     }
+
+    async fn _resolve(state: &SharedData, did: &str) -> (StatusCode, serde_json::Value) {
+        let response = resolver_handler(
+            State(state.clone()),
+            Path(did.to_string()),
+            HeaderMap::new(),
+        )
+        .await;
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn resolves_and_counts_every_success() {
+        let state = _shared_data().await;
+
+        let (status, body) = _resolve(&state, DID_KEY).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["id"], DID_KEY);
+        _resolve(&state, DID_KEY).await;
+
+        let stats = state.stats.lock().await.clone();
+        let expected = format!("{}", stats);
+        assert!(expected.contains("success(2)"), "{}", expected);
+        assert!(expected.contains("Hits(1 "), "{}", expected);
+    }
+
+    #[tokio::test]
+    async fn error_status_codes() {
+        let state = _shared_data().await;
+
+        let (status, body) = _resolve(&state, "did:key:z6MkNotAKey").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalidDid");
+
+        let (status, body) = _resolve(&state, "did:unknown:1234").await;
+        assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+        assert_eq!(body["error"], "methodNotSupported");
+
+        assert_eq!(
+            error_response(&DIDCacheError::NotFound("did:web:example.com".into())).status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            error_response(&DIDCacheError::InvalidDid("bad id".into())).status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            error_response(&DIDCacheError::TransportError("connection refused".into())).status(),
+            StatusCode::BAD_GATEWAY
+        );
+        assert_eq!(
+            error_response(&DIDCacheError::NetworkTimeout).status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert!(format!("{}", state.stats.lock().await).contains("error(2)"));
+    }
+
+    #[test]
+    fn explicit_representations() {
+        assert_eq!(
+            negotiate_representation(Some("application/did+ld+json")),
+            Some(MediaType::JsonLd)
+        );
+        assert_eq!(
+            negotiate_representation(Some("application/did+json")),
+            Some(MediaType::Json)
+        );
+    }
+
+    #[test]
+    fn defaults_to_json_ld() {
+        assert_eq!(negotiate_representation(None), Some(MediaType::JsonLd));
+        assert_eq!(
+            negotiate_representation(Some("*/*")),
+            Some(MediaType::JsonLd)
+        );
+        assert_eq!(
+            negotiate_representation(Some(
+                "application/ld+json;profile=\"https://w3id.org/did-resolution\""
+            )),
+            Some(MediaType::JsonLd)
+        );
+    }
+
+    #[test]
+    fn quality_values() {
+        assert_eq!(
+            negotiate_representation(Some("application/did+ld+json;q=0.5, application/did+json")),
+            Some(MediaType::Json)
+        );
+        assert_eq!(
+            negotiate_representation(Some("application/did+json;q=0, */*;q=0.1")),
+            Some(MediaType::JsonLd)
+        );
+    }
+
+    #[test]
+    fn not_acceptable() {
+        assert_eq!(negotiate_representation(Some("text/html")), None);
+        assert_eq!(
+            negotiate_representation(Some("application/did+json;q=0")),
+            None
+        );
+    }
 }
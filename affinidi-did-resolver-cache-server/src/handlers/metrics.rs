@@ -0,0 +1,65 @@
+use crate::SharedData;
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+};
+use http::{header, HeaderValue};
+
+/// Content type of the Prometheus text exposition format
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Returns the server statistics for Prometheus to scrape
+pub async fn metrics_handler(State(state): State<SharedData>) -> Response {
+    let stats = state.stats.lock().await.clone();
+    let body = stats.to_prometheus(state.resolver.cache_stats().entry_count);
+
+    (
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(PROMETHEUS_CONTENT_TYPE),
+        )],
+        body,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use affinidi_did_resolver_cache_sdk::{config::ClientConfigBuilder, DIDCacheClient};
+    use axum::body::to_bytes;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn renders_metrics() {
+        let resolver = DIDCacheClient::new(ClientConfigBuilder::default().build())
+            .await
+            .unwrap();
+        resolver
+            .resolve("did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv")
+            .await
+            .unwrap();
+        resolver.get_cache().run_pending_tasks().await;
+        let state = SharedData {
+            service_start_timestamp: chrono::Utc::now(),
+            stats: Arc::new(Default::default()),
+            resolver,
+            response_cache: None,
+            max_frame_bytes: 65536,
+            shutdown: Default::default(),
+            rate_limiter: Default::default(),
+            deep_health: Default::default(),
+        };
+        state.stats.lock().await.increment_ws_opened();
+
+        let response = metrics_handler(State(state)).await;
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            PROMETHEUS_CONTENT_TYPE
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("did_resolver_ws_connections 1\n"));
+        assert!(body.contains("did_resolver_cache_entries 1\n"));
+    }
+}
@@ -1,8 +1,11 @@
 use crate::{config::Config, SharedData};
-use axum::{extract::State, response::IntoResponse, routing::get, Json, Router};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
 use tracing::info;
 
 pub(crate) mod http;
+pub(crate) mod metrics;
 pub(crate) mod websocket;
 
 pub fn application_routes(shared_data: &SharedData, config: &Config) -> Router {
@@ -10,7 +13,7 @@ pub fn application_routes(shared_data: &SharedData, config: &Config) -> Router {
 
     if config.enable_websocket_endpoint {
         info!("Enabling WebSocket Resolver endpoint");
-        app = app.route("/ws", get(websocket::websocket_handler));
+        app = app.route(&config.websocket_path, get(websocket::websocket_handler));
     }
 
     if config.enable_http_endpoint {
@@ -18,9 +21,19 @@ pub fn application_routes(shared_data: &SharedData, config: &Config) -> Router {
         app = app.route("/resolve/{did}", get(http::resolver_handler));
     }
 
-    Router::new()
-        .nest("/did/v1", app)
-        .with_state(shared_data.to_owned())
+    if config.enable_metrics_endpoint {
+        info!("Enabling Prometheus metrics endpoint");
+        app = app.route("/metrics", get(metrics::metrics_handler));
+    }
+
+    // axum can't nest at the root
+    if config.path_prefix.is_empty() {
+        app.with_state(shared_data.to_owned())
+    } else {
+        Router::new()
+            .nest(&config.path_prefix, app)
+            .with_state(shared_data.to_owned())
+    }
 }
 
 pub async fn health_checker_handler(State(state): State<SharedData>) -> impl IntoResponse {
@@ -36,3 +49,127 @@ pub async fn health_checker_handler(State(state): State<SharedData>) -> impl Int
     });
     Json(response_json)
 }
+
+/// The last deep health check result, reused for ttl
+/// The endpoint is unauthenticated and each check makes outbound requests, so probes (or anyone
+/// else) can't trigger more than one check per ttl, and only one check runs at a time
+pub struct DeepHealth {
+    ttl: Duration,
+    last: Mutex<Option<(Instant, StatusCode, serde_json::Value)>>,
+}
+
+impl DeepHealth {
+    pub fn new(ttl: Duration) -> Self {
+        DeepHealth {
+            ttl,
+            last: Mutex::new(None),
+        }
+    }
+}
+
+impl Default for DeepHealth {
+    fn default() -> Self {
+        DeepHealth::new(Duration::from_secs(30))
+    }
+}
+
+/// Deep health check, resolves a sample DID for each supported method
+/// Returns 503 if any method is unhealthy, with the status of each method
+/// The result is cached for deep_health_cache_ttl
+pub async fn deep_health_checker_handler(State(state): State<SharedData>) -> impl IntoResponse {
+    // Held while checking, concurrent probes wait for the result
+    let mut last = state.deep_health.last.lock().await;
+    if let Some((checked, status_code, response_json)) = last.as_ref() {
+        if checked.elapsed() < state.deep_health.ttl {
+            return (*status_code, Json(response_json.clone()));
+        }
+    }
+
+    let results = state.resolver.self_test().await;
+
+    let healthy = results.values().all(|result| result.is_ok());
+    let methods: serde_json::Map<String, serde_json::Value> = results
+        .into_iter()
+        .map(|(method, result)| {
+            let status = match result {
+                Ok(_) => "ok".to_string(),
+                Err(e) => e.to_string(),
+            };
+            (method.to_string(), status.into())
+        })
+        .collect();
+
+    let status_code = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let response_json = serde_json::json!({
+        "status": if healthy { "success" } else { "unhealthy" },
+        "methods": methods,
+    });
+    *last = Some((Instant::now(), status_code, response_json.clone()));
+    (status_code, Json(response_json))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use affinidi_did_resolver_cache_sdk::{config::ClientConfigBuilder, DIDCacheClient, DIDMethod};
+    use std::sync::Arc;
+
+    async fn _shared_data(key_did: &str, deep_health: Arc<DeepHealth>) -> SharedData {
+        SharedData {
+            service_start_timestamp: chrono::Utc::now(),
+            stats: Arc::new(Default::default()),
+            resolver: DIDCacheClient::new(
+                ClientConfigBuilder::default()
+                    .with_self_test_did(DIDMethod::KEY, key_did)
+                    .build(),
+            )
+            .await
+            .unwrap(),
+            response_cache: None,
+            max_frame_bytes: 65536,
+            shutdown: Default::default(),
+            rate_limiter: Default::default(),
+            deep_health,
+        }
+    }
+
+    async fn _status(state: SharedData) -> StatusCode {
+        deep_health_checker_handler(State(state))
+            .await
+            .into_response()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn deep_health_result_is_cached() {
+        let deep_health = Arc::new(DeepHealth::new(Duration::from_secs(60)));
+        let healthy = _shared_data(
+            "did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv",
+            deep_health.clone(),
+        )
+        .await;
+        let unhealthy = _shared_data("did:key:invalid", deep_health).await;
+
+        assert_eq!(_status(healthy).await, StatusCode::OK);
+        // Within the ttl the cached result is returned without checking again
+        assert_eq!(_status(unhealthy).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn deep_health_rechecks_after_ttl() {
+        let deep_health = Arc::new(DeepHealth::new(Duration::ZERO));
+        let healthy = _shared_data(
+            "did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv",
+            deep_health.clone(),
+        )
+        .await;
+        let unhealthy = _shared_data("did:key:invalid", deep_health).await;
+
+        assert_eq!(_status(healthy).await, StatusCode::OK);
+        assert_eq!(_status(unhealthy).await, StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
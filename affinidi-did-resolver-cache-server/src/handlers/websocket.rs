@@ -1,117 +1,522 @@
 use affinidi_did_resolver_cache_sdk::networking::{
-    WSRequest, WSResponse, WSResponseError, WSResponseType,
+    request_hash, WSRequest, WSResponseError, WSResponseType, UNSUPPORTED_PROTOCOL,
+    WS_PROTOCOL_VERSION,
 };
 use axum::{
     extract::{
-        ws::{Message, WebSocket},
+        ws::{close_code, CloseFrame, Message, WebSocket},
         State, WebSocketUpgrade,
     },
     response::IntoResponse,
 };
-use blake2::{Blake2s256, Digest};
+use http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderMap, StatusCode};
 use tokio::select;
+use tokio_tungstenite::tungstenite::{error::CapacityError, Error as WsError};
 use tracing::{debug, info, span, warn, Instrument};
 
-use crate::SharedData;
+use crate::{response_cache::response_frame, session::Session, SharedData};
+
+/// Error sent back when a client exceeds the rate limit, the connection is kept open
+pub const RATE_LIMITED: &str = "rate_limited";
 
 // Handles the switching of the protocol to a websocket connection
 pub async fn websocket_handler(
-    //session: Session,
+    session: Session,
     ws: WebSocketUpgrade,
+    headers: HeaderMap,
     State(state): State<SharedData>,
 ) -> impl IntoResponse {
     let _span = span!(
         tracing::Level::DEBUG,
         "websocket_handler",
-        // session = session.session_id
+        session = session.session_id
     );
-    /*async move { ws.on_upgrade(move |socket| handle_socket(socket, state, session)) }
+
+    if state.shutdown.is_cancelled() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Server is shutting down".to_string(),
+        )
+            .into_response();
+    }
+
+    if !_supports_protocol(&headers) {
+        warn!(
+            "ws: Client doesn't support protocol version ({})",
+            WS_PROTOCOL_VERSION
+        );
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "{}, expected ({})",
+                UNSUPPORTED_PROTOCOL, WS_PROTOCOL_VERSION
+            ),
+        )
+            .into_response();
+    }
+
+    async move {
+        // Oversized messages are rejected before they are buffered or parsed
+        ws.protocols([WS_PROTOCOL_VERSION])
+            .max_message_size(state.max_frame_bytes)
+            .max_frame_size(state.max_frame_bytes)
+            .on_upgrade(move |socket| handle_socket(socket, state, session))
+    }
     .instrument(_span)
-    .await*/
+    .await
+    .into_response()
+}
+
+/// Can we talk to this client?
+/// Clients that don't offer any subprotocol predate protocol versioning and speak the
+/// original protocol, so they are accepted. Otherwise [WS_PROTOCOL_VERSION] must be offered
+fn _supports_protocol(headers: &HeaderMap) -> bool {
+    let mut offered = headers
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .peekable();
 
-    async move { ws.on_upgrade(move |socket| handle_socket(socket, state)) }
-        .instrument(_span)
-        .await
+    offered.peek().is_none() || offered.any(|protocol| protocol == WS_PROTOCOL_VERSION)
+}
+
+/// Close frame sent to the client when a message can't be received
+/// Oversized messages are closed with 1009 (message too big), anything else with 1011
+fn _close_frame(error: axum::Error) -> CloseFrame {
+    match error.into_inner().downcast::<WsError>().map(|e| *e) {
+        Ok(WsError::Capacity(CapacityError::MessageTooLong { size, max_size })) => CloseFrame {
+            code: close_code::SIZE,
+            reason: format!(
+                "Message ({} bytes) exceeds max_frame_bytes ({} bytes)",
+                size, max_size
+            )
+            .into(),
+        },
+        Ok(e) => CloseFrame {
+            code: close_code::ERROR,
+            reason: format!("Error receiving message: {}", e).into(),
+        },
+        Err(e) => CloseFrame {
+            code: close_code::ERROR,
+            reason: format!("Error receiving message: {}", e).into(),
+        },
+    }
 }
 
 /// WebSocket state machine. This is spawned per connection.
-//async fn handle_socket(mut socket: WebSocket, state: SharedData, session: Session) {
-async fn handle_socket(mut socket: WebSocket, state: SharedData) {
+async fn handle_socket(mut socket: WebSocket, state: SharedData, session: Session) {
     let _span = span!(
         tracing::Level::DEBUG,
         "handle_socket",
-        //session = session.session_id
+        session = session.session_id
     );
     async move {
         state.stats().await.increment_ws_opened();
         info!("Websocket connection established");
 
+        // Clean: the client closed the connection, or the server is shutting down
+        // Otherwise it was dropped or closed on an error (e.g. an oversized message)
+        let mut clean = false;
         loop {
             select! {
+                // Only checked between messages, so a resolution in progress is answered first
+                _ = state.shutdown.cancelled() => {
+                    info!("ws: Server is shutting down, closing connection");
+                    let _ = socket.send(Message::Close(Some(CloseFrame {
+                        code: close_code::AWAY,
+                        reason: "Server is shutting down".into(),
+                    }))).await;
+                    clean = true;
+                    break;
+                }
                 value = socket.recv() => {
-                    if let Some(msg) = value {
-                        if let Ok(msg) = msg {
-                            if let Message::Text(msg) = msg {
-                                debug!("ws: Received text message: {:?}", msg);
-                                let request: WSRequest = match serde_json::from_str(&msg) {
-                                    Ok(request) => request,
-                                    Err(e) => {
-                                        warn!("ws: Error parsing message: {:?}", e);
-                                        break;
-                                    }
-                                };
-
-                                match state.resolver.resolve(&request.did).await {
-                                    Ok(response) => {
-                                        let message = WSResponseType::Response(WSResponse {
-                                            did: response.did.clone(),
-                                            hash: response.did_hash,
-                                            document: response.doc,
-                                        });
-                                        let mut stats = state.stats().await;
-                                        stats.increment_resolver_success();
-                                        if response.cache_hit { stats.increment_cache_hit();}
-                                        stats.increment_did_method_success(response.method);
-                                        drop(stats);
-                                        info!("resolved DID: ({}) cache_hit?({})", response.did, response.cache_hit);
-                                        if let Err(e) = socket.send(Message::Text(serde_json::to_string(&message).unwrap().into())).await {
-                                            warn!("ws: Error sending response: {:?}", e);
-                                            break;
-                                        } else {
-                                            debug!("Sent response: {:?}", message);
-                                        }
-                                    }
-                                    Err(e) => {
-                                        // Couldn't resolve the DID, send an error back
-                                        let mut hasher = Blake2s256::new();
-                                        hasher.update(request.did.clone());
-                                        let did_hash = format!("{:x}", hasher.finalize());
-                                        warn!("Couldn't resolve DID: ({}) Reason: {}", &request.did, e);
-                                        state.stats().await.increment_resolver_error();
-                                        if let Err(e) = socket.send(Message::Text(serde_json::to_string(&WSResponseType::Error(WSResponseError {did: request.did, hash: did_hash, error: e.to_string()})).unwrap().into())).await {
-                                            warn!("ws: Error sending error response: {:?}", e);
-                                            break;
-                                        }
-                                    }
-                                }
+                    let msg = match value {
+                        Some(Ok(Message::Text(msg))) => msg,
+                        Some(Ok(Message::Close(frame))) => {
+                            info!("ws: Client closed the connection: {:?}", frame);
+                            // tungstenite queued the close reply, reading again sends it and ends
+                            // the connection, completing the close handshake
+                            let _ = socket.recv().await;
+                            clean = true;
+                            break;
+                        }
+                        Some(Ok(Message::Ping(payload))) => {
+                            debug!("ws: Received ping");
+                            if let Err(e) = socket.send(Message::Pong(payload)).await {
+                                warn!("ws: Error sending pong: {:?}", e);
+                                break;
+                            }
+                            continue;
+                        }
+                        Some(Ok(Message::Pong(_))) => continue,
+                        Some(Ok(Message::Binary(_))) => {
+                            warn!("ws: Received binary message, ignoring");
+                            continue;
+                        }
+                        Some(Err(e)) => {
+                            let close = _close_frame(e);
+                            warn!("ws: Closing connection: {}", close.reason);
+                            let _ = socket.send(Message::Close(Some(close))).await;
+                            break;
+                        }
+                        None => {
+                            debug!("ws: Connection dropped without a close frame");
+                            break;
+                        }
+                    };
+
+                    debug!("ws: Received text message: {:?}", msg);
+                    let request: WSRequest = match serde_json::from_str(&msg) {
+                        Ok(request) => request,
+                        Err(e) => {
+                            warn!("ws: Error parsing message: {:?}", e);
+                            break;
+                        }
+                    };
+
+                    if let Err(retry_after) = state.rate_limiter.check(session.remote_addr.ip()) {
+                        warn!("ws: Rate limit exceeded for ({})", session.remote_addr.ip());
+                        let error = WSResponseType::Error(WSResponseError {
+                            request_id: request.request_id,
+                            hash: request_hash(&request.did),
+                            did: request.did,
+                            code: RATE_LIMITED.into(),
+                            error: RATE_LIMITED.into(),
+                            retry_after_ms: Some(retry_after.as_millis().max(1) as u64),
+                        });
+                        if let Err(e) = socket.send(Message::Text(serde_json::to_string(&error).unwrap().into())).await {
+                            warn!("ws: Error sending error response: {:?}", e);
+                            break;
+                        }
+                        continue;
+                    }
+
+                    match state.resolver.resolve(&request.did).await {
+                        Ok(response) => {
+                            let frame = response_frame(state.response_cache.as_ref(), &response, &request).await;
+                            let mut stats = state.stats().await;
+                            stats.increment_resolver_success();
+                            if response.cache_hit { stats.increment_cache_hit();}
+                            stats.increment_did_method_success(response.method);
+                            drop(stats);
+                            info!("resolved DID: ({}) cache_hit?({})", response.did, response.cache_hit);
+                            if let Err(e) = socket.send(Message::Text(frame)).await {
+                                warn!("ws: Error sending response: {:?}", e);
+                                break;
                             } else {
-                                warn!("Received non-text message, ignoring");
-                                continue;
+                                debug!("Sent response for DID: ({})", response.did);
+                            }
+                        }
+                        Err(e) => {
+                            // Couldn't resolve the DID, send an error back
+                            let hash = request_hash(&request.did);
+                            warn!("Couldn't resolve DID: ({}) Reason: {}", &request.did, e);
+                            state.stats().await.increment_resolver_error();
+                            if let Err(e) = socket.send(Message::Text(serde_json::to_string(&WSResponseType::Error(WSResponseError {request_id: request.request_id, did: request.did, hash, code: e.code().into(), error: e.to_string(), retry_after_ms: None})).unwrap().into())).await {
+                                warn!("ws: Error sending error response: {:?}", e);
+                                break;
                             }
                         }
-                    } else {
-                        debug!("Received None, closing connection");
-                        break;
                     }
                 }
             }
         }
 
         // We're done, close the connection
-        state.stats().await.increment_ws_closed();
+        state.stats().await.increment_ws_closed(clean);
 
-        info!("Websocket connection closed");
+        info!("Websocket connection closed: clean({})", clean);
     }
     .instrument(_span)
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_limit::RateLimiter;
+    use affinidi_did_resolver_cache_sdk::{config::ClientConfigBuilder, DIDCacheClient};
+    use axum::{routing::get, Router};
+    use futures_util::{SinkExt, StreamExt};
+    use http::HeaderValue;
+    use std::{net::SocketAddr, sync::Arc};
+    use tokio_tungstenite::tungstenite::{
+        client::IntoClientRequest, protocol::frame::coding::CloseCode, Message as WsMessage,
+    };
+
+    fn _headers(protocols: &[&'static str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for protocol in protocols {
+            headers.append(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static(protocol));
+        }
+        headers
+    }
+
+    #[test]
+    fn protocol_negotiation() {
+        assert!(_supports_protocol(&_headers(&[WS_PROTOCOL_VERSION])));
+        assert!(_supports_protocol(&_headers(&[
+            "affinidi-did-resolver.v2, affinidi-did-resolver.v1"
+        ])));
+        // Clients from before protocol versioning
+        assert!(_supports_protocol(&_headers(&[])));
+
+        assert!(!_supports_protocol(&_headers(&[
+            "affinidi-did-resolver.v2"
+        ])));
+    }
+
+    type ClientSocket = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    async fn _state(max_frame_bytes: usize, rate_limiter: RateLimiter) -> SharedData {
+        SharedData {
+            service_start_timestamp: chrono::Utc::now(),
+            stats: Arc::new(Default::default()),
+            resolver: DIDCacheClient::new(ClientConfigBuilder::default().build())
+                .await
+                .unwrap(),
+            response_cache: None,
+            max_frame_bytes,
+            shutdown: Default::default(),
+            rate_limiter,
+            deep_health: Default::default(),
+        }
+    }
+
+    /// Starts a websocket server, returns the address it listens on
+    async fn _serve(state: SharedData) -> SocketAddr {
+        let app = Router::new()
+            .route("/ws", get(websocket_handler))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+        });
+        address
+    }
+
+    /// Starts a websocket server and connects to it
+    async fn _connect(max_frame_bytes: usize) -> (SharedData, ClientSocket) {
+        _connect_with(_state(max_frame_bytes, RateLimiter::default()).await).await
+    }
+
+    async fn _connect_with(state: SharedData) -> (SharedData, ClientSocket) {
+        let address = _serve(state.clone()).await;
+
+        let mut request = format!("ws://{}/ws", address)
+            .into_client_request()
+            .unwrap();
+        request.headers_mut().insert(
+            SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_static(WS_PROTOCOL_VERSION),
+        );
+        let (websocket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+        (state, websocket)
+    }
+
+    #[tokio::test]
+    async fn response_echoes_request_id() {
+        let (_, mut websocket) = _connect(65536).await;
+
+        for (did, request_id) in [
+            (
+                "did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv",
+                "abc",
+            ),
+            ("did:unknown:123", "def"),
+        ] {
+            let request = WSRequest {
+                did: did.to_string(),
+                request_id: request_id.to_string(),
+            };
+            websocket
+                .send(WsMessage::text(serde_json::to_string(&request).unwrap()))
+                .await
+                .unwrap();
+
+            let Some(Ok(WsMessage::Text(frame))) = websocket.next().await else {
+                panic!("expected a text frame");
+            };
+            let echoed = match serde_json::from_str(frame.as_str()).unwrap() {
+                WSResponseType::Response(response) => response.request_id,
+                WSResponseType::Error(error) => {
+                    assert_eq!(error.code, "unsupported_method");
+                    error.request_id
+                }
+                WSResponseType::Unknown(unknown) => panic!("unexpected {:?}", unknown),
+            };
+            assert_eq!(echoed, request_id);
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_limited_requests_get_an_error() {
+        // One request, then nothing for a long time
+        let state = _state(65536, RateLimiter::new(0.001, 1)).await;
+        let (_, mut websocket) = _connect_with(state).await;
+
+        let mut errors = Vec::new();
+        for request_id in ["1", "2", "3"] {
+            let request = WSRequest {
+                did: "did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv".to_string(),
+                request_id: request_id.to_string(),
+            };
+            websocket
+                .send(WsMessage::text(serde_json::to_string(&request).unwrap()))
+                .await
+                .unwrap();
+
+            let Some(Ok(WsMessage::Text(frame))) = websocket.next().await else {
+                panic!("expected a text frame");
+            };
+            if let WSResponseType::Error(error) = serde_json::from_str(frame.as_str()).unwrap() {
+                assert_eq!(error.request_id, request_id);
+                assert_eq!(error.code, RATE_LIMITED);
+                // The bucket refills one token every 1000 seconds
+                assert!(error.retry_after_ms.is_some_and(|ms| ms > 900_000));
+                errors.push(error.error);
+            }
+        }
+
+        // The connection stays open, later requests are rejected
+        assert_eq!(errors, [RATE_LIMITED, RATE_LIMITED]);
+    }
+
+    #[tokio::test]
+    async fn shutdown_closes_connection() {
+        let (state, mut websocket) = _connect(65536).await;
+        // Let the server side of the connection start
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(state.stats().await.ws_current(), 1);
+
+        state.shutdown.cancel();
+        match websocket.next().await {
+            Some(Ok(WsMessage::Close(Some(frame)))) => {
+                assert_eq!(frame.code, CloseCode::Away);
+                assert_eq!(frame.reason.as_str(), "Server is shutting down");
+            }
+            other => panic!("expected a close frame, got {:?}", other),
+        }
+        while websocket.next().await.is_some() {}
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(state.stats().await.ws_current(), 0);
+
+        // New connections are refused
+        let address = _serve(state).await;
+        let result = tokio_tungstenite::connect_async(format!("ws://{}/ws", address)).await;
+        assert!(matches!(
+            result,
+            Err(WsError::Http(response)) if response.status() == StatusCode::SERVICE_UNAVAILABLE
+        ));
+    }
+
+    #[tokio::test]
+    async fn oversized_message_closes_connection() {
+        let (state, mut websocket) = _connect(1024).await;
+        websocket
+            .send(WsMessage::text("x".repeat(2048)))
+            .await
+            .unwrap();
+
+        match websocket.next().await {
+            Some(Ok(WsMessage::Close(Some(frame)))) => {
+                assert_eq!(frame.code, CloseCode::Size);
+                assert_eq!(
+                    frame.reason.as_str(),
+                    "Message (2048 bytes) exceeds max_frame_bytes (1024 bytes)"
+                );
+            }
+            other => panic!("expected a close frame, got {:?}", other),
+        }
+
+        // The server ends the session once the connection is closed
+        while websocket.next().await.is_some() {}
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(state
+            .stats()
+            .await
+            .to_prometheus(0)
+            .contains("did_resolver_ws_connections_closed_total{close=\"abrupt\"} 1\n"));
+    }
+
+    /// Waits for the server side of the connection to end, returns (clean, abrupt) closes
+    async fn _closes(state: &SharedData) -> (i64, i64) {
+        for _ in 0..50 {
+            let stats = state.stats().await;
+            if stats.ws_current() == 0 {
+                return (stats.ws_closed_clean(), stats.ws_closed_abrupt());
+            }
+            drop(stats);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        panic!("connection wasn't closed");
+    }
+
+    #[tokio::test]
+    async fn client_close_is_acknowledged() {
+        let (state, mut websocket) = _connect(65536).await;
+        websocket.close(None).await.unwrap();
+
+        // The server answers with its own close frame, then the stream ends
+        let reply = websocket.next().await;
+        assert!(
+            matches!(reply, Some(Ok(WsMessage::Close(_)))),
+            "{:?}",
+            reply
+        );
+        while websocket.next().await.is_some() {}
+        assert_eq!(_closes(&state).await, (1, 0));
+    }
+
+    #[tokio::test]
+    async fn ping_is_answered_and_binary_ignored() {
+        let (state, mut websocket) = _connect(65536).await;
+
+        websocket
+            .send(WsMessage::Ping(b"keepalive".to_vec().into()))
+            .await
+            .unwrap();
+        match websocket.next().await {
+            Some(Ok(WsMessage::Pong(payload))) => assert_eq!(payload.as_ref(), b"keepalive"),
+            other => panic!("expected a pong, got {:?}", other),
+        }
+
+        // Binary messages are ignored, the connection stays usable
+        websocket
+            .send(WsMessage::Binary(vec![1, 2, 3].into()))
+            .await
+            .unwrap();
+        let request = WSRequest {
+            did: "did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv".to_string(),
+            request_id: "1".to_string(),
+        };
+        websocket
+            .send(WsMessage::text(serde_json::to_string(&request).unwrap()))
+            .await
+            .unwrap();
+        assert!(matches!(
+            websocket.next().await,
+            Some(Ok(WsMessage::Text(_)))
+        ));
+        assert_eq!(state.stats().await.ws_current(), 1);
+    }
+
+    #[tokio::test]
+    async fn dropped_connection_is_abrupt() {
+        let (state, websocket) = _connect(65536).await;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        // No close frame, the TCP connection just goes away
+        drop(websocket);
+        assert_eq!(_closes(&state).await, (0, 1));
+    }
+}
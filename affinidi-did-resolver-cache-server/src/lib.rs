@@ -6,14 +6,20 @@ use axum::{
     http::request::Parts,
 };
 use chrono::{DateTime, Utc};
+use handlers::DeepHealth;
+use rate_limit::RateLimiter;
+use response_cache::ResponseCache;
 use session::SessionError;
 use statistics::Statistics;
 use tokio::sync::{Mutex, MutexGuard};
+use tokio_util::sync::CancellationToken;
 
 pub(crate) mod common;
 pub mod config;
 pub mod errors;
 pub mod handlers;
+pub mod rate_limit;
+pub mod response_cache;
 pub mod server;
 pub mod session;
 pub mod statistics;
@@ -23,6 +29,15 @@ pub struct SharedData {
     pub service_start_timestamp: DateTime<Utc>,
     pub stats: Arc<Mutex<Statistics>>,
     pub resolver: DIDCacheClient,
+    pub response_cache: Option<ResponseCache>,
+    /// Largest websocket message accepted from a client in bytes
+    pub max_frame_bytes: usize,
+    /// Cancelled when the server shuts down, websocket connections are then closed
+    pub shutdown: CancellationToken,
+    /// Limits websocket resolutions per remote IP address
+    pub rate_limiter: RateLimiter,
+    /// Cached result of the deep health check
+    pub deep_health: Arc<DeepHealth>,
 }
 
 impl<S> FromRequestParts<S> for SharedData
@@ -38,7 +53,7 @@ where
 }
 
 impl SharedData {
-    pub async fn stats(&self) -> MutexGuard<Statistics> {
+    pub async fn stats(&self) -> MutexGuard<'_, Statistics> {
         self.stats.lock().await
     }
 }
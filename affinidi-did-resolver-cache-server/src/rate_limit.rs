@@ -0,0 +1,117 @@
+//! Token bucket rate limiting of DID resolutions per remote IP address
+//!
+//! Each remote IP address has a bucket of `burst` tokens, refilled at `per_second` tokens a second.
+//! A resolution takes a token, and is rejected when the bucket is empty.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Buckets are pruned once there are this many, so the map can't grow without bound
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Per remote IP address rate limiter, cheap to clone (the buckets are shared)
+/// per_second: Tokens added to each bucket per second, 0 disables rate limiting
+/// burst: Size of each bucket, the number of resolutions allowed at once
+#[derive(Clone, Debug, Default)]
+pub struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    buckets: Arc<Mutex<HashMap<IpAddr, Bucket>>>,
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter, per_second of 0 disables rate limiting
+    /// A burst of 0 is treated as 1
+    pub fn new(per_second: f64, burst: u32) -> Self {
+        RateLimiter {
+            per_second: per_second.max(0.0),
+            burst: burst.max(1).into(),
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Is rate limiting enabled?
+    pub fn is_enabled(&self) -> bool {
+        self.per_second > 0.0
+    }
+
+    /// Takes a token for the address
+    /// Returns how long until the next token if the address has exceeded the rate limit
+    pub fn check(&self, address: IpAddr) -> Result<(), Duration> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() >= PRUNE_THRESHOLD {
+            // A bucket that has had time to refill is the same as a new one
+            let refill_secs = self.burst / self.per_second;
+            buckets.retain(|_, bucket| {
+                now.duration_since(bucket.last_refill).as_secs_f64() < refill_secs
+            });
+        }
+
+        let bucket = buckets.entry(address).or_insert(Bucket {
+            tokens: self.burst,
+            last_refill: now,
+        });
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.per_second,
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const CLIENT_A: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const CLIENT_B: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    #[test]
+    fn disabled() {
+        let limiter = RateLimiter::default();
+        assert!(!limiter.is_enabled());
+        assert!((0..1000).all(|_| limiter.check(CLIENT_A).is_ok()));
+    }
+
+    #[test]
+    fn burst_then_refill() {
+        let limiter = RateLimiter::new(20.0, 3);
+
+        assert!((0..3).all(|_| limiter.check(CLIENT_A).is_ok()));
+        // One token is added every 50ms
+        let retry_after = limiter.check(CLIENT_A).unwrap_err();
+        assert!(
+            retry_after > Duration::from_millis(40) && retry_after <= Duration::from_millis(50)
+        );
+        // Each address has its own bucket
+        assert!(limiter.check(CLIENT_B).is_ok());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(limiter.check(CLIENT_A).is_ok());
+        assert!(limiter.check(CLIENT_A).is_err());
+    }
+}
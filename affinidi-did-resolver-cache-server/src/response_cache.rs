@@ -0,0 +1,306 @@
+//! Optional cache of pre-serialized websocket responses
+//!
+//! Resolving a cached DID is cheap, but serializing the DID Document into a [WSResponseType]
+//! JSON frame happens on every request. When enabled, the serialized DID Document is cached by
+//! did_hash so that a cache hit only serializes the small frame around it.
+//!
+//! Entries are removed whenever the DID Document is evicted from the resolver cache, and are
+//! only used when the resolver reports a cache hit, so a response never outlives its document.
+//!
+//! The document and metadata are cached already serialized, and each frame is built from them
+//! with the request_id, DID and hash of its request. The DID and hash are echoed as requested
+//! (not canonicalized), older clients match responses by the hash of the DID they sent.
+
+use crate::config::Config;
+use affinidi_did_resolver_cache_sdk::{
+    cache_key::CacheKey,
+    metadata::CacheEntry,
+    networking::{request_hash, WSRequest},
+    ResolveResponse,
+};
+use axum::extract::ws::Utf8Bytes;
+use moka::future::Cache;
+use serde::Serialize;
+use serde_json::value::{to_raw_value, RawValue};
+use std::{sync::Arc, time::Duration};
+
+/// The serialized parts of a response that are the same for every request
+/// raw: Only set when it differs from the document (e.g. did:web fields the document drops)
+#[derive(Debug)]
+pub struct CachedResponse {
+    document: Box<RawValue>,
+    resolution_metadata: Box<RawValue>,
+    document_metadata: Box<RawValue>,
+    raw: Option<Box<RawValue>>,
+}
+
+impl CachedResponse {
+    /// Serializes the document and metadata of a resolved DID Document
+    pub fn new(response: &ResolveResponse) -> Self {
+        let raw = serde_json::to_value(&response.doc)
+            .is_ok_and(|doc| doc != response.raw)
+            .then(|| to_raw_value(&response.raw).unwrap());
+        CachedResponse {
+            document: to_raw_value(&response.doc).unwrap(),
+            resolution_metadata: to_raw_value(&response.resolution_metadata).unwrap(),
+            document_metadata: to_raw_value(&response.document_metadata).unwrap(),
+            raw,
+        }
+    }
+}
+
+/// Serialized response parts, keyed by did_hash
+pub type ResponseCache = Cache<String, Arc<CachedResponse>>;
+
+/// Builds the DID Document cache for the resolver, and the response cache if it is enabled
+/// Evictions from the DID Document cache also remove the response
+pub fn build_caches(config: &Config) -> (Cache<CacheKey, CacheEntry>, Option<ResponseCache>) {
+    let ttl = Duration::from_secs(config.cache_expire.into());
+    let doc_cache = Cache::builder()
+        .time_to_live(ttl)
+        .max_capacity(config.cache_capacity_count.into())
+        // Needed for clear_method()
+        .support_invalidation_closures();
+
+    if !config.cache_responses {
+        return (doc_cache.build(), None);
+    }
+
+    let responses: ResponseCache = Cache::builder()
+        .time_to_live(ttl)
+        .max_capacity(config.cache_capacity_count.into())
+        .build();
+
+    let listener_cache = responses.clone();
+    let doc_cache = doc_cache
+        .async_eviction_listener(move |key: Arc<CacheKey>, _, _| {
+            let responses = listener_cache.clone();
+            // The cache key displays as the did_hash
+            Box::pin(async move { responses.invalidate(&key.to_string()).await })
+        })
+        .build();
+
+    (doc_cache, Some(responses))
+}
+
+/// A `WSResponseType::Response` frame, serialized from the cached parts
+/// The fields (and their order) are those of
+/// [WSResponse](affinidi_did_resolver_cache_sdk::networking::WSResponse)
+#[derive(Serialize)]
+enum ResponseFrame<'a> {
+    Response(FrameBody<'a>),
+}
+
+/// See [ResponseFrame]
+#[derive(Serialize)]
+struct FrameBody<'a> {
+    request_id: &'a str,
+    did: &'a str,
+    hash: &'a str,
+    document: &'a RawValue,
+    resolution_metadata: &'a RawValue,
+    document_metadata: &'a RawValue,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw: Option<&'a RawValue>,
+}
+
+/// Builds the frame for one request from the serialized parts
+fn _frame(parts: &CachedResponse, request: &WSRequest) -> Utf8Bytes {
+    let frame = ResponseFrame::Response(FrameBody {
+        request_id: &request.request_id,
+        did: &request.did,
+        hash: &request_hash(&request.did),
+        document: &parts.document,
+        resolution_metadata: &parts.resolution_metadata,
+        document_metadata: &parts.document_metadata,
+        raw: parts.raw.as_deref(),
+    });
+    serde_json::to_string(&frame).unwrap().into()
+}
+
+/// Serializes a resolved DID Document to a websocket response frame
+/// request: its request_id, DID and hash are echoed back so the client can match the response
+pub fn serialize_response(response: &ResolveResponse, request: &WSRequest) -> Utf8Bytes {
+    _frame(&CachedResponse::new(response), request)
+}
+
+/// Returns the websocket response frame for a resolved DID Document
+/// Uses the cached parts on a resolver cache hit, otherwise serializes them (and caches them)
+pub async fn response_frame(
+    responses: Option<&ResponseCache>,
+    response: &ResolveResponse,
+    request: &WSRequest,
+) -> Utf8Bytes {
+    let Some(responses) = responses else {
+        return serialize_response(response, request);
+    };
+
+    if response.cache_hit {
+        if let Some(parts) = responses.get(&response.did_hash).await {
+            return _frame(&parts, request);
+        }
+    }
+
+    let parts = Arc::new(CachedResponse::new(response));
+    responses
+        .insert(response.did_hash.clone(), parts.clone())
+        .await;
+    _frame(&parts, request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use affinidi_did_resolver_cache_sdk::{
+        config::ClientConfigBuilder,
+        networking::{WSResponse, WSResponseType},
+        DIDCacheClient,
+    };
+
+    const DID_KEY: &str = "did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv";
+
+    async fn setup() -> (DIDCacheClient, ResponseCache) {
+        let config = Config {
+            cache_responses: true,
+            ..Default::default()
+        };
+        let (doc_cache, responses) = build_caches(&config);
+        let client = DIDCacheClient::with_cache(ClientConfigBuilder::default().build(), doc_cache)
+            .await
+            .unwrap();
+        (client, responses.unwrap())
+    }
+
+    fn request(did: &str, request_id: &str) -> WSRequest {
+        WSRequest {
+            did: did.to_string(),
+            request_id: request_id.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_hit_uses_cached_frame() {
+        let (client, responses) = setup().await;
+
+        let response = client.resolve(DID_KEY).await.unwrap();
+        let frame = response_frame(Some(&responses), &response, &request(DID_KEY, "")).await;
+        assert!(responses.get(&response.did_hash).await.is_some());
+
+        let response = client.resolve(DID_KEY).await.unwrap();
+        assert!(response.cache_hit);
+        assert_eq!(
+            response_frame(Some(&responses), &response, &request(DID_KEY, "")).await,
+            frame
+        );
+        assert_eq!(frame, serialize_response(&response, &request(DID_KEY, "")));
+    }
+
+    #[tokio::test]
+    async fn frame_matches_ws_response() {
+        let (client, _) = setup().await;
+        let response = client.resolve(DID_KEY).await.unwrap();
+
+        // The frame must stay what serializing the SDK's WSResponse gives
+        let message = WSResponseType::Response(Box::new(WSResponse {
+            request_id: "1".into(),
+            did: DID_KEY.into(),
+            hash: request_hash(DID_KEY),
+            document: response.doc.clone(),
+            resolution_metadata: response.resolution_metadata.clone(),
+            document_metadata: response.document_metadata.clone(),
+            raw: None,
+        }));
+        assert_eq!(
+            serialize_response(&response, &request(DID_KEY, "1")).as_str(),
+            serde_json::to_string(&message).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_frame_echoes_request_id() {
+        let (client, responses) = setup().await;
+
+        client.resolve(DID_KEY).await.unwrap();
+        let response = client.resolve(DID_KEY).await.unwrap();
+        response_frame(Some(&responses), &response, &request(DID_KEY, "first")).await;
+
+        // Needs escaping in JSON
+        let request_id = "req-\"2\"";
+        let frame =
+            response_frame(Some(&responses), &response, &request(DID_KEY, request_id)).await;
+        assert_eq!(
+            frame,
+            serialize_response(&response, &request(DID_KEY, request_id))
+        );
+        match serde_json::from_str(frame.as_str()).unwrap() {
+            WSResponseType::Response(response) => assert_eq!(response.request_id, request_id),
+            WSResponseType::Error(_) | WSResponseType::Unknown(_) => {
+                panic!("expected a response")
+            }
+        }
+    }
+
+    /// The DID and hash are echoed as requested, older clients match responses by the hash
+    #[tokio::test]
+    async fn frame_echoes_requested_did() {
+        let (client, responses) = setup().await;
+        let requested = DID_KEY.replace("did:key", "did:KEY");
+
+        let response = client.resolve(&requested).await.unwrap();
+        assert_eq!(response.did, DID_KEY);
+        for _ in 0..2 {
+            let frame = response_frame(Some(&responses), &response, &request(&requested, "")).await;
+            match serde_json::from_str(frame.as_str()).unwrap() {
+                WSResponseType::Response(response) => {
+                    assert_eq!(response.did, requested);
+                    assert_eq!(response.hash, request_hash(&requested));
+                }
+                _ => panic!("expected a response"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn eviction_removes_frame() {
+        let (client, responses) = setup().await;
+
+        let response = client.resolve(DID_KEY).await.unwrap();
+        response_frame(Some(&responses), &response, &request(DID_KEY, "")).await;
+
+        client.remove(DID_KEY).await;
+        client.get_cache().run_pending_tasks().await;
+        assert!(responses.get(&response.did_hash).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn raw_only_sent_when_it_differs() {
+        let parse = |frame: Utf8Bytes| match serde_json::from_str(frame.as_str()).unwrap() {
+            WSResponseType::Response(response) => response,
+            _ => panic!("expected a response"),
+        };
+        let (client, _) = setup().await;
+        let response = client.resolve(DID_KEY).await.unwrap();
+        assert!(parse(serialize_response(&response, &request(DID_KEY, "")))
+            .raw
+            .is_none());
+
+        let did = "did:web:example.com";
+        let body = serde_json::json!({ "@context": "https://www.w3.org/ns/did/v1", "id": did });
+        let resolver = std::collections::HashMap::from([(
+            "https://example.com/.well-known/did.json".to_string(),
+            body.to_string().into_bytes(),
+        )]);
+        let client = DIDCacheClient::new(
+            ClientConfigBuilder::default()
+                .with_web_resolver(Arc::new(resolver))
+                .build(),
+        )
+        .await
+        .unwrap();
+        let response = client.resolve(did).await.unwrap();
+        assert_eq!(
+            parse(serialize_response(&response, &request(did, ""))).raw,
+            Some(body)
+        );
+    }
+}
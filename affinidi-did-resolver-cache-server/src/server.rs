@@ -1,16 +1,21 @@
 use crate::{
     config::init,
-    handlers::{application_routes, health_checker_handler},
+    handlers::{
+        application_routes, deep_health_checker_handler, health_checker_handler, DeepHealth,
+    },
+    rate_limit::RateLimiter,
+    response_cache::build_caches,
     statistics::{statistics, Statistics},
     SharedData,
 };
 use affinidi_did_resolver_cache_sdk::{
-    config::ClientConfigBuilder, errors::DIDCacheError, DIDCacheClient,
+    config::ClientConfigBuilder, errors::DIDCacheError, DIDCacheClient, DIDMethod,
 };
 use axum::{routing::get, Router};
 use http::Method;
-use std::{env, net::SocketAddr, sync::Arc};
-use tokio::sync::Mutex;
+use std::{env, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{select, signal, sync::Mutex, time::Instant};
+use tokio_util::sync::CancellationToken;
 use tower_http::{
     cors::CorsLayer,
     trace::{self, TraceLayer},
@@ -70,18 +75,27 @@ pub async fn start() -> Result<(), DIDCacheError> {
     let config = init(Some(reload_handle)).expect("Couldn't initialize DID Cache!");
 
     // Use the affinidi-did-resolver-cache-sdk in local mode
-    let cache_config = ClientConfigBuilder::default()
+    let mut cache_config = ClientConfigBuilder::default()
         .with_cache_capacity(config.cache_capacity_count)
-        .with_cache_ttl(config.cache_expire)
-        .build();
+        .with_cache_ttl(config.cache_expire);
+    if let Some(did) = &config.deep_health_web_did {
+        cache_config = cache_config.with_self_test_did(DIDMethod::WEB, did);
+    }
+    let cache_config = cache_config.build();
 
-    let resolver = DIDCacheClient::new(cache_config).await?;
+    let (cache, response_cache) = build_caches(&config);
+    let resolver = DIDCacheClient::with_cache(cache_config, cache).await?;
 
     // Create the shared application State
     let shared_state = SharedData {
         service_start_timestamp: chrono::Utc::now(),
         stats: Arc::new(Mutex::new(Statistics::default())),
         resolver,
+        response_cache,
+        max_frame_bytes: config.max_frame_bytes,
+        shutdown: CancellationToken::new(),
+        rate_limiter: RateLimiter::new(config.rate_limit_per_second, config.rate_limit_burst),
+        deep_health: Arc::new(DeepHealth::new(config.deep_health_cache_ttl)),
     };
 
     // Start the statistics thread
@@ -119,13 +133,68 @@ pub async fn start() -> Result<(), DIDCacheError> {
         // Add the healthcheck route after the tracing so we don't fill up logs with healthchecks
         .route(
             "/did/healthchecker",
-            get(health_checker_handler).with_state(shared_state),
+            get(health_checker_handler).with_state(shared_state.clone()),
+        )
+        .route(
+            "/did/healthchecker/deep",
+            get(deep_health_checker_handler).with_state(shared_state.clone()),
+        );
+
+    // Stop accepting connections on shutdown, and give open connections the grace period to finish
+    let handle = axum_server::Handle::new();
+    let _handle = handle.clone();
+    let _state = shared_state.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        event!(
+            Level::INFO,
+            "Shutting down, ({}) open websocket connections, grace period ({} seconds)",
+            _state.stats().await.ws_current(),
+            config.shutdown_grace_period.as_secs()
         );
+        _state.shutdown.cancel();
+        _handle.graceful_shutdown(Some(config.shutdown_grace_period));
+    });
 
     axum_server::bind(config.listen_address.parse().unwrap())
+        .handle(handle)
         .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 
+    // Upgraded websocket connections aren't tracked by the server handle, wait for them here
+    let deadline = Instant::now() + config.shutdown_grace_period;
+    while shared_state.stats().await.ws_current() > 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    event!(
+        Level::INFO,
+        "Shutdown complete, ({}) websocket connections force closed",
+        shared_state.stats().await.ws_current()
+    );
+
     Ok(())
 }
+
+/// Resolves on SIGTERM (e.g. a redeploy) or Ctrl-C
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                event!(Level::WARN, "Couldn't listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    select! {
+        _ = signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+}
@@ -47,21 +47,22 @@ impl IntoResponse for SessionError {
 #[derive(Clone, Debug, Serialize)]
 pub struct Session {
     pub session_id: String, // Unique session transaction ID
+    pub remote_addr: SocketAddr,
 }
 
 impl<S> FromRequestParts<S> for Session
 where
     SharedData: FromRef<S>,
-    S: Send + Sync + Debug,
+    S: Send + Sync,
 {
     type Rejection = SessionError;
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        if let Some(address) = parts
+        let remote_addr = if let Some(address) = parts
             .extensions
             .get::<axum::extract::ConnectInfo<SocketAddr>>()
             .map(|ci| ci.0)
         {
-            address.to_string()
+            address
         } else {
             warn!("No remote address in request!");
             return Err(SessionError::SessionError(
@@ -73,7 +74,10 @@ where
 
         info!("{}: Connection accepted", &session_id);
 
-        let session = Session { session_id };
+        let session = Session {
+            session_id,
+            remote_addr,
+        };
 
         Ok(session)
     }
@@ -2,8 +2,8 @@
 //! Creates a parallel task that logs cache statistics based on an interval
 use crate::errors::CacheError;
 use affinidi_did_resolver_cache_sdk::DIDMethod;
+use affinidi_did_resolver_cache_sdk::{cache_key::CacheKey, metadata::CacheEntry};
 use moka::future::Cache;
-use ssi::dids::Document;
 use std::{
     collections::HashMap,
     fmt::{self, Display, Formatter},
@@ -17,6 +17,8 @@ use tracing::{debug, info, span, Instrument, Level};
 /// Contains information about the cache, websocket connections, and resolver requests
 /// ws_opened: number of opened websocket connections
 /// ws_closed: number of closed websocket connections
+/// ws_closed_clean: closed websocket connections that were closed by the client (close frame) or
+///   on shutdown, the rest were abrupt (dropped without a close frame, or closed on an error)
 /// cache_size: number of entries in the cache (approximate)
 /// resolver_success: number of successful resolver requests
 /// resolver_error: number of failed resolver requests
@@ -26,6 +28,7 @@ use tracing::{debug, info, span, Instrument, Level};
 pub struct Statistics {
     ws_opened: i64,
     ws_closed: i64,
+    ws_closed_clean: i64,
     cache_size: i64,
     resolver_success: u64,
     resolver_error: u64,
@@ -50,7 +53,7 @@ impl Display for Statistics {
             f,
             r#"
     Cache: count({}) Hits({} {:.2}%)
-    Connections: ws_open({}) ws_close({}) ws_current({})
+    Connections: ws_open({}) ws_close({} clean({}) abrupt({})) ws_current({})
     Resolver: total({}) success({}) error({})
     Methods (METHOD: COUNT): {}
             "#,
@@ -59,6 +62,8 @@ impl Display for Statistics {
             cache_hit_rate,
             self.ws_opened,
             self.ws_closed,
+            self.ws_closed_clean,
+            self.ws_closed_abrupt(),
             self.ws_opened - self.ws_closed,
             self.resolver_success + self.resolver_error,
             self.resolver_success,
@@ -77,6 +82,7 @@ impl Statistics {
         Statistics {
             ws_opened: self.ws_opened - previous.ws_opened,
             ws_closed: self.ws_closed - previous.ws_closed,
+            ws_closed_clean: self.ws_closed_clean - previous.ws_closed_clean,
             cache_size: self.cache_size - previous.cache_size,
             resolver_success: self.resolver_success - previous.resolver_success,
             resolver_error: self.resolver_error - previous.resolver_error,
@@ -95,8 +101,27 @@ impl Statistics {
     }
 
     /// Increments the number of closed websocket connections
-    pub fn increment_ws_closed(&mut self) {
+    /// clean: Closed by the client or on shutdown, rather than dropped or closed on an error
+    pub fn increment_ws_closed(&mut self, clean: bool) {
         self.ws_closed += 1;
+        if clean {
+            self.ws_closed_clean += 1;
+        }
+    }
+
+    /// Number of websocket connections closed by the client or on shutdown
+    pub fn ws_closed_clean(&self) -> i64 {
+        self.ws_closed_clean
+    }
+
+    /// Number of websocket connections dropped, or closed on an error
+    pub fn ws_closed_abrupt(&self) -> i64 {
+        self.ws_closed - self.ws_closed_clean
+    }
+
+    /// Number of currently open websocket connections
+    pub fn ws_current(&self) -> i64 {
+        self.ws_opened - self.ws_closed
     }
 
     /// Increments the number of successful resolver requests
@@ -119,7 +144,92 @@ impl Statistics {
         self.method
             .entry(method)
             .and_modify(|v| *v += 1)
-            .or_insert(0);
+            .or_insert(1);
+    }
+
+    /// Renders the statistics in the Prometheus text exposition format
+    /// cache_entries: Current number of DID Documents in the cache (read live, cache_size is
+    /// only refreshed by the statistics task)
+    pub fn to_prometheus(&self, cache_entries: u64) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, type_: &str, help: &str, samples: &[(String, String)]| {
+            out.push_str(&format!(
+                "# HELP {} {}\n# TYPE {} {}\n",
+                name, help, name, type_
+            ));
+            for (labels, value) in samples {
+                out.push_str(&format!("{}{} {}\n", name, labels, value));
+            }
+        };
+        let single = |value: String| [(String::new(), value)];
+
+        metric(
+            "did_resolver_ws_connections_opened_total",
+            "counter",
+            "Websocket connections opened",
+            &single(self.ws_opened.to_string()),
+        );
+        metric(
+            "did_resolver_ws_connections_closed_total",
+            "counter",
+            "Websocket connections closed, clean (by the client or on shutdown) or abrupt",
+            &[
+                (
+                    "{close=\"abrupt\"}".to_string(),
+                    self.ws_closed_abrupt().to_string(),
+                ),
+                (
+                    "{close=\"clean\"}".to_string(),
+                    self.ws_closed_clean.to_string(),
+                ),
+            ],
+        );
+        metric(
+            "did_resolver_ws_connections",
+            "gauge",
+            "Websocket connections currently open",
+            &single((self.ws_opened - self.ws_closed).to_string()),
+        );
+        metric(
+            "did_resolver_success_total",
+            "counter",
+            "Successful resolver requests",
+            &single(self.resolver_success.to_string()),
+        );
+        metric(
+            "did_resolver_error_total",
+            "counter",
+            "Failed resolver requests",
+            &single(self.resolver_error.to_string()),
+        );
+        metric(
+            "did_resolver_cache_hits_total",
+            "counter",
+            "Resolver requests answered from the cache",
+            &single(self.cache_hit.to_string()),
+        );
+        metric(
+            "did_resolver_cache_entries",
+            "gauge",
+            "DID Documents in the cache (approximate)",
+            &single(cache_entries.to_string()),
+        );
+
+        // Sorted so the output is stable between scrapes
+        let mut methods: Vec<(String, String)> = self
+            .method
+            .iter()
+            .map(|(method, count)| (format!("{{method=\"{}\"}}", method), count.to_string()))
+            .collect();
+        methods.sort();
+        metric(
+            "did_resolver_method_success_total",
+            "counter",
+            "Successful resolver requests per DID method",
+            &methods,
+        );
+
+        out
     }
 }
 
@@ -128,7 +238,7 @@ impl Statistics {
 pub async fn statistics(
     interval: Duration,
     stats: &Arc<Mutex<Statistics>>,
-    cache: Cache<String, Document>,
+    cache: Cache<CacheKey, CacheEntry>,
 ) -> Result<(), CacheError> {
     let _span = span!(Level::INFO, "statistics");
 
@@ -154,3 +264,38 @@ pub async fn statistics(
     .instrument(_span)
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prometheus_format() {
+        let mut stats = Statistics::default();
+        stats.increment_ws_opened();
+        stats.increment_ws_opened();
+        stats.increment_ws_closed(true);
+        stats.increment_resolver_success();
+        stats.increment_resolver_error();
+        stats.increment_cache_hit();
+        stats.increment_did_method_success(DIDMethod::WEB);
+        stats.increment_did_method_success(DIDMethod::KEY);
+        stats.increment_did_method_success(DIDMethod::KEY);
+
+        let text = stats.to_prometheus(5);
+        for line in [
+            "# TYPE did_resolver_ws_connections gauge",
+            "did_resolver_ws_connections_opened_total 2",
+            "did_resolver_ws_connections 1",
+            "did_resolver_ws_connections_closed_total{close=\"abrupt\"} 0\ndid_resolver_ws_connections_closed_total{close=\"clean\"} 1",
+            "did_resolver_success_total 1",
+            "did_resolver_error_total 1",
+            "did_resolver_cache_hits_total 1",
+            "did_resolver_cache_entries 5",
+            "did_resolver_method_success_total{method=\"key\"} 2\ndid_resolver_method_success_total{method=\"web\"} 1",
+        ] {
+            assert!(text.contains(line), "missing ({}) in:\n{}", line, text);
+        }
+        assert!(text.ends_with('\n'));
+    }
+}
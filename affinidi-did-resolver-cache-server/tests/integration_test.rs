@@ -1,6 +1,7 @@
-use affinidi_did_resolver_cache_sdk::{config::ClientConfigBuilder, DIDCacheClient};
+use affinidi_did_resolver_cache_sdk::{
+    cache_key::cache_key, config::ClientConfigBuilder, DIDCacheClient,
+};
 use affinidi_did_resolver_cache_server::server::start;
-use blake2::{Blake2s256, Digest};
 use did_peer::{
     DIDPeer, DIDPeerCreateKeys, DIDPeerKeyType, DIDPeerKeys, DIDPeerService, PeerServiceEndPoint,
     PeerServiceEndPointLong,
@@ -47,16 +48,16 @@ async fn test_cache_server() {
     // Match doc in cache with resolved doc
     let cache = client.get_cache().clone();
     for (i, did) in dids.clone().iter().enumerate() {
-        let in_cache_doc = cache.get(&_hash_did(did)).await.unwrap();
-        assert_eq!(in_cache_doc, did_docs_vec[i]);
+        let in_cache_doc = cache.get(&cache_key(did)).await.unwrap();
+        assert_eq!(in_cache_doc.doc, did_docs_vec[i]);
     }
     client.remove(DID_PKH).await.unwrap();
-    assert!(!client.get_cache().contains_key(&_hash_did(DID_PKH)));
+    assert!(!client.get_cache().contains_key(&cache_key(DID_PKH)));
 
     sleep(Duration::from_secs(11)).await;
     // Validate cache expiry
     for did in dids.clone() {
-        assert!(!client.get_cache().contains_key(&_hash_did(did)));
+        assert!(!client.get_cache().contains_key(&cache_key(did)));
     }
 }
 
@@ -97,12 +98,6 @@ fn _validate_did_peer(did_peer: &str, e_did_key: &str, v_did_key: &str) {
     assert_eq!(parts[1], "peer");
 }
 
-fn _hash_did(did: &str) -> String {
-    let mut hasher = Blake2s256::new();
-    hasher.update(did);
-    format!("{:x}", hasher.clone().finalize())
-}
-
 fn _get_keys(
     key_type: DIDPeerKeyType,
     with_pub_key: bool,
@@ -36,6 +36,7 @@ use ssi::{
         RelativeDIDURLBuf, DID, DIDURL,
     },
     jwk::Params,
+    multicodec::{self, MultiEncoded, MultiEncodedBuf},
     prelude::*,
     OneOrMany,
 };
@@ -53,7 +54,7 @@ pub enum DIDPeerError {
     UnsupportedSource,
     #[error("Syntax error on Service definition: {0}")]
     SyntaxErrorServiceDefinition(String),
-    #[error("Unsupported Method. Must be method 2")]
+    #[error("Unsupported Method. Must be method 0, 2 or 4")]
     MethodNotSupported,
     #[error("Key Parsing error {0}")]
     KeyParsingError(String),
@@ -63,6 +64,10 @@ pub enum DIDPeerError {
     JsonParsingError(String),
     #[error("Internal error: {0}")]
     InternalError(String),
+    #[error("Invalid did:peer DID: {0}")]
+    InvalidPeerDID(String),
+    #[error("did:peer:4 short form DID can't be resolved without its long form: {0}")]
+    ShortFormNotResolvable(String),
 }
 
 // Converts DIDPeerError to JsValue which is required for propagating errors to WASM
@@ -74,7 +79,28 @@ impl From<DIDPeerError> for JsValue {
 
 pub struct DIDPeer;
 
-#[derive(Serialize, Deserialize)]
+/// did:peer numeric algorithms (numalgo) that are supported
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DIDPeerNumalgo {
+    /// did:peer:0 - Single inception key (equivalent to did:key)
+    Zero,
+    /// did:peer:2 - Multiple inception keys and services
+    Two,
+    /// did:peer:4 - Hash of a DID Document, with the document encoded in the long form
+    Four,
+}
+
+impl fmt::Display for DIDPeerNumalgo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DIDPeerNumalgo::Zero => write!(f, "0"),
+            DIDPeerNumalgo::Two => write!(f, "2"),
+            DIDPeerNumalgo::Four => write!(f, "4"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum PeerServiceEndPoint {
     Short(PeerServiceEndPointShort),
@@ -102,18 +128,24 @@ impl PeerServiceEndPoint {
 }
 
 /// DID serviceEndPoint structure in short format
+/// `a` (accept) and `r` (routingKeys) are optional
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PeerServiceEndPointShort {
     pub uri: String,
+    #[serde(default)]
     pub a: Vec<String>,
+    #[serde(default)]
     pub r: Vec<String>,
 }
 
 /// DID serviceEndPoint structure in long format
+/// Serializes with the DID Document key names (`accept`, `routingKeys`)
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PeerServiceEndPointLong {
     pub uri: String,
+    #[serde(default)]
     pub accept: Vec<String>,
+    #[serde(default, rename = "routingKeys", alias = "routing_keys")]
     pub routing_keys: Vec<String>,
 }
 
@@ -127,7 +159,7 @@ impl From<PeerServiceEndPointShort> for PeerServiceEndPointLong {
     }
 }
 /// DID Service structure in abbreviated format
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DIDPeerService {
     #[serde(rename = "t")]
     #[serde(alias = "t")]
@@ -138,28 +170,55 @@ pub struct DIDPeerService {
     pub id: Option<String>,
 }
 
-impl From<DIDPeerService> for Service {
-    fn from(service: DIDPeerService) -> Self {
+impl DIDPeerService {
+    /// Expands the abbreviated service into a DID Document Service
+    /// index: Position of the service in the DID, used for the default id
+    ///        (#service, #service-1, #service-2 ...)
+    ///
+    /// The type `dm` is expanded to `DIDCommMessaging`, and the endpoint uses the full key names
+    /// (`accept`, `routingKeys`). Relative ids (e.g. `#my-service`) are prefixed with `did:peer:`
+    /// as the SSI crate expects a URI for the service id.
+    pub fn to_service(&self, index: u32) -> Result<Service, DIDPeerError> {
         let service_endpoint =
-            match serde_json::to_value(PeerServiceEndPoint::to_long(&service.service_end_point)) {
+            match serde_json::to_value(PeerServiceEndPoint::to_long(&self.service_end_point)) {
                 Ok(value) => Some(OneOrMany::One(Endpoint::Map(value))),
                 Err(_) => None,
             };
 
-        let id = if let Some(id) = service.id {
-            UriBuf::new(id.into()).unwrap()
-        } else {
-            // TODO: Should be #service
-            // SSI Crate expects a URI for the service ID
-            UriBuf::new("did:peer:#service".into()).unwrap()
+        let id = match &self.id {
+            Some(id) if id.starts_with('#') => ["did:peer:", id].concat(),
+            Some(id) => id.to_string(),
+            None if index == 0 => "did:peer:#service".to_string(),
+            None => ["did:peer:#service-", &index.to_string()].concat(),
+        };
+        let id = UriBuf::new(id.into()).map_err(|e| {
+            DIDPeerError::SyntaxErrorServiceDefinition(format!(
+                "Service id isn't a valid URI ({})",
+                String::from_utf8_lossy(&e.0)
+            ))
+        })?;
+
+        let type_ = match self._type.as_str() {
+            "dm" => "DIDCommMessaging",
+            other => other,
         };
 
-        Service {
+        Ok(Service {
             id,
-            type_: OneOrMany::One("DIDCommMessaging".into()),
+            type_: OneOrMany::One(type_.into()),
             service_endpoint,
             property_set: BTreeMap::new(),
-        }
+        })
+    }
+}
+
+impl TryFrom<DIDPeerService> for Service {
+    type Error = DIDPeerError;
+
+    /// Expands the service as if it were the first service in the DID
+    /// Use [DIDPeerService::to_service] to set the index
+    fn try_from(service: DIDPeerService) -> Result<Self, Self::Error> {
+        service.to_service(0)
     }
 }
 
@@ -274,10 +333,18 @@ impl DIDMethodResolver for DIDPeer {
             return DIDKey.resolve_method_representation(id, options).await;
         }
 
-        // Only supports method 2 for did:peer
+        if method_specific_id.starts_with('4') {
+            let doc = DIDPeer::_resolve_numalgo_4(method_specific_id).map_err(|e| match e {
+                DIDPeerError::ShortFormNotResolvable(_) => Error::NotFound,
+                e => Error::InvalidMethodSpecificId(e.to_string()),
+            })?;
+            return _represent(doc, options);
+        }
+
+        // Otherwise only supports method 2 for did:peer
         if !method_specific_id.starts_with('2') {
             return Err(Error::MethodNotSupported(
-                "did:peer version 2 supported only".to_string(),
+                "did:peer version 0, 2 and 4 supported only".to_string(),
             ));
         }
 
@@ -434,7 +501,7 @@ impl DIDMethodResolver for DIDPeer {
                         }
                         'S' => {
                             // Service
-                            let raw = match BASE64_URL_SAFE_NO_PAD.decode(part[1..].as_bytes()) {
+                            let raw = match BASE64_URL_SAFE_NO_PAD.decode(&part.as_bytes()[1..]) {
                                 Ok(raw) => raw,
                                 Err(e) => {
                                     return Err(Error::Internal(format!(
@@ -455,17 +522,9 @@ impl DIDMethodResolver for DIDPeer {
                                 )));
                             };
 
-                            let mut service: Service = service.into();
-                            if service_idx > 0 {
-                                // TODO: Should be #service-1, #service-2, etc
-                                // SSI Crate expects a URI for the service ID
-                                service.id = UriBuf::new(
-                                    ["did:peer:#service-", &service_idx.to_string()]
-                                        .concat()
-                                        .into(),
-                                )
-                                .unwrap();
-                            }
+                            let service = service
+                                .to_service(service_idx)
+                                .map_err(|e| Error::Internal(e.to_string()))?;
                             services.push(service);
                             service_idx += 1;
                         }
@@ -484,43 +543,92 @@ impl DIDMethodResolver for DIDPeer {
             }
         }
 
-        let vm_type = match options.parameters.public_key_format {
-            Some(name) => VerificationMethodType::from_name(&name).ok_or_else(|| {
-                Error::Internal(format!(
-                    "verification method type `{name}` unsupported by did:peer"
-                ))
-            })?,
-            None => VerificationMethodType::Multikey,
-        };
-
         let mut doc =
             Document::new(DIDBuf::from_string(["did:peer:", method_specific_id].concat()).unwrap());
         doc.verification_method = verification_methods;
         doc.verification_relationships = verification_relationships;
         doc.service = services;
 
-        let mut json_ld_context = Vec::new();
-        if let Some(context) = vm_type.context_entry() {
-            json_ld_context.push(context)
-        }
+        _represent(doc, options)
+    }
+}
+
+/// Converts a resolved did:peer DID Document into the requested representation
+fn _represent(doc: Document, options: Options) -> Result<Output<Vec<u8>>, Error> {
+    let vm_type = match options.parameters.public_key_format {
+        Some(name) => VerificationMethodType::from_name(&name).ok_or_else(|| {
+            Error::Internal(format!(
+                "verification method type `{name}` unsupported by did:peer"
+            ))
+        })?,
+        None => VerificationMethodType::Multikey,
+    };
 
-        let content_type = options.accept.unwrap_or(MediaType::JsonLd);
+    let mut json_ld_context = Vec::new();
+    if let Some(context) = vm_type.context_entry() {
+        json_ld_context.push(context)
+    }
 
-        let represented = doc.into_representation(representation::Options::from_media_type(
-            content_type,
-            move || representation::json_ld::Options {
-                context: representation::json_ld::Context::array(
-                    representation::json_ld::DIDContext::V1,
-                    json_ld_context,
-                ),
-            },
-        ));
+    let content_type = options.accept.unwrap_or(MediaType::JsonLd);
 
-        Ok(resolution::Output::new(
-            represented.to_bytes(),
-            document::Metadata::default(),
-            resolution::Metadata::from_content_type(Some(content_type.to_string())),
-        ))
+    let represented = doc.into_representation(representation::Options::from_media_type(
+        content_type,
+        move || representation::json_ld::Options {
+            context: representation::json_ld::Context::array(
+                representation::json_ld::DIDContext::V1,
+                json_ld_context,
+            ),
+        },
+    ));
+
+    Ok(resolution::Output::new(
+        represented.to_bytes(),
+        document::Metadata::default(),
+        resolution::Metadata::from_content_type(Some(content_type.to_string())),
+    ))
+}
+
+/// did:peer:4 hash of the encoded document: base58btc multibase of the sha2-256 multihash
+fn _numalgo_4_hash(encoded: &str) -> String {
+    let digest = ssi::crypto::hashes::sha256::sha256(encoded.as_bytes());
+    let multihash = [&[multicodec::SHA2_256 as u8, 32][..], &digest].concat();
+    multibase::encode(multibase::Base::Base58Btc, multihash)
+}
+
+/// Makes ids and references relative (`#key-1`) to the DID, and drops controllers set to the DID
+fn _make_relative(value: &mut Value, did: &str) {
+    match value {
+        Value::Object(object) => {
+            if object.get("controller").and_then(Value::as_str) == Some(did) {
+                object.remove("controller");
+            }
+            object
+                .values_mut()
+                .for_each(|value| _make_relative(value, did));
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|value| _make_relative(value, did)),
+        Value::String(s) => {
+            if let Some(fragment) = s.strip_prefix(did).filter(|rest| rest.starts_with('#')) {
+                *s = fragment.to_string();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Resolves relative ids and references (`#key-1`) against the DID
+fn _make_absolute(value: &mut Value, did: &str) {
+    match value {
+        Value::Object(object) => object
+            .values_mut()
+            .for_each(|value| _make_absolute(value, did)),
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|value| _make_absolute(value, did)),
+        Value::String(s) if s.starts_with('#') => *s = [did, s].concat(),
+        _ => {}
     }
 }
 
@@ -697,6 +805,299 @@ impl DIDPeer {
         Ok((result, private_keys))
     }
 
+    /// Validates the structure of a did:peer DID without resolving it to a DID Document
+    ///
+    /// Checks the numalgo, that each key is a base58-btc multibase encoded public key of a
+    /// supported key type, and that each service segment is valid base64url encoded JSON.
+    ///
+    /// Returns the numalgo of the DID if it is valid
+    pub fn validate(did: &str) -> Result<DIDPeerNumalgo, DIDPeerError> {
+        let method_specific_id = did.strip_prefix("did:peer:").ok_or_else(|| {
+            DIDPeerError::InvalidPeerDID(format!("DID must start with did:peer: ({})", did))
+        })?;
+
+        if let Some(key) = method_specific_id.strip_prefix('0') {
+            Self::_validate_key(key)?;
+            Ok(DIDPeerNumalgo::Zero)
+        } else if let Some(elements) = method_specific_id.strip_prefix('2') {
+            if elements.is_empty() {
+                return Ok(DIDPeerNumalgo::Two);
+            }
+
+            let elements = elements.strip_prefix('.').ok_or_else(|| {
+                DIDPeerError::InvalidPeerDID(format!(
+                    "Expected '.' after numalgo 2 ({})",
+                    method_specific_id
+                ))
+            })?;
+
+            for element in elements.split('.') {
+                let mut chars = element.chars();
+                match chars.next() {
+                    Some('A' | 'D' | 'E' | 'I' | 'V') => Self::_validate_key(chars.as_str())?,
+                    Some('S') => {
+                        let raw = BASE64_URL_SAFE_NO_PAD
+                            .decode(chars.as_str().as_bytes())
+                            .map_err(|e| {
+                                DIDPeerError::SyntaxErrorServiceDefinition(format!(
+                                    "Service isn't valid base64url ({}). Reason: {}",
+                                    chars.as_str(),
+                                    e
+                                ))
+                            })?;
+                        serde_json::from_slice::<DIDPeerService>(&raw).map_err(|e| {
+                            DIDPeerError::SyntaxErrorServiceDefinition(format!(
+                                "Service isn't valid JSON ({}). Reason: {}",
+                                String::from_utf8_lossy(&raw),
+                                e
+                            ))
+                        })?;
+                    }
+                    Some(other) => {
+                        return Err(DIDPeerError::InvalidPeerDID(format!(
+                            "An invalid Purpose Code ({}) was found in the DID",
+                            other
+                        )))
+                    }
+                    None => {
+                        return Err(DIDPeerError::InvalidPeerDID(
+                            "DID contains an empty element".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            Ok(DIDPeerNumalgo::Two)
+        } else if let Some(hash) = method_specific_id.strip_prefix('4') {
+            match Self::_resolve_numalgo_4(method_specific_id) {
+                // Short form DIDs are valid, but only the hash can be checked
+                Err(DIDPeerError::ShortFormNotResolvable(_)) => {
+                    Self::_validate_numalgo_4_hash(hash)?
+                }
+                result => {
+                    result?;
+                }
+            }
+            Ok(DIDPeerNumalgo::Four)
+        } else {
+            Err(DIDPeerError::MethodNotSupported)
+        }
+    }
+
+    /// Creates a did:peer:4 DID (long form) from a DID Document
+    ///
+    /// The document's own id is replaced by the did:peer:4 DID on resolution, so any id can be
+    /// used (e.g. `did:example:123`). Ids and references starting with it are stored relative
+    /// (`#key-1`), and controllers set to it are dropped, so both resolve against the new DID.
+    ///
+    /// Use [DIDPeer::short_form] to get the short form of the DID
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let doc: Document = serde_json::from_value(json!({
+    ///     "id": "did:example:123",
+    ///     "verificationMethod": [{
+    ///         "id": "did:example:123#key-1",
+    ///         "type": "Multikey",
+    ///         "controller": "did:example:123",
+    ///         "publicKeyMultibase": "z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv"
+    ///     }],
+    ///     "authentication": ["did:example:123#key-1"]
+    /// }))?;
+    /// let did_peer = DIDPeer::create_numalgo_4(&doc)?;
+    /// ```
+    pub fn create_numalgo_4(document: &Document) -> Result<String, DIDPeerError> {
+        let mut value = serde_json::to_value(document)
+            .map_err(|e| DIDPeerError::JsonParsingError(e.to_string()))?;
+        if let Some(object) = value.as_object_mut() {
+            object.remove("id");
+        }
+        _make_relative(&mut value, document.id.as_str());
+
+        let json = serde_json::to_vec(&value)
+            .map_err(|e| DIDPeerError::JsonParsingError(e.to_string()))?;
+        let encoded = multibase::encode(
+            multibase::Base::Base58Btc,
+            MultiEncodedBuf::encode_bytes(multicodec::JSON, &json).into_bytes(),
+        );
+
+        Ok(["did:peer:4", &_numalgo_4_hash(&encoded), ":", &encoded].concat())
+    }
+
+    /// Returns the short form (`did:peer:4<hash>`) of a long form did:peer:4 DID
+    pub fn short_form(did: &str) -> Result<String, DIDPeerError> {
+        let method_specific_id = did
+            .strip_prefix("did:peer:4")
+            .ok_or_else(|| DIDPeerError::InvalidPeerDID(format!("Not a did:peer:4 ({})", did)))?;
+        let hash = match method_specific_id.split_once(':') {
+            Some((hash, _)) => hash,
+            None => method_specific_id,
+        };
+        Ok(["did:peer:4", hash].concat())
+    }
+
+    /// Resolves a long form did:peer:4 DID
+    /// Checks the hash matches the encoded document, then contextualizes the document:
+    /// sets the id, adds the short form to alsoKnownAs, resolves relative ids against the DID
+    /// and sets missing verification method controllers to the DID
+    fn _resolve_numalgo_4(method_specific_id: &str) -> Result<Document, DIDPeerError> {
+        let did = ["did:peer:", method_specific_id].concat();
+        let (hash, encoded) = method_specific_id[1..]
+            .split_once(':')
+            .ok_or_else(|| DIDPeerError::ShortFormNotResolvable(did.clone()))?;
+
+        if _numalgo_4_hash(encoded) != hash {
+            return Err(DIDPeerError::InvalidPeerDID(format!(
+                "did:peer:4 hash doesn't match the encoded document ({})",
+                did
+            )));
+        }
+
+        let (_, bytes) = multibase::decode(encoded).map_err(|e| {
+            DIDPeerError::InvalidPeerDID(format!(
+                "did:peer:4 document isn't valid multibase ({}). Reason: {}",
+                did, e
+            ))
+        })?;
+        let multi_encoded = MultiEncoded::new(&bytes).map_err(|e| {
+            DIDPeerError::InvalidPeerDID(format!(
+                "did:peer:4 document isn't multicodec encoded ({}). Reason: {}",
+                did, e
+            ))
+        })?;
+        if multi_encoded.codec() != multicodec::JSON {
+            return Err(DIDPeerError::InvalidPeerDID(format!(
+                "did:peer:4 document must be JSON encoded ({})",
+                did
+            )));
+        }
+        let mut value: Value = serde_json::from_slice(multi_encoded.data())
+            .map_err(|e| DIDPeerError::JsonParsingError(e.to_string()))?;
+
+        let object = value.as_object_mut().ok_or_else(|| {
+            DIDPeerError::InvalidPeerDID(format!(
+                "did:peer:4 document must be a JSON object ({})",
+                did
+            ))
+        })?;
+        if object.contains_key("id") {
+            return Err(DIDPeerError::InvalidPeerDID(format!(
+                "did:peer:4 document must not contain an id ({})",
+                did
+            )));
+        }
+
+        _make_absolute(&mut value, &did);
+        let short_form = ["did:peer:4", hash].concat();
+        if let Some(object) = value.as_object_mut() {
+            // The JSON-LD context is added when the document is represented
+            object.remove("@context");
+            object.insert("id".to_string(), Value::String(did.clone()));
+            match object.get_mut("alsoKnownAs") {
+                Some(Value::Array(aka)) => aka.push(Value::String(short_form)),
+                _ => {
+                    object.insert("alsoKnownAs".to_string(), serde_json::json!([short_form]));
+                }
+            }
+            for key in [
+                "verificationMethod",
+                "authentication",
+                "assertionMethod",
+                "keyAgreement",
+                "capabilityInvocation",
+                "capabilityDelegation",
+            ] {
+                if let Some(Value::Array(items)) = object.get_mut(key) {
+                    for vm in items.iter_mut().filter_map(Value::as_object_mut) {
+                        vm.entry("controller")
+                            .or_insert_with(|| Value::String(did.clone()));
+                    }
+                }
+            }
+        }
+
+        serde_json::from_value(value).map_err(|e| DIDPeerError::JsonParsingError(e.to_string()))
+    }
+
+    /// Checks a did:peer:4 hash is a multibase encoded sha2-256 multihash
+    fn _validate_numalgo_4_hash(hash: &str) -> Result<(), DIDPeerError> {
+        let valid = multibase::decode(hash)
+            .map(|(base, bytes)| {
+                base == multibase::Base::Base58Btc
+                    && bytes.len() == 34
+                    && bytes[0] == multicodec::SHA2_256 as u8
+                    && bytes[1] == 32
+            })
+            .unwrap_or(false);
+        if valid {
+            Ok(())
+        } else {
+            Err(DIDPeerError::InvalidPeerDID(format!(
+                "did:peer:4 hash isn't a base58btc sha2-256 multihash ({})",
+                hash
+            )))
+        }
+    }
+
+    /// Checks that a key is a base58-btc multibase multicodec encoded public key of a supported type
+    fn _validate_key(key: &str) -> Result<(), DIDPeerError> {
+        if !key.starts_with('z') {
+            return Err(DIDPeerError::KeyParsingError(format!(
+                "Key must be base58-btc multibase encoded (start with 'z') ({})",
+                key
+            )));
+        }
+
+        let (_, bytes) = multibase::decode(key).map_err(|e| {
+            DIDPeerError::KeyParsingError(format!(
+                "Couldn't decode multibase key ({}). Reason: {}",
+                key, e
+            ))
+        })?;
+
+        let encoded = MultiEncoded::new(&bytes).map_err(|e| {
+            DIDPeerError::KeyParsingError(format!(
+                "Couldn't decode multicodec key ({}). Reason: {}",
+                key, e
+            ))
+        })?;
+
+        match encoded.codec() {
+            multicodec::ED25519_PUB | multicodec::X25519_PUB => {
+                if encoded.data().len() != 32 {
+                    return Err(DIDPeerError::KeyParsingError(format!(
+                        "Key has an invalid length ({})",
+                        key
+                    )));
+                }
+            }
+            multicodec::SECP256K1_PUB | multicodec::P256_PUB => {
+                if encoded.data().len() != 33 {
+                    return Err(DIDPeerError::KeyParsingError(format!(
+                        "Key has an invalid length ({})",
+                        key
+                    )));
+                }
+            }
+            multicodec::P384_PUB => {
+                if encoded.data().len() != 49 {
+                    return Err(DIDPeerError::KeyParsingError(format!(
+                        "Key has an invalid length ({})",
+                        key
+                    )));
+                }
+            }
+            codec => {
+                return Err(DIDPeerError::UnsupportedCurve(format!(
+                    "multicodec ({:#x})",
+                    codec
+                )))
+            }
+        }
+
+        Ok(())
+    }
+
     /// Expands an existing DID Document from the did:key Multikeys to full JWT keys
     /// This is useful for when you want to resolve a did:peer DID Document to a full JWT included DID Document
     /// Converts base58 multi-keys to full JWTs in verificationMethod
@@ -716,6 +1117,11 @@ impl DIDPeer {
     async fn _convert_vm(
         method: &DIDVerificationMethod,
     ) -> Result<DIDVerificationMethod, DIDPeerError> {
+        // Already a full key (e.g. from a did:peer:4 document)
+        if method.properties.contains_key("publicKeyJwk") {
+            return Ok(method.clone());
+        }
+
         let current_controller = method.controller.clone();
         let current_id = method.id.clone();
         let did_key = if let Some(key) = method.properties.get("publicKeyBase58") {
@@ -772,12 +1178,16 @@ impl DIDPeer {
 #[cfg(test)]
 mod test {
     use crate::{
-        DIDPeer, DIDPeerCreateKeys, DIDPeerKeyType, DIDPeerKeys, DIDPeerService,
-        PeerServiceEndPoint, PeerServiceEndPointLong,
+        DIDPeer, DIDPeerCreateKeys, DIDPeerError, DIDPeerKeyType, DIDPeerKeys, DIDPeerNumalgo,
+        DIDPeerService, PeerServiceEndPoint, PeerServiceEndPointLong,
     };
 
     use ssi::{
-        dids::{document::DIDVerificationMethod, DIDBuf, DIDResolver, DID},
+        dids::{
+            document::{DIDVerificationMethod, Service},
+            resolution::Error,
+            DIDBuf, DIDResolver, Document, DID,
+        },
         JWK,
     };
 
@@ -1057,6 +1467,308 @@ mod test {
         assert_eq!(parts[1], "peer");
     }
 
+    #[test]
+    fn validate_numalgo_2_works() {
+        assert_eq!(DIDPeer::validate(DID_PEER).unwrap(), DIDPeerNumalgo::Two);
+    }
+
+    #[test]
+    fn validate_numalgo_0_works() {
+        assert_eq!(
+            DIDPeer::validate("did:peer:0z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv")
+                .unwrap(),
+            DIDPeerNumalgo::Zero
+        );
+    }
+
+    #[test]
+    fn validate_created_did_works() {
+        for key_type in [
+            DIDPeerKeyType::Ed25519,
+            DIDPeerKeyType::P256,
+            DIDPeerKeyType::Secp256k1,
+        ] {
+            let (_, _, keys) = _get_keys(Some(key_type), true);
+            let (did, _) = DIDPeer::create_peer_did(&keys, None).unwrap();
+            assert_eq!(DIDPeer::validate(&did).unwrap(), DIDPeerNumalgo::Two);
+        }
+    }
+
+    #[test]
+    fn validate_wrong_method() {
+        assert!(matches!(
+            DIDPeer::validate("did:key:z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv"),
+            Err(DIDPeerError::InvalidPeerDID(_))
+        ));
+    }
+
+    #[test]
+    fn validate_unsupported_numalgo() {
+        assert!(matches!(
+            DIDPeer::validate("did:peer:1zQmZMygzYqNwU6Uhmewx5Xepf2VLp5S4HLSwwgf2aiKZuwa"),
+            Err(DIDPeerError::MethodNotSupported)
+        ));
+    }
+
+    fn _numalgo_4_input() -> Document {
+        serde_json::from_value(serde_json::json!({
+            "id": "did:example:123",
+            "verificationMethod": [{
+                "id": "did:example:123#key-1",
+                "type": "Multikey",
+                "controller": "did:example:123",
+                "publicKeyMultibase": "z6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv"
+            }],
+            "authentication": ["did:example:123#key-1"],
+            "keyAgreement": [{
+                "id": "did:example:123#key-2",
+                "type": "Multikey",
+                "controller": "did:example:123",
+                "publicKeyMultibase": "z6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc"
+            }],
+            "service": [{
+                "id": "did:example:123#didcomm",
+                "type": "DIDCommMessaging",
+                "serviceEndpoint": "https://example.com/didcomm"
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn numalgo_4_round_trip() {
+        let input = _numalgo_4_input();
+        let did = DIDPeer::create_numalgo_4(&input).unwrap();
+        assert!(did.starts_with("did:peer:4zQm"));
+        assert_eq!(DIDPeer::validate(&did).unwrap(), DIDPeerNumalgo::Four);
+
+        let doc = DIDPeer
+            .resolve(DID::new::<String>(&did).unwrap())
+            .await
+            .unwrap()
+            .document
+            .into_document();
+
+        // Same document, contextualized to the did:peer:4 DID
+        let short_form = DIDPeer::short_form(&did).unwrap();
+        let expected = serde_json::to_string(&input)
+            .unwrap()
+            .replace("did:example:123", &did)
+            .replace(
+                &format!("\"id\":\"{}\"", did),
+                &format!("\"id\":\"{}\",\"alsoKnownAs\":[\"{}\"]", did, short_form),
+            );
+        let expected: Document = serde_json::from_str(&expected).unwrap();
+        assert_eq!(doc, expected);
+        assert_eq!(doc.also_known_as[0].as_str(), short_form);
+
+        // Keys can be expanded like did:peer:2
+        let expanded = DIDPeer::expand_keys(&doc).await.unwrap();
+        assert!(expanded.verification_method[0]
+            .properties
+            .contains_key("publicKeyJwk"));
+    }
+
+    #[tokio::test]
+    async fn numalgo_4_spec_vector() {
+        // Long form example from the did:peer specification
+        const DID_PEER_4: &str = "did:peer:4zQmd8CpeFPci817KDsbSAKWcXAE2mjvCQSasRewvbSF54Bd:z2M1k7h4psgp4CmJcnQn2Ljp7Pz7ktsd7oBhMU3dWY5s4fhFNj17qcRTQ427C7QHNT6cQ7T3XfRh35Q2GhaNFZmWHVFq4vL7F8nm36PA9Y96DvdrUiRUaiCuXnBFrn1o7mxFZAx14JL4t8vUWpuDPwQuddVo1T8myRiVH7wdxuoYbsva5x6idEpCQydJdFjiHGCpNc2UtjzPQ8awSXkctGCnBmgkhrj5gto3D4i3EREXYq4Z8r2cWGBr2UzbSmnxW2BuYddFo9Yfm6mKjtJyLpF74ytqrF5xtf84MnGFg1hMBmh1xVx1JwjZ2BeMJs7mNS8DTZhKC7KH38EgqDtUZzfjhpjmmUfkXg2KFEA3EGbbVm1DPqQXayPYKAsYPS9AyKkcQ3fzWafLPP93UfNhtUPL8JW5pMcSV3P8v6j3vPXqnnGknNyBprD6YGUVtgLiAqDBDUF3LSxFQJCVYYtghMTv8WuSw9h1a1SRFrDQLGHE4UrkgoRvwaGWr64aM87T1eVGkP5Dt4L1AbboeK2ceLArPScrdYGTpi3BpTkLwZCdjdiFSfTy9okL1YNRARqUf2wm8DvkVGUU7u5nQA3ZMaXWJAewk6k1YUxKd7LvofGUK4YEDtoxN5vb6r1Q2godrGqaPkjfL3RoYPpDYymf9XhcgG8Kx3DZaA6cyTs24t45KxYAfeCw4wqUpCH9HbpD78TbEUr9PPAsJgXBvBj2VVsxnr7FKbK4KykGcg1W8M1JPz21Z4Y72LWgGQCmixovrkHktcTX1uNHjAvKBqVD5C7XmVfHgXCHj7djCh3vzLNuVLtEED8J1hhqsB1oCBGiuh3xXr7fZ9wUjJCQ1HYHqxLJKdYKtoCiPmgKM7etVftXkmTFETZmpM19aRyih3bao76LdpQtbw636r7a3qt8v4WfxsXJetSL8c7t24SqQBcAY89FBsbEnFNrQCMK3JEseKHVaU388ctvRD45uQfe5GndFxthj4iSDomk4uRFd1uRbywoP1tRuabHTDX42UxPjz";
+
+        assert_eq!(DIDPeer::validate(DID_PEER_4).unwrap(), DIDPeerNumalgo::Four);
+        let doc = DIDPeer
+            .resolve(DID::new::<str>(DID_PEER_4).unwrap())
+            .await
+            .unwrap()
+            .document
+            .into_document();
+        assert_eq!(doc.id.as_str(), DID_PEER_4);
+        assert!(!doc.verification_method.is_empty());
+        for vm in &doc.verification_method {
+            assert!(vm.id.as_str().starts_with(DID_PEER_4));
+            assert_eq!(vm.controller.as_str(), DID_PEER_4);
+        }
+    }
+
+    #[tokio::test]
+    async fn numalgo_4_short_form_not_resolvable() {
+        let did = DIDPeer::create_numalgo_4(&_numalgo_4_input()).unwrap();
+        let short_form = DIDPeer::short_form(&did).unwrap();
+        assert_eq!(short_form.len(), "did:peer:4".len() + 47);
+        assert_eq!(
+            DIDPeer::validate(&short_form).unwrap(),
+            DIDPeerNumalgo::Four
+        );
+
+        assert!(matches!(
+            DIDPeer
+                .resolve(DID::new::<String>(&short_form).unwrap())
+                .await,
+            Err(Error::NotFound)
+        ));
+    }
+
+    #[test]
+    fn numalgo_4_tampered_document() {
+        let did = DIDPeer::create_numalgo_4(&_numalgo_4_input()).unwrap();
+        let (short_form, _) = did.rsplit_once(':').unwrap();
+
+        // A different document under the same hash
+        let mut other = _numalgo_4_input();
+        other.service.clear();
+        let other = DIDPeer::create_numalgo_4(&other).unwrap();
+        let (_, other_encoded) = other.rsplit_once(':').unwrap();
+
+        let tampered = [short_form, ":", other_encoded].concat();
+        assert!(matches!(
+            DIDPeer::validate(&tampered),
+            Err(DIDPeerError::InvalidPeerDID(msg)) if msg.contains("hash doesn't match")
+        ));
+    }
+
+    #[test]
+    fn validate_invalid_purpose_code() {
+        assert!(matches!(
+            DIDPeer::validate("did:peer:2.Xz6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv"),
+            Err(DIDPeerError::InvalidPeerDID(_))
+        ));
+    }
+
+    #[test]
+    fn validate_malformed_key() {
+        // Not multibase base58-btc
+        assert!(matches!(
+            DIDPeer::validate("did:peer:2.V6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv"),
+            Err(DIDPeerError::KeyParsingError(_))
+        ));
+        // Truncated ed25519 key
+        let mut truncated = vec![0xed, 0x01];
+        truncated.extend_from_slice(&[1; 31]);
+        let truncated = multibase::encode(multibase::Base::Base58Btc, truncated);
+        assert!(matches!(
+            DIDPeer::validate(&["did:peer:2.V", &truncated].concat()),
+            Err(DIDPeerError::KeyParsingError(_))
+        ));
+        // Invalid base58 character (0)
+        assert!(matches!(
+            DIDPeer::validate("did:peer:2.Vz0MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv"),
+            Err(DIDPeerError::KeyParsingError(_))
+        ));
+    }
+
+    #[test]
+    fn validate_unsupported_key_type() {
+        // BLS12-381 G2 keys can't be used in a did:peer
+        let mut bls = vec![0xeb, 0x01];
+        bls.extend_from_slice(&[1; 96]);
+        let bls = multibase::encode(multibase::Base::Base58Btc, bls);
+        assert!(matches!(
+            DIDPeer::validate(&["did:peer:0", &bls].concat()),
+            Err(DIDPeerError::UnsupportedCurve(_))
+        ));
+    }
+
+    #[test]
+    fn validate_bad_service() {
+        // Not base64url
+        assert!(matches!(
+            DIDPeer::validate(
+                "did:peer:2.Vz6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv.S!!notbase64!!"
+            ),
+            Err(DIDPeerError::SyntaxErrorServiceDefinition(_))
+        ));
+        // Valid base64url, but not a service
+        assert!(matches!(
+            DIDPeer::validate(
+                "did:peer:2.Vz6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv.SeyJmb28iOiJiYXIifQ"
+            ),
+            Err(DIDPeerError::SyntaxErrorServiceDefinition(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolve_multiple_services() {
+        use base64::prelude::*;
+
+        let services = [
+            r#"{"t":"dm","s":{"uri":"https://example.com/didcomm","a":["didcomm/v2"],"r":["did:example:mediator#key-1"]}}"#,
+            r#"{"t":"dm","s":{"uri":"wss://example.com/ws","a":["didcomm/v2"]}}"#,
+        ];
+        let did = services.iter().fold(
+            "did:peer:2.Vz6MkiToqovww7vYtxm1xNM15u9JzqzUFZ1k7s7MazYJUyAxv".to_string(),
+            |did, service| [&did, ".S", &BASE64_URL_SAFE_NO_PAD.encode(service)].concat(),
+        );
+
+        let peer = DIDPeer;
+        let output = peer
+            .resolve(DID::new::<String>(&did).unwrap())
+            .await
+            .unwrap();
+        let services = &output.document.document().service;
+
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].id, "did:peer:#service");
+        assert_eq!(services[1].id, "did:peer:#service-1");
+
+        let services = serde_json::to_value(services).unwrap();
+        for service in services.as_array().unwrap() {
+            assert_eq!(service["type"], "DIDCommMessaging");
+            assert_eq!(service["serviceEndpoint"]["accept"][0], "didcomm/v2");
+        }
+        assert_eq!(
+            services[0]["serviceEndpoint"]["uri"],
+            "https://example.com/didcomm"
+        );
+        assert_eq!(
+            services[0]["serviceEndpoint"]["routingKeys"][0],
+            "did:example:mediator#key-1"
+        );
+        assert_eq!(
+            services[1]["serviceEndpoint"]["uri"],
+            "wss://example.com/ws"
+        );
+        // Missing routing keys default to empty
+        assert_eq!(
+            services[1]["serviceEndpoint"]["routingKeys"],
+            serde_json::json!([])
+        );
+    }
+
+    #[test]
+    fn service_explicit_id() {
+        let service = DIDPeerService {
+            _type: "LinkedDomains".into(),
+            service_end_point: PeerServiceEndPoint::Long(PeerServiceEndPointLong {
+                uri: "https://example.com".into(),
+                accept: vec![],
+                routing_keys: vec![],
+            }),
+            id: Some("#domain".into()),
+        }
+        .to_service(3)
+        .unwrap();
+
+        assert_eq!(service.id, "did:peer:#domain");
+        assert_eq!(service.type_.first().unwrap(), "LinkedDomains");
+    }
+
+    #[test]
+    fn service_invalid_id() {
+        let service = DIDPeerService {
+            _type: "dm".into(),
+            service_end_point: PeerServiceEndPoint::Long(PeerServiceEndPointLong {
+                uri: "https://example.com".into(),
+                accept: vec![],
+                routing_keys: vec![],
+            }),
+            id: Some("not a uri".into()),
+        };
+
+        assert!(matches!(
+            Service::try_from(service),
+            Err(DIDPeerError::SyntaxErrorServiceDefinition(_))
+        ));
+    }
+
     fn _get_keys(
         key_type: Option<DIDPeerKeyType>,
         with_pub_key: bool,